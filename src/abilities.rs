@@ -0,0 +1,419 @@
+//! The hotbar of activated abilities, paid with combinations of dice from the bag.
+
+use std::time::Duration;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::controls::{Action, Actions};
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::sound::{PlaySoundEvent, Sound};
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::{
+    Asteroid, DiceBag, DiceNumber, FontAssets, GameState, ImageAssets, Planet, PlanetShield,
+    PLANET_SHIELD_MAX_CHARGES,
+};
+
+const SHOCKWAVE_RADIUS: f32 = 400.0;
+const SHOCKWAVE_FORCE: f32 = 6.0;
+const SPEED_BOOST_DURATION: u64 = 5; // in second
+const SPEED_BOOST_FACTOR: f32 = 2.0;
+const TIME_STOP_DURATION: u64 = 5; // in second
+const TIME_STOP_FLASH_DURATION: f32 = 0.5; // in second
+const TIME_STOP_OVERLAY_COLOR: Color = Color::rgba(0.4, 0.6, 1.0, 0.15);
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ShipSpeedBoost::default())
+            .insert_resource(PowerCharges::default())
+            .insert_resource(TimeStop::default())
+            .add_event::<AbilityActivatedEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_abilities)
+                    .with_system(setup_hotbar)
+                    .with_system(setup_time_stop_overlay),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(press_abilities)
+                    .with_system(activate_abilities)
+                    .with_system(grey_out_unaffordable_abilities)
+                    .with_system(push_asteroids_with_shockwave.after(activate_abilities))
+                    .with_system(boost_ships_speed.after(activate_abilities))
+                    .with_system(refill_planet_shield.after(activate_abilities))
+                    .with_system(stop_time.after(activate_abilities))
+                    .with_system(freeze_asteroids_during_time_stop.after(stop_time))
+                    .with_system(draw_time_stop_overlay.after(stop_time)),
+            );
+    }
+}
+
+/// An ability of the hotbar, attached to its button.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Ability {
+    /// Pushes the asteroids around the planet away.
+    Shockwave,
+    /// Makes the ships faster for a few seconds.
+    SpeedBoost,
+    /// Fills all the charges of the planet shield.
+    ShieldRefill,
+    /// The ultimate, freezes all the asteroids and supercharges the ships.
+    TimeStop,
+}
+
+impl Ability {
+    pub const ALL: [Ability; 4] =
+        [Ability::Shockwave, Ability::SpeedBoost, Ability::ShieldRefill, Ability::TimeStop];
+
+    /// The dice consumed from the bag to activate this ability.
+    pub fn cost(self) -> &'static [DiceNumber] {
+        match self {
+            Ability::Shockwave => &[DiceNumber::One, DiceNumber::One],
+            Ability::SpeedBoost => &[DiceNumber::Three, DiceNumber::Four],
+            Ability::ShieldRefill => &[DiceNumber::Five, DiceNumber::Six],
+            Ability::TimeStop => &DiceNumber::ALL,
+        }
+    }
+
+    /// An ultimate can only be paid with dice, never with power charges.
+    fn is_ultimate(self) -> bool {
+        self == Ability::TimeStop
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Ability::Shockwave => "Shockwave",
+            Ability::SpeedBoost => "Speed Boost",
+            Ability::ShieldRefill => "Shield Refill",
+            Ability::TimeStop => "Time Stop",
+        }
+    }
+}
+
+/// Sent once the cost of an ability has been consumed from the bag.
+pub struct AbilityActivatedEvent(pub Ability);
+
+/// Rare charges that pay for any ability when the bag can't.
+#[derive(Debug, Default)]
+pub struct PowerCharges(pub u32);
+
+/// Makes the ships faster until the timer finishes.
+#[derive(Debug, Default)]
+pub struct ShipSpeedBoost(Option<Timer>);
+
+impl ShipSpeedBoost {
+    pub fn factor(&self) -> f32 {
+        if self.0.is_some() {
+            SPEED_BOOST_FACTOR
+        } else {
+            1.0
+        }
+    }
+}
+
+/// The asteroids are frozen until the timer finishes.
+#[derive(Debug, Default)]
+pub struct TimeStop(Option<Timer>);
+
+impl TimeStop {
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+/// The velocity of an asteroid before the time stopped, restored afterward.
+#[derive(Component, Debug)]
+struct Frozen(Velocity);
+
+/// The full-screen tint displayed while the time is stopped.
+#[derive(Component, Debug)]
+struct TimeStopOverlay;
+
+fn reset_abilities(
+    mut speed_boost: ResMut<ShipSpeedBoost>,
+    mut power_charges: ResMut<PowerCharges>,
+    mut time_stop: ResMut<TimeStop>,
+) {
+    *speed_boost = ShipSpeedBoost::default();
+    *power_charges = PowerCharges::default();
+    *time_stop = TimeStop::default();
+}
+
+fn setup_hotbar(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { bottom: Val::Px(20.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            for (i, ability) in Ability::ALL.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            // The ultimate costs many more dice than the other abilities.
+                            size: Size::new(
+                                Val::Px(130f32.max(ability.cost().len() as f32 * 24.0 + 10.0)),
+                                Val::Px(60.0),
+                            ),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            flex_direction: FlexDirection::ColumnReverse,
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button_disabled.into(),
+                        ..default()
+                    })
+                    .insert(ability)
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            format!("{}. {}", i + 1, ability.label()),
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 16.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                color: Color::NONE.into(),
+                                focus_policy: FocusPolicy::Pass,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for number in ability.cost() {
+                                    parent.spawn_bundle(ImageBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(20.0), Val::Px(20.0)),
+                                            margin: UiRect::all(Val::Px(2.0)),
+                                            ..default()
+                                        },
+                                        image: image_assets
+                                            .handle_for_dice_number(*number)
+                                            .clone()
+                                            .into(),
+                                        focus_policy: FocusPolicy::Pass,
+                                        ..default()
+                                    });
+                                }
+                            });
+                    });
+            }
+        });
+}
+
+/// The abilities clicked in the hotbar or triggered by their key.
+fn press_abilities(
+    actions: Actions,
+    buttons: Query<(&Interaction, &Ability), (Changed<Interaction>, With<Button>)>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    let clicked = buttons
+        .iter()
+        .filter(|(interaction, _)| **interaction == Interaction::Clicked)
+        .map(|(_, ability)| *ability);
+    let pressed =
+        Ability::ALL.into_iter().filter(|ability| actions.just_pressed(Action::Ability(*ability)));
+
+    for ability in clicked.chain(pressed) {
+        player_inputs.send(PlayerInputEvent(PlayerInput::Ability(ability)));
+    }
+}
+
+/// Consume the cost of the pressed abilities, a power charge
+/// is consumed instead when the bag can't pay for it.
+fn activate_abilities(
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut power_charges: ResMut<PowerCharges>,
+    mut activated: EventWriter<AbilityActivatedEvent>,
+) {
+    let abilities = sim_inputs.iter().filter_map(|SimInputEvent { input, .. }| match *input {
+        PlayerInput::Ability(ability) => Some(ability),
+        _otherwise => None,
+    });
+
+    for ability in abilities {
+        if dice_bag.try_consume_combo(ability.cost()) {
+            activated.send(AbilityActivatedEvent(ability));
+        } else if power_charges.0 > 0 && !ability.is_ultimate() {
+            power_charges.0 -= 1;
+            activated.send(AbilityActivatedEvent(ability));
+        }
+    }
+}
+
+fn grey_out_unaffordable_abilities(
+    palette: Res<Palette>,
+    dice_bag: Res<DiceBag>,
+    power_charges: Res<PowerCharges>,
+    mut buttons: Query<(&Interaction, &Ability, &mut UiColor), With<Button>>,
+) {
+    for (interaction, ability, mut color) in &mut buttons {
+        let affordable = dice_bag.contains_combo(ability.cost())
+            || (power_charges.0 > 0 && !ability.is_ultimate());
+        *color = palette.button_if(affordable, *interaction).into();
+    }
+}
+
+fn push_asteroids_with_shockwave(
+    mut activated: EventReader<AbilityActivatedEvent>,
+    planet: Query<&Transform, With<Planet>>,
+    mut asteroids: Query<(&Transform, &mut ExternalImpulse), With<Asteroid>>,
+) {
+    for AbilityActivatedEvent(ability) in activated.iter() {
+        if *ability == Ability::Shockwave {
+            let planet_translation = planet.single().translation;
+            for (transform, mut ext_impl) in &mut asteroids {
+                let diff = (transform.translation - planet_translation).xy();
+                if diff.length() <= SHOCKWAVE_RADIUS {
+                    ext_impl.impulse = diff.normalize_or_zero() * SHOCKWAVE_FORCE;
+                }
+            }
+        }
+    }
+}
+
+fn boost_ships_speed(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut activated: EventReader<AbilityActivatedEvent>,
+    mut speed_boost: ResMut<ShipSpeedBoost>,
+) {
+    for AbilityActivatedEvent(ability) in activated.iter() {
+        // The time stop supercharges the ships for as long as it lasts.
+        let duration = match ability {
+            Ability::SpeedBoost => SPEED_BOOST_DURATION,
+            Ability::TimeStop => TIME_STOP_DURATION,
+            _ => continue,
+        };
+        speed_boost.0 = Some(Timer::new(Duration::from_secs(duration), false));
+    }
+
+    // Only borrowed mutably while running, not to mark it changed every frame.
+    if speed_boost.0.is_some() {
+        if let Some(timer) = &mut speed_boost.0 {
+            if timer.tick(speed.delta(&time)).finished() {
+                speed_boost.0 = None;
+            }
+        }
+    }
+}
+
+fn refill_planet_shield(
+    mut activated: EventReader<AbilityActivatedEvent>,
+    mut shield: ResMut<PlanetShield>,
+) {
+    for AbilityActivatedEvent(ability) in activated.iter() {
+        if *ability == Ability::ShieldRefill {
+            shield.charges = PLANET_SHIELD_MAX_CHARGES;
+        }
+    }
+}
+
+fn setup_time_stop_overlay(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            color: TIME_STOP_OVERLAY_COLOR.into(),
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(TimeStopOverlay);
+}
+
+fn stop_time(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut activated: EventReader<AbilityActivatedEvent>,
+    mut time_stop: ResMut<TimeStop>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    for AbilityActivatedEvent(ability) in activated.iter() {
+        if *ability == Ability::TimeStop {
+            time_stop.0 = Some(Timer::new(Duration::from_secs(TIME_STOP_DURATION), false));
+            play_sound.send(PlaySoundEvent::new(Sound::TimeStop));
+        }
+    }
+
+    // Only borrowed mutably while running, not to mark it changed every frame.
+    if time_stop.0.is_some() {
+        if let Some(timer) = &mut time_stop.0 {
+            if timer.tick(speed.delta(&time)).finished() {
+                time_stop.0 = None;
+            }
+        }
+    }
+}
+
+/// Keep the asteroids still while the time is stopped, even the ones spawning
+/// meanwhile, and give them their velocity back once it restarts.
+fn freeze_asteroids_during_time_stop(
+    mut commands: Commands,
+    time_stop: Res<TimeStop>,
+    mut asteroids: Query<
+        (Entity, &mut Velocity, &mut ExternalImpulse, Option<&Frozen>),
+        With<Asteroid>,
+    >,
+) {
+    for (entity, mut velocity, mut ext_impl, frozen) in &mut asteroids {
+        match (time_stop.is_active(), frozen) {
+            (true, frozen) => {
+                if frozen.is_none() {
+                    commands.entity(entity).insert(Frozen(*velocity));
+                }
+                *velocity = Velocity::zero();
+                *ext_impl = ExternalImpulse::default();
+            }
+            (false, Some(Frozen(before))) => {
+                *velocity = *before;
+                commands.entity(entity).remove::<Frozen>();
+            }
+            (false, None) => (),
+        }
+    }
+}
+
+/// Tint the screen while the time is stopped, with a flash when it starts.
+fn draw_time_stop_overlay(
+    time_stop: Res<TimeStop>,
+    mut overlay: Query<(&mut Visibility, &mut UiColor), With<TimeStopOverlay>>,
+) {
+    if time_stop.is_changed() {
+        for (mut visibility, mut color) in &mut overlay {
+            visibility.is_visible = time_stop.is_active();
+            if let Some(timer) = &time_stop.0 {
+                let flash = 1.0 - timer.elapsed_secs() / TIME_STOP_FLASH_DURATION;
+                let alpha = TIME_STOP_OVERLAY_COLOR.a() + flash.max(0.0) * 0.25;
+                color.0 = TIME_STOP_OVERLAY_COLOR;
+                color.0.set_a(alpha);
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! The accessibility layer, the UI elements carry a spoken label and the key events
+//! of the run are announced so the low-vision players can follow it.
+//!
+//! The announcements are shown in a large caption on the screen and spoken by the
+//! speech service the screen readers of the platform use: speech-dispatcher on Linux,
+//! the `say` command on macOS and the speech synthesizer of Windows. The UI tree is
+//! not exposed to the screen readers, the mobile and web builds only get the captions.
+
+use std::io;
+use std::process::Command;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::dice::DiceBag;
+use crate::settings::GameSettings;
+use crate::waves::WaveEvent;
+use crate::{FontAssets, GameState, Persistent, PlanetHealth, PLANET_MAX_HEALTH};
+
+const CAPTION_DURATION: u64 = 4; // in second
+
+pub struct AccessibilityPlugin;
+
+impl Plugin for AccessibilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AnnounceEvent>()
+            .insert_resource(Speech(spawn_speech_thread()))
+            .add_startup_system(setup_caption)
+            .add_system(announce_focused_elements)
+            .add_system(announce.after(announce_focused_elements))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(announce_dice_count.before(announce))
+                    .with_system(announce_wave_starts.before(announce))
+                    .with_system(announce_planet_health.before(announce)),
+            );
+    }
+}
+
+/// The label read when the UI element is focused.
+#[derive(Component, Debug, Clone)]
+pub struct AccessibleLabel(pub String);
+
+impl AccessibleLabel {
+    pub fn new(label: impl Into<String>) -> AccessibleLabel {
+        AccessibleLabel(label.into())
+    }
+}
+
+/// A message to read to the player.
+pub struct AnnounceEvent(pub String);
+
+/// Sends the announcements to the thread speaking them, when the platform can.
+struct Speech(Option<Sender<String>>);
+
+/// The speaking thread only reads the last announcement when they come faster
+/// than spoken, it stops if the platform has no speech service installed.
+fn spawn_speech_thread() -> Option<Sender<String>> {
+    if cfg!(not(any(target_os = "linux", target_os = "macos", target_os = "windows"))) {
+        return None;
+    }
+
+    let (sender, receiver) = mpsc::channel::<String>();
+    let spawned = thread::Builder::new().name("speech".into()).spawn(move || {
+        while let Ok(mut message) = receiver.recv() {
+            while let Ok(next) = receiver.try_recv() {
+                message = next;
+            }
+            if let Err(e) = speak(&message) {
+                return warn!("The announcements will not be spoken: {}", e);
+            }
+        }
+    });
+    spawned.ok().map(|_| sender)
+}
+
+#[cfg(target_os = "linux")]
+fn speak(message: &str) -> io::Result<()> {
+    Command::new("spd-say").args(["--wait", "--", message]).status().map(drop)
+}
+
+#[cfg(target_os = "macos")]
+fn speak(message: &str) -> io::Result<()> {
+    Command::new("say").args(["--", message]).status().map(drop)
+}
+
+#[cfg(target_os = "windows")]
+fn speak(message: &str) -> io::Result<()> {
+    // The message is passed in the environment to never be read as a script.
+    let script = "Add-Type -AssemblyName System.Speech; \
+        (New-Object System.Speech.Synthesis.SpeechSynthesizer).Speak($env:ANNOUNCEMENT)";
+    Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("ANNOUNCEMENT", message)
+        .status()
+        .map(drop)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn speak(_message: &str) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "no speech service"))
+}
+
+/// The caption showing the last announcement.
+#[derive(Component, Debug)]
+struct Caption {
+    timer: Timer,
+}
+
+fn setup_caption(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { bottom: Val::Px(130.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(Persistent)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 28.0,
+                            color: Color::YELLOW,
+                        },
+                    ),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(Caption {
+                    timer: Timer::new(Duration::from_secs(CAPTION_DURATION), false),
+                });
+        });
+}
+
+/// Read the label of the UI element under the cursor.
+fn announce_focused_elements(
+    elements: Query<(&Interaction, &AccessibleLabel), Changed<Interaction>>,
+    mut announcements: EventWriter<AnnounceEvent>,
+) {
+    for (interaction, AccessibleLabel(label)) in &elements {
+        if *interaction == Interaction::Hovered {
+            announcements.send(AnnounceEvent(label.clone()));
+        }
+    }
+}
+
+fn announce_dice_count(
+    dice_bag: Res<DiceBag>,
+    mut last_count: Local<Option<usize>>,
+    mut announcements: EventWriter<AnnounceEvent>,
+) {
+    if dice_bag.is_changed() && *last_count != Some(dice_bag.len()) {
+        *last_count = Some(dice_bag.len());
+        announcements.send(AnnounceEvent(format!("{} dice in the bag", dice_bag.len())));
+    }
+}
+
+fn announce_wave_starts(
+    mut wave_events: EventReader<WaveEvent>,
+    mut announcements: EventWriter<AnnounceEvent>,
+) {
+    for event in wave_events.iter() {
+        if let WaveEvent::Started(number) = event {
+            announcements.send(AnnounceEvent(format!("Wave {} starts", number)));
+        }
+    }
+}
+
+/// Warn when the cities fall under half, a quarter and the last one.
+fn announce_planet_health(
+    health: Res<PlanetHealth>,
+    mut last_health: Local<u32>,
+    mut announcements: EventWriter<AnnounceEvent>,
+) {
+    if !health.is_changed() {
+        return;
+    }
+
+    let thresholds = [(PLANET_MAX_HEALTH / 2, "half"), (PLANET_MAX_HEALTH / 4, "a quarter")];
+    for (threshold, name) in thresholds {
+        if health.current <= threshold && *last_health > threshold {
+            let message = format!("Cities under {}, {} left", name, health.current);
+            announcements.send(AnnounceEvent(message));
+        }
+    }
+    if health.current == 1 && *last_health > 1 {
+        announcements.send(AnnounceEvent("Only one city left standing".to_string()));
+    }
+    *last_health = health.current;
+}
+
+fn announce(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    speech: Res<Speech>,
+    mut announcements: EventReader<AnnounceEvent>,
+    mut caption: Query<(&mut Caption, &mut Text, &mut Visibility)>,
+) {
+    let last = announcements.iter().fold(None, |_, AnnounceEvent(message)| {
+        if settings.announcements {
+            info!(target: "accessibility", "{}", message);
+            if let Some(sender) = &speech.0 {
+                // The thread is gone when the platform has no speech service.
+                let _ = sender.send(message.clone());
+            }
+        }
+        Some(message)
+    });
+
+    for (mut caption, mut text, mut visibility) in &mut caption {
+        match last {
+            Some(message) if settings.announcements => {
+                text.sections[0].value = message.clone();
+                visibility.is_visible = true;
+                caption.timer.reset();
+            }
+            _ => {
+                if caption.timer.tick(time.delta()).just_finished() || !settings.announcements {
+                    visibility.is_visible = false;
+                }
+            }
+        }
+    }
+}
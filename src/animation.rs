@@ -0,0 +1,108 @@
+//! The frame by frame animations of the sprites, e.g. the dice loot rolling
+//! through its faces before showing its number.
+//!
+//! An animation plays clips, ranges of its frames at a given rate, and goes
+//! from one clip to another once a clip has played enough times.
+
+use std::ops::Range;
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+use crate::speed::SimulationSpeed;
+use crate::GameState;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(animate_sprites));
+    }
+}
+
+/// A range of the frames of an animation and what to play once it reaches its end.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub frames: Range<usize>,
+    pub fps: f32,
+    pub transition: Transition,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Transition {
+    /// Stay on the last frame of the clip.
+    Hold,
+    /// Play the clip this number of times, then the clip at the given index.
+    After { loops: u32, clip: usize },
+}
+
+#[derive(Component, Debug)]
+pub struct SpriteAnimation {
+    frames: Vec<Handle<Image>>,
+    clips: Vec<AnimationClip>,
+    clip: usize,
+    frame: usize,
+    loops: u32,
+    timer: Timer,
+}
+
+impl SpriteAnimation {
+    /// An animation of these frames starting with the first clip.
+    pub fn new(frames: Vec<Handle<Image>>, clips: Vec<AnimationClip>) -> SpriteAnimation {
+        let mut animation =
+            SpriteAnimation { frames, clips, clip: 0, frame: 0, loops: 0, timer: default() };
+        animation.play(0);
+        animation
+    }
+
+    /// Start the clip at this index from its first frame.
+    pub fn play(&mut self, clip: usize) {
+        let AnimationClip { frames, fps, .. } = &self.clips[clip];
+        self.clip = clip;
+        self.frame = frames.start;
+        self.loops = 0;
+        self.timer = Timer::new(Duration::from_secs_f32(fps.recip()), true);
+    }
+
+    pub fn image(&self) -> &Handle<Image> {
+        &self.frames[self.frame]
+    }
+
+    /// Move to the next frame, following the transition at the end of the clip.
+    fn advance(&mut self) {
+        let AnimationClip { frames, transition, .. } = &self.clips[self.clip];
+        if self.frame + 1 < frames.end {
+            self.frame += 1;
+            return;
+        }
+
+        match *transition {
+            Transition::Hold => (),
+            Transition::After { loops, clip } => {
+                self.loops += 1;
+                if self.loops >= loops {
+                    self.play(clip);
+                } else {
+                    self.frame = frames.start;
+                }
+            }
+        }
+    }
+}
+
+fn animate_sprites(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut sprites: Query<(&mut SpriteAnimation, &mut Handle<Image>)>,
+) {
+    for (mut animation, mut image) in &mut sprites {
+        animation.timer.tick(speed.delta(&time));
+        for _ in 0..animation.timer.times_finished_this_tick() {
+            animation.advance();
+        }
+
+        if *image != *animation.image() {
+            *image = animation.image().clone();
+        }
+    }
+}
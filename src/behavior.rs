@@ -0,0 +1,194 @@
+//! The behavior of the ships, how they pick the asteroids they go after.
+//!
+//! Every ship power has its profile defined in a `<power>.behavior.ron` data file,
+//! so the ships can behave differently without writing new systems.
+
+use std::fs;
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use serde::Deserialize;
+
+use crate::hull::ShipHull;
+use crate::logic::{self, TargetCandidate};
+use crate::mutators::RunRules;
+use crate::ron_asset::RonAssetLoader;
+use crate::{Asteroid, AsteroidKindName, GameState, Planet, Ship, ShipPower, ShipTarget};
+
+const BUMP_PROFILE_PATH: &str = "bump.behavior.ron";
+const DESTROY_PROFILE_PATH: &str = "destroy.behavior.ron";
+const MINER_PROFILE_PATH: &str = "miner.behavior.ron";
+
+pub struct BehaviorPlugin;
+
+impl Plugin for BehaviorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BehaviorProfile>()
+            .add_asset_loader(RonAssetLoader::<BehaviorProfile>::new(&["behavior.ron"]))
+            .add_startup_system(load_behavior_profiles)
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(lock_ship_targets),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "c3a81f5e-2b7d-4e96-8d14-6f0b9a2e7c58"]
+pub struct BehaviorProfile {
+    /// The distance from the ship under which an asteroid can be targeted.
+    trigger_range: f32,
+    /// The distance from the planet beyond which the ship gives up on its target.
+    leash_distance: f32,
+    /// The part of the hull under which the ship stops fighting and goes back to the planet.
+    retreat_hull: f32,
+    /// The kinds of asteroids targeted first when in range, by name.
+    preferred_asteroids: Vec<String>,
+}
+
+/// The behavior of the ships while their profile is still loading.
+impl Default for BehaviorProfile {
+    fn default() -> BehaviorProfile {
+        BehaviorProfile {
+            trigger_range: 400.0,
+            leash_distance: 500.0,
+            retreat_hull: 0.0,
+            preferred_asteroids: Vec::new(),
+        }
+    }
+}
+
+impl BehaviorProfile {
+    fn should_retreat(&self, hull: &ShipHull) -> bool {
+        (hull.current as f32) < hull.max as f32 * self.retreat_hull
+    }
+
+    fn prefers(&self, kind: &AsteroidKindName) -> bool {
+        self.preferred_asteroids.contains(&kind.0)
+    }
+}
+
+pub struct BehaviorProfileHandles {
+    bump: Handle<BehaviorProfile>,
+    destroy: Handle<BehaviorProfile>,
+    miner: Handle<BehaviorProfile>,
+}
+
+/// The profiles of every power read from the assets directory without the
+/// asset server, for the server and the sweeps.
+#[derive(Debug, Clone)]
+pub struct BehaviorProfileFiles {
+    bump: BehaviorProfile,
+    destroy: BehaviorProfile,
+    miner: BehaviorProfile,
+}
+
+impl BehaviorProfileFiles {
+    pub fn read() -> Result<BehaviorProfileFiles, String> {
+        let read = |path: &str| {
+            let path = format!("assets/{}", path);
+            fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| ron::from_str(&content).map_err(|e| e.to_string()))
+                .map_err(|e| format!("{}: {}", path, e))
+        };
+        Ok(BehaviorProfileFiles {
+            bump: read(BUMP_PROFILE_PATH)?,
+            destroy: read(DESTROY_PROFILE_PATH)?,
+            miner: read(MINER_PROFILE_PATH)?,
+        })
+    }
+}
+
+impl BehaviorProfileHandles {
+    pub fn from_files(
+        files: BehaviorProfileFiles,
+        profiles: &mut Assets<BehaviorProfile>,
+    ) -> BehaviorProfileHandles {
+        BehaviorProfileHandles {
+            bump: profiles.add(files.bump),
+            destroy: profiles.add(files.destroy),
+            miner: profiles.add(files.miner),
+        }
+    }
+
+    /// The distance under which the ships of this power target the asteroids.
+    pub fn trigger_range(&self, profiles: &Assets<BehaviorProfile>, power: ShipPower) -> f32 {
+        match profiles.get(self.get(power)) {
+            Some(profile) => profile.trigger_range,
+            None => BehaviorProfile::default().trigger_range,
+        }
+    }
+
+    fn get(&self, power: ShipPower) -> &Handle<BehaviorProfile> {
+        match power {
+            ShipPower::Bump => &self.bump,
+            ShipPower::Destroy => &self.destroy,
+            ShipPower::Mine => &self.miner,
+        }
+    }
+}
+
+fn load_behavior_profiles(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(BehaviorProfileHandles {
+        bump: asset_server.load(BUMP_PROFILE_PATH),
+        destroy: asset_server.load(DESTROY_PROFILE_PATH),
+        miner: asset_server.load(MINER_PROFILE_PATH),
+    });
+}
+
+/// Keep the target of every ship while it stays close enough to the planet,
+/// otherwise lock onto the closest asteroid in range, preferred kinds first.
+fn lock_ship_targets(
+    profiles: Res<Assets<BehaviorProfile>>,
+    handles: Res<BehaviorProfileHandles>,
+    rules: Res<RunRules>,
+    planet: Query<&Transform, With<Planet>>,
+    asteroids: Query<(Entity, &Transform, &AsteroidKindName), With<Asteroid>>,
+    mut ships: Query<(&Transform, &ShipPower, &ShipHull, &mut ShipTarget), With<Ship>>,
+) {
+    let _span = info_span!("lock_ship_targets").entered();
+    let planet_position = match planet.get_single() {
+        Ok(planet_transform) => planet_transform.translation.truncate().to_array(),
+        Err(_) => return,
+    };
+
+    let default_profile = BehaviorProfile::default();
+    for (ship_transform, power, hull, mut ship_target) in &mut ships {
+        let profile = profiles.get(handles.get(*power)).unwrap_or(&default_profile);
+        let leash_distance = match rules.leash_distance {
+            Some(distance) => distance.min(profile.leash_distance),
+            None => profile.leash_distance,
+        };
+
+        let target = if profile.should_retreat(hull) {
+            None
+        } else {
+            match ship_target.0.map(|e| asteroids.get(e)) {
+                Some(Ok((entity, transform, _))) => {
+                    let position = transform.translation.truncate().to_array();
+                    logic::is_leashed(planet_position, position, leash_distance).then_some(entity)
+                }
+                _otherwise => {
+                    let candidates =
+                        asteroids.iter().map(|(entity, transform, kind)| TargetCandidate {
+                            id: entity,
+                            position: transform.translation.truncate().to_array(),
+                            preferred: profile.prefers(kind),
+                        });
+                    logic::choose_target(
+                        ship_transform.translation.truncate().to_array(),
+                        planet_position,
+                        profile.trigger_range,
+                        leash_distance,
+                        candidates,
+                    )
+                }
+            }
+        };
+
+        // Only touch the target when it moves so that it is changed once per lock.
+        if ship_target.0 != target {
+            ship_target.0 = target;
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! The co-op server, it pairs the players by room code, relays the inputs of
+//! the lockstep between them and simulates their run with the game itself,
+//! headlessly, as the reference of the state of the run.
+//!
+//! A client sends a first line, `HOST` to open a room or `JOIN <code>` to join
+//! one. The host receives `Room("<code>")` and waits, both players receive
+//! `Joined` once the guest is there, and everything they send next is relayed
+//! as is. The server sends the checkpoints of its simulation to both players
+//! once the run started. The replies are messages of the game, written in RON.
+//! The room of a host is closed when the host leaves before its guest joined.
+//!
+//! A client has a few seconds to send its first line, the lines are at most
+//! `MAX_LINE_LENGTH` bytes long and the server only serves a bounded number of
+//! connections at the same time.
+//!
+//! Usage: `server [address]`, it listens on `0.0.0.0:7878` by default and
+//! reads the assets in the `assets` directory of the working directory.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{env, thread};
+
+use combine_and_defend::{RoomSimulation, MAX_LINE_LENGTH};
+use rand::seq::SliceRandom;
+
+const DEFAULT_ADDRESS: &str = "0.0.0.0:7878";
+const ROOM_CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ";
+const ROOM_CODE_LENGTH: usize = 4;
+/// How often a waiting host is checked for a closed connection.
+const HOST_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How long a new client has to send its `HOST` or `JOIN <code>` line.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// The connections served at the same time, every one of them holds a thread.
+const MAX_CONNECTIONS: usize = 256;
+
+/// The hosts waiting for their guest, by room code, the guest is sent to its host.
+type Rooms = Arc<Mutex<HashMap<String, Sender<Client>>>>;
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    slot: ConnectionSlot,
+}
+
+impl Client {
+    fn new(stream: TcpStream, slot: ConnectionSlot) -> io::Result<Client> {
+        stream.set_nodelay(true)?;
+        let writer = stream.try_clone()?;
+        Ok(Client { reader: BufReader::new(stream), writer, slot })
+    }
+
+    fn reply(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)
+    }
+
+    /// Whether the client closed its connection, waiting for it at most `timeout`.
+    fn has_left(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.reader.get_ref().set_read_timeout(Some(timeout))?;
+        // The bytes sent meanwhile stay in the buffer of the reader.
+        let left = match self.reader.fill_buf() {
+            Ok(buffer) => Ok(buffer.is_empty()),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => Ok(false),
+            Err(e) => Err(e),
+        };
+        self.reader.get_ref().set_read_timeout(None)?;
+        left
+    }
+}
+
+/// A connection counted as served until it is dropped.
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    /// A slot for a new connection, none once `MAX_CONNECTIONS` are served.
+    fn take(connections: &Arc<AtomicUsize>) -> Option<ConnectionSlot> {
+        connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < MAX_CONNECTIONS).then_some(count + 1)
+            })
+            .ok()?;
+        Some(ConnectionSlot(connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Read a line of at most `MAX_LINE_LENGTH` bytes, a longer line is an error.
+fn read_line(reader: &mut BufReader<TcpStream>, line: &mut String) -> io::Result<usize> {
+    let read = reader.take(MAX_LINE_LENGTH as u64).read_line(line)?;
+    if read == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Err(io::Error::new(ErrorKind::InvalidData, "the line is too long"));
+    }
+    Ok(read)
+}
+
+fn main() -> io::Result<()> {
+    let address = env::args().nth(1).unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let listener = TcpListener::bind(&address)?;
+    println!("Listening on {}", address);
+
+    let rooms = Rooms::default();
+    let connections = Arc::default();
+    for stream in listener.incoming() {
+        let rooms = rooms.clone();
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                eprintln!("Could not accept a client: {}", e);
+                continue;
+            }
+        };
+        let slot = match ConnectionSlot::take(&connections) {
+            Some(slot) => slot,
+            None => {
+                let _ = writeln!(&stream, "Error(\"The server is full\")");
+                continue;
+            }
+        };
+        match Client::new(stream, slot) {
+            Ok(client) => {
+                thread::spawn(move || {
+                    if let Err(e) = handle_client(client, &rooms) {
+                        eprintln!("A client left: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("Could not accept a client: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_client(mut client: Client, rooms: &Rooms) -> io::Result<()> {
+    let mut line = String::new();
+    client.reader.get_ref().set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    read_line(&mut client.reader, &mut line)?;
+    client.reader.get_ref().set_read_timeout(None)?;
+
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["HOST"] => host_room(client, rooms),
+        ["JOIN", code] => {
+            let room = rooms.lock().unwrap().remove(&code.to_uppercase());
+            // The host can leave right before its guest arrives.
+            let unjoined = match room {
+                Some(room) => room.send(client).err().map(|mpsc::SendError(client)| client),
+                None => Some(client),
+            };
+            match unjoined {
+                Some(mut client) => client.reply("Error(\"There is no room with this code\")"),
+                None => Ok(()),
+            }
+        }
+        _otherwise => client.reply("Error(\"Expected HOST or JOIN <code>\")"),
+    }
+}
+
+/// Open a room and play the run once the guest joined it.
+fn host_room(mut host: Client, rooms: &Rooms) -> io::Result<()> {
+    let (room, guests) = mpsc::channel();
+    let code = {
+        let mut rooms = rooms.lock().unwrap();
+        let code = loop {
+            let code = random_room_code();
+            if !rooms.contains_key(&code) {
+                break code;
+            }
+        };
+        rooms.insert(code.clone(), room);
+        code
+    };
+    println!("Room {} opened", code);
+
+    let mut guest = match wait_for_guest(&mut host, &code, &guests) {
+        Ok(Some(guest)) => guest,
+        result => {
+            // The room is closed whatever the reason the host left.
+            rooms.lock().unwrap().remove(&code);
+            if let Ok(mut guest) = guests.try_recv() {
+                let _ = guest.reply("Error(\"The host left the room\")");
+            }
+            println!("Room {} closed", code);
+            return result.map(drop);
+        }
+    };
+
+    host.reply("Joined")?;
+    guest.reply("Joined")?;
+    println!("Room {} started", code);
+    play(host, guest)
+}
+
+/// The guest of the room, none once the host closed its connection.
+fn wait_for_guest(
+    host: &mut Client,
+    code: &str,
+    guests: &Receiver<Client>,
+) -> io::Result<Option<Client>> {
+    host.reply(&format!("Room(\"{}\")", code))?;
+    loop {
+        match guests.try_recv() {
+            Ok(guest) => return Ok(Some(guest)),
+            Err(TryRecvError::Disconnected) => return Ok(None),
+            Err(TryRecvError::Empty) => {
+                if host.has_left(HOST_POLL_INTERVAL)? {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Forward every line one player sends to the other and simulate the run from
+/// them, until one of the players leaves.
+fn play(host: Client, guest: Client) -> io::Result<()> {
+    // The slots are freed once both connections are closed.
+    let Client { reader: host_reader, writer: mut host_writer, slot: _host_slot } = host;
+    let Client { reader: guest_reader, writer: mut guest_writer, slot: _guest_slot } = guest;
+
+    // A line is `None` once its player left.
+    let (sender, lines) = mpsc::channel();
+    for (from_host, mut reader) in [(true, host_reader), (false, guest_reader)] {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut line = String::new();
+            while matches!(read_line(&mut reader, &mut line), Ok(read) if read > 0) {
+                let trimmed = line.trim_end_matches(['\r', '\n']).to_string();
+                if sender.send((from_host, Some(trimmed))).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+            let _ = sender.send((from_host, None));
+        });
+    }
+
+    let result = relay(&lines, &mut host_writer, &mut guest_writer);
+
+    // Closing the connections stops the reader of the player still there.
+    let _ = host_writer.shutdown(Shutdown::Both);
+    let _ = guest_writer.shutdown(Shutdown::Both);
+    result
+}
+
+/// Forward the lines until a player leaves, the checkpoints go to both players.
+fn relay(
+    lines: &Receiver<(bool, Option<String>)>,
+    host: &mut TcpStream,
+    guest: &mut TcpStream,
+) -> io::Result<()> {
+    let mut simulation = RoomSimulation::default();
+    while let Ok((from_host, Some(line))) = lines.recv() {
+        let partner = if from_host { &mut *guest } else { &mut *host };
+        writeln!(partner, "{}", line)?;
+
+        for checkpoint in simulation.receive(from_host, &line) {
+            writeln!(host, "{}", checkpoint)?;
+            writeln!(guest, "{}", checkpoint)?;
+        }
+    }
+    Ok(())
+}
+
+fn random_room_code() -> String {
+    let mut rng = rand::thread_rng();
+    (0..ROOM_CODE_LENGTH).map(|_| *ROOM_CODE_ALPHABET.choose(&mut rng).unwrap() as char).collect()
+}
@@ -0,0 +1,128 @@
+//! The modes of the camera of a run, switched with a key: fixed on the planet,
+//! following the selected ship, or framing the planet and the closest threats.
+//!
+//! The camera glides to the point of its mode instead of jumping to it. On the
+//! planet it only glides back after a switch, the fingers can still pan it.
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use ordered_float::OrderedFloat;
+
+use crate::controls::{Action, Actions};
+use crate::selection::SelectedShip;
+use crate::toasts::ToastEvent;
+use crate::{Asteroid, GameState, Planet, SpaceCamera};
+
+/// How fast the camera closes the distance to its point, in part per second.
+const FOLLOW_RATE: f32 = 4.0;
+/// The distance under which the camera is back on the planet, in world units.
+const SETTLE_DISTANCE: f32 = 0.5;
+/// The number of asteroids framed with the planet by the action camera.
+const ACTION_THREATS: usize = 3;
+
+pub struct CameraModesPlugin;
+
+impl Plugin for CameraModesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CameraFollow::default())
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(cycle_camera_mode)
+                    .with_system(follow_camera_mode.after(cycle_camera_mode)),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(reset_camera_mode));
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum CameraMode {
+    #[default]
+    Planet,
+    SelectedShip,
+    Action,
+}
+
+impl CameraMode {
+    const ALL: [CameraMode; 3] = [CameraMode::Planet, CameraMode::SelectedShip, CameraMode::Action];
+
+    fn label(self) -> &'static str {
+        match self {
+            CameraMode::Planet => "Planet",
+            CameraMode::SelectedShip => "Selected ship",
+            CameraMode::Action => "Action",
+        }
+    }
+
+    fn next(self) -> CameraMode {
+        let index = CameraMode::ALL.iter().position(|mode| *mode == self).unwrap_or(0);
+        CameraMode::ALL[(index + 1) % CameraMode::ALL.len()]
+    }
+}
+
+#[derive(Debug, Default)]
+struct CameraFollow {
+    mode: CameraMode,
+    /// Whether the camera is still gliding back to the planet after a switch.
+    settling: bool,
+}
+
+fn cycle_camera_mode(
+    actions: Actions,
+    mut follow: ResMut<CameraFollow>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    if actions.just_pressed(Action::CycleCameraMode) {
+        follow.mode = follow.mode.next();
+        follow.settling = true;
+        toasts.send(ToastEvent::info(format!("Camera: {}", follow.mode.label())));
+    }
+}
+
+fn follow_camera_mode(
+    time: Res<Time>,
+    mut follow: ResMut<CameraFollow>,
+    selected: Res<SelectedShip>,
+    planet: Query<&Transform, (With<Planet>, Without<SpaceCamera>)>,
+    ships: Query<&Transform, Without<SpaceCamera>>,
+    asteroids: Query<&Transform, (With<Asteroid>, Without<SpaceCamera>)>,
+    mut camera: Query<&mut Transform, With<SpaceCamera>>,
+) {
+    let planet_position = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+    if follow.mode == CameraMode::Planet && !follow.settling {
+        return;
+    }
+
+    let target = match follow.mode {
+        CameraMode::Planet => planet_position,
+        CameraMode::SelectedShip => selected
+            .0
+            .and_then(|ship| ships.get(ship).ok())
+            .map_or(planet_position, |transform| transform.translation.xy()),
+        CameraMode::Action => {
+            let mut threats: Vec<_> = asteroids.iter().map(|t| t.translation.xy()).collect();
+            threats.sort_by_key(|position| OrderedFloat(position.distance(planet_position)));
+            let framed: Vec<_> = threats.into_iter().take(ACTION_THREATS).collect();
+            (planet_position + framed.iter().sum::<Vec2>()) / (framed.len() + 1) as f32
+        }
+    };
+
+    for mut transform in &mut camera {
+        let position = transform.translation.xy();
+        let step = 1.0 - (-FOLLOW_RATE * time.delta_seconds()).exp();
+        let moved = position.lerp(target, step);
+        transform.translation = moved.extend(transform.translation.z);
+
+        if follow.mode == CameraMode::Planet && moved.distance(target) < SETTLE_DISTANCE {
+            transform.translation = target.extend(transform.translation.z);
+            follow.settling = false;
+        }
+    }
+}
+
+/// The next run starts with the camera fixed on the planet.
+fn reset_camera_mode(mut follow: ResMut<CameraFollow>) {
+    *follow = CameraFollow::default();
+}
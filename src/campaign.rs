@@ -0,0 +1,475 @@
+//! The campaign, hand-authored levels with a fixed starting fleet,
+//! scripted waves and their own win condition.
+//!
+//! The levels are defined in the `levels.campaign.ron` data file. A level can
+//! place other planets around the home planet, the asteroids head to any of
+//! them and the level is lost as soon as one of them is destroyed.
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::time::Stopwatch;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+
+use crate::accessibility::AccessibleLabel;
+use crate::dice::DiceNumber;
+use crate::event_log::EventLog;
+use crate::layers::RenderLayer;
+use crate::menu::{spawn_menu_button, MenuButton};
+use crate::profile::Profile;
+use crate::ron_asset::RonAssetLoader;
+use crate::shapes;
+use crate::sound::{PlaySoundEvent, Sound};
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::toasts::ToastEvent;
+use crate::waves::{ScriptedWave, Wave, WaveSchedule};
+use crate::{
+    Asteroid, DiceOwnedEvent, FontAssets, GameMode, GameState, PlanetHealth, RunSetup, ShipPower,
+    PLANET_MAX_HEALTH, PLANET_RADIUS,
+};
+
+const MAX_STARS: u8 = 3;
+/// The number of impacts the other planets of a level withstand.
+const LEVEL_PLANET_MAX_HEALTH: u32 = 5;
+const LEVEL_PLANET_COLOR: Color = Color::rgb(0.3, 0.75, 0.45);
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Campaign>()
+            .add_asset_loader(RonAssetLoader::<Campaign>::new(&["campaign.ron"]))
+            .insert_resource(LevelProgress::default())
+            .add_startup_system(load_campaign)
+            .add_system_set(
+                SystemSet::on_enter(GameState::LevelSelect).with_system(setup_level_select),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::LevelSelect)
+                    .with_system(select_levels)
+                    .with_system(highlight_level_buttons),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_level_progress)
+                    .with_system(setup_level_planets)
+                    .with_system(setup_level_goal_indicator),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(track_level_progress)
+                    .with_system(damage_level_planets_on_asteroid_collision)
+                    .with_system(win_level_on_goal_reached.after(track_level_progress)),
+            );
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "3b8f1d62-a4e7-4c19-9f05-6d2e8a7b1c34"]
+pub struct Campaign {
+    levels: Vec<Level>,
+}
+
+impl Campaign {
+    /// The first level is always playable, the next ones once the previous one is cleared.
+    fn is_unlocked(&self, index: usize, profile: &Profile) -> bool {
+        match index.checked_sub(1).and_then(|previous| self.levels.get(previous)) {
+            Some(previous) => profile.level_stars(&previous.name) > 0,
+            None => true,
+        }
+    }
+}
+
+/// The stars earned by clearing a level, depending on the health the planet kept.
+fn stars_for_health(health: u32) -> u8 {
+    if health >= PLANET_MAX_HEALTH {
+        MAX_STARS
+    } else if health * 2 >= PLANET_MAX_HEALTH {
+        2
+    } else {
+        1
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    name: String,
+    description: String,
+    /// The ships the player starts the level with.
+    fleet: Vec<ShipPower>,
+    /// The dice the player starts the level with.
+    #[serde(default)]
+    dice: Vec<DiceNumber>,
+    /// The waves of the level, once they are all played the last one repeats.
+    waves: Vec<ScriptedWave>,
+    /// The positions of the other planets to protect, the home planet is at the center.
+    #[serde(default)]
+    planets: Vec<[f32; 2]>,
+    goal: LevelGoal,
+}
+
+/// What the player must achieve to win a level.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum LevelGoal {
+    /// Clear this number of waves.
+    SurviveWaves(u32),
+    /// Keep the planet alive for this number of seconds.
+    SurviveSeconds(u64),
+    /// Collect this number of dice.
+    CollectDice(u32),
+    /// Keep every planet of the level standing for this number of seconds.
+    ProtectPlanets(u64),
+}
+
+impl LevelGoal {
+    fn is_reached(self, wave: &Wave, progress: &LevelProgress) -> bool {
+        match self {
+            LevelGoal::SurviveWaves(n) => {
+                wave.number > n || (wave.number == n && wave.is_intermission())
+            }
+            LevelGoal::SurviveSeconds(secs) | LevelGoal::ProtectPlanets(secs) => {
+                progress.elapsed.elapsed_secs() >= secs as f32
+            }
+            LevelGoal::CollectDice(n) => progress.dice_collected >= n,
+        }
+    }
+
+    fn description(self, wave: &Wave, progress: &LevelProgress) -> String {
+        match self {
+            LevelGoal::SurviveWaves(n) => {
+                let cleared = if wave.is_intermission() { wave.number } else { wave.number - 1 };
+                format!("Goal: clear {} waves ({}/{})", n, cleared.min(n), n)
+            }
+            LevelGoal::SurviveSeconds(secs) => {
+                let elapsed = (progress.elapsed.elapsed_secs() as u64).min(secs);
+                format!("Goal: survive {}s ({}s left)", secs, secs - elapsed)
+            }
+            LevelGoal::CollectDice(n) => {
+                format!("Goal: collect {} dice ({}/{})", n, progress.dice_collected.min(n), n)
+            }
+            LevelGoal::ProtectPlanets(secs) => {
+                let elapsed = (progress.elapsed.elapsed_secs() as u64).min(secs);
+                format!("Goal: protect the planets for {}s ({}s left)", secs, secs - elapsed)
+            }
+        }
+    }
+}
+
+pub struct CampaignHandle(pub Handle<Campaign>);
+
+/// What the player did in the current level to reach its goal.
+#[derive(Debug, Default)]
+struct LevelProgress {
+    elapsed: Stopwatch,
+    dice_collected: u32,
+}
+
+#[derive(Component, Debug)]
+struct LevelButton(usize);
+
+/// A planet of the level other than the home planet, the asteroids head to it too.
+#[derive(Component, Debug)]
+pub struct LevelPlanet {
+    health: u32,
+}
+
+#[derive(Component, Debug)]
+struct LevelGoalIndicator;
+
+fn load_campaign(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CampaignHandle(asset_server.load("levels.campaign.ron")));
+}
+
+fn setup_level_select(
+    mut commands: Commands,
+    handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    profile: Res<Profile>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    let campaign = campaigns.get(&handle.0);
+    let levels = campaign.map_or(&[][..], |campaign| &campaign.levels[..]);
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(
+                TextBundle::from_section(
+                    "Campaign",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style { margin: UiRect::all(Val::Px(20.0)), ..default() }),
+            );
+
+            for (index, level) in levels.iter().enumerate() {
+                let unlocked = campaign.is_some_and(|c| c.is_unlocked(index, &profile));
+                let status = if !unlocked {
+                    "Locked".to_string()
+                } else {
+                    match profile.level_stars(&level.name) {
+                        0 => "Not cleared yet".to_string(),
+                        stars => format!("{}/{} stars", stars, MAX_STARS),
+                    }
+                };
+
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(480.0), Val::Auto),
+                            margin: UiRect::all(Val::Px(6.0)),
+                            padding: UiRect::all(Val::Px(8.0)),
+                            flex_direction: FlexDirection::ColumnReverse,
+                            ..default()
+                        },
+                        color: palette.button_if(unlocked, Interaction::None).into(),
+                        ..default()
+                    })
+                    .insert(LevelButton(index))
+                    .insert(AccessibleLabel::new(format!("{}, {}", level.name, status)))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            format!("{}. {}", index + 1, level.name),
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 24.0,
+                                color: Color::WHITE,
+                            },
+                        ));
+                        parent.spawn_bundle(TextBundle::from_section(
+                            level.description.as_str(),
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 16.0,
+                                color: Color::GRAY,
+                            },
+                        ));
+                        parent.spawn_bundle(TextBundle::from_section(
+                            status,
+                            TextStyle {
+                                font: font_assets.fira_sans.clone(),
+                                font_size: 16.0,
+                                color: Color::GOLD,
+                            },
+                        ));
+                    });
+            }
+
+            spawn_menu_button(parent, &font_assets, MenuButton::BackToMenu);
+        });
+}
+
+/// Prepare the run with the fleet, the dice and the waves of the clicked level.
+fn select_levels(
+    buttons: Query<(&Interaction, &LevelButton), Changed<Interaction>>,
+    handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    profile: Res<Profile>,
+    mut state: ResMut<State<GameState>>,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+) {
+    let campaign = match campaigns.get(&handle.0) {
+        Some(campaign) => campaign,
+        None => return,
+    };
+
+    for (interaction, LevelButton(index)) in &buttons {
+        if *interaction != Interaction::Clicked || !campaign.is_unlocked(*index, &profile) {
+            continue;
+        }
+
+        if let Some(level) = campaign.levels.get(*index) {
+            // The run setup is changed in place, the run starts in this same frame.
+            *game_mode = GameMode::Campaign(*index);
+            *run_setup =
+                RunSetup { fleet: level.fleet.clone(), dice: level.dice.clone(), seed: None };
+            *schedule = WaveSchedule { waves: level.waves.clone(), ..default() };
+            let _ = state.set(GameState::Playing);
+        }
+    }
+}
+
+fn highlight_level_buttons(
+    handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    profile: Res<Profile>,
+    palette: Res<Palette>,
+    mut buttons: Query<(&Interaction, ChangeTrackers<Interaction>, &LevelButton, &mut UiColor)>,
+) {
+    let campaign = match campaigns.get(&handle.0) {
+        Some(campaign) => campaign,
+        None => return,
+    };
+
+    for (interaction, tracker, LevelButton(index), mut color) in &mut buttons {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button_if(campaign.is_unlocked(*index, &profile), *interaction);
+        }
+    }
+}
+
+fn reset_level_progress(mut progress: ResMut<LevelProgress>) {
+    *progress = LevelProgress::default();
+}
+
+fn setup_level_planets(
+    mut commands: Commands,
+    game_mode: Res<GameMode>,
+    handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let level = match (*game_mode, campaigns.get(&handle.0)) {
+        (GameMode::Campaign(index), Some(campaign)) => match campaign.levels.get(index) {
+            Some(level) => level,
+            None => return,
+        },
+        _ => return,
+    };
+
+    let radius = PLANET_RADIUS * 0.75;
+    for position in &level.planets {
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: meshes.add(shapes::circle(radius)).into(),
+                material: materials.add(ColorMaterial::from(LEVEL_PLANET_COLOR)),
+                transform: Transform::from_translation(
+                    RenderLayer::Planet.at(Vec2::from(*position)),
+                ),
+                ..default()
+            })
+            .insert(LevelPlanet { health: LEVEL_PLANET_MAX_HEALTH })
+            .insert(Collider::ball(radius))
+            .insert(ActiveEvents::COLLISION_EVENTS);
+    }
+}
+
+fn setup_level_goal_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(50.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 16.0,
+                        color: Color::GOLD,
+                    },
+                ))
+                .insert(LevelGoalIndicator);
+        });
+}
+
+fn track_level_progress(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut progress: ResMut<LevelProgress>,
+    mut dice_owned: EventReader<DiceOwnedEvent>,
+) {
+    progress.elapsed.tick(speed.delta(&time));
+    progress.dice_collected += dice_owned.iter().filter(|event| event.is_pickup()).count() as u32;
+}
+
+/// The other planets of a level have no shield, the level is lost once one is destroyed.
+fn damage_level_planets_on_asteroid_collision(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut log: ResMut<EventLog>,
+    mut planets: Query<&mut LevelPlanet>,
+    asteroids: Query<&Transform, With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut state: ResMut<State<GameState>>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let (planet, asteroid) = match (planets.contains(*e1), asteroids.contains(*e2)) {
+                (true, true) => (*e1, *e2),
+                _ if planets.contains(*e2) && asteroids.contains(*e1) => (*e2, *e1),
+                _ => continue,
+            };
+
+            let mut planet = planets.get_mut(planet).unwrap();
+            let position = asteroids.get(asteroid).unwrap().translation.truncate();
+            play_sound.send(PlaySoundEvent::at(Sound::PlanetImpact, position));
+            commands.entity(asteroid).despawn();
+
+            planet.health = planet.health.saturating_sub(1);
+            log.push(&time, format!("Planet hit - {} impacts left", planet.health));
+            if planet.health == 0 && state.set(GameState::GameOver).is_ok() {
+                toasts.send(ToastEvent::warning("A planet of the level was destroyed!"));
+                play_sound.send(PlaySoundEvent::new(Sound::GameOver));
+            }
+        }
+    }
+}
+
+fn win_level_on_goal_reached(
+    game_mode: Res<GameMode>,
+    handle: Res<CampaignHandle>,
+    campaigns: Res<Assets<Campaign>>,
+    wave: Res<Wave>,
+    progress: Res<LevelProgress>,
+    health: Res<PlanetHealth>,
+    mut profile: ResMut<Profile>,
+    mut state: ResMut<State<GameState>>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut indicator: Query<&mut Text, With<LevelGoalIndicator>>,
+) {
+    let level = match (*game_mode, campaigns.get(&handle.0)) {
+        (GameMode::Campaign(index), Some(campaign)) => match campaign.levels.get(index) {
+            Some(level) => level,
+            None => return,
+        },
+        _ => return,
+    };
+
+    for mut text in &mut indicator {
+        let value = level.goal.description(&wave, &progress);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+
+    if level.goal.is_reached(&wave, &progress) {
+        let stars = stars_for_health(health.current);
+        profile.record_level_stars(&level.name, stars);
+        toasts.send(ToastEvent::success(format!(
+            "Level {} cleared with {}/{} stars!",
+            level.name, stars, MAX_STARS
+        )));
+        let _ = state.set(GameState::LevelSelect);
+    }
+}
@@ -0,0 +1,291 @@
+//! The chat and the pings of the co-op runs, to agree with the partner on the
+//! asteroid to intercept.
+//!
+//! The T key opens the chat, Return sends the message and Escape cancels it.
+//! An Alt-click drops a ping, a ring fading after a few seconds on both games.
+//! Neither goes through the lockstep, they don't change the simulation.
+
+use std::collections::VecDeque;
+
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::ui::FocusPolicy;
+
+use crate::layers::RenderLayer;
+use crate::lockstep::LockstepSession;
+use crate::net::NetMessage;
+use crate::{cursor_world_position, shapes, FontAssets, GameState, SpaceCamera};
+
+const CHAT_MAX_LENGTH: usize = 80;
+const CHAT_VISIBLE_LINES: usize = 6;
+const CHAT_LINE_DURATION: f64 = 10.0; // in second
+const PING_DURATION: f32 = 3.0; // in second
+const PING_RADIUS: f32 = 30.0;
+const LOCAL_COLOR: Color = Color::rgb(0.4, 0.9, 1.0);
+const PARTNER_COLOR: Color = Color::rgb(1.0, 0.8, 0.3);
+
+pub struct ChatPlugin;
+
+impl Plugin for ChatPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Chat::default())
+            .add_event::<PartnerEvent>()
+            .add_system_to_stage(CoreStage::PreUpdate, type_chat_message.after(InputSystem))
+            .add_system_to_stage(CoreStage::PreUpdate, ping_on_alt_click.after(InputSystem))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_chat)
+                    .with_system(setup_chat_overlay),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(receive_partner_events)
+                    .with_system(fade_ping_markers)
+                    .with_system(draw_chat.after(receive_partner_events)),
+            );
+    }
+}
+
+/// The chat messages and the pings of the partner, sent by the lockstep.
+pub enum PartnerEvent {
+    Chat(String),
+    Ping(Vec2),
+}
+
+#[derive(Debug, Default)]
+struct Chat {
+    /// The message being typed, none when the chat is closed.
+    typing: Option<String>,
+    lines: VecDeque<ChatLine>,
+}
+
+impl Chat {
+    fn push(&mut self, time: &Time, text: String, from_partner: bool) {
+        if self.lines.len() == CHAT_VISIBLE_LINES {
+            self.lines.pop_front();
+        }
+        let sent = time.seconds_since_startup();
+        self.lines.push_back(ChatLine { text, from_partner, sent });
+    }
+}
+
+#[derive(Debug)]
+struct ChatLine {
+    text: String,
+    from_partner: bool,
+    /// The seconds elapsed since the start of the game when the line was sent.
+    sent: f64,
+}
+
+#[derive(Component, Debug)]
+struct ChatOverlay;
+
+#[derive(Component, Debug)]
+struct PingMarker(Timer);
+
+fn reset_chat(mut chat: ResMut<Chat>) {
+    *chat = Chat::default();
+}
+
+fn setup_chat_overlay(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), top: Val::Px(120.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexEnd,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(ChatOverlay);
+}
+
+/// Type a message while the chat is open, the keys don't reach the game meanwhile.
+fn type_chat_message(
+    session: Option<ResMut<LockstepSession>>,
+    time: Res<Time>,
+    mut chat: ResMut<Chat>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut keys: ResMut<Input<KeyCode>>,
+) {
+    let mut session = match session {
+        Some(session) => session,
+        None => {
+            if chat.typing.is_some() {
+                chat.typing = None;
+            }
+            return;
+        }
+    };
+
+    if chat.typing.is_none() {
+        if keys.just_pressed(KeyCode::T) {
+            keys.clear_just_pressed(KeyCode::T);
+            chat.typing = Some(String::new());
+            // The character of the T key must not start the message.
+            characters.clear();
+        }
+        return;
+    }
+
+    // The chat is only touched on a key press to not redraw it every frame.
+    let typed: Vec<_> = characters.iter().map(|c| c.char).filter(|c| !c.is_control()).collect();
+    if !typed.is_empty() || keys.just_pressed(KeyCode::Back) {
+        if let Some(typing) = &mut chat.typing {
+            if keys.just_pressed(KeyCode::Back) {
+                typing.pop();
+            }
+            let room = CHAT_MAX_LENGTH.saturating_sub(typing.chars().count());
+            typing.extend(typed.into_iter().take(room));
+        }
+    }
+
+    if keys.just_pressed(KeyCode::Return) {
+        let text = chat.typing.take().unwrap_or_default().trim().to_string();
+        if !text.is_empty() {
+            session.send(&NetMessage::Chat(text.clone()));
+            chat.push(&time, text, false);
+        }
+    } else if keys.just_pressed(KeyCode::Escape) {
+        chat.typing = None;
+    }
+
+    keys.clear();
+}
+
+/// Drop a ping where the player Alt-clicks, the click doesn't reach the game.
+fn ping_on_alt_click(
+    mut commands: Commands,
+    session: Option<ResMut<LockstepSession>>,
+    keys: Res<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    wnds: Res<Windows>,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let mut session = match session {
+        Some(session) => session,
+        None => return,
+    };
+
+    let alt = keys.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if !alt || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    buttons.clear_just_pressed(MouseButton::Left);
+
+    let (camera, camera_transform) = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    if let Some(position) = cursor_world_position(&wnds, camera, camera_transform) {
+        session.send(&NetMessage::Ping(position.to_array()));
+        spawn_ping_marker(&mut commands, &mut meshes, &mut materials, position, LOCAL_COLOR);
+    }
+}
+
+fn receive_partner_events(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut events: EventReader<PartnerEvent>,
+    mut chat: ResMut<Chat>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for event in events.iter() {
+        match event {
+            PartnerEvent::Chat(text) => chat.push(&time, text.clone(), true),
+            PartnerEvent::Ping(position) => {
+                spawn_ping_marker(
+                    &mut commands,
+                    &mut meshes,
+                    &mut materials,
+                    *position,
+                    PARTNER_COLOR,
+                );
+            }
+        }
+    }
+}
+
+fn spawn_ping_marker(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    position: Vec2,
+    color: Color,
+) {
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(shapes::ring(PING_RADIUS, 4.0)).into(),
+            material: materials.add(ColorMaterial::from(color)),
+            transform: Transform::from_translation(RenderLayer::Overlays.at(position)),
+            ..default()
+        })
+        .insert(PingMarker(Timer::from_seconds(PING_DURATION, false)));
+}
+
+fn fade_ping_markers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut markers: Query<(Entity, &mut PingMarker, &Handle<ColorMaterial>)>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (entity, mut marker, material) in &mut markers {
+        if marker.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        } else if let Some(material) = materials.get_mut(material) {
+            material.color.set_a(marker.0.percent_left());
+        }
+    }
+}
+
+/// Redraw the lines when a message arrives, is typed or expires.
+fn draw_chat(
+    mut commands: Commands,
+    time: Res<Time>,
+    font_assets: Res<FontAssets>,
+    mut chat: ResMut<Chat>,
+    overlay: Query<Entity, With<ChatOverlay>>,
+) {
+    let now = time.seconds_since_startup();
+    let expired = |line: &ChatLine| now - line.sent >= CHAT_LINE_DURATION;
+    if chat.lines.iter().any(expired) {
+        chat.lines.retain(|line| !expired(line));
+    }
+
+    if !chat.is_changed() {
+        return;
+    }
+
+    let text_style =
+        |color| TextStyle { font: font_assets.fira_sans.clone(), font_size: 16.0, color };
+    for overlay in &overlay {
+        commands.entity(overlay).despawn_descendants();
+        commands.entity(overlay).with_children(|parent| {
+            for line in &chat.lines {
+                let (name, color) = if line.from_partner {
+                    ("Partner", PARTNER_COLOR)
+                } else {
+                    ("You", LOCAL_COLOR)
+                };
+                parent.spawn_bundle(TextBundle::from_section(
+                    format!("{}: {}", name, line.text),
+                    text_style(color),
+                ));
+            }
+            if let Some(typing) = &chat.typing {
+                parent.spawn_bundle(TextBundle::from_section(
+                    format!("> {}_", typing),
+                    text_style(Color::WHITE),
+                ));
+            }
+        });
+    }
+}
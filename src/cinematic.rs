@@ -0,0 +1,246 @@
+//! The intro cinematic played before the first wave of every run, the camera
+//! sweeps in from deep space while the fleet warps in around the planet.
+//!
+//! The cinematic is pushed on top of the run, that is paused until the end
+//! of the timeline or until the player skips it.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy_rapier2d::prelude::*;
+use bevy_tweening::lens::{TextColorLens, TransformPositionLens};
+use bevy_tweening::{Animator, Delay, EaseFunction, Lens, Tween, TweeningType};
+
+use crate::headless::Headless;
+use crate::{FontAssets, GameState, Ship, SpaceCamera};
+
+const CAMERA_START_POSITION: Vec2 = Vec2::new(0.0, 2400.0);
+const CAMERA_START_SCALE: f32 = 6.0;
+const WARP_DISTANCE: f32 = 8.0; // in multiple of the distance to the planet
+const WARP_INTERVAL: u64 = 250; // in millisecond, between two ships
+
+/// The steps of the intro, started at the given time from the beginning of it.
+const INTRO_TIMELINE: [(u64, CinematicStep); 3] = [
+    (0, CinematicStep::CameraSweep { duration: 3000 }),
+    (500, CinematicStep::WarpInShips { duration: 800 }),
+    (2500, CinematicStep::TitleCard { fade: 500, hold: 1500 }),
+];
+const INTRO_DURATION: u64 = 5200; // in millisecond
+
+pub struct CinematicPlugin;
+
+impl Plugin for CinematicPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Cinematic::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(queue_intro_cinematic),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(start_intro_cinematic),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Cinematic).with_system(pause_physics))
+            .add_system_set(
+                SystemSet::on_update(GameState::Cinematic)
+                    .with_system(play_cinematic_timeline)
+                    .with_system(end_cinematic.after(play_cinematic_timeline)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Cinematic)
+                    .with_system(finish_cinematic_tweens)
+                    .with_system(resume_physics),
+            );
+    }
+}
+
+/// A step of the timeline, the durations are in millisecond.
+#[derive(Debug, Clone, Copy)]
+enum CinematicStep {
+    /// The camera moves from deep space down to the planet, zooming in.
+    CameraSweep { duration: u64 },
+    /// The ships of the fleet warp in one after the other to their place.
+    WarpInShips { duration: u64 },
+    /// The title card fades in, stays on screen and fades out.
+    TitleCard { fade: u64, hold: u64 },
+}
+
+#[derive(Debug, Default)]
+struct Cinematic {
+    /// Whether the intro starts in the next frame of the run.
+    pending: bool,
+    elapsed: Stopwatch,
+    next_step: usize,
+}
+
+/// The transform an entity ends the cinematic with, set right away when it is skipped.
+#[derive(Component, Debug)]
+struct CinematicTarget(Transform);
+
+#[derive(Component, Debug)]
+struct TitleCard;
+
+/// Interpolates both the position and the zoom of the camera.
+struct CameraSweepLens {
+    start: Transform,
+    end: Transform,
+}
+
+impl Lens<Transform> for CameraSweepLens {
+    fn lerp(&mut self, target: &mut Transform, ratio: f32) {
+        target.translation = self.start.translation.lerp(self.end.translation, ratio);
+        target.scale = self.start.scale.lerp(self.end.scale, ratio);
+    }
+}
+
+/// Nobody watches the headless runs, they start right away.
+fn queue_intro_cinematic(mut cinematic: ResMut<Cinematic>, headless: Option<Res<Headless>>) {
+    *cinematic = Cinematic { pending: headless.is_none(), ..default() };
+}
+
+/// The state can't be pushed while the run is being entered, it is in its first frame.
+fn start_intro_cinematic(mut cinematic: ResMut<Cinematic>, mut state: ResMut<State<GameState>>) {
+    if cinematic.pending && state.push(GameState::Cinematic).is_ok() {
+        cinematic.pending = false;
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+/// Start the steps of the timeline once their time has come.
+fn play_cinematic_timeline(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut cinematic: ResMut<Cinematic>,
+    camera: Query<(Entity, &Transform), With<SpaceCamera>>,
+    ships: Query<(Entity, &Transform), With<Ship>>,
+    font_assets: Res<FontAssets>,
+) {
+    cinematic.elapsed.tick(time.delta());
+
+    while let Some((start, step)) = INTRO_TIMELINE.get(cinematic.next_step) {
+        if cinematic.elapsed.elapsed() < Duration::from_millis(*start) {
+            break;
+        }
+        cinematic.next_step += 1;
+
+        match *step {
+            CinematicStep::CameraSweep { duration } => {
+                for (entity, transform) in &camera {
+                    // The camera stays in front of the layers of the world.
+                    let start = CAMERA_START_POSITION.extend(transform.translation.z);
+                    let start = Transform::from_translation(start)
+                        .with_scale(Vec3::splat(CAMERA_START_SCALE));
+                    commands.entity(entity).insert(CinematicTarget(*transform)).insert(
+                        Animator::new(Tween::new(
+                            EaseFunction::QuadraticOut,
+                            TweeningType::Once,
+                            Duration::from_millis(duration),
+                            CameraSweepLens { start, end: *transform },
+                        )),
+                    );
+                }
+            }
+            CinematicStep::WarpInShips { duration } => {
+                for (i, (entity, transform)) in ships.iter().enumerate() {
+                    let end = transform.translation;
+                    let start = (end.truncate() * WARP_DISTANCE).extend(end.z);
+                    let delay = Duration::from_millis(i as u64 * WARP_INTERVAL);
+                    commands
+                        .entity(entity)
+                        .insert(CinematicTarget(*transform))
+                        .insert(Transform { translation: start, ..*transform })
+                        .insert(Animator::new(Delay::new(delay).then(Tween::new(
+                            EaseFunction::ExponentialOut,
+                            TweeningType::Once,
+                            Duration::from_millis(duration),
+                            TransformPositionLens { start, end },
+                        ))));
+                }
+            }
+            CinematicStep::TitleCard { fade, hold } => {
+                spawn_title_card(&mut commands, &font_assets, fade, hold);
+            }
+        }
+    }
+}
+
+fn spawn_title_card(commands: &mut Commands, font_assets: &FontAssets, fade: u64, hold: u64) {
+    let fade_tween = |start, end| {
+        Tween::new(
+            EaseFunction::QuadraticInOut,
+            TweeningType::Once,
+            Duration::from_millis(fade),
+            TextColorLens { start, end, section: 0 },
+        )
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(TitleCard)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "Combine and Defend",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 64.0,
+                        color: Color::NONE,
+                    },
+                ))
+                .insert(Animator::new(
+                    fade_tween(Color::NONE, Color::WHITE)
+                        .then(Delay::new(Duration::from_millis(hold)))
+                        .then(fade_tween(Color::WHITE, Color::NONE)),
+                ));
+        });
+}
+
+/// The run starts at the end of the timeline or as soon as the player skips the intro.
+fn end_cinematic(
+    mut keys: ResMut<Input<KeyCode>>,
+    mut buttons: ResMut<Input<MouseButton>>,
+    cinematic: Res<Cinematic>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let skipped = keys.just_pressed(KeyCode::Return)
+        || keys.just_pressed(KeyCode::Escape)
+        || buttons.just_pressed(MouseButton::Left);
+    if skipped || cinematic.elapsed.elapsed() >= Duration::from_millis(INTRO_DURATION) {
+        // The inputs are consumed to not start the first wave in the resumed run.
+        keys.clear_just_pressed(KeyCode::Return);
+        keys.clear_just_pressed(KeyCode::Escape);
+        buttons.clear_just_pressed(MouseButton::Left);
+        let _ = state.pop();
+    }
+}
+
+/// Stop the tweens where they are and put every entity at its final place.
+fn finish_cinematic_tweens(
+    mut commands: Commands,
+    mut targets: Query<(Entity, &CinematicTarget, &mut Transform)>,
+    title_card: Query<Entity, With<TitleCard>>,
+) {
+    for (entity, CinematicTarget(target), mut transform) in &mut targets {
+        *transform = *target;
+        commands.entity(entity).remove::<CinematicTarget>().remove::<Animator<Transform>>();
+    }
+
+    title_card.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
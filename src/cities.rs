@@ -0,0 +1,126 @@
+//! The cities on the surface of the planet, drawn as twinkling lights.
+//!
+//! Every impact the shield doesn't absorb ruins the standing city closest to it,
+//! the health of the planet is the number of cities still standing and the run
+//! is lost once they are all ruined. The standing cities raise dice at the end
+//! of every wave, the fewer the cities the fewer the dice.
+
+use std::f32::consts::PI;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use ordered_float::OrderedFloat;
+
+use crate::dice::DiceNumber;
+use crate::toasts::ToastEvent;
+use crate::waves::WaveEvent;
+use crate::{
+    DiceOwnedEvent, GameRng, GameState, Planet, PlanetHealth, PlanetImpactEvent, PLANET_MAX_HEALTH,
+    PLANET_RADIUS,
+};
+
+const CITY_SIZE: f32 = 6.0;
+/// How far under the surface of the planet the cities are built.
+const CITY_DEPTH: f32 = 8.0;
+const CITY_LIGHTS_COLOR: Color = Color::rgb(1.0, 0.85, 0.4);
+const RUINS_COLOR: Color = Color::rgb(0.3, 0.12, 0.1);
+/// How fast the lights of the cities twinkle, in radian per second.
+const LIGHTS_TWINKLE_SPEED: f32 = 3.0;
+/// The standing cities raise one dice for every this many of them, rounded up.
+const CITIES_BY_INCOME_DICE: u32 = 4;
+
+pub struct CitiesPlugin;
+
+impl Plugin for CitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(build_cities_on_planets)
+                .with_system(ruin_cities_on_planet_impact)
+                .with_system(twinkle_city_lights)
+                .with_system(raise_dice_from_cities),
+        );
+    }
+}
+
+#[derive(Component, Debug)]
+struct City {
+    standing: bool,
+}
+
+/// The cities are spread evenly around the surface, as many as the health of the planet.
+fn build_cities_on_planets(mut commands: Commands, planets: Query<Entity, Added<Planet>>) {
+    for planet in &planets {
+        commands.entity(planet).with_children(|parent| {
+            for i in 0..PLANET_MAX_HEALTH {
+                let angle = i as f32 / PLANET_MAX_HEALTH as f32 * PI * 2.0;
+                let position = Vec2::new(angle.cos(), angle.sin()) * (PLANET_RADIUS - CITY_DEPTH);
+                parent
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            color: CITY_LIGHTS_COLOR,
+                            custom_size: Some(Vec2::splat(CITY_SIZE)),
+                            ..default()
+                        },
+                        // The cities are drawn on the planet.
+                        transform: Transform::from_translation(position.extend(1.0)),
+                        ..default()
+                    })
+                    .insert(City { standing: true });
+            }
+        });
+    }
+}
+
+fn ruin_cities_on_planet_impact(
+    mut planet_impacts: EventReader<PlanetImpactEvent>,
+    mut cities: Query<(&GlobalTransform, &mut City, &mut Sprite)>,
+) {
+    for PlanetImpactEvent { shielded, position } in planet_impacts.iter() {
+        if *shielded {
+            continue;
+        }
+
+        let closest =
+            cities.iter_mut().filter(|(_, city, _)| city.standing).min_by_key(|(transform, ..)| {
+                OrderedFloat(transform.translation().xy().distance(*position))
+            });
+        if let Some((_, mut city, mut sprite)) = closest {
+            city.standing = false;
+            sprite.color = RUINS_COLOR;
+        }
+    }
+}
+
+fn twinkle_city_lights(time: Res<Time>, mut cities: Query<(&City, &mut Sprite)>) {
+    let elapsed = time.seconds_since_startup() as f32 * LIGHTS_TWINKLE_SPEED;
+    for (i, (city, mut sprite)) in cities.iter_mut().enumerate() {
+        if city.standing {
+            // Every city twinkles out of step with the others.
+            let brightness = 0.75 + 0.25 * (elapsed + i as f32 * 1.7).sin();
+            sprite.color.set_a(brightness);
+        }
+    }
+}
+
+/// The raised dice go straight into the bag, owned by no player they are not
+/// picked up and count neither in the score nor in the goals of the run.
+fn raise_dice_from_cities(
+    health: Res<PlanetHealth>,
+    mut rng: ResMut<GameRng>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut dice_owned: EventWriter<DiceOwnedEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    for event in wave_events.iter() {
+        if let WaveEvent::Cleared(_) = event {
+            let dice = health.current.div_ceil(CITIES_BY_INCOME_DICE);
+            for _ in 0..dice {
+                dice_owned.send(DiceOwnedEvent(DiceNumber::from_rng(&mut *rng), None));
+            }
+            if dice > 0 {
+                toasts.send(ToastEvent::success(format!("The cities raised {} dice", dice)));
+            }
+        }
+    }
+}
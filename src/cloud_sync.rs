@@ -0,0 +1,231 @@
+//! The sync of the profile with a WebDAV server, for the players who switch
+//! machines, only built with the `cloud-sync` feature.
+//!
+//! The server is configured in `cloud.ron`. The profile is downloaded when the
+//! game starts, the most recently changed of the local and the remote profiles
+//! wins, and it is uploaded every time it changes. The requests are made on
+//! their own threads to never block the frame.
+//!
+//! Only plain HTTP is supported, the credentials are then only sent to a server
+//! on this machine, like a tunnel doing the TLS to the real server. The S3
+//! endpoints are not supported, their requests must be signed.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::profile::Profile;
+use crate::save::load_ron_file;
+
+const CLOUD_CONFIG_PATH: &str = "cloud.ron";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct CloudSyncPlugin;
+
+impl Plugin for CloudSyncPlugin {
+    fn build(&self, app: &mut App) {
+        let config = load_ron_file::<CloudConfig>(CLOUD_CONFIG_PATH);
+        let endpoint = config.url.as_ref().map(|url| Endpoint::new(url, &config));
+        match endpoint {
+            Some(Ok(endpoint)) => {
+                app.insert_resource(CloudSync::start(endpoint))
+                    .add_system(apply_downloaded_profile)
+                    .add_system(upload_profile_on_change.after(apply_downloaded_profile));
+            }
+            Some(Err(e)) => warn!("The cloud sync is disabled: {}", e),
+            None => (),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CloudConfig {
+    /// The URL of the profile on the server, `http://host[:port]/path/profile.ron`.
+    #[serde(default)]
+    url: Option<String>,
+    /// Only sent to a server on this machine, see the module documentation.
+    #[serde(default)]
+    username: String,
+    #[serde(default)]
+    password: String,
+}
+
+/// Where the profile is stored on the server.
+#[derive(Debug, Clone)]
+struct Endpoint {
+    /// The host and the port, as written in the `Host` header.
+    host: String,
+    path: String,
+    authorization: Option<String>,
+}
+
+impl Endpoint {
+    fn new(url: &str, config: &CloudConfig) -> io::Result<Endpoint> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            io::Error::new(ErrorKind::InvalidInput, "only the http:// URLs are supported")
+        })?;
+        let (host, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        if !config.username.is_empty() && !is_loopback(host) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "the credentials would be sent in cleartext to a remote server",
+            ));
+        }
+        let authorization = (!config.username.is_empty()).then(|| {
+            let credentials = format!("{}:{}", config.username, config.password);
+            format!("Basic {}", base64::encode(credentials))
+        });
+        Ok(Endpoint { host: host.to_string(), path: path.to_string(), authorization })
+    }
+
+    /// Send a request and return the status code and the body of the response.
+    fn request(&self, method: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let address = if self.host.contains(':') {
+            self.host.to_socket_addrs()
+        } else {
+            (self.host.as_str(), 80).to_socket_addrs()
+        }?
+        .next()
+        .ok_or(ErrorKind::AddrNotAvailable)?;
+
+        let mut stream = TcpStream::connect_timeout(&address, REQUEST_TIMEOUT)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+
+        // HTTP/1.0 for the server to close the connection after a body that is never chunked.
+        let mut head = format!(
+            "{} {} HTTP/1.0\r\nHost: {}\r\nContent-Length: {}\r\n",
+            method,
+            self.path,
+            self.host,
+            body.len()
+        );
+        if let Some(authorization) = &self.authorization {
+            head.push_str(&format!("Authorization: {}\r\n", authorization));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "invalid HTTP response");
+        let end = response.windows(4).position(|w| w == b"\r\n\r\n").ok_or_else(invalid)?;
+        let status = std::str::from_utf8(&response[..end])
+            .ok()
+            .and_then(|head| head.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(invalid)?;
+        Ok((status, response[end + 4..].to_vec()))
+    }
+
+    /// The profile saved on the server, none when there is no profile yet.
+    fn download(&self) -> io::Result<Option<Profile>> {
+        match self.request("GET", &[])? {
+            (200, body) => ron::de::from_bytes(&body)
+                .map(Some)
+                .map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            (404, _) => Ok(None),
+            (status, _) => {
+                Err(io::Error::other(format!("the server answered {} to the download", status)))
+            }
+        }
+    }
+
+    fn upload(&self, content: String) -> io::Result<()> {
+        match self.request("PUT", content.as_bytes())? {
+            (200..=299, _) => Ok(()),
+            (status, _) => {
+                Err(io::Error::other(format!("the server answered {} to the upload", status)))
+            }
+        }
+    }
+}
+
+/// Whether the host, with or without its port, is this machine.
+fn is_loopback(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _otherwise => host,
+    };
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+enum Download {
+    Running,
+    Done(Option<Profile>),
+    /// The profile is never uploaded after a failed download, it could erase a more recent one.
+    Failed,
+    Applied,
+}
+
+struct CloudSync {
+    endpoint: Endpoint,
+    download: Arc<Mutex<Download>>,
+}
+
+impl CloudSync {
+    fn start(endpoint: Endpoint) -> CloudSync {
+        let download = Arc::new(Mutex::new(Download::Running));
+        let thread_endpoint = endpoint.clone();
+        let thread_download = download.clone();
+        thread::spawn(move || {
+            let result = match thread_endpoint.download() {
+                Ok(profile) => Download::Done(profile),
+                Err(e) => {
+                    warn!("Could not download the profile: {}", e);
+                    Download::Failed
+                }
+            };
+            *thread_download.lock().unwrap() = result;
+        });
+        CloudSync { endpoint, download }
+    }
+
+    fn upload(&self, profile: &Profile) {
+        let content = match ron::ser::to_string_pretty(profile, ron::ser::PrettyConfig::default()) {
+            Ok(content) => content,
+            Err(e) => return warn!("Could not serialize the profile: {}", e),
+        };
+        let endpoint = self.endpoint.clone();
+        thread::spawn(move || {
+            if let Err(e) = endpoint.upload(content) {
+                warn!("Could not upload the profile: {}", e);
+            }
+        });
+    }
+}
+
+/// Keep the most recently changed profile once it is downloaded.
+fn apply_downloaded_profile(sync: Res<CloudSync>, mut profile: ResMut<Profile>) {
+    let mut download = sync.download.lock().unwrap();
+    if !matches!(*download, Download::Done(_)) {
+        return;
+    }
+
+    match std::mem::replace(&mut *download, Download::Applied) {
+        Download::Done(Some(remote)) if remote.updated_at() > profile.updated_at() => {
+            info!("The profile of the server is more recent, it replaces the local one");
+            *profile = remote;
+        }
+        // The local profile wins, the server gets it.
+        _otherwise => sync.upload(&profile),
+    }
+}
+
+fn upload_profile_on_change(sync: Res<CloudSync>, profile: Res<Profile>) {
+    let applied = matches!(*sync.download.lock().unwrap(), Download::Applied);
+    if applied && profile.is_changed() && !profile.is_added() {
+        sync.upload(&profile);
+    }
+}
@@ -0,0 +1,386 @@
+//! The input map of the actions of a run and the controls screen rebinding them.
+//!
+//! Every action has a default binding, only the rebound actions are saved in the
+//! settings file. An action is bound to a key, a mouse button or a gamepad button,
+//! the left mouse button stays the pointer and a tap presses it as well. The text
+//! entries, the photo mode and the menus keep their own keys.
+
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+use bevy::ecs::system::SystemParam;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::Ability;
+use crate::accessibility::AccessibleLabel;
+use crate::inventory::CONSUMABLE_HOTBAR_SIZE;
+use crate::menu::{spawn_menu_screen, MenuButton};
+use crate::settings::GameSettings;
+use crate::theme::Palette;
+use crate::toasts::ToastEvent;
+use crate::touch::TouchGestures;
+use crate::{cursor_world_position, screen_to_world_position, FontAssets, GameState};
+
+const CONFLICT_COLOR: Color = Color::rgb(1.0, 0.4, 0.3);
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Rebinding::default())
+            // The captured input is consumed before the game sees it.
+            .add_system_to_stage(CoreStage::PreUpdate, capture_binding.after(InputSystem))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Controls).with_system(setup_controls_screen),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Controls).with_system(stop_rebinding))
+            .add_system_set(
+                SystemSet::on_update(GameState::Controls)
+                    .with_system(press_control_buttons)
+                    .with_system(draw_control_buttons.after(press_control_buttons))
+                    .with_system(highlight_control_buttons),
+            );
+    }
+}
+
+/// What the player can do during a run with a key or a button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Action {
+    Ability(Ability),
+    /// Use the consumable of this slot of the inventory.
+    Consumable(usize),
+    SkipIntermission,
+    ToggleInventory,
+    ToggleEventLog,
+    ToggleMute,
+    CycleCameraMode,
+}
+
+impl Action {
+    pub fn all() -> Vec<Action> {
+        let mut actions: Vec<_> = Ability::ALL.into_iter().map(Action::Ability).collect();
+        actions.extend((0..CONSUMABLE_HOTBAR_SIZE).map(Action::Consumable));
+        actions.extend([
+            Action::SkipIntermission,
+            Action::ToggleInventory,
+            Action::ToggleEventLog,
+            Action::ToggleMute,
+            Action::CycleCameraMode,
+        ]);
+        actions
+    }
+
+    fn label(self) -> String {
+        match self {
+            Action::Ability(ability) => ability.label().to_string(),
+            Action::Consumable(slot) => format!("Consumable slot {}", slot + 1),
+            Action::SkipIntermission => "Skip the intermission".to_string(),
+            Action::ToggleInventory => "Inventory".to_string(),
+            Action::ToggleEventLog => "Event log".to_string(),
+            Action::ToggleMute => "Mute".to_string(),
+            Action::CycleCameraMode => "Camera mode".to_string(),
+        }
+    }
+
+    fn default_binding(self) -> Binding {
+        let key = match self {
+            Action::Ability(Ability::Shockwave) => KeyCode::Key1,
+            Action::Ability(Ability::SpeedBoost) => KeyCode::Key2,
+            Action::Ability(Ability::ShieldRefill) => KeyCode::Key3,
+            Action::Ability(Ability::TimeStop) => KeyCode::Key4,
+            Action::Consumable(slot) => [KeyCode::Q, KeyCode::W, KeyCode::E][slot % 3],
+            Action::SkipIntermission => KeyCode::Return,
+            Action::ToggleInventory => KeyCode::I,
+            Action::ToggleEventLog => KeyCode::L,
+            Action::ToggleMute => KeyCode::M,
+            Action::CycleCameraMode => KeyCode::C,
+        };
+        Binding::Key(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    fn label(self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(MouseButton::Other(button)) => format!("Mouse {}", button),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Gamepad {:?}", button),
+        }
+    }
+}
+
+/// The actions bound to something else than their default binding.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    rebound: BTreeMap<Action, Binding>,
+}
+
+impl InputMap {
+    pub fn binding(&self, action: Action) -> Binding {
+        self.rebound.get(&action).copied().unwrap_or_else(|| action.default_binding())
+    }
+
+    fn rebind(&mut self, action: Action, binding: Binding) {
+        if binding == action.default_binding() {
+            self.rebound.remove(&action);
+        } else {
+            self.rebound.insert(action, binding);
+        }
+    }
+
+    /// The other actions sharing the binding of this action.
+    fn conflicts(&self, action: Action) -> Vec<Action> {
+        let binding = self.binding(action);
+        Action::all().into_iter().filter(|a| *a != action && self.binding(*a) == binding).collect()
+    }
+}
+
+/// Reads the actions pressed with the bindings of the settings.
+#[derive(SystemParam)]
+pub struct Actions<'w, 's> {
+    settings: Res<'w, GameSettings>,
+    keys: Res<'w, Input<KeyCode>>,
+    mouse_buttons: Res<'w, Input<MouseButton>>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepads: Res<'w, Gamepads>,
+    windows: Res<'w, Windows>,
+    touches: Res<'w, TouchGestures>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Actions<'_, '_> {
+    pub fn just_pressed(&self, action: Action) -> bool {
+        match self.settings.controls.binding(action) {
+            Binding::Key(key) => self.keys.just_pressed(key),
+            Binding::Mouse(button) => self.mouse_buttons.just_pressed(button),
+            Binding::Gamepad(button) => self.gamepads.iter().any(|gamepad| {
+                self.gamepad_buttons.just_pressed(GamepadButton::new(*gamepad, button))
+            }),
+        }
+    }
+
+    /// The world positions pressed by the left mouse button or tapped this frame.
+    pub fn pointer_presses(
+        &self,
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+    ) -> Vec<Vec2> {
+        let mut presses: Vec<_> = self
+            .touches
+            .taps
+            .iter()
+            .filter_map(|tap| {
+                screen_to_world_position(&self.windows, camera, camera_transform, *tap)
+            })
+            .collect();
+        if self.mouse_buttons.just_pressed(MouseButton::Left) {
+            presses.extend(cursor_world_position(&self.windows, camera, camera_transform));
+        }
+        presses
+    }
+}
+
+/// The action waiting for its new binding, the next input pressed.
+#[derive(Debug, Default)]
+struct Rebinding(Option<Action>);
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum ControlButton {
+    Rebind(Action),
+    Reset,
+}
+
+fn setup_controls_screen(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    let hint = "Click an action and press its new key or button, Escape cancels".to_string();
+    spawn_menu_screen(&mut commands, &font_assets, "Controls", &[hint], &[MenuButton::Settings]);
+
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+    let button = |width: f32| ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Px(32.0)),
+            margin: UiRect::all(Val::Px(3.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        color: palette.button.into(),
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for action in Action::all() {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style { align_items: AlignItems::Center, ..default() },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn_bundle(
+                            TextBundle::from_section(action.label(), text_style.clone())
+                                .with_style(Style {
+                                    size: Size::new(Val::Px(220.0), Val::Auto),
+                                    ..default()
+                                }),
+                        );
+                        row.spawn_bundle(button(200.0))
+                            .insert(ControlButton::Rebind(action))
+                            .insert(AccessibleLabel::new(format!("Rebind {}", action.label())))
+                            .with_children(|button| {
+                                button
+                                    .spawn_bundle(TextBundle::from_section("", text_style.clone()));
+                            });
+                    });
+            }
+
+            parent
+                .spawn_bundle(button(220.0))
+                .insert(ControlButton::Reset)
+                .insert(AccessibleLabel::new("Reset the controls to their defaults"))
+                .with_children(|button| {
+                    button.spawn_bundle(TextBundle::from_section(
+                        "Reset to defaults",
+                        text_style.clone(),
+                    ));
+                });
+        });
+}
+
+fn stop_rebinding(mut rebinding: ResMut<Rebinding>) {
+    *rebinding = Rebinding::default();
+}
+
+/// Bind the waiting action to the first key or button pressed.
+fn capture_binding(
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<GameSettings>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut mouse_buttons: ResMut<Input<MouseButton>>,
+    mut gamepad_buttons: ResMut<Input<GamepadButton>>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    let action = match rebinding.0 {
+        Some(action) => action,
+        None => return,
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        keys.clear_just_pressed(KeyCode::Escape);
+        rebinding.0 = None;
+        return;
+    }
+
+    let key = keys.get_just_pressed().next().copied();
+    let mouse_button = mouse_buttons.get_just_pressed().find(|b| **b != MouseButton::Left).copied();
+    let gamepad_button = gamepad_buttons.get_just_pressed().next().copied();
+    let binding = if let Some(key) = key {
+        keys.clear_just_pressed(key);
+        Binding::Key(key)
+    } else if let Some(button) = mouse_button {
+        mouse_buttons.clear_just_pressed(button);
+        Binding::Mouse(button)
+    } else if let Some(button) = gamepad_button {
+        gamepad_buttons.clear_just_pressed(button);
+        Binding::Gamepad(button.button_type)
+    } else {
+        return;
+    };
+
+    rebinding.0 = None;
+    settings.controls.rebind(action, binding);
+    for other in settings.controls.conflicts(action) {
+        let message = format!("{} is also bound to {}", binding.label(), other.label());
+        toasts.send(ToastEvent::warning(message));
+    }
+}
+
+fn press_control_buttons(
+    buttons: Query<(&Interaction, &ControlButton), Changed<Interaction>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<GameSettings>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            ControlButton::Rebind(action) => rebinding.0 = Some(*action),
+            ControlButton::Reset => {
+                rebinding.0 = None;
+                settings.controls = InputMap::default();
+            }
+        }
+    }
+}
+
+/// The bindings are shown on their buttons, in red when shared by many actions.
+fn draw_control_buttons(
+    settings: Res<GameSettings>,
+    rebinding: Res<Rebinding>,
+    added: Query<(), Added<ControlButton>>,
+    buttons: Query<(&ControlButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() && !rebinding.is_changed() && added.is_empty() {
+        return;
+    }
+
+    for (button, children) in &buttons {
+        let action = match button {
+            ControlButton::Rebind(action) => *action,
+            ControlButton::Reset => continue,
+        };
+        let (value, color) = if rebinding.0 == Some(action) {
+            ("Press a key...".to_string(), Color::YELLOW)
+        } else if settings.controls.conflicts(action).is_empty() {
+            (settings.controls.binding(action).label(), Color::WHITE)
+        } else {
+            (settings.controls.binding(action).label(), CONFLICT_COLOR)
+        };
+
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = value.clone();
+                text.sections[0].style.color = color;
+            }
+        }
+    }
+}
+
+fn highlight_control_buttons(
+    palette: Res<Palette>,
+    mut buttons: Query<(&Interaction, &mut UiColor), (With<ControlButton>, Changed<Interaction>)>,
+) {
+    for (interaction, mut color) in &mut buttons {
+        *color = palette.button(*interaction).into();
+    }
+}
@@ -0,0 +1,274 @@
+//! The crafting panel of the intermissions, turning dice and scrap into consumables.
+//!
+//! The recipes are defined in the `consumables.crafting.ron` data file so they can be modded.
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::ui::FocusPolicy;
+use serde::Deserialize;
+
+use crate::dice::{DiceBag, DiceNumber};
+use crate::inventory::{Consumable, Inventory};
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::ron_asset::RonAssetLoader;
+use crate::scrap::Scrap;
+use crate::theme::Palette;
+use crate::waves::Wave;
+use crate::{FontAssets, GameState, ImageAssets};
+
+pub struct CraftingPlugin;
+
+impl Plugin for CraftingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<CraftingRecipes>()
+            .add_asset_loader(RonAssetLoader::<CraftingRecipes>::new(&["crafting.ron"]))
+            .add_startup_system(load_crafting_recipes)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(spawn_crafting_panel),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(respawn_crafting_panel_on_reload)
+                    .with_system(
+                        show_crafting_panel_during_intermission
+                            .after(respawn_crafting_panel_on_reload),
+                    )
+                    .with_system(press_craft_buttons)
+                    .with_system(craft_consumables)
+                    .with_system(grey_out_unaffordable_crafts),
+            );
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "c4a1e7b3-2f9d-4b6e-8d15-7e3a9c0f5b28"]
+pub struct CraftingRecipes {
+    recipes: Vec<CraftingRecipe>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CraftingRecipe {
+    name: String,
+    dice: Vec<DiceNumber>,
+    scrap: u32,
+    output: Consumable,
+}
+
+impl CraftingRecipe {
+    fn is_affordable(&self, dice_bag: &DiceBag, scrap: &Scrap) -> bool {
+        dice_bag.contains_combo(&self.dice) && scrap.0 >= self.scrap
+    }
+}
+
+pub struct CraftingRecipesHandle(pub Handle<CraftingRecipes>);
+
+/// The panel listing the recipes, rebuilt every time the recipes are (re)loaded.
+#[derive(Component, Debug)]
+struct CraftingPanel;
+
+/// The index of the recipe a button crafts.
+#[derive(Component, Debug)]
+struct CraftButton(usize);
+
+fn load_crafting_recipes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(CraftingRecipesHandle(asset_server.load("consumables.crafting.ron")));
+}
+
+fn spawn_crafting_panel(
+    mut commands: Commands,
+    recipes: Res<Assets<CraftingRecipes>>,
+    handle: Res<CraftingRecipesHandle>,
+    wave: Res<Wave>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    // When the recipes are still loading the panel is spawned once they are created.
+    if let Some(recipes) = recipes.get(&handle.0) {
+        build_crafting_panel(&mut commands, recipes, &wave, &palette, &font_assets, &image_assets);
+    }
+}
+
+fn respawn_crafting_panel_on_reload(
+    mut commands: Commands,
+    mut recipes_events: EventReader<AssetEvent<CraftingRecipes>>,
+    recipes: Res<Assets<CraftingRecipes>>,
+    handle: Res<CraftingRecipesHandle>,
+    panel: Query<Entity, With<CraftingPanel>>,
+    wave: Res<Wave>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    let reloaded = recipes_events.iter().any(|event| match event {
+        AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } => *h == handle.0,
+        AssetEvent::Removed { .. } => false,
+    });
+
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) if reloaded => recipes,
+        _ => return,
+    };
+
+    panel.for_each(|entity| commands.entity(entity).despawn_recursive());
+    build_crafting_panel(&mut commands, recipes, &wave, &palette, &font_assets, &image_assets);
+}
+
+fn build_crafting_panel(
+    commands: &mut Commands,
+    recipes: &CraftingRecipes,
+    wave: &Wave,
+    palette: &Palette,
+    font_assets: &FontAssets,
+    image_assets: &ImageAssets,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 14.0, color: Color::WHITE };
+    let icon = |image: &Handle<Image>| ImageBundle {
+        style: Style { size: Size::new(Val::Px(16.0), Val::Px(16.0)), ..default() },
+        image: image.clone().into(),
+        focus_policy: FocusPolicy::Pass,
+        ..default()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(260.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: wave.is_intermission() },
+            ..default()
+        })
+        .insert(CraftingPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("Crafting", text_style.clone()));
+
+            for (i, recipe) in recipes.recipes.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(220.0), Val::Px(26.0)),
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button_disabled.into(),
+                        ..default()
+                    })
+                    .insert(CraftButton(i))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(icon(recipe.output.image(image_assets)));
+                        parent.spawn_bundle(TextBundle::from_section(
+                            recipe.name.clone(),
+                            text_style.clone(),
+                        ));
+
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style { align_items: AlignItems::Center, ..default() },
+                                color: Color::NONE.into(),
+                                focus_policy: FocusPolicy::Pass,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for number in &recipe.dice {
+                                    parent.spawn_bundle(icon(
+                                        image_assets.handle_for_dice_number(*number),
+                                    ));
+                                }
+                                if recipe.scrap > 0 {
+                                    parent.spawn_bundle(TextBundle::from_section(
+                                        format!(" {}", recipe.scrap),
+                                        text_style.clone(),
+                                    ));
+                                    parent.spawn_bundle(icon(&image_assets.scrap));
+                                }
+                            });
+                    });
+            }
+        });
+}
+
+fn show_crafting_panel_during_intermission(
+    wave: Res<Wave>,
+    mut panel: Query<&mut Visibility, With<CraftingPanel>>,
+) {
+    for mut visibility in &mut panel {
+        if visibility.is_visible != wave.is_intermission() {
+            visibility.is_visible = wave.is_intermission();
+        }
+    }
+}
+
+fn press_craft_buttons(
+    wave: Res<Wave>,
+    buttons: Query<(&Interaction, &CraftButton), Changed<Interaction>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    for (interaction, CraftButton(index)) in &buttons {
+        if *interaction == Interaction::Clicked && wave.is_intermission() {
+            player_inputs.send(PlayerInputEvent(PlayerInput::Craft { recipe: *index }));
+        }
+    }
+}
+
+fn craft_consumables(
+    wave: Res<Wave>,
+    recipes: Res<Assets<CraftingRecipes>>,
+    handle: Res<CraftingRecipesHandle>,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut scrap: ResMut<Scrap>,
+    mut inventory: ResMut<Inventory>,
+) {
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) if wave.is_intermission() => recipes,
+        _ => return,
+    };
+
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let recipe = match *input {
+            PlayerInput::Craft { recipe } => recipes.recipes.get(recipe),
+            _otherwise => continue,
+        };
+        let recipe = match recipe {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+
+        // Both costs are checked first to never consume one without the other.
+        if recipe.is_affordable(&dice_bag, &scrap)
+            && dice_bag.try_consume_combo(&recipe.dice)
+            && scrap.try_spend(recipe.scrap)
+        {
+            inventory.add(recipe.output, 1);
+        }
+    }
+}
+
+fn grey_out_unaffordable_crafts(
+    palette: Res<Palette>,
+    recipes: Res<Assets<CraftingRecipes>>,
+    handle: Res<CraftingRecipesHandle>,
+    dice_bag: Res<DiceBag>,
+    scrap: Res<Scrap>,
+    mut buttons: Query<(&Interaction, &CraftButton, &mut UiColor)>,
+) {
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) => recipes,
+        None => return,
+    };
+
+    for (interaction, CraftButton(index), mut color) in &mut buttons {
+        let affordable =
+            recipes.recipes.get(*index).is_some_and(|r| r.is_affordable(&dice_bag, &scrap));
+        *color = palette.button_if(affordable, *interaction).into();
+    }
+}
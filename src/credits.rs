@@ -0,0 +1,141 @@
+//! The credits, slowly scrolling the contributors and the attributions.
+
+use bevy::prelude::*;
+
+use crate::menu::{spawn_menu_button, MenuButton};
+use crate::{FontAssets, GameState};
+
+const CREDITS_SCROLL_SPEED: f32 = 40.0; // in pixel per second
+
+/// The sections of the credits, a title followed by its lines.
+const CREDITS: &[(&str, &[&str])] = &[
+    ("Combine and Defend", &["A game about rolling dice to save a planet"]),
+    ("Contributors", &["Kerollmops"]),
+    ("Engine", &["Bevy - MIT or Apache-2.0"]),
+    (
+        "Libraries",
+        &[
+            "bevy_rapier2d - Apache-2.0",
+            "bevy_tweening - MIT or Apache-2.0",
+            "bevy_asset_loader - MIT or Apache-2.0",
+            "rand - MIT or Apache-2.0",
+            "serde - MIT or Apache-2.0",
+            "ron - MIT or Apache-2.0",
+            "serde_json - MIT or Apache-2.0",
+            "ordered-float - MIT",
+            "rhai - MIT or Apache-2.0",
+            "gilrs - MIT or Apache-2.0",
+            "rodio - MIT or Apache-2.0",
+            "bevy_egui - MIT",
+            "base64 - MIT or Apache-2.0",
+        ],
+    ),
+    ("Font", &["Fira Sans by Mozilla - SIL Open Font License 1.1"]),
+    ("Music and sounds", &["Synthesized for the game"]),
+    ("Thank you for playing!", &[]),
+];
+
+pub struct CreditsPlugin;
+
+impl Plugin for CreditsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Credits).with_system(setup_credits))
+            .add_system_set(SystemSet::on_update(GameState::Credits).with_system(scroll_credits));
+    }
+}
+
+/// The scrolling column of the credits.
+#[derive(Component, Debug)]
+struct CreditsRoll {
+    /// The distance from the top of the screen.
+    top: f32,
+}
+
+fn setup_credits(mut commands: Commands, windows: Res<Windows>, font_assets: Res<FontAssets>) {
+    let start = windows.get_primary().map_or(720.0, |window| window.height());
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                overflow: Overflow::Hidden,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Percent(100.0), Val::Auto),
+                        position_type: PositionType::Absolute,
+                        position: UiRect { top: Val::Px(start), ..default() },
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(CreditsRoll { top: start })
+                .with_children(|parent| {
+                    for (title, lines) in CREDITS {
+                        parent.spawn_bundle(
+                            TextBundle::from_section(
+                                *title,
+                                TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 32.0,
+                                    color: Color::GOLD,
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect { top: Val::Px(40.0), ..default() },
+                                ..default()
+                            }),
+                        );
+
+                        for line in *lines {
+                            parent.spawn_bundle(TextBundle::from_section(
+                                *line,
+                                TextStyle {
+                                    font: font_assets.fira_sans.clone(),
+                                    font_size: 20.0,
+                                    color: Color::WHITE,
+                                },
+                            ));
+                        }
+                    }
+                });
+        });
+
+    // The skip button stays at the bottom of the screen, over the credits.
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), bottom: Val::Px(20.0), ..default() },
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| spawn_menu_button(parent, &font_assets, MenuButton::Skip));
+}
+
+/// Scroll the credits up and return to the menu once they are all gone.
+fn scroll_credits(
+    time: Res<Time>,
+    mut state: ResMut<State<GameState>>,
+    mut rolls: Query<(&mut CreditsRoll, &mut Style, &Node)>,
+) {
+    for (mut roll, mut style, node) in &mut rolls {
+        roll.top -= CREDITS_SCROLL_SPEED * time.delta_seconds();
+        style.position.top = Val::Px(roll.top);
+
+        if roll.top < -node.size.y {
+            let _ = state.set(GameState::MainMenu);
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! The dice collected by the player and the bag that holds them.
+
+use std::array;
+use std::collections::vec_deque::{self, VecDeque};
+
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::logic;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DiceNumber {
+    One,
+    Two,
+    Three,
+    Four,
+    Five,
+    Six,
+}
+
+impl DiceNumber {
+    pub const ALL: [DiceNumber; 6] = [
+        DiceNumber::One,
+        DiceNumber::Two,
+        DiceNumber::Three,
+        DiceNumber::Four,
+        DiceNumber::Five,
+        DiceNumber::Six,
+    ];
+
+    pub fn pips(self) -> u32 {
+        match self {
+            DiceNumber::One => 1,
+            DiceNumber::Two => 2,
+            DiceNumber::Three => 3,
+            DiceNumber::Four => 4,
+            DiceNumber::Five => 5,
+            DiceNumber::Six => 6,
+        }
+    }
+
+    /// Rolls a fair dice, every number has the same chance.
+    pub fn from_rng<R: Rng + ?Sized>(rng: &mut R) -> DiceNumber {
+        DiceNumber::ALL[rng.gen_range(0..DiceNumber::ALL.len())]
+    }
+}
+
+/// The dice owned by the player, the oldest dice are the first to be consumed.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DiceBag {
+    bag: VecDeque<DiceNumber>,
+}
+
+impl DiceBag {
+    pub fn push(&mut self, dice: DiceNumber) {
+        self.bag.push_back(dice);
+    }
+
+    pub fn len(&self) -> usize {
+        self.bag.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bag.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<DiceNumber> {
+        self.bag.get(index).copied()
+    }
+
+    /// Returns the dice that will be consumed first.
+    pub fn peek(&self) -> Option<DiceNumber> {
+        self.bag.front().copied()
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<DiceNumber> {
+        self.bag.remove(index)
+    }
+
+    pub fn try_consume<const N: usize>(&mut self) -> Option<[DiceNumber; N]> {
+        if self.len() >= N {
+            Some(array::from_fn(|_| self.bag.pop_front().unwrap()))
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the `n` oldest dice of the bag, only if there is enough of them.
+    pub fn try_consume_many(&mut self, n: usize) -> Option<Vec<DiceNumber>> {
+        if self.len() >= n {
+            Some(self.bag.drain(..n).collect())
+        } else {
+            None
+        }
+    }
+
+    /// The number of dice of the given number in the bag.
+    pub fn count(&self, number: DiceNumber) -> usize {
+        self.iter().filter(|n| **n == number).count()
+    }
+
+    /// Whether the bag contains at least all the dice of the combination.
+    pub fn contains_combo(&self, combo: &[DiceNumber]) -> bool {
+        logic::contains_combo(&logic::dice_counts(self), combo)
+    }
+
+    /// Removes the dice of the combination from the bag, only if all of them are present.
+    pub fn try_consume_combo(&mut self, combo: &[DiceNumber]) -> bool {
+        if !self.contains_combo(combo) {
+            return false;
+        }
+
+        for number in combo {
+            let index = self.bag.iter().position(|n| n == number).unwrap();
+            self.bag.remove(index);
+        }
+
+        true
+    }
+
+    /// The sum of the pips of all the dice in the bag.
+    pub fn total_pips(&self) -> u32 {
+        self.iter().map(|n| n.pips()).sum()
+    }
+
+    /// Changes the order in which the dice will be consumed.
+    pub fn shuffle<R: Rng>(&mut self, rng: &mut R) {
+        self.bag.make_contiguous().shuffle(rng);
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, DiceNumber> {
+        self.bag.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a DiceBag {
+    type Item = &'a DiceNumber;
+    type IntoIter = vec_deque::Iter<'a, DiceNumber>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Extend<DiceNumber> for DiceBag {
+    fn extend<I: IntoIterator<Item = DiceNumber>>(&mut self, iter: I) {
+        self.bag.extend(iter);
+    }
+}
+
+impl FromIterator<DiceNumber> for DiceBag {
+    fn from_iter<I: IntoIterator<Item = DiceNumber>>(iter: I) -> Self {
+        DiceBag { bag: iter.into_iter().collect() }
+    }
+}
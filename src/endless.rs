@@ -0,0 +1,218 @@
+//! The endless mode, the waves never stop getting harder and the run is scored
+//! on the waves reached, the dice collected and the difficulty.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::evacuation::EVACUEE_SCORE_BONUS;
+use crate::profile::Profile;
+use crate::speed::SimulationSpeed;
+use crate::waves::{Wave, WaveEvent};
+use crate::{DiceOwnedEvent, FontAssets, GameMode, GameState};
+
+pub struct EndlessPlugin;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EndlessRun::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_endless_run)
+                    .with_system(setup_score_indicator),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(track_endless_run)
+                    .with_system(record_endless_pace.after(track_endless_run))
+                    .with_system(draw_score_indicator.after(track_endless_run)),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver).with_system(record_endless_score),
+            );
+    }
+}
+
+/// The difficulty of the endless mode, chosen in the main menu.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// The difficulty coming after this one in the co-op lobby.
+    pub fn next(self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    /// How much faster the asteroids spawn compared to the normal difficulty.
+    pub fn spawn_rate_factor(self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.3,
+        }
+    }
+
+    fn score_factor(self) -> u64 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Normal => 2,
+            Difficulty::Hard => 3,
+        }
+    }
+}
+
+/// What the player did during the current endless run.
+#[derive(Debug, Default)]
+pub struct EndlessRun {
+    pub dice_collected: u32,
+    /// The transports that escaped the planet during its evacuation.
+    pub evacuated: u32,
+    /// The score at the start of every wave, compared with the best run by its ghost.
+    pub pace: Vec<u64>,
+}
+
+/// The score of an endless run: the waves reached × the dice collected, plus
+/// a bonus for every evacuated transport, × the difficulty.
+pub fn endless_score(
+    wave: &Wave,
+    run: &EndlessRun,
+    difficulty: Difficulty,
+    speed: SimulationSpeed,
+) -> u64 {
+    let evacuation_bonus = run.evacuated as u64 * EVACUEE_SCORE_BONUS;
+    let score = wave.number as u64 * run.dice_collected as u64 + evacuation_bonus;
+    speed.scale_score(score * difficulty.score_factor())
+}
+
+/// A run in the leaderboard of the endless mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeaderboardEntry {
+    pub score: u64,
+    pub wave: u32,
+    pub difficulty: Difficulty,
+    /// The score at the start of every wave of the run.
+    #[serde(default)]
+    pub pace: Vec<u64>,
+}
+
+#[derive(Component, Debug)]
+struct ScoreIndicator;
+
+fn reset_endless_run(mut run: ResMut<EndlessRun>) {
+    *run = EndlessRun::default();
+}
+
+fn setup_score_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(50.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 16.0,
+                        color: Color::GOLD,
+                    },
+                ))
+                .insert(ScoreIndicator);
+        });
+}
+
+fn track_endless_run(mut run: ResMut<EndlessRun>, mut dice_owned: EventReader<DiceOwnedEvent>) {
+    let collected = dice_owned.iter().filter(|event| event.is_pickup()).count() as u32;
+    if collected > 0 {
+        run.dice_collected += collected;
+    }
+}
+
+fn record_endless_pace(
+    game_mode: Res<GameMode>,
+    wave: Res<Wave>,
+    speed: Res<SimulationSpeed>,
+    mut run: ResMut<EndlessRun>,
+    mut wave_events: EventReader<WaveEvent>,
+) {
+    let difficulty = match *game_mode {
+        GameMode::Endless(difficulty) => difficulty,
+        GameMode::Standard | GameMode::Campaign(_) => return,
+    };
+
+    for event in wave_events.iter() {
+        if let WaveEvent::Started(_) = event {
+            let score = endless_score(&wave, &run, difficulty, *speed);
+            run.pace.push(score);
+        }
+    }
+}
+
+fn draw_score_indicator(
+    game_mode: Res<GameMode>,
+    wave: Res<Wave>,
+    run: Res<EndlessRun>,
+    speed: Res<SimulationSpeed>,
+    mut indicator: Query<&mut Text, With<ScoreIndicator>>,
+) {
+    let difficulty = match *game_mode {
+        GameMode::Endless(difficulty) => difficulty,
+        GameMode::Standard | GameMode::Campaign(_) => return,
+    };
+
+    let value = format!(
+        "{} - score {} ({} dice)",
+        difficulty.label(),
+        endless_score(&wave, &run, difficulty, *speed),
+        run.dice_collected
+    );
+    for mut text in &mut indicator {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
+
+/// Keep the score of the lost endless run in the leaderboard of the profile.
+pub fn record_endless_score(
+    game_mode: Res<GameMode>,
+    wave: Res<Wave>,
+    run: Res<EndlessRun>,
+    speed: Res<SimulationSpeed>,
+    mut profile: ResMut<Profile>,
+) {
+    if let GameMode::Endless(difficulty) = *game_mode {
+        let score = endless_score(&wave, &run, difficulty, *speed);
+        profile.record_endless_run(LeaderboardEntry {
+            score,
+            wave: wave.number,
+            difficulty,
+            pace: run.pace.clone(),
+        });
+    }
+}
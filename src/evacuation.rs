@@ -0,0 +1,177 @@
+//! The evacuation of the planet, once only a few cities still stand transports
+//! take off from the planet and flee to the edge of the screen.
+//!
+//! The transports don't fight back, an asteroid touching one destroys it and
+//! the fleet has to clear their way. Every transport escaping the planet adds
+//! to the score of the run, whether the planet falls or not.
+
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::endless::EndlessRun;
+use crate::event_log::EventLog;
+use crate::layers::RenderLayer;
+use crate::speed::SimulationSpeed;
+use crate::toasts::ToastEvent;
+use crate::{
+    logic, Asteroid, GameRng, GameState, OutOfBounds, Planet, PlanetHealth, PLANET_RADIUS,
+};
+
+/// The number of standing cities under which the evacuation starts.
+const CRITICAL_CITIES: u32 = 3;
+const TRANSPORT_INTERVAL: u64 = 8; // in second
+const TRANSPORT_SPEED: f32 = 50.0;
+const TRANSPORT_SIZE: Vec2 = Vec2::new(8.0, 14.0);
+const TRANSPORT_COLOR: Color = Color::rgb(0.6, 0.9, 1.0);
+/// The distance from the planet at which a transport is out of danger,
+/// around the edge of the screen when the camera is on the planet.
+const ESCAPE_DISTANCE: f32 = 640.0;
+/// The score added for every transport escaping the planet.
+pub const EVACUEE_SCORE_BONUS: u64 = 100;
+
+pub struct EvacuationPlugin;
+
+impl Plugin for EvacuationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Evacuation::default())
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_evacuation))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(launch_transports)
+                    .with_system(destroy_transports_on_asteroid_collision)
+                    .with_system(evacuate_escaped_transports),
+            );
+    }
+}
+
+/// The timer between two transports, it only runs while the planet is critical.
+#[derive(Debug)]
+struct Evacuation {
+    timer: Timer,
+    started: bool,
+}
+
+impl Default for Evacuation {
+    fn default() -> Evacuation {
+        Evacuation {
+            timer: Timer::new(Duration::from_secs(TRANSPORT_INTERVAL), true),
+            started: false,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct Transport;
+
+fn reset_evacuation(mut evacuation: ResMut<Evacuation>) {
+    *evacuation = Evacuation::default();
+}
+
+/// The first transport takes off as soon as the planet is critical.
+fn launch_transports(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    health: Res<PlanetHealth>,
+    mut rng: ResMut<GameRng>,
+    mut evacuation: ResMut<Evacuation>,
+    mut toasts: EventWriter<ToastEvent>,
+    planet: Query<&Transform, With<Planet>>,
+) {
+    if health.current == 0 || health.current > CRITICAL_CITIES {
+        *evacuation = Evacuation::default();
+        return;
+    }
+
+    let first = !evacuation.started;
+    evacuation.started = true;
+    if !evacuation.timer.tick(speed.delta(&time)).just_finished() && !first {
+        return;
+    }
+
+    let center = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+    let position =
+        Vec2::from(logic::random_point_around(&mut *rng, center.to_array(), PLANET_RADIUS));
+    let direction = (position - center).normalize_or_zero();
+    let angle = direction.y.atan2(direction.x) - PI / 2.0;
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: TRANSPORT_COLOR,
+                custom_size: Some(TRANSPORT_SIZE),
+                ..default()
+            },
+            transform: Transform::from_translation(RenderLayer::Ships.at(position))
+                .with_rotation(Quat::from_rotation_z(angle)),
+            ..default()
+        })
+        .insert(Transport)
+        .insert(OutOfBounds::Despawn)
+        .insert(RigidBody::KinematicVelocityBased)
+        .insert(Velocity::linear(direction * TRANSPORT_SPEED))
+        .insert(Collider::cuboid(TRANSPORT_SIZE.x / 2.0, TRANSPORT_SIZE.y / 2.0))
+        .insert(Sensor)
+        .insert(ActiveEvents::COLLISION_EVENTS);
+
+    if first {
+        toasts.send(ToastEvent::warning("Evacuation started, escort the transports!"));
+    }
+}
+
+fn destroy_transports_on_asteroid_collision(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut log: ResMut<EventLog>,
+    transports: Query<(), With<Transport>>,
+    asteroids: Query<(), With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    let mut destroyed = HashSet::new();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let transport = if transports.contains(*e1) && asteroids.contains(*e2) {
+                Some(*e1)
+            } else if transports.contains(*e2) && asteroids.contains(*e1) {
+                Some(*e2)
+            } else {
+                None
+            };
+
+            // A transport touching two asteroids at once is only destroyed once.
+            if let Some(entity) = transport.filter(|entity| destroyed.insert(*entity)) {
+                commands.entity(entity).despawn();
+                log.push(&time, "A transport was destroyed");
+            }
+        }
+    }
+}
+
+fn evacuate_escaped_transports(
+    mut commands: Commands,
+    mut run: ResMut<EndlessRun>,
+    mut toasts: EventWriter<ToastEvent>,
+    planet: Query<&Transform, With<Planet>>,
+    transports: Query<(Entity, &Transform), With<Transport>>,
+) {
+    let center = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    for (entity, transform) in &transports {
+        if transform.translation.xy().distance(center) >= ESCAPE_DISTANCE {
+            commands.entity(entity).despawn();
+            run.evacuated += 1;
+            toasts.send(ToastEvent::success(format!("{} transports evacuated", run.evacuated)));
+        }
+    }
+}
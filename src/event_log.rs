@@ -0,0 +1,196 @@
+//! The in-game log of the recent events, toggled with the L key.
+
+use std::collections::VecDeque;
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::abilities::AbilityActivatedEvent;
+use crate::controls::{Action, Actions};
+use crate::theme::{Palette, ThemedPanel};
+use crate::waves::WaveEvent;
+use crate::{FontAssets, GameState};
+
+const EVENT_LOG_CAPACITY: usize = 100;
+const EVENT_LOG_VISIBLE_LINES: usize = 12;
+
+pub struct EventLogPlugin;
+
+impl Plugin for EventLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EventLog::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_event_log)
+                    .with_system(setup_event_log_panel),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(toggle_event_log_panel)
+                    .with_system(scroll_event_log)
+                    .with_system(log_wave_events)
+                    .with_system(log_activated_abilities)
+                    .with_system(
+                        draw_event_log
+                            .after(scroll_event_log)
+                            .after(log_wave_events)
+                            .after(log_activated_abilities),
+                    ),
+            );
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    /// The seconds elapsed since the start of the game.
+    pub time: f64,
+    pub message: String,
+}
+
+/// The last events of the game, the oldest entries are dropped first.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    entries: VecDeque<LogEntry>,
+    /// The number of lines scrolled up from the most recent entry.
+    scroll: usize,
+}
+
+impl EventLog {
+    pub fn push(&mut self, time: &Time, message: impl Into<String>) {
+        if self.entries.len() == EVENT_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        let entry = LogEntry { time: time.seconds_since_startup(), message: message.into() };
+        self.entries.push_back(entry);
+    }
+
+    /// The entries visible in the panel, from the oldest to the most recent.
+    fn visible_entries(&self) -> impl Iterator<Item = &LogEntry> {
+        let end = self.entries.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(EVENT_LOG_VISIBLE_LINES);
+        self.entries.range(start..end)
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        let max_scroll = self.entries.len().saturating_sub(EVENT_LOG_VISIBLE_LINES);
+        self.scroll = self.scroll.saturating_add_signed(lines).min(max_scroll);
+    }
+}
+
+#[derive(Component, Debug)]
+struct EventLogPanel;
+
+#[derive(Component, Debug)]
+struct EventLogText;
+
+fn reset_event_log(mut log: ResMut<EventLog>) {
+    *log = EventLog::default();
+}
+
+fn setup_event_log_panel(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(420.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Percent(30.0), bottom: Val::Px(100.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            color: palette.panel.into(),
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(EventLogPanel)
+        .insert(ThemedPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Event Log (L, scroll to see older events)",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 16.0,
+                    color: Color::GRAY,
+                },
+            ));
+
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(EventLogText);
+        });
+}
+
+fn toggle_event_log_panel(
+    actions: Actions,
+    mut panel: Query<&mut Visibility, With<EventLogPanel>>,
+) {
+    if actions.just_pressed(Action::ToggleEventLog) {
+        for mut visibility in &mut panel {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+fn scroll_event_log(
+    mut wheel: EventReader<MouseWheel>,
+    panel: Query<&Visibility, With<EventLogPanel>>,
+    mut log: ResMut<EventLog>,
+) {
+    let lines: f32 = wheel.iter().map(|event| event.y).sum();
+    if lines != 0.0 && panel.iter().any(|v| v.is_visible) {
+        log.scroll_by(lines.round() as isize);
+    }
+}
+
+fn log_wave_events(
+    time: Res<Time>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut log: ResMut<EventLog>,
+) {
+    for event in wave_events.iter() {
+        match event {
+            WaveEvent::Started(number) => log.push(&time, format!("Wave {} incoming", number)),
+            WaveEvent::Cleared(number) => log.push(&time, format!("Wave {} cleared", number)),
+        }
+    }
+}
+
+fn log_activated_abilities(
+    time: Res<Time>,
+    mut activated: EventReader<AbilityActivatedEvent>,
+    mut log: ResMut<EventLog>,
+) {
+    for AbilityActivatedEvent(ability) in activated.iter() {
+        log.push(&time, format!("{} activated", ability.label()));
+    }
+}
+
+fn draw_event_log(log: Res<EventLog>, mut text: Query<&mut Text, With<EventLogText>>) {
+    if log.is_changed() {
+        let value = log
+            .visible_entries()
+            .map(|LogEntry { time, message }| {
+                let seconds = *time as u64;
+                format!("[{:02}:{:02}] {}", seconds / 60, seconds % 60, message)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        for mut text in &mut text {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
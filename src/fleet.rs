@@ -0,0 +1,93 @@
+//! The maximum size of the fleet, grown by buying hangar bays in the shop,
+//! and the HUD counter of the ships.
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::{FontAssets, GameState, RunSetup, Ship};
+
+const DEFAULT_FLEET_CAPACITY: usize = 4;
+pub const MAX_FLEET_CAPACITY: usize = 8;
+
+pub struct FleetPlugin;
+
+impl Plugin for FleetPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FleetCapacity::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_fleet_capacity)
+                    .with_system(setup_fleet_counter),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(draw_fleet_counter),
+            );
+    }
+}
+
+/// The number of ships the fleet can hold.
+#[derive(Debug)]
+pub struct FleetCapacity(pub usize);
+
+impl Default for FleetCapacity {
+    fn default() -> FleetCapacity {
+        FleetCapacity(DEFAULT_FLEET_CAPACITY)
+    }
+}
+
+#[derive(Component, Debug)]
+struct FleetCounter;
+
+/// The levels starting with a bigger fleet than the default capacity are never over it.
+fn reset_fleet_capacity(run_setup: Res<RunSetup>, mut capacity: ResMut<FleetCapacity>) {
+    *capacity = FleetCapacity(DEFAULT_FLEET_CAPACITY.max(run_setup.fleet.len()));
+}
+
+fn setup_fleet_counter(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { bottom: Val::Px(75.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(FleetCounter);
+        });
+}
+
+fn draw_fleet_counter(
+    capacity: Res<FleetCapacity>,
+    ships: Query<(), With<Ship>>,
+    added_ships: Query<(), Added<Ship>>,
+    removed_ships: RemovedComponents<Ship>,
+    spawned: Query<(), Added<FleetCounter>>,
+    mut counter: Query<&mut Text, With<FleetCounter>>,
+) {
+    let fleet_changed = !added_ships.is_empty() || removed_ships.iter().next().is_some();
+    if !capacity.is_changed() && !fleet_changed && spawned.is_empty() {
+        return;
+    }
+
+    let value = format!("Fleet {}/{}", ships.iter().count(), capacity.0);
+    for mut text in &mut counter {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
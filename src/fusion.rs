@@ -0,0 +1,247 @@
+//! The fusion recipes of the combine panel, turning dice of the bag into better things.
+//!
+//! The recipes are defined in the `dice.fusion.ron` data file so they can be modded.
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::ui::FocusPolicy;
+use serde::Deserialize;
+
+use crate::dice::{DiceBag, DiceNumber};
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::ron_asset::RonAssetLoader;
+use crate::theme::Palette;
+use crate::{FontAssets, GameRng, GameState, ImageAssets, PlanetShield, PLANET_SHIELD_MAX_CHARGES};
+
+pub struct FusionPlugin;
+
+impl Plugin for FusionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<FusionRecipes>()
+            .add_asset_loader(RonAssetLoader::<FusionRecipes>::new(&["fusion.ron"]))
+            .add_startup_system(load_fusion_recipes)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(spawn_combine_panel),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(respawn_combine_panel_on_reload)
+                    .with_system(press_fusion_buttons)
+                    .with_system(fuse_dice)
+                    .with_system(grey_out_unaffordable_recipes),
+            );
+    }
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "5b6f4d2e-8a43-4c1e-9f3a-2d7c6b1e0a91"]
+pub struct FusionRecipes {
+    recipes: Vec<FusionRecipe>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct FusionRecipe {
+    name: String,
+    inputs: Vec<DiceNumber>,
+    output: FusionOutput,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum FusionOutput {
+    /// A dice of the given number goes into the bag.
+    Dice(DiceNumber),
+    /// A dice of a random number goes into the bag.
+    RandomDice,
+    /// The planet shield gains a charge.
+    ShieldCharge,
+}
+
+pub struct FusionRecipesHandle(pub Handle<FusionRecipes>);
+
+/// The panel listing the recipes, rebuilt every time the recipes are (re)loaded.
+#[derive(Component, Debug)]
+struct CombinePanel;
+
+/// The index of the recipe a button executes.
+#[derive(Component, Debug)]
+struct FusionButton(usize);
+
+fn load_fusion_recipes(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(FusionRecipesHandle(asset_server.load("dice.fusion.ron")));
+}
+
+fn spawn_combine_panel(
+    mut commands: Commands,
+    recipes: Res<Assets<FusionRecipes>>,
+    handle: Res<FusionRecipesHandle>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    // When the recipes are still loading the panel is spawned once they are created.
+    if let Some(recipes) = recipes.get(&handle.0) {
+        build_combine_panel(&mut commands, recipes, &palette, &font_assets, &image_assets);
+    }
+}
+
+fn respawn_combine_panel_on_reload(
+    mut commands: Commands,
+    mut recipes_events: EventReader<AssetEvent<FusionRecipes>>,
+    recipes: Res<Assets<FusionRecipes>>,
+    handle: Res<FusionRecipesHandle>,
+    panel: Query<Entity, With<CombinePanel>>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    let reloaded = recipes_events.iter().any(|event| match event {
+        AssetEvent::Created { handle: h } | AssetEvent::Modified { handle: h } => *h == handle.0,
+        AssetEvent::Removed { .. } => false,
+    });
+
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) if reloaded => recipes,
+        _ => return,
+    };
+
+    panel.for_each(|entity| commands.entity(entity).despawn_recursive());
+    build_combine_panel(&mut commands, recipes, &palette, &font_assets, &image_assets);
+}
+
+fn build_combine_panel(
+    commands: &mut Commands,
+    recipes: &FusionRecipes,
+    palette: &Palette,
+    font_assets: &FontAssets,
+    image_assets: &ImageAssets,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 14.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(CombinePanel)
+        .with_children(|parent| {
+            for (i, recipe) in recipes.recipes.iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(170.0), Val::Px(26.0)),
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button_disabled.into(),
+                        ..default()
+                    })
+                    .insert(FusionButton(i))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            recipe.name.clone(),
+                            text_style.clone(),
+                        ));
+
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                color: Color::NONE.into(),
+                                focus_policy: FocusPolicy::Pass,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for number in &recipe.inputs {
+                                    parent.spawn_bundle(ImageBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(16.0), Val::Px(16.0)),
+                                            margin: UiRect::all(Val::Px(1.0)),
+                                            ..default()
+                                        },
+                                        image: image_assets
+                                            .handle_for_dice_number(*number)
+                                            .clone()
+                                            .into(),
+                                        focus_policy: FocusPolicy::Pass,
+                                        ..default()
+                                    });
+                                }
+                            });
+                    });
+            }
+        });
+}
+
+fn press_fusion_buttons(
+    buttons: Query<(&Interaction, &FusionButton), Changed<Interaction>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    for (interaction, FusionButton(index)) in &buttons {
+        if *interaction == Interaction::Clicked {
+            player_inputs.send(PlayerInputEvent(PlayerInput::Fuse { recipe: *index }));
+        }
+    }
+}
+
+fn fuse_dice(
+    recipes: Res<Assets<FusionRecipes>>,
+    handle: Res<FusionRecipesHandle>,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut shield: ResMut<PlanetShield>,
+    mut rng: ResMut<GameRng>,
+) {
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) => recipes,
+        None => return,
+    };
+
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let recipe = match *input {
+            PlayerInput::Fuse { recipe } => recipes.recipes.get(recipe),
+            _otherwise => continue,
+        };
+        let recipe = match recipe {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+
+        if dice_bag.try_consume_combo(&recipe.inputs) {
+            match recipe.output {
+                FusionOutput::Dice(number) => dice_bag.push(number),
+                FusionOutput::RandomDice => dice_bag.push(DiceNumber::from_rng(&mut *rng)),
+                FusionOutput::ShieldCharge => {
+                    shield.charges = (shield.charges + 1).min(PLANET_SHIELD_MAX_CHARGES)
+                }
+            }
+        }
+    }
+}
+
+fn grey_out_unaffordable_recipes(
+    palette: Res<Palette>,
+    recipes: Res<Assets<FusionRecipes>>,
+    handle: Res<FusionRecipesHandle>,
+    dice_bag: Res<DiceBag>,
+    mut buttons: Query<(&Interaction, &FusionButton, &mut UiColor)>,
+) {
+    let recipes = match recipes.get(&handle.0) {
+        Some(recipes) => recipes,
+        None => return,
+    };
+
+    for (interaction, FusionButton(index), mut color) in &mut buttons {
+        let affordable =
+            recipes.recipes.get(*index).is_some_and(|r| dice_bag.contains_combo(&r.inputs));
+        *color = palette.button_if(affordable, *interaction).into();
+    }
+}
@@ -0,0 +1,297 @@
+//! The gamble station where the player can wager dice during the intermissions.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::abilities::PowerCharges;
+use crate::dice::{DiceBag, DiceNumber};
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::speed::SimulationSpeed;
+use crate::theme::{Palette, ThemedPanel};
+use crate::waves::Wave;
+use crate::{FontAssets, GameRng, GameState};
+
+const GAMBLE_MAX_WAGER: usize = 3;
+const GAMBLE_SPIN_DURATION: u64 = 1500; // in millisecond
+const GAMBLE_SPIN_STEP: u64 = 100; // in millisecond
+
+pub struct GamblePlugin;
+
+impl Plugin for GamblePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GambleStation::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_gamble_station)
+                    .with_system(setup_gamble_station),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(show_gamble_station_during_intermission)
+                    .with_system(press_gamble_buttons)
+                    .with_system(highlight_gamble_buttons)
+                    .with_system(start_gamble_spins)
+                    .with_system(spin_gamble_wheel.after(start_gamble_spins))
+                    .with_system(draw_gamble_station.after(spin_gamble_wheel)),
+            );
+    }
+}
+
+/// What the wheel can land on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GambleOutcome {
+    /// The wagered dice come back twice.
+    Double,
+    /// The wagered dice turn into a power charge.
+    PowerCharge,
+    /// The wagered dice are gone.
+    Lose,
+}
+
+impl GambleOutcome {
+    const WHEEL: [(GambleOutcome, u32); 3] =
+        [(GambleOutcome::Double, 40), (GambleOutcome::PowerCharge, 20), (GambleOutcome::Lose, 40)];
+
+    fn label(self) -> &'static str {
+        match self {
+            GambleOutcome::Double => "Double!",
+            GambleOutcome::PowerCharge => "Power charge!",
+            GambleOutcome::Lose => "Lost...",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GambleStation {
+    wager: usize,
+    spin: Option<GambleSpin>,
+    last_outcome: Option<GambleOutcome>,
+}
+
+impl Default for GambleStation {
+    fn default() -> GambleStation {
+        GambleStation { wager: 1, spin: None, last_outcome: None }
+    }
+}
+
+/// The wheel is spinning, the outcome is rolled as soon as
+/// the dice are wagered but only revealed when it stops.
+#[derive(Debug)]
+struct GambleSpin {
+    timer: Timer,
+    wagered: Vec<DiceNumber>,
+    outcome: GambleOutcome,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum GambleButton {
+    DecreaseWager,
+    IncreaseWager,
+    Spin,
+}
+
+/// The root of the panel, only visible during intermissions.
+#[derive(Component, Debug)]
+struct GambleStationPanel;
+
+#[derive(Component, Debug)]
+struct GambleStationText;
+
+fn reset_gamble_station(mut station: ResMut<GambleStation>) {
+    *station = GambleStation::default();
+}
+
+fn setup_gamble_station(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 18.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(220.0), Val::Px(110.0)),
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), top: Val::Px(60.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: palette.panel.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(GambleStationPanel)
+        .insert(ThemedPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                .insert(GambleStationText);
+
+            parent
+                .spawn_bundle(NodeBundle { color: Color::NONE.into(), ..default() })
+                .with_children(|parent| {
+                    let buttons = [
+                        (GambleButton::DecreaseWager, "-"),
+                        (GambleButton::Spin, "Spin"),
+                        (GambleButton::IncreaseWager, "+"),
+                    ];
+
+                    for (button, label) in buttons {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(60.0), Val::Px(30.0)),
+                                    margin: UiRect::all(Val::Px(4.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                color: palette.button.into(),
+                                ..default()
+                            })
+                            .insert(button)
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle::from_section(
+                                    label,
+                                    text_style.clone(),
+                                ));
+                            });
+                    }
+                });
+        });
+}
+
+fn show_gamble_station_during_intermission(
+    wave: Res<Wave>,
+    mut panel: Query<&mut Visibility, With<GambleStationPanel>>,
+) {
+    if wave.is_changed() {
+        for mut visibility in &mut panel {
+            if visibility.is_visible != wave.is_intermission() {
+                visibility.is_visible = wave.is_intermission();
+            }
+        }
+    }
+}
+
+fn press_gamble_buttons(
+    wave: Res<Wave>,
+    mut station: ResMut<GambleStation>,
+    buttons: Query<(&Interaction, &GambleButton), Changed<Interaction>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked || !wave.is_intermission() {
+            continue;
+        }
+
+        match button {
+            GambleButton::DecreaseWager => station.wager = station.wager.saturating_sub(1).max(1),
+            GambleButton::IncreaseWager => {
+                station.wager = (station.wager + 1).min(GAMBLE_MAX_WAGER)
+            }
+            GambleButton::Spin if station.spin.is_none() => {
+                player_inputs.send(PlayerInputEvent(PlayerInput::Gamble { wager: station.wager }))
+            }
+            GambleButton::Spin => (),
+        }
+    }
+}
+
+/// The outcome is rolled from the rng of the run, the same in every game.
+fn start_gamble_spins(
+    wave: Res<Wave>,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut station: ResMut<GambleStation>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut rng: ResMut<GameRng>,
+) {
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let wager = match *input {
+            PlayerInput::Gamble { wager } if wave.is_intermission() && station.spin.is_none() => {
+                wager.clamp(1, GAMBLE_MAX_WAGER)
+            }
+            _otherwise => continue,
+        };
+
+        if let Some(wagered) = dice_bag.try_consume_many(wager) {
+            let (outcome, _) =
+                *GambleOutcome::WHEEL.choose_weighted(&mut *rng, |(_, w)| *w).unwrap();
+            let timer = Timer::new(Duration::from_millis(GAMBLE_SPIN_DURATION), false);
+            station.spin = Some(GambleSpin { timer, wagered, outcome });
+            station.last_outcome = None;
+        }
+    }
+}
+
+fn highlight_gamble_buttons(
+    palette: Res<Palette>,
+    mut buttons: Query<
+        (&Interaction, ChangeTrackers<Interaction>, &mut UiColor),
+        With<GambleButton>,
+    >,
+) {
+    for (interaction, tracker, mut color) in &mut buttons {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+}
+
+fn spin_gamble_wheel(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut station: ResMut<GambleStation>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut power_charges: ResMut<PowerCharges>,
+) {
+    let finished = match &mut station.spin {
+        Some(spin) => spin.timer.tick(speed.delta(&time)).finished(),
+        None => return,
+    };
+
+    if finished {
+        let GambleSpin { wagered, outcome, .. } = station.spin.take().unwrap();
+        match outcome {
+            GambleOutcome::Double => dice_bag.extend(wagered.iter().chain(&wagered).copied()),
+            GambleOutcome::PowerCharge => power_charges.0 += 1,
+            GambleOutcome::Lose => (),
+        }
+        station.last_outcome = Some(outcome);
+    }
+}
+
+fn draw_gamble_station(
+    station: Res<GambleStation>,
+    power_charges: Res<PowerCharges>,
+    spawned: Query<(), Added<GambleStationText>>,
+    mut text: Query<&mut Text, With<GambleStationText>>,
+) {
+    if !station.is_changed() && !power_charges.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    let wheel = match &station.spin {
+        Some(spin) => {
+            // The wheel cycles through the outcomes while it spins.
+            let step = (spin.timer.elapsed().as_millis() / GAMBLE_SPIN_STEP as u128) as usize;
+            GambleOutcome::WHEEL[step % GambleOutcome::WHEEL.len()].0.label()
+        }
+        None => station.last_outcome.map_or("Feeling lucky?", GambleOutcome::label),
+    };
+
+    let value =
+        format!("Gamble {} dice\n{}\nPower charges: {}", station.wager, wheel, power_charges.0);
+
+    for mut text in &mut text {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! The ghost of the best endless run, a faint line under the score telling
+//! whether the current run is ahead of the record at the same wave.
+//!
+//! The ghost is the best run of the leaderboard played with the same difficulty,
+//! it can be hidden from the settings screen.
+
+use bevy::prelude::*;
+
+use crate::endless::{EndlessRun, LeaderboardEntry};
+use crate::profile::Profile;
+use crate::settings::GameSettings;
+use crate::{FontAssets, GameMode, GameState};
+
+const GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.4);
+
+pub struct GhostPlugin;
+
+impl Plugin for GhostPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Ghost::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(pick_ghost)
+                    .with_system(setup_ghost_indicator),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(draw_ghost_indicator),
+            );
+    }
+}
+
+/// The best run recorded before the current one started, none outside of the endless mode.
+#[derive(Debug, Default)]
+struct Ghost(Option<LeaderboardEntry>);
+
+#[derive(Component, Debug)]
+struct GhostIndicator;
+
+fn pick_ghost(game_mode: Res<GameMode>, profile: Res<Profile>, mut ghost: ResMut<Ghost>) {
+    ghost.0 = match *game_mode {
+        GameMode::Endless(difficulty) => profile
+            .endless_leaderboard()
+            .iter()
+            .find(|entry| entry.difficulty == difficulty)
+            .cloned(),
+        GameMode::Standard | GameMode::Campaign(_) => None,
+    };
+}
+
+fn setup_ghost_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(68.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 14.0,
+                        color: GHOST_COLOR,
+                    },
+                ))
+                .insert(GhostIndicator);
+        });
+}
+
+/// Compare the score at the start of the last wave with the one of the ghost at the same wave.
+fn draw_ghost_indicator(
+    settings: Res<GameSettings>,
+    ghost: Res<Ghost>,
+    run: Res<EndlessRun>,
+    spawned: Query<(), Added<GhostIndicator>>,
+    mut indicator: Query<&mut Text, With<GhostIndicator>>,
+) {
+    let changed = settings.is_changed() || ghost.is_changed() || run.is_changed();
+    if !changed && spawned.is_empty() {
+        return;
+    }
+
+    let value = match &ghost.0 {
+        Some(best) if settings.ghost => {
+            let reached = run.pace.len();
+            match (run.pace.last(), reached.checked_sub(1).and_then(|i| best.pace.get(i))) {
+                (Some(score), Some(best_score)) if score >= best_score => {
+                    format!("Best wave {} - {} ahead", best.wave, score - best_score)
+                }
+                (Some(score), Some(best_score)) => {
+                    format!("Best wave {} - {} behind", best.wave, best_score - score)
+                }
+                (Some(_), None) => format!("Past the best wave {}", best.wave),
+                (None, _) => format!("Best wave {}", best.wave),
+            }
+        }
+        _otherwise => String::new(),
+    };
+
+    for mut text in &mut indicator {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! The headless simulation of a run, the game without a window nor a renderer
+//! stepped at the fixed physics timestep, used by the balance sweeps and by the
+//! co-op server.
+//!
+//! The app is built from the same plugins as the game. The assets loaded by the
+//! asset server are waited for before the run starts, but the tuning and the
+//! behavior profiles are read from their files, the mods are ignored.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use bevy::asset::LoadState;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy::window::WindowSettings;
+use bevy::winit::WinitPlugin;
+use bevy_rapier2d::prelude::*;
+
+use crate::behavior::{BehaviorProfile, BehaviorProfileFiles, BehaviorProfileHandles};
+use crate::campaign::CampaignHandle;
+use crate::crafting::CraftingRecipesHandle;
+use crate::fusion::FusionRecipesHandle;
+use crate::lockstep::{
+    Checkpoint, PlayerInput, SimIds, SimInputEvent, CHECKPOINT_INTERVAL, INPUT_DELAY,
+};
+use crate::net::NetMessage;
+use crate::players::{PlayerId, Players};
+use crate::scripting::ScriptHandles;
+use crate::speed::SimulationSpeed;
+use crate::tuning::{Tuning, TuningHandle};
+use crate::waves::{Wave, WaveSchedule};
+use crate::{
+    Asteroid, GameMode, GamePlugin, GameState, PlanetHealth, RunSetup, Ship, PHYSICS_TIMESTEP,
+};
+
+pub const TUNING_FILE_PATH: &str = "assets/game.tuning.ron";
+/// The runs start without the assets still not loaded after this time.
+const ASSETS_LOAD_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Present in the headless apps, their runs are stepped by the simulation
+/// instead of the frame time, and nobody watches nor plays them.
+pub struct Headless;
+
+/// Read the tuning of the assets directory, the mods are ignored.
+pub fn read_tuning() -> Result<Tuning, String> {
+    fs::read_to_string(TUNING_FILE_PATH)
+        .map_err(|e| e.to_string())
+        .and_then(|content| ron::from_str(&content).map_err(|e| e.to_string()))
+}
+
+/// A standard game played from this setup, the run is entered but none of
+/// its steps is simulated until `update_step` is called.
+pub fn headless_app(
+    tuning: Tuning,
+    behaviors: BehaviorProfileFiles,
+    run_setup: RunSetup,
+    schedule: WaveSchedule,
+) -> App {
+    let mut app = App::new();
+    app.insert_resource(WgpuSettings { backends: None, ..default() })
+        .insert_resource(WindowSettings { add_primary_window: false, ..default() })
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.disable::<WinitPlugin>().disable::<LogPlugin>()
+        })
+        .insert_resource(Headless)
+        .add_plugin(GamePlugin);
+    wait_for_assets(&mut app);
+
+    let handle = app.world.resource_mut::<Assets<Tuning>>().add(tuning);
+    app.insert_resource(TuningHandle(handle));
+    let profiles = BehaviorProfileHandles::from_files(
+        behaviors,
+        &mut app.world.resource_mut::<Assets<BehaviorProfile>>(),
+    );
+    app.insert_resource(profiles)
+        .insert_resource(GameMode::Standard)
+        .insert_resource(Players::coop(true))
+        .insert_resource(run_setup)
+        .insert_resource(schedule);
+
+    // Like in the lockstep, the frame entering the run doesn't simulate anything.
+    app.world.resource_mut::<SimulationSpeed>().set_step(Some(Duration::ZERO));
+    app.world.resource_mut::<RapierConfiguration>().physics_pipeline_active = false;
+    let _ = app.world.resource_mut::<State<GameState>>().set(GameState::Playing);
+    app.update();
+    app
+}
+
+/// Update the app until the levels, the recipes and the scripts are loaded.
+fn wait_for_assets(app: &mut App) {
+    let deadline = Instant::now() + ASSETS_LOAD_TIMEOUT;
+    loop {
+        app.update();
+
+        let world = &app.world;
+        let mut ids = Vec::new();
+        ids.extend(world.get_resource::<CampaignHandle>().map(|handle| handle.0.id));
+        ids.extend(world.get_resource::<CraftingRecipesHandle>().map(|handle| handle.0.id));
+        ids.extend(world.get_resource::<FusionRecipesHandle>().map(|handle| handle.0.id));
+        if let Some(ScriptHandles(handles)) = world.get_resource() {
+            ids.extend(handles.iter().map(|handle| handle.id));
+        }
+
+        let state = world.resource::<AssetServer>().get_group_load_state(ids);
+        if matches!(state, LoadState::Loaded | LoadState::Failed) {
+            return;
+        } else if Instant::now() >= deadline {
+            return eprintln!("The assets are still not loaded, the run starts without them");
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Simulate the next step of the run, at the fixed physics timestep.
+pub fn update_step(app: &mut App) {
+    let mut speed = app.world.resource_mut::<SimulationSpeed>();
+    speed.set_step(Some(Duration::from_secs_f32(PHYSICS_TIMESTEP)));
+    let factor = speed.factor();
+
+    let mut rapier_config = app.world.resource_mut::<RapierConfiguration>();
+    rapier_config.timestep_mode =
+        TimestepMode::Fixed { dt: PHYSICS_TIMESTEP * factor, substeps: 1 };
+    rapier_config.physics_pipeline_active = true;
+    app.update();
+}
+
+/// The run of a co-op room simulated by the server, from the messages the
+/// players send each other, the server is the reference of the state of the run.
+#[derive(Default)]
+pub struct RoomSimulation {
+    run: Option<HeadlessRun>,
+}
+
+struct HeadlessRun {
+    app: App,
+    /// The next step to simulate.
+    step: u64,
+    host: BTreeMap<u64, Vec<PlayerInput>>,
+    guest: BTreeMap<u64, Vec<PlayerInput>>,
+}
+
+impl RoomSimulation {
+    /// Follow a message sent by a player to the other, the returned messages
+    /// are the checkpoints of the simulation to send to both players.
+    pub fn receive(&mut self, from_host: bool, line: &str) -> Vec<String> {
+        let message = match ron::from_str(line) {
+            Ok(message) => message,
+            Err(_) => return Vec::new(),
+        };
+
+        match (message, &mut self.run) {
+            (NetMessage::Start(setup), _) if from_host => {
+                self.run = match (read_tuning(), BehaviorProfileFiles::read()) {
+                    (Ok(tuning), Ok(behaviors)) => {
+                        let run_setup = setup.run_setup();
+                        let schedule = setup.wave_schedule();
+                        let app = headless_app(tuning, behaviors, run_setup, schedule);
+                        Some(HeadlessRun::new(app))
+                    }
+                    (Err(e), _) => {
+                        eprintln!("Could not read the tuning from {}: {}", TUNING_FILE_PATH, e);
+                        None
+                    }
+                    (_, Err(e)) => {
+                        eprintln!("Could not read the behavior profiles: {}", e);
+                        None
+                    }
+                };
+                Vec::new()
+            }
+            (NetMessage::Inputs { step, inputs }, Some(run)) => {
+                let side = if from_host { &mut run.host } else { &mut run.guest };
+                side.insert(step, inputs);
+                run.simulate()
+                    .into_iter()
+                    .filter_map(|message| ron::to_string(&message).ok())
+                    .collect()
+            }
+            _otherwise => Vec::new(),
+        }
+    }
+}
+
+impl HeadlessRun {
+    fn new(app: App) -> HeadlessRun {
+        // Nobody can make an input for the first steps, like in the lockstep.
+        let empty: BTreeMap<_, _> = (0..INPUT_DELAY).map(|step| (step, Vec::new())).collect();
+        HeadlessRun { app, step: 0, host: empty.clone(), guest: empty }
+    }
+
+    /// Simulate the steps the inputs of both players are known for, the run
+    /// stops once the planet is destroyed.
+    fn simulate(&mut self) -> Vec<NetMessage> {
+        let mut checkpoints = Vec::new();
+        while self.app.world.resource::<PlanetHealth>().current > 0
+            && self.host.contains_key(&self.step)
+            && self.guest.contains_key(&self.step)
+        {
+            // The host inputs are applied first, like in the lockstep.
+            let host = self.host.remove(&self.step).unwrap_or_default();
+            let guest = self.guest.remove(&self.step).unwrap_or_default();
+            let host =
+                host.into_iter().map(|input| SimInputEvent { player: PlayerId::FIRST, input });
+            let guest =
+                guest.into_iter().map(|input| SimInputEvent { player: PlayerId::SECOND, input });
+            let inputs = host.chain(guest);
+            self.app.world.resource_mut::<Events<SimInputEvent>>().extend(inputs);
+
+            update_step(&mut self.app);
+            if self.step.is_multiple_of(CHECKPOINT_INTERVAL) {
+                let world = &mut self.app.world;
+                let mut ships = world.query_filtered::<&Transform, With<Ship>>();
+                let mut asteroids = world.query_filtered::<&Transform, With<Asteroid>>();
+                let checkpoint = Checkpoint::new(
+                    world.resource::<Wave>(),
+                    world.resource::<PlanetHealth>(),
+                    world.resource::<SimIds>(),
+                    ships.iter(world),
+                    asteroids.iter(world),
+                );
+                checkpoints.push(NetMessage::Checkpoint { step: self.step, checkpoint });
+            }
+            self.step += 1;
+        }
+        checkpoints
+    }
+}
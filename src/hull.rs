@@ -0,0 +1,139 @@
+//! The hull of the ships, damaged by the asteroids hitting them when the tuning
+//! says so and repaired with the dice of the bag once out of combat.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::Stopwatch;
+use bevy_rapier2d::prelude::*;
+
+use crate::dice::DiceBag;
+use crate::event_log::EventLog;
+use crate::sound::{PlaySoundEvent, Sound};
+use crate::speed::SimulationSpeed;
+use crate::tuning::{Tuning, TuningHandle};
+use crate::{Asteroid, GameState, Ship};
+
+pub const SHIP_MAX_HULL: u32 = 6;
+/// The time without being hit after which a ship is out of combat.
+const OUT_OF_COMBAT_DELAY: Duration = Duration::from_secs(3);
+
+pub struct HullPlugin;
+
+impl Plugin for HullPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(damage_ships_on_asteroid_collision)
+                .with_system(track_ships_out_of_combat)
+                .with_system(destroy_wrecked_ships.after(damage_ships_on_asteroid_collision)),
+        );
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct ShipHull {
+    pub current: u32,
+    pub max: u32,
+    since_hit: Stopwatch,
+}
+
+impl Default for ShipHull {
+    fn default() -> ShipHull {
+        ShipHull::new(SHIP_MAX_HULL)
+    }
+}
+
+impl ShipHull {
+    pub fn new(max: u32) -> ShipHull {
+        let mut since_hit = Stopwatch::new();
+        since_hit.set_elapsed(OUT_OF_COMBAT_DELAY);
+        ShipHull { current: max, max, since_hit }
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.current < self.max
+    }
+
+    pub fn is_out_of_combat(&self) -> bool {
+        self.since_hit.elapsed() >= OUT_OF_COMBAT_DELAY
+    }
+
+    /// Restore the hull by the pips of a dice, the extra pips are lost.
+    pub fn repair(&mut self, pips: u32) {
+        self.current = (self.current + pips).min(self.max);
+    }
+
+    fn hit(&mut self, damage: u32) {
+        self.current = self.current.saturating_sub(damage);
+        self.since_hit.reset();
+    }
+}
+
+/// Pick the dice of the bag repairing the most of the hull while wasting the least pips,
+/// the smallest dice covering the whole damage or else the biggest dice of the bag.
+pub fn best_repair_dice(hull: &ShipHull, dice_bag: &DiceBag) -> Option<usize> {
+    let missing = hull.max - hull.current;
+    let covering = dice_bag
+        .iter()
+        .enumerate()
+        .filter(|(_, number)| number.pips() >= missing)
+        .min_by_key(|(_, number)| number.pips());
+    let biggest = || dice_bag.iter().enumerate().max_by_key(|(_, number)| number.pips());
+    covering.or_else(biggest).map(|(index, _)| index)
+}
+
+fn damage_ships_on_asteroid_collision(
+    mut ships: Query<&mut ShipHull, With<Ship>>,
+    asteroids: Query<(), With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+) {
+    let damage = tuning.balance(&tunings).asteroid_hull_damage;
+    if damage == 0 {
+        return collision_events.clear();
+    }
+
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let ship = if asteroids.contains(*e2) {
+                *e1
+            } else if asteroids.contains(*e1) {
+                *e2
+            } else {
+                continue;
+            };
+
+            if let Ok(mut hull) = ships.get_mut(ship) {
+                hull.hit(damage);
+            }
+        }
+    }
+}
+
+fn track_ships_out_of_combat(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut ships: Query<&mut ShipHull>,
+) {
+    for mut hull in &mut ships {
+        hull.since_hit.tick(speed.delta(&time));
+    }
+}
+
+fn destroy_wrecked_ships(
+    mut commands: Commands,
+    time: Res<Time>,
+    ships: Query<(Entity, &ShipHull, &Transform), With<Ship>>,
+    mut log: ResMut<EventLog>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    for (entity, hull, transform) in &ships {
+        if hull.current == 0 {
+            commands.entity(entity).despawn_recursive();
+            log.push(&time, "A ship was destroyed by the asteroids");
+            play_sound.send(PlaySoundEvent::at(Sound::Explosion, transform.translation.truncate()));
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! The physics bodies rendered between the last two ticks of the simulation.
+//!
+//! When the simulation ticks at a fixed rate, a frame can be rendered between two
+//! ticks, or without any tick at high refresh rates. The transforms of the bodies
+//! are moved between their last two ticks before being rendered, and back to the
+//! simulated transforms at the start of the next frame, so the gameplay systems
+//! and the physics never see the rendered positions. The rendered bodies lag up
+//! to one tick behind the simulation.
+
+use std::mem;
+
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_rapier2d::prelude::*;
+
+/// A body moving this far in a single tick was teleported, it is not interpolated.
+const SNAP_DISTANCE: f32 = 100.0;
+
+pub struct InterpolationPlugin;
+
+impl Plugin for InterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TickProgress::default())
+            .add_system_to_stage(CoreStage::First, restore_simulated_transforms)
+            .add_system_to_stage(CoreStage::PostUpdate, track_new_bodies)
+            .add_system_to_stage(CoreStage::PostUpdate, track_physics_ticks)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_transforms
+                    .after(track_physics_ticks)
+                    .before(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Where the rendered frame is between the last two ticks of the simulation,
+/// written by the lockstep of the co-op runs and from the physics otherwise.
+#[derive(Debug)]
+pub struct TickProgress {
+    /// Whether the simulation ticked during this frame.
+    pub ticked: bool,
+    /// From zero on the previous tick to one on the last tick.
+    pub alpha: f32,
+}
+
+impl Default for TickProgress {
+    fn default() -> TickProgress {
+        TickProgress { ticked: true, alpha: 1.0 }
+    }
+}
+
+/// The translation and the rotation of a body on its last two ticks.
+#[derive(Component, Debug)]
+struct RenderInterpolation {
+    previous: (Vec3, Quat),
+    current: (Vec3, Quat),
+    /// Whether the transform holds the rendered position instead of the simulated one.
+    rendered: bool,
+}
+
+/// Only the bodies without a parent are interpolated, their global transform
+/// is their transform.
+fn track_new_bodies(
+    mut commands: Commands,
+    bodies: Query<
+        (Entity, &Transform),
+        (With<RigidBody>, Without<Parent>, Without<RenderInterpolation>),
+    >,
+) {
+    for (entity, transform) in &bodies {
+        let simulated = (transform.translation, transform.rotation);
+        commands.entity(entity).insert(RenderInterpolation {
+            previous: simulated,
+            current: simulated,
+            rendered: false,
+        });
+    }
+}
+
+fn track_physics_ticks(
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    sim_to_render: Res<SimulationToRenderTime>,
+    mut last_diff: Local<f32>,
+    mut progress: ResMut<TickProgress>,
+) {
+    let elapsed = *last_diff + time.delta_seconds();
+    *last_diff = sim_to_render.diff;
+
+    match rapier_config.timestep_mode {
+        // The fixed rate ticks and the lockstep step the physics themselves.
+        TimestepMode::Fixed { .. } => (),
+        TimestepMode::Variable { .. } => {
+            *progress = TickProgress { ticked: rapier_config.physics_pipeline_active, alpha: 1.0 };
+        }
+        TimestepMode::Interpolated { dt, .. } => {
+            // The physics steps while the simulation is behind the frames.
+            progress.ticked = rapier_config.physics_pipeline_active && elapsed > 0.0;
+            progress.alpha = ((dt + sim_to_render.diff) / dt).clamp(0.0, 1.0);
+        }
+    }
+}
+
+fn interpolate_transforms(
+    progress: Res<TickProgress>,
+    mut bodies: Query<(&mut Transform, &mut RenderInterpolation)>,
+) {
+    let _span = info_span!("interpolate_transforms").entered();
+    for (mut transform, mut interpolation) in &mut bodies {
+        let simulated = (transform.translation, transform.rotation);
+        if progress.ticked {
+            interpolation.previous = interpolation.current;
+            interpolation.current = simulated;
+        }
+
+        // Moved by the gameplay between two ticks or teleported.
+        let (previous, current) = (interpolation.previous.0, interpolation.current.0);
+        if simulated != interpolation.current || previous.distance(current) > SNAP_DISTANCE {
+            interpolation.previous = simulated;
+            interpolation.current = simulated;
+        }
+
+        let translation = interpolation.previous.0.lerp(interpolation.current.0, progress.alpha);
+        let rotation = interpolation.previous.1.slerp(interpolation.current.1, progress.alpha);
+        if (translation, rotation) != simulated {
+            transform.translation = translation;
+            transform.rotation = rotation;
+            interpolation.rendered = true;
+        }
+    }
+}
+
+fn restore_simulated_transforms(
+    mut bodies: Query<(&mut Transform, &mut GlobalTransform, &mut RenderInterpolation)>,
+) {
+    for (mut transform, mut global_transform, mut interpolation) in &mut bodies {
+        if mem::take(&mut interpolation.rendered) {
+            (transform.translation, transform.rotation) = interpolation.current;
+            // The physics compares the global transform with the one it simulated.
+            *global_transform = GlobalTransform::from(*transform);
+        }
+    }
+}
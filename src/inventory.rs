@@ -0,0 +1,632 @@
+//! The inventory of consumables bought in the shop, they are dragged from
+//! the inventory panel to their own hotbar and deployed under the cursor.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::controls::{Action, Actions};
+use crate::layers::RenderLayer;
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::loot::roll_loot_on_asteroid_destroyed;
+use crate::players::PlayerId;
+use crate::speed::SimulationSpeed;
+use crate::theme::{Palette, ThemedPanel};
+use crate::{
+    cursor_world_position, Asteroid, AsteroidDestroyedEvent, DestroyCause, FollowsCursor,
+    FontAssets, GameState, ImageAssets, OutOfBounds, Planet, SpaceCamera,
+};
+
+pub const CONSUMABLE_HOTBAR_SIZE: usize = 3;
+const MINE_RADIUS: f32 = 12.0;
+const GRAVITY_WELL_RADIUS: f32 = 250.0;
+const GRAVITY_WELL_PULL: f32 = 300.0; // by second
+const GRAVITY_WELL_DURATION: u64 = 8; // in second
+const EMP_RADIUS: f32 = 500.0;
+const EMP_STUN_DURATION: u64 = 3; // in second
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Inventory::default())
+            .insert_resource(DraggedConsumable::default())
+            .add_event::<ConsumableUsedEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_inventory)
+                    .with_system(setup_inventory_panel)
+                    .with_system(setup_consumable_hotbar),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(toggle_inventory_panel)
+                    .with_system(drag_consumable_from_inventory)
+                    .with_system(
+                        drop_dragged_consumable_on_hotbar.after(drag_consumable_from_inventory),
+                    )
+                    .with_system(press_consumable_keys)
+                    .with_system(use_consumables)
+                    .with_system(deploy_consumables.after(use_consumables))
+                    .with_system(detonate_mines.before(roll_loot_on_asteroid_destroyed))
+                    .with_system(pull_asteroids_into_gravity_wells)
+                    .with_system(wake_up_stunned_asteroids)
+                    .with_system(highlight_hovered_slots)
+                    .with_system(recolor_inventory_entries)
+                    .with_system(draw_inventory.after(drop_dragged_consumable_on_hotbar))
+                    .with_system(draw_dragged_consumable),
+            );
+    }
+}
+
+/// A consumable bought in the shop and deployed from the consumable hotbar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Consumable {
+    /// Destroys the first asteroid that touches it.
+    Mine,
+    /// Pulls the asteroids around it for a few seconds.
+    GravityWell,
+    /// Stuns all the asteroids around the planet.
+    Emp,
+}
+
+impl Consumable {
+    pub const ALL: [Consumable; 3] = [Consumable::Mine, Consumable::GravityWell, Consumable::Emp];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Consumable::Mine => "Mine",
+            Consumable::GravityWell => "Gravity Well",
+            Consumable::Emp => "EMP Charge",
+        }
+    }
+
+    pub fn image(self, image_assets: &ImageAssets) -> &Handle<Image> {
+        match self {
+            Consumable::Mine => &image_assets.mine,
+            Consumable::GravityWell => &image_assets.gravity_well,
+            Consumable::Emp => &image_assets.emp,
+        }
+    }
+}
+
+/// The consumables owned by the player and the ones assigned to the hotbar.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    counts: BTreeMap<Consumable, u32>,
+    hotbar: [Option<Consumable>; CONSUMABLE_HOTBAR_SIZE],
+}
+
+impl Inventory {
+    pub fn add(&mut self, consumable: Consumable, amount: u32) {
+        *self.counts.entry(consumable).or_default() += amount;
+    }
+
+    pub fn count(&self, consumable: Consumable) -> u32 {
+        self.counts.get(&consumable).copied().unwrap_or(0)
+    }
+
+    /// Removes one of the consumable, only if there is one left.
+    pub fn try_use(&mut self, consumable: Consumable) -> bool {
+        match self.counts.get_mut(&consumable) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn slot(&self, slot: usize) -> Option<Consumable> {
+        self.hotbar.get(slot).copied().flatten()
+    }
+
+    /// Assigns the consumable to a slot of the hotbar,
+    /// removing it from the slot it was previously assigned to.
+    pub fn assign(&mut self, slot: usize, consumable: Consumable) {
+        for assigned in &mut self.hotbar {
+            if *assigned == Some(consumable) {
+                *assigned = None;
+            }
+        }
+        if let Some(assigned) = self.hotbar.get_mut(slot) {
+            *assigned = Some(consumable);
+        }
+    }
+}
+
+/// The consumable dragged from the inventory panel to the hotbar.
+#[derive(Debug, Default)]
+struct DraggedConsumable(Option<Consumable>);
+
+/// Sent once a consumable has been removed from the inventory to be deployed there.
+pub struct ConsumableUsedEvent {
+    pub consumable: Consumable,
+    pub position: Vec2,
+    pub player: PlayerId,
+}
+
+#[derive(Component, Debug)]
+struct InventoryPanel;
+
+/// A line of the inventory panel, pressing it starts dragging the consumable.
+#[derive(Component, Debug)]
+struct InventoryEntry(Consumable);
+
+#[derive(Component, Debug)]
+struct InventoryEntryCount(Consumable);
+
+/// A slot of the consumable hotbar, consumables are dropped on it.
+#[derive(Component, Debug)]
+struct ConsumableSlot(usize);
+
+#[derive(Component, Debug)]
+struct ConsumableSlotIcon(usize);
+
+#[derive(Component, Debug)]
+struct ConsumableSlotCount(usize);
+
+#[derive(Component, Debug)]
+struct DraggedConsumableIcon;
+
+#[derive(Component, Debug)]
+struct Mine;
+
+/// Pulls the asteroids around it until the timer finishes.
+#[derive(Component, Debug)]
+struct GravityWell(Timer);
+
+/// The velocity of an asteroid before an EMP stunned it, restored afterward.
+#[derive(Component, Debug)]
+struct Stunned {
+    timer: Timer,
+    velocity: Velocity,
+}
+
+fn reset_inventory(mut inventory: ResMut<Inventory>, mut dragged: ResMut<DraggedConsumable>) {
+    *inventory = Inventory::default();
+    *dragged = DraggedConsumable::default();
+}
+
+fn setup_inventory_panel(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 18.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Percent(40.0), top: Val::Percent(30.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            color: palette.panel.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(InventoryPanel)
+        .insert(ThemedPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("Inventory (I)", text_style.clone()));
+
+            for consumable in Consumable::ALL {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            margin: UiRect::all(Val::Px(4.0)),
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button.into(),
+                        ..default()
+                    })
+                    .insert(Interaction::default())
+                    .insert(InventoryEntry(consumable))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(ImageBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(25.0), Val::Px(25.0)),
+                                margin: UiRect::all(Val::Px(4.0)),
+                                ..default()
+                            },
+                            image: consumable.image(&image_assets).clone().into(),
+                            focus_policy: FocusPolicy::Pass,
+                            ..default()
+                        });
+
+                        parent.spawn_bundle(TextBundle::from_section(
+                            consumable.label(),
+                            text_style.clone(),
+                        ));
+
+                        parent
+                            .spawn_bundle(
+                                TextBundle::from_section("x0", text_style.clone()).with_style(
+                                    Style { margin: UiRect::all(Val::Px(6.0)), ..default() },
+                                ),
+                            )
+                            .insert(InventoryEntryCount(consumable));
+                    });
+            }
+        });
+}
+
+fn setup_consumable_hotbar(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 14.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), bottom: Val::Px(20.0), ..default() },
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            for slot in 0..CONSUMABLE_HOTBAR_SIZE {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(60.0), Val::Px(60.0)),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            flex_direction: FlexDirection::ColumnReverse,
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button.into(),
+                        ..default()
+                    })
+                    .insert(Interaction::default())
+                    .insert(ConsumableSlot(slot))
+                    .with_children(|parent| {
+                        let key = ["Q", "W", "E"][slot];
+                        parent.spawn_bundle(TextBundle::from_section(key, text_style.clone()));
+
+                        parent
+                            .spawn_bundle(ImageBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(25.0), Val::Px(25.0)),
+                                    ..default()
+                                },
+                                focus_policy: FocusPolicy::Pass,
+                                visibility: Visibility { is_visible: false },
+                                ..default()
+                            })
+                            .insert(ConsumableSlotIcon(slot));
+
+                        parent
+                            .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                            .insert(ConsumableSlotCount(slot));
+                    });
+            }
+        });
+}
+
+fn toggle_inventory_panel(
+    actions: Actions,
+    mut panel: Query<&mut Visibility, With<InventoryPanel>>,
+) {
+    if actions.just_pressed(Action::ToggleInventory) {
+        for mut visibility in &mut panel {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+/// Start dragging a consumable when the player presses its line in the inventory panel.
+fn drag_consumable_from_inventory(
+    inventory: Res<Inventory>,
+    mut dragged: ResMut<DraggedConsumable>,
+    panel: Query<&Visibility, With<InventoryPanel>>,
+    entries: Query<(&Interaction, &InventoryEntry)>,
+    buttons: Res<Input<MouseButton>>,
+) {
+    if buttons.just_pressed(MouseButton::Left) && panel.iter().any(|v| v.is_visible) {
+        for (interaction, InventoryEntry(consumable)) in &entries {
+            if *interaction == Interaction::Clicked && inventory.count(*consumable) > 0 {
+                dragged.0 = Some(*consumable);
+            }
+        }
+    }
+}
+
+/// Assign the dragged consumable to the hotbar slot it is released on.
+fn drop_dragged_consumable_on_hotbar(
+    mut inventory: ResMut<Inventory>,
+    mut dragged: ResMut<DraggedConsumable>,
+    slots: Query<(&Interaction, &ConsumableSlot)>,
+    buttons: Res<Input<MouseButton>>,
+) {
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+
+    if let Some(consumable) = dragged.0.take() {
+        // The pressed node keeps the interaction, the ones under the cursor are only hovered.
+        let slot = slots.iter().find(|(interaction, _)| **interaction == Interaction::Hovered);
+        if let Some((_, ConsumableSlot(slot))) = slot {
+            inventory.assign(*slot, consumable);
+        }
+    }
+}
+
+/// The consumables of the hotbar deployed under the cursor when their key is pressed.
+fn press_consumable_keys(
+    actions: Actions,
+    wnds: Res<Windows>,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let world_pos = match cursor_world_position(&wnds, camera, camera_transform) {
+        Some(world_pos) => world_pos,
+        None => return,
+    };
+
+    for slot in 0..CONSUMABLE_HOTBAR_SIZE {
+        if actions.just_pressed(Action::Consumable(slot)) {
+            let position = world_pos.to_array();
+            player_inputs.send(PlayerInputEvent(PlayerInput::Consumable { slot, position }));
+        }
+    }
+}
+
+/// Take the deployed consumables out of the inventory.
+fn use_consumables(
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut inventory: ResMut<Inventory>,
+    mut consumable_used: EventWriter<ConsumableUsedEvent>,
+) {
+    for SimInputEvent { player, input } in sim_inputs.iter() {
+        if let PlayerInput::Consumable { slot, position } = *input {
+            if let Some(consumable) = inventory.slot(slot) {
+                if inventory.try_use(consumable) {
+                    let position = Vec2::from(position);
+                    let player = *player;
+                    consumable_used.send(ConsumableUsedEvent { consumable, position, player });
+                }
+            }
+        }
+    }
+}
+
+fn deploy_consumables(
+    mut commands: Commands,
+    mut consumable_used: EventReader<ConsumableUsedEvent>,
+    image_assets: Res<ImageAssets>,
+    planet: Query<&Transform, With<Planet>>,
+    mut asteroids: Query<(Entity, &Transform, &mut Velocity, Option<&Stunned>), With<Asteroid>>,
+) {
+    for ConsumableUsedEvent { consumable, position, player } in consumable_used.iter() {
+        let translation = RenderLayer::Background.at(*position);
+        match consumable {
+            Consumable::Mine => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: Some(Vec2::splat(MINE_RADIUS * 2.0)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(translation),
+                        texture: image_assets.mine.clone(),
+                        ..default()
+                    })
+                    .insert(Mine)
+                    .insert(*player)
+                    .insert(OutOfBounds::Despawn)
+                    .insert(Collider::ball(MINE_RADIUS))
+                    .insert(Sensor)
+                    .insert(ActiveEvents::COLLISION_EVENTS);
+            }
+            Consumable::GravityWell => {
+                commands
+                    .spawn_bundle(SpriteBundle {
+                        sprite: Sprite { custom_size: Some(Vec2::splat(40.0)), ..default() },
+                        transform: Transform::from_translation(translation),
+                        texture: image_assets.gravity_well.clone(),
+                        ..default()
+                    })
+                    .insert(GravityWell(Timer::new(
+                        Duration::from_secs(GRAVITY_WELL_DURATION),
+                        false,
+                    )))
+                    .insert(OutOfBounds::Despawn);
+            }
+            // The EMP goes off from the planet wherever the cursor is.
+            Consumable::Emp => {
+                let planet_translation = planet.single().translation;
+                for (entity, transform, mut velocity, stunned) in &mut asteroids {
+                    if transform.translation.xy().distance(planet_translation.xy()) <= EMP_RADIUS {
+                        let timer = Timer::new(Duration::from_secs(EMP_STUN_DURATION), false);
+                        let velocity = match stunned {
+                            Some(stunned) => stunned.velocity,
+                            None => std::mem::replace(&mut *velocity, Velocity::zero()),
+                        };
+                        commands.entity(entity).insert(Stunned { timer, velocity });
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Destroy the asteroids touching a mine, the mine only goes off once.
+fn detonate_mines(
+    mut commands: Commands,
+    mines: Query<(Entity, &PlayerId), With<Mine>>,
+    asteroids: Query<(Entity, &Transform), With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut asteroid_destroyed: EventWriter<AsteroidDestroyedEvent>,
+) {
+    let mut detonated = HashSet::new();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let comps = if let (Ok(mine), Ok(comps)) = (mines.get(*e1), asteroids.get(*e2)) {
+                Some((mine, comps))
+            } else if let (Ok(mine), Ok(comps)) = (mines.get(*e2), asteroids.get(*e1)) {
+                Some((mine, comps))
+            } else {
+                None
+            };
+
+            if let Some(((mine, owner), (entity, transform))) = comps {
+                if detonated.insert(mine) {
+                    commands.entity(mine).despawn();
+                    asteroid_destroyed.send(AsteroidDestroyedEvent {
+                        entity,
+                        translation: transform.translation,
+                        cause: DestroyCause::Destroy,
+                        player: Some(*owner),
+                    });
+                }
+            }
+        }
+    }
+}
+
+fn pull_asteroids_into_gravity_wells(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut wells: Query<(Entity, &Transform, &mut GravityWell)>,
+    mut asteroids: Query<(&Transform, &mut Velocity), (With<Asteroid>, Without<Stunned>)>,
+) {
+    for (entity, well_transform, mut well) in &mut wells {
+        if well.0.tick(speed.delta(&time)).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        for (transform, mut velocity) in &mut asteroids {
+            let diff = well_transform.translation.xy() - transform.translation.xy();
+            if diff.length() <= GRAVITY_WELL_RADIUS {
+                velocity.linvel +=
+                    diff.normalize_or_zero() * GRAVITY_WELL_PULL * speed.delta_seconds(&time);
+            }
+        }
+    }
+}
+
+/// Keep the stunned asteroids still and give them their velocity back once the stun wears off.
+fn wake_up_stunned_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut asteroids: Query<(Entity, &mut Velocity, &mut Stunned), With<Asteroid>>,
+) {
+    for (entity, mut velocity, mut stunned) in &mut asteroids {
+        if stunned.timer.tick(speed.delta(&time)).finished() {
+            *velocity = stunned.velocity;
+            commands.entity(entity).remove::<Stunned>();
+        } else {
+            *velocity = Velocity::zero();
+        }
+    }
+}
+
+fn highlight_hovered_slots(
+    palette: Res<Palette>,
+    dragged: Res<DraggedConsumable>,
+    mut slots: Query<(&Interaction, &mut UiColor), With<ConsumableSlot>>,
+) {
+    for (interaction, mut color) in &mut slots {
+        let hovered = dragged.0.is_some() && *interaction == Interaction::Hovered;
+        color.0 = if hovered { palette.button_hovered } else { palette.button };
+    }
+}
+
+fn recolor_inventory_entries(
+    palette: Res<Palette>,
+    mut entries: Query<&mut UiColor, With<InventoryEntry>>,
+) {
+    if palette.is_changed() {
+        entries.for_each_mut(|mut color| color.0 = palette.button);
+    }
+}
+
+fn draw_inventory(
+    inventory: Res<Inventory>,
+    image_assets: Res<ImageAssets>,
+    mut entry_counts: Query<(&mut Text, &InventoryEntryCount), Without<ConsumableSlotCount>>,
+    mut slot_icons: Query<(&mut UiImage, &mut Visibility, &ConsumableSlotIcon)>,
+    mut slot_counts: Query<(&mut Text, &ConsumableSlotCount), Without<InventoryEntryCount>>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+
+    for (mut text, InventoryEntryCount(consumable)) in &mut entry_counts {
+        text.sections[0].value = format!("x{}", inventory.count(*consumable));
+    }
+
+    for (mut image, mut visibility, ConsumableSlotIcon(slot)) in &mut slot_icons {
+        match inventory.slot(*slot) {
+            Some(consumable) => {
+                *image = consumable.image(&image_assets).clone().into();
+                visibility.is_visible = true;
+            }
+            None => visibility.is_visible = false,
+        }
+    }
+
+    for (mut text, ConsumableSlotCount(slot)) in &mut slot_counts {
+        text.sections[0].value = match inventory.slot(*slot) {
+            Some(consumable) => inventory.count(consumable).to_string(),
+            None => String::new(),
+        };
+    }
+}
+
+/// The dragged consumable follows the cursor.
+fn draw_dragged_consumable(
+    mut commands: Commands,
+    dragged: Res<DraggedConsumable>,
+    image_assets: Res<ImageAssets>,
+    wnds: Res<Windows>,
+    icons: Query<Entity, With<DraggedConsumableIcon>>,
+) {
+    if !dragged.is_changed() {
+        return;
+    }
+
+    icons.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let cursor = wnds.get_primary().and_then(|wnd| wnd.cursor_position());
+    if let (Some(consumable), Some(cursor)) = (dragged.0, cursor) {
+        let follows = FollowsCursor { half_size: Vec2::splat(12.5) };
+        commands
+            .spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(25.0), Val::Px(25.0)),
+                    position_type: PositionType::Absolute,
+                    position: follows.position(cursor),
+                    ..default()
+                },
+                image: consumable.image(&image_assets).clone().into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            })
+            .insert(DraggedConsumableIcon)
+            .insert(follows);
+    }
+}
@@ -0,0 +1,36 @@
+//! The layers the things of the world are drawn in, from the back to the front.
+//!
+//! Every sprite and mesh of the world is spawned at the z of its layer, the
+//! children are drawn relatively to their parent, slightly in front or behind.
+//! The 2D camera only sees the positive z, the layers start above zero.
+
+use bevy::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RenderLayer {
+    /// The range rings and the consumables deployed in space.
+    Background,
+    Planet,
+    Asteroids,
+    /// The dice, the scrap and the consumables dropped by the asteroids.
+    Loot,
+    Ships,
+    /// The particles and the animations of the abilities.
+    Effects,
+    /// The markers drawn over everything else, like the pings of the chat.
+    Overlays,
+}
+
+impl RenderLayer {
+    /// The distance between two layers, for the children to fit in between.
+    const SPACING: f32 = 10.0;
+
+    pub fn z(self) -> f32 {
+        (self as u8 + 1) as f32 * RenderLayer::SPACING
+    }
+
+    /// This position on this layer.
+    pub fn at(self, position: Vec2) -> Vec3 {
+        position.extend(self.z())
+    }
+}
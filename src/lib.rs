@@ -0,0 +1,1692 @@
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
+
+use std::collections::HashSet;
+use std::f32::consts::PI;
+use std::mem;
+use std::time::Duration;
+
+use bevy::asset::{AssetPlugin, AssetServerSettings};
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy::ui::FocusPolicy;
+use bevy::window::WindowSettings;
+use bevy_asset_loader::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_tweening::lens::TransformRotateZLens;
+use bevy_tweening::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::{AbilitiesPlugin, ShipSpeedBoost};
+use crate::accessibility::AccessibilityPlugin;
+use crate::animation::{AnimationClip, AnimationPlugin, SpriteAnimation, Transition};
+use crate::behavior::BehaviorPlugin;
+use crate::camera_modes::CameraModesPlugin;
+use crate::campaign::{CampaignPlugin, LevelPlanet};
+use crate::chat::ChatPlugin;
+use crate::cinematic::CinematicPlugin;
+use crate::cities::CitiesPlugin;
+#[cfg(feature = "cloud-sync")]
+use crate::cloud_sync::CloudSyncPlugin;
+use crate::controls::{Actions, ControlsPlugin};
+use crate::crafting::CraftingPlugin;
+use crate::credits::CreditsPlugin;
+use crate::dice::{DiceBag, DiceNumber};
+use crate::endless::{Difficulty, EndlessPlugin};
+use crate::evacuation::EvacuationPlugin;
+use crate::event_log::{EventLog, EventLogPlugin};
+use crate::fleet::FleetPlugin;
+use crate::fusion::FusionPlugin;
+use crate::gamble::GamblePlugin;
+use crate::ghost::GhostPlugin;
+use crate::hull::{HullPlugin, ShipHull};
+use crate::interpolation::InterpolationPlugin;
+use crate::inventory::InventoryPlugin;
+use crate::layers::RenderLayer;
+use crate::lifecycle::LifecyclePlugin;
+use crate::lobby::LobbyPlugin;
+use crate::lockstep::{LockstepPlugin, PlayerInput, PlayerInputEvent, SimId, SimInputEvent};
+use crate::lod::LodPlugin;
+use crate::loot::{LootPlugin, LootTable};
+use crate::lucky::LuckyPlugin;
+use crate::menu::MenuPlugin;
+use crate::merge::{MergePlugin, ShipTier};
+use crate::metrics::MetricsPlugin;
+use crate::mining::MiningPlugin;
+use crate::mods::{ModAssetIoPlugin, ModsPlugin};
+use crate::music::MusicPlugin;
+use crate::mutators::{MutatorsPlugin, RunRules};
+use crate::objectives::ObjectivesPlugin;
+use crate::photo::PhotoPlugin;
+use crate::players::{PlayerId, Players, PlayersPlugin};
+use crate::poker::{HeldHand, PokerPlugin};
+use crate::profile::ProfilePlugin;
+use crate::quit::QuitPlugin;
+use crate::rumble::RumblePlugin;
+use crate::scrap::ScrapPlugin;
+use crate::scripting::ScriptingPlugin;
+use crate::seeds::SeedsPlugin;
+use crate::selection::SelectionPlugin;
+use crate::settings::{GameSettings, SettingsPlugin};
+use crate::shipyard::ShipyardPlugin;
+use crate::shop::{DiceInsurance, ShopPlugin};
+use crate::sound::{PlaySoundEvent, Sound, SoundPlugin};
+use crate::speed::{SimulationSpeed, SpeedPlugin};
+use crate::sweep::run_sweep;
+use crate::theme::ThemePlugin;
+use crate::threat_view::ThreatViewPlugin;
+use crate::toasts::{ToastEvent, ToastsPlugin};
+use crate::touch::TouchControlsPlugin;
+use crate::tuning::{AsteroidKind, Tuning, TuningChanged, TuningHandle, TuningPlugin};
+#[cfg(feature = "dev-tools")]
+use crate::tuning_panel::TuningPanelPlugin;
+use crate::ui_scale::UiScalePlugin;
+use crate::victory::VictoryPlugin;
+use crate::watchdog::{Watchdog, WatchdogPlugin};
+use crate::waves::{Wave, WavesPlugin};
+
+/// The co-op server simulates the runs of its rooms, and reads the lines of
+/// the messages like the games.
+pub use crate::headless::RoomSimulation;
+pub use crate::net::MAX_LINE_LENGTH;
+
+mod abilities;
+mod accessibility;
+mod animation;
+mod behavior;
+mod camera_modes;
+mod campaign;
+mod chat;
+mod cinematic;
+mod cities;
+#[cfg(feature = "cloud-sync")]
+mod cloud_sync;
+mod controls;
+mod crafting;
+mod credits;
+mod dice;
+mod endless;
+mod evacuation;
+mod event_log;
+mod fleet;
+mod fusion;
+mod gamble;
+mod ghost;
+mod headless;
+mod hull;
+mod interpolation;
+mod inventory;
+mod layers;
+mod lifecycle;
+mod lobby;
+mod lockstep;
+mod lod;
+mod logic;
+mod loot;
+mod lucky;
+mod menu;
+mod merge;
+mod metrics;
+mod mining;
+mod mods;
+mod music;
+mod mutators;
+mod net;
+mod objectives;
+mod photo;
+mod players;
+mod poker;
+mod profile;
+mod quality;
+mod quit;
+mod ron_asset;
+mod rumble;
+mod save;
+mod scrap;
+mod scripting;
+mod seeds;
+mod selection;
+mod settings;
+mod shapes;
+mod shipyard;
+mod shop;
+mod sound;
+mod speed;
+mod sweep;
+mod theme;
+mod threat_view;
+mod toasts;
+mod touch;
+mod tuning;
+#[cfg(feature = "dev-tools")]
+mod tuning_panel;
+mod ui_scale;
+mod victory;
+mod watchdog;
+mod waves;
+
+const ASTEROID_SPAWN_RADIUS_DISTANCE: f32 = 800.0;
+const ASTEROID_RADIUS: f32 = 10.0;
+const ASTEROID_HEALTH: u32 = 3; // in bumps
+
+const PLANET_RADIUS: f32 = 50.0;
+const PLANET_MAX_HEALTH: u32 = 10;
+const PLANET_SHIELD_MAX_CHARGES: u32 = 3;
+const PLANET_SHIELD_COLOR: Color = Color::rgba(0.5, 0.8, 1.0, 0.3);
+
+const DICE_ROLL_FPS: f32 = 15.0;
+const SHIP_BUMP_FORCE_BY_PIP: f32 = 0.5;
+const SHIP_DICE_DROP_RADIUS: f32 = 20.0;
+const SHIP_DESTROY_BLAST_RADIUS_BY_PIP: f32 = 5.0;
+const SHIP_DESTROY_BLAST_MAX_RADIUS: f32 = 60.0;
+const SHIP_PLANET_SIGHT: f32 = 100.0;
+
+const WORLD_RADIUS: f32 = 1200.0;
+const PHYSICS_TIMESTEP: f32 = 1.0 / 60.0; // in second
+
+/// Starts the game, or the balance sweep with the `--sweep` argument.
+#[bevy_main]
+pub fn main() {
+    if std::env::args().any(|arg| arg == "--sweep") {
+        return run_sweep();
+    }
+
+    let mut app = App::new();
+
+    // The quit plugin asks for a confirmation before closing the window,
+    // the assets are reloaded when modified on disk in the dev builds.
+    app.insert_resource(WindowSettings { close_when_requested: false, ..default() })
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: cfg!(debug_assertions),
+            ..default()
+        })
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.add_before::<AssetPlugin, _>(ModAssetIoPlugin)
+        })
+        .insert_resource(ClearColor(Color::BLACK))
+        .insert_resource(Msaa::default())
+        .add_plugin(GamePlugin);
+
+    #[cfg(feature = "debug-render")]
+    app.add_plugin(RapierDebugRenderPlugin::default());
+
+    #[cfg(feature = "cloud-sync")]
+    app.add_plugin(CloudSyncPlugin);
+
+    #[cfg(feature = "dev-tools")]
+    app.add_plugin(TuningPanelPlugin);
+
+    app.run();
+}
+
+/// The gameplay, the screens and the sounds of the game, without the window and
+/// the renderer, shared by the game and the headless simulations of the runs.
+struct GamePlugin;
+
+impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(TweeningPlugin)
+            .insert_resource(DiceBag::default())
+            .insert_resource(WorldBounds { radius: WORLD_RADIUS })
+            .insert_resource(DraggedDice::default())
+            .insert_resource(PlanetShield { charges: PLANET_SHIELD_MAX_CHARGES })
+            .insert_resource(PlanetHealth { current: PLANET_MAX_HEALTH })
+            .insert_resource(GameRng::from_entropy())
+            .insert_resource(AsteroidRng::from_seed(thread_rng().gen()))
+            .insert_resource(GameMode::Endless(Difficulty::Normal))
+            .insert_resource(RunSetup::default())
+            .add_state(GameState::MainMenu)
+            .add_event::<DiceOwnedEvent>()
+            .add_event::<DiceLostEvent>()
+            .add_event::<PlanetImpactEvent>()
+            .add_event::<AsteroidDestroyedEvent>()
+            .init_collection::<ImageAssets>()
+            .init_collection::<FontAssets>()
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
+            .insert_resource(RapierConfiguration {
+                gravity: Vec2::ZERO,
+                timestep_mode: physics_timestep_mode(),
+                ..default()
+            });
+
+        app.add_plugin(TuningPlugin)
+            .add_plugin(AnimationPlugin)
+            .add_plugin(SoundPlugin)
+            .add_plugin(ProfilePlugin)
+            .add_plugin(MenuPlugin)
+            .add_plugin(CampaignPlugin)
+            .add_plugin(EndlessPlugin)
+            .add_plugin(GhostPlugin)
+            .add_plugin(VictoryPlugin)
+            .add_plugin(CreditsPlugin)
+            .add_plugin(QuitPlugin)
+            .add_plugin(SettingsPlugin)
+            .add_plugin(ControlsPlugin)
+            .add_plugin(TouchControlsPlugin)
+            .add_plugin(ModsPlugin)
+            .add_plugin(MusicPlugin)
+            .add_plugin(RumblePlugin)
+            .add_plugin(AccessibilityPlugin)
+            .add_plugin(UiScalePlugin)
+            .add_plugin(ThemePlugin)
+            .add_plugin(PhotoPlugin)
+            .add_plugin(LifecyclePlugin)
+            .add_plugin(CinematicPlugin)
+            .add_plugin(SpeedPlugin)
+            .add_plugin(MutatorsPlugin)
+            .add_plugin(InterpolationPlugin)
+            .add_plugin(LockstepPlugin)
+            .add_plugin(LobbyPlugin)
+            .add_plugin(ChatPlugin)
+            .add_plugin(PlayersPlugin)
+            .add_plugin(MetricsPlugin)
+            .add_plugin(SeedsPlugin)
+            .add_plugin(FleetPlugin)
+            .add_plugin(SelectionPlugin)
+            .add_plugin(HullPlugin)
+            .add_plugin(BehaviorPlugin)
+            .add_plugin(MergePlugin)
+            .add_plugin(ToastsPlugin)
+            .add_plugin(WavesPlugin)
+            .add_plugin(AbilitiesPlugin)
+            .add_plugin(PokerPlugin)
+            .add_plugin(GamblePlugin)
+            .add_plugin(FusionPlugin)
+            .add_plugin(LuckyPlugin)
+            .add_plugin(ShopPlugin)
+            .add_plugin(ScrapPlugin)
+            .add_plugin(LootPlugin)
+            .add_plugin(InventoryPlugin)
+            .add_plugin(CraftingPlugin)
+            .add_plugin(EventLogPlugin)
+            .add_plugin(ObjectivesPlugin)
+            .add_plugin(ScriptingPlugin)
+            .add_plugin(WatchdogPlugin)
+            .add_plugin(LodPlugin)
+            .add_plugin(CameraModesPlugin)
+            .add_plugin(ThreatViewPlugin)
+            .add_plugin(CitiesPlugin)
+            .add_plugin(EvacuationPlugin)
+            .add_plugin(MiningPlugin)
+            .add_plugin(ShipyardPlugin);
+
+        // Every screen is cleared when the game leaves it.
+        for state in GameState::ALL {
+            app.add_system_set(SystemSet::on_exit(state).with_system(despawn_screen));
+        }
+
+        app.add_startup_system(setup_graphics)
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_run)
+                    .with_system(setup_planet)
+                    .with_system(setup_planet_health_indicator)
+                    // .with_system(setup_debug)
+                    .with_system(setup_asteroid_spawning)
+                    .with_system(setup_ships),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_asteroids)
+                    .with_system(refresh_asteroid_loot)
+                    .with_system(move_ships)
+                    .with_system(despawn_asteroids_on_planet_collision)
+                    .with_system(damage_planet_on_asteroid_collision)
+                    .with_system(bump_asteroids_on_ship_collision_with_bump_power)
+                    .with_system(destroy_asteroids_on_ship_collision_with_destroy_power)
+                    .with_system(collect_dices_by_clicking)
+                    .with_system(collect_picked_dice)
+                    .with_system(drag_dice_from_bag)
+                    .with_system(drop_dragged_dice_on_ships.after(drag_dice_from_bag))
+                    .with_system(invest_dice_into_ships)
+                    .with_system(manage_dice_events)
+                    .with_system(draw_dice_bag)
+                    .with_system(move_cursor_followers.after(draw_dice_bag))
+                    .with_system(enforce_world_bounds)
+                    .with_system(draw_planet_shield)
+                    .with_system(draw_planet_health.after(damage_planet_on_asteroid_collision))
+                    .with_system(lose_when_the_planet_is_destroyed),
+            );
+    }
+}
+
+/// The deterministic mode steps the physics by a fixed amount of time on the
+/// ticks of the run, so that the outcome of the same inputs doesn't depend on
+/// the frame rate.
+fn physics_timestep_mode() -> TimestepMode {
+    if cfg!(feature = "deterministic") {
+        TimestepMode::Fixed { dt: PHYSICS_TIMESTEP, substeps: 1 }
+    } else {
+        RapierConfiguration::default().timestep_mode
+    }
+}
+
+fn setup_graphics(mut commands: Commands) {
+    commands.spawn_bundle(Camera2dBundle::default()).insert(SpaceCamera).insert(Persistent);
+}
+
+/// Despawn everything but the persistent entities, the next screen spawns its own entities.
+fn despawn_screen(
+    mut commands: Commands,
+    entities: Query<Entity, (Without<Parent>, Without<Persistent>)>,
+) {
+    entities.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
+
+/// Start a new run from the setup chosen in the menus, the resources
+/// are mutated in place for the systems running in the same frame.
+fn reset_run(
+    run_setup: Res<RunSetup>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut dragged: ResMut<DraggedDice>,
+    mut shield: ResMut<PlanetShield>,
+    mut health: ResMut<PlanetHealth>,
+) {
+    *dice_bag = run_setup.dice.iter().copied().collect();
+    *dragged = DraggedDice::default();
+    *shield = PlanetShield { charges: PLANET_SHIELD_MAX_CHARGES };
+    *health = PlanetHealth { current: PLANET_MAX_HEALTH };
+}
+
+/// Configure the main planet to defend
+fn setup_planet(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // Planet Earth
+    let planet_radius = PLANET_RADIUS;
+
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(shapes::circle(planet_radius)).into(),
+            material: materials.add(ColorMaterial::from(Color::rgb(0.302, 0.302, 1.0))),
+            transform: Transform::from_translation(RenderLayer::Planet.at(Vec2::ZERO)),
+            ..default()
+        })
+        .insert(Planet)
+        .insert(Collider::ball(planet_radius))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .with_children(|parent| {
+            // The shield is drawn behind the planet and fades with its charges.
+            parent
+                .spawn_bundle(MaterialMesh2dBundle {
+                    mesh: meshes.add(shapes::circle(planet_radius + 10.0)).into(),
+                    material: materials.add(ColorMaterial::from(PLANET_SHIELD_COLOR)),
+                    transform: Transform::from_xyz(0.0, 0.0, -1.0),
+                    ..default()
+                })
+                .insert(PlanetShieldBubble);
+        });
+}
+
+#[allow(unused)]
+fn setup_debug(mut dice_writer: EventWriter<DiceOwnedEvent>) {
+    let mut rng = thread_rng();
+    for _ in 0..rng.gen_range(2..5) {
+        dice_writer.send(DiceOwnedEvent(DiceNumber::from_rng(&mut rng), None));
+    }
+}
+
+/// Configure our asteroid spawning algorithm
+fn setup_asteroid_spawning(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let texture = shapes::disc(settings.quality.asteroid_texture_size());
+    commands.insert_resource(AsteroidSpawnConfig {
+        // A repeating timer of one second, ticked faster for shorter spawn intervals.
+        timer: Timer::new(Duration::from_secs(1), true),
+        texture: images.add(texture),
+    })
+}
+
+/// Spawn the ships of the fleet around the planet.
+fn setup_ships(
+    mut commands: Commands,
+    run_setup: Res<RunSetup>,
+    players: Res<Players>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (i, power) in run_setup.fleet.iter().enumerate() {
+        let owner = players.owner_of_ship(i);
+        spawn_ship(&mut commands, &mut meshes, &mut materials, *power, owner, fleet_position(i));
+    }
+}
+
+/// The place of the ship at this index of the fleet around the planet.
+fn fleet_position(index: usize) -> Vec2 {
+    // The ships are placed a quarter turn apart, starting from the top right,
+    // the next four are placed in between them.
+    let angle = PI / 4.0 - (index % 4) as f32 * PI / 2.0 + (index / 4) as f32 * PI / 4.0;
+    Vec2::new(angle.cos(), angle.sin()) * 100.0 * 2f32.sqrt()
+}
+
+fn spawn_ship(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<ColorMaterial>,
+    power: ShipPower,
+    owner: PlayerId,
+    position: Vec2,
+) -> Entity {
+    let a = Vec2::new(-0.5, 0.0);
+    let b = Vec2::new(0.0, 1.0);
+    let c = Vec2::new(0.5, 0.0);
+
+    let mut ship = commands.spawn_bundle(MaterialMesh2dBundle {
+        mesh: meshes.add(shapes::polygon(&[a, c, b])).into(),
+        transform: Transform::from_translation(RenderLayer::Ships.at(position))
+            .with_scale(Vec3::splat(ShipTier::default().scale())),
+        material: materials.add(ColorMaterial::from(Color::PURPLE)),
+        ..default()
+    });
+
+    match power {
+        ShipPower::Bump => ship.insert(ContactBumpPower),
+        ShipPower::Destroy => ship.insert(ContactDestroyPower),
+        ShipPower::Mine => ship.insert(MinerPower),
+    };
+
+    ship.insert(Ship)
+        .insert(power)
+        .insert(owner)
+        .insert(ShipCost::default())
+        .insert(ShipTier::default())
+        .insert(ShipHull::default())
+        .insert(DiceInvestment::default())
+        .insert(ShipTarget(None))
+        .insert(OutOfBounds::Recall)
+        .insert(RigidBody::Dynamic)
+        .insert(Collider::triangle(a, b, c))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Velocity::default())
+        .id()
+}
+
+fn spawn_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    planet: Query<&Transform, With<Planet>>,
+    level_planets: Query<&Transform, With<LevelPlanet>>,
+    wave: Res<Wave>,
+    held_hand: Res<HeldHand>,
+    rules: Res<RunRules>,
+    watchdog: Res<Watchdog>,
+    mut rng: ResMut<AsteroidRng>,
+    mut config: ResMut<AsteroidSpawnConfig>,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+) {
+    let _span = info_span!("spawn_asteroids").entered();
+    let tuning = match tunings.get(&tuning.0) {
+        Some(tuning) if !wave.is_intermission() => tuning,
+        _ => return,
+    };
+
+    // The later the wave the faster asteroids spawn, the poker hand held
+    // in the bag and the watchdog when over budget slow the spawning down.
+    let factor = wave.spawn_rate_factor() * rules.spawn_rate_factor * watchdog.spawn_rate_factor()
+        / held_hand.spawn_interval_factor()
+        / tuning.balance.spawn_interval;
+    config.timer.tick(speed.delta(&time).mul_f32(factor));
+
+    if config.timer.finished() {
+        let rng = &mut rng.0;
+        let kind = match tuning.choose_asteroid_kind(rng) {
+            Some(kind) => kind,
+            None => return,
+        };
+
+        if let Ok(planet_transform) = planet.get_single() {
+            // The asteroids head to any of the planets of a campaign level.
+            let others: Vec<_> = level_planets.iter().map(|t| t.translation).collect();
+            let planet_translation = match others.len() {
+                0 => planet_transform.translation,
+                n => match rng.gen_range(0..=n) {
+                    0 => planet_transform.translation,
+                    i => others[i - 1],
+                },
+            };
+            spawn_asteroid(&mut commands, &config.texture, rng, &rules, kind, planet_translation);
+        }
+    }
+}
+
+/// Spawn an asteroid of this kind at a random place around the planet, heading to it.
+fn spawn_asteroid(
+    commands: &mut Commands,
+    texture: &Handle<Image>,
+    rng: &mut GameRng,
+    rules: &RunRules,
+    kind: &AsteroidKind,
+    planet_translation: Vec3,
+) {
+    let center = planet_translation.truncate().to_array();
+    let position = logic::random_point_around(rng, center, ASTEROID_SPAWN_RADIUS_DISTANCE);
+    let translation = RenderLayer::Asteroids.at(Vec2::from(position));
+    let color = kind.choose_color(rng);
+
+    let diff = (planet_translation - translation).xy();
+    let direction = diff.normalize_or_zero();
+    let radius = ASTEROID_RADIUS * rules.asteroid_scale;
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { color, custom_size: Some(Vec2::splat(radius * 2.0)), ..default() },
+            texture: texture.clone(),
+            transform: Transform::from_translation(translation),
+            ..default()
+        })
+        .insert(Asteroid)
+        .insert(AsteroidHealth(ASTEROID_HEALTH * rules.asteroid_health_factor))
+        .insert(AsteroidKindName(kind.name.clone()))
+        .insert(kind.loot.clone())
+        .insert(OutOfBounds::Despawn)
+        .insert(RigidBody::Dynamic)
+        .insert(ExternalImpulse {
+            impulse: direction * kind.speed * rules.asteroid_speed_factor,
+            torque_impulse: 0.0,
+        })
+        .insert(Velocity::default())
+        .insert(Collider::ball(radius))
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Sleeping::disabled());
+}
+
+fn despawn_asteroids_on_planet_collision(
+    mut commands: Commands,
+    planet: Query<(), With<Planet>>,
+    asteroids: Query<Entity, With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    let _span = info_span!("despawn_asteroids_on_planet_collision").entered();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            if let (Ok(_), Ok(entity)) = (planet.get(*e1), asteroids.get(*e2)) {
+                commands.entity(entity).despawn();
+            } else if let (Ok(_), Ok(entity)) = (planet.get(*e2), asteroids.get(*e1)) {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+/// The charges of the planet shield absorb the impacts first, then every
+/// impact ruins a city of the planet and costs a dice of the bag.
+fn damage_planet_on_asteroid_collision(
+    time: Res<Time>,
+    mut log: ResMut<EventLog>,
+    planet: Query<(), With<Planet>>,
+    asteroids: Query<&Transform, With<Asteroid>>,
+    mut shield: ResMut<PlanetShield>,
+    mut health: ResMut<PlanetHealth>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut dice_lost: EventWriter<DiceLostEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+    mut planet_impacts: EventWriter<PlanetImpactEvent>,
+) {
+    let _span = info_span!("damage_planet_on_asteroid_collision").entered();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let hit = match (planet.get(*e1), asteroids.get(*e2)) {
+                (Ok(_), Ok(transform)) => Some(transform),
+                _ => match (planet.get(*e2), asteroids.get(*e1)) {
+                    (Ok(_), Ok(transform)) => Some(transform),
+                    _ => None,
+                },
+            };
+
+            if let Some(asteroid_transform) = hit {
+                let position = asteroid_transform.translation.truncate();
+                play_sound.send(PlaySoundEvent::at(Sound::PlanetImpact, position));
+                planet_impacts.send(PlanetImpactEvent { shielded: shield.charges > 0, position });
+
+                if shield.charges > 0 {
+                    shield.charges -= 1;
+                    let message = format!("Shield hit - {} charges left", shield.charges);
+                    log.push(&time, message);
+                    if shield.charges == 0 {
+                        toasts.send(ToastEvent::warning("Shield down!"));
+                    }
+                } else {
+                    health.current = health.current.saturating_sub(1);
+                    dice_lost.send(DiceLostEvent);
+                }
+            }
+        }
+    }
+}
+
+/// Bump the asteroids touching the ships with the bump power,
+/// every bump damages the asteroid until it breaks into scrap.
+fn bump_asteroids_on_ship_collision_with_bump_power(
+    mut ships: Query<
+        (&Transform, &DiceInvestment, &ShipTier, &PlayerId),
+        (With<Ship>, With<ContactBumpPower>),
+    >,
+    mut asteroids: Query<
+        (Entity, &Transform, &mut ExternalImpulse, &mut AsteroidHealth),
+        With<Asteroid>,
+    >,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut asteroid_destroyed: EventWriter<AsteroidDestroyedEvent>,
+) {
+    let _span = info_span!("bump_asteroids_on_ship_collision_with_bump_power").entered();
+    let bump_force = tuning.balance(&tunings).bump_force;
+    for (e1, e2) in started_collisions(&mut collision_events) {
+        let components =
+            if let (Ok(ship_comps), Ok(comps)) = (ships.get_mut(e1), asteroids.get_mut(e2)) {
+                Some((ship_comps, comps))
+            } else if let (Ok(ship_comps), Ok(comps)) = (ships.get_mut(e2), asteroids.get_mut(e1)) {
+                Some((ship_comps, comps))
+            } else {
+                None
+            };
+
+        if let Some((
+            (ship_transform, investment, tier, owner),
+            (entity, transform, mut ext_impl, mut health),
+        )) = components
+        {
+            let diff = (transform.translation - ship_transform.translation).xy();
+            let direction = diff.normalize_or_zero();
+            let force = (bump_force + investment.pips as f32 * SHIP_BUMP_FORCE_BY_PIP)
+                * tier.power_factor();
+            ext_impl.impulse = direction * force;
+            ext_impl.torque_impulse = 0.001;
+
+            health.0 = health.0.saturating_sub(1);
+            if health.0 == 0 {
+                asteroid_destroyed.send(AsteroidDestroyedEvent {
+                    entity,
+                    translation: transform.translation,
+                    cause: DestroyCause::Bump,
+                    player: Some(*owner),
+                });
+            }
+        }
+    }
+}
+
+/// Destroy the asteroids touching the ships with the destroy power, the dice
+/// invested in a ship make it blast the asteroids around the impact too.
+fn destroy_asteroids_on_ship_collision_with_destroy_power(
+    rapier_context: Res<RapierContext>,
+    mut ships: Query<
+        (&DiceInvestment, &ShipTier, &PlayerId),
+        (With<Ship>, With<ContactDestroyPower>),
+    >,
+    mut asteroids: Query<(Entity, &Transform), With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut asteroid_destroyed: EventWriter<AsteroidDestroyedEvent>,
+) {
+    let _span = info_span!("destroy_asteroids_on_ship_collision_with_destroy_power").entered();
+    for (e1, e2) in started_collisions(&mut collision_events) {
+        let comps = if let (Ok(investment), Ok(comps)) = (ships.get_mut(e1), asteroids.get_mut(e2))
+        {
+            Some((investment, comps))
+        } else if let (Ok(investment), Ok(comps)) = (ships.get_mut(e2), asteroids.get_mut(e1)) {
+            Some((investment, comps))
+        } else {
+            None
+        };
+
+        if let Some(((investment, tier, owner), (entity, transform))) = comps {
+            let translation = transform.translation;
+            asteroid_destroyed.send(AsteroidDestroyedEvent {
+                entity,
+                translation,
+                cause: DestroyCause::Destroy,
+                player: Some(*owner),
+            });
+
+            let blast_radius = (investment.pips as f32 * SHIP_DESTROY_BLAST_RADIUS_BY_PIP)
+                .min(SHIP_DESTROY_BLAST_MAX_RADIUS)
+                * tier.power_factor();
+            if blast_radius > 0.0 {
+                rapier_context.intersections_with_shape(
+                    translation.xy(),
+                    0.0,
+                    &Collider::ball(blast_radius),
+                    QueryFilter::default().exclude_collider(entity),
+                    |other| {
+                        if let Ok((entity, transform)) = asteroids.get(other) {
+                            let translation = transform.translation;
+                            asteroid_destroyed.send(AsteroidDestroyedEvent {
+                                entity,
+                                translation,
+                                cause: DestroyCause::Destroy,
+                                player: Some(*owner),
+                            });
+                        }
+                        true
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// The pairs of entities that started touching this frame, sorted so that
+/// the impulses and the loot rolls happen in the same order on every machine.
+fn started_collisions(collision_events: &mut EventReader<CollisionEvent>) -> Vec<(Entity, Entity)> {
+    let mut pairs: Vec<_> = collision_events
+        .iter()
+        .filter_map(|event| match *event {
+            CollisionEvent::Started(e1, e2, _) => Some((e1.min(e2), e1.max(e2))),
+            CollisionEvent::Stopped(..) => None,
+        })
+        .collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+/// Spawn a dice the player can collect by clicking on it,
+/// it rolls through its faces before showing its number.
+fn spawn_dice_loot(
+    commands: &mut Commands,
+    image_assets: &ImageAssets,
+    translation: Vec3,
+    number: DiceNumber,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { custom_size: Some(Vec2::splat(25.0)), ..default() },
+            transform: Transform::from_translation(RenderLayer::Loot.at(translation.truncate())),
+            texture: image_assets.handle_for_dice_number(number).clone(),
+            ..default()
+        })
+        .insert(DiceLoot { number })
+        .insert(dice_roll_animation(image_assets, number))
+        .insert(OutOfBounds::Despawn)
+        .insert(Animator::new(Tween::new(
+            EaseFunction::QuadraticInOut,
+            TweeningType::PingPong,
+            Duration::from_millis(150),
+            TransformRotateZLens { start: 0.0, end: PI / 6.0 },
+        )));
+}
+
+/// The dice rolls through all its faces twice, then stays on its number.
+fn dice_roll_animation(image_assets: &ImageAssets, number: DiceNumber) -> SpriteAnimation {
+    let face = number.pips() as usize - 1;
+    SpriteAnimation::new(
+        image_assets.dice_faces(),
+        vec![
+            AnimationClip {
+                frames: 0..DiceNumber::ALL.len(),
+                fps: DICE_ROLL_FPS,
+                transition: Transition::After { loops: 2, clip: 1 },
+            },
+            AnimationClip {
+                frames: face..face + 1,
+                fps: DICE_ROLL_FPS,
+                transition: Transition::Hold,
+            },
+        ],
+    )
+}
+
+/// The asteroids already in space drop the loot of the modified tuning.
+fn refresh_asteroid_loot(
+    mut tuning_changed: EventReader<TuningChanged>,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+    mut asteroids: Query<(&AsteroidKindName, &mut LootTable), With<Asteroid>>,
+) {
+    let tuning = match tunings.get(&tuning.0) {
+        Some(tuning) if tuning_changed.iter().count() > 0 => tuning,
+        _ => return,
+    };
+
+    for (name, mut loot) in &mut asteroids {
+        if let Some(kind) = tuning.asteroid_kind(&name.0) {
+            *loot = kind.loot.clone();
+        }
+    }
+}
+
+/// Move the ships to intercept the targeted asteroids and
+/// toward the planet when there is no target.
+fn move_ships(
+    time: Res<Time>,
+    simulation_speed: Res<SimulationSpeed>,
+    speed_boost: Res<ShipSpeedBoost>,
+    held_hand: Res<HeldHand>,
+    planet: Query<&Transform, With<Planet>>,
+    asteroids: Query<(&Transform, &Velocity), (With<Asteroid>, Without<Ship>)>,
+    mut ships: Query<(&Transform, &mut Velocity, &ShipTarget), With<Ship>>,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+) {
+    let _span = info_span!("move_ships").entered();
+    let planet_transform = match planet.get_single() {
+        Ok(planet_transform) => planet_transform,
+        Err(_) => return,
+    };
+
+    let speed =
+        tuning.balance(&tunings).ship_speed * speed_boost.factor() * held_hand.ship_speed_factor();
+    for (ship_transform, mut ship_velocity, ship_target) in &mut ships {
+        match ship_target.0.map(|e| asteroids.get(e)) {
+            Some(Ok((transform, velocity))) => {
+                // The ships head to where they will meet the asteroid, not to where it is.
+                let ship_position = ship_transform.translation.truncate();
+                let position = transform.translation.truncate();
+                let ship_speed = speed * simulation_speed.step_seconds(&time);
+                let aim = logic::intercept_point(
+                    ship_position.to_array(),
+                    ship_speed,
+                    position.to_array(),
+                    velocity.linvel.to_array(),
+                )
+                .map_or(position, Vec2::from);
+                let direction = (aim - ship_position).normalize_or_zero();
+                ship_velocity.linvel = direction * ship_speed;
+            }
+            _otherwise => {
+                let diff = (planet_transform.translation - ship_transform.translation).xy();
+                if diff.length() >= SHIP_PLANET_SIGHT {
+                    let direction = diff.normalize_or_zero();
+                    ship_velocity.linvel = direction * speed * simulation_speed.step_seconds(&time);
+                } else {
+                    ship_velocity.linvel = Vec2::ZERO;
+                }
+            }
+        }
+    }
+}
+
+/// Recall the ships and despawn everything else that drifted beyond the world bounds,
+/// physics islands must not be simulated forever far away from the planet.
+fn enforce_world_bounds(
+    mut commands: Commands,
+    bounds: Res<WorldBounds>,
+    planet: Query<&Transform, (With<Planet>, Without<OutOfBounds>)>,
+    mut entities: Query<(Entity, &OutOfBounds, &mut Transform, Option<&mut Velocity>)>,
+) {
+    let planet_translation = match planet.get_single() {
+        Ok(planet_transform) => planet_transform.translation,
+        Err(_) => return,
+    };
+
+    for (entity, out_of_bounds, mut transform, velocity) in &mut entities {
+        let diff = (transform.translation - planet_translation).xy();
+        if diff.length() <= bounds.radius {
+            continue;
+        }
+
+        match out_of_bounds {
+            OutOfBounds::Recall => {
+                // We bring it back in sight of the planet, from the side it went away.
+                let direction = diff.normalize_or_zero();
+                let position = planet_translation.xy() + direction * SHIP_PLANET_SIGHT;
+                transform.translation = position.extend(transform.translation.z);
+                if let Some(mut velocity) = velocity {
+                    *velocity = Velocity::zero();
+                }
+            }
+            OutOfBounds::Despawn => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
+
+fn setup_planet_health_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { bottom: Val::Px(95.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(PlanetHealthIndicator);
+        });
+}
+
+fn draw_planet_health(
+    health: Res<PlanetHealth>,
+    spawned: Query<(), Added<PlanetHealthIndicator>>,
+    mut indicator: Query<&mut Text, With<PlanetHealthIndicator>>,
+) {
+    if !health.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for mut text in &mut indicator {
+        text.sections[0].value = format!("Cities {}/{}", health.current, PLANET_MAX_HEALTH);
+    }
+}
+
+fn lose_when_the_planet_is_destroyed(
+    health: Res<PlanetHealth>,
+    mut state: ResMut<State<GameState>>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    if health.current == 0 && state.set(GameState::GameOver).is_ok() {
+        play_sound.send(PlaySoundEvent::new(Sound::GameOver));
+    }
+}
+
+fn draw_planet_shield(
+    shield: Res<PlanetShield>,
+    bubble: Query<&Handle<ColorMaterial>, With<PlanetShieldBubble>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if shield.is_changed() {
+        for handle in &bubble {
+            if let Some(material) = materials.get_mut(handle) {
+                let ratio = shield.charges as f32 / PLANET_SHIELD_MAX_CHARGES as f32;
+                material.color = PLANET_SHIELD_COLOR;
+                material.color.set_a(PLANET_SHIELD_COLOR.a() * ratio);
+            }
+        }
+    }
+}
+
+/// Collect the dice clicked or tapped.
+fn collect_dices_by_clicking(
+    actions: Actions,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    dices: Query<(&Sprite, &GlobalTransform, &SimId), With<DiceLoot>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let presses = actions.pointer_presses(camera, camera_transform);
+    for (sprite, transform, dice) in &dices {
+        if presses.iter().any(|world_pos| is_over_sprite(*world_pos, sprite, transform)) {
+            player_inputs.send(PlayerInputEvent(PlayerInput::CollectDice { dice: *dice }));
+        }
+    }
+}
+
+fn collect_picked_dice(
+    mut commands: Commands,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_owned: EventWriter<DiceOwnedEvent>,
+    dices: Query<(Entity, &SimId, &DiceLoot)>,
+) {
+    // Both players can click on the same dice in the same step.
+    let mut collected = HashSet::new();
+    for SimInputEvent { player, input } in sim_inputs.iter() {
+        let dice = match *input {
+            PlayerInput::CollectDice { dice } => dice,
+            _otherwise => continue,
+        };
+
+        let picked =
+            dices.iter().find(|(entity, id, _)| **id == dice && !collected.contains(entity));
+        if let Some((entity, _, loot)) = picked {
+            collected.insert(entity);
+            dice_owned.send(DiceOwnedEvent(loot.number, Some(*player)));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Whether the world position is over the sprite, with a margin to make clicking easier.
+fn is_over_sprite(world_pos: Vec2, sprite: &Sprite, transform: &GlobalTransform) -> bool {
+    match sprite.custom_size {
+        Some(size) => {
+            let translation = transform.translation().xy();
+            let p = world_pos;
+
+            let b_left = translation.x - size.x;
+            let b_right = translation.x + size.x;
+            let b_top = translation.y - size.y;
+            let b_bottom = translation.y + size.y;
+
+            (p.x >= b_left && p.x <= b_right) && (p.y >= b_top && p.y <= b_bottom)
+        }
+        None => false,
+    }
+}
+
+/// Returns the position of the cursor in world coordinates when it is inside the window.
+fn cursor_world_position(
+    wnds: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    // check if the cursor is inside the window and get its position
+    let screen_pos = camera_window(wnds, camera)?.cursor_position()?;
+    screen_to_world_position(wnds, camera, camera_transform, screen_pos)
+}
+
+/// Converts a position of the window of the camera, from its bottom left corner,
+/// into world coordinates, the headless games have no window.
+fn screen_to_world_position(
+    wnds: &Windows,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    screen_pos: Vec2,
+) -> Option<Vec2> {
+    let wnd = camera_window(wnds, camera)?;
+    // get the size of the window
+    let window_size = Vec2::new(wnd.width(), wnd.height());
+    // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
+    let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
+    // matrix for undoing the projection and camera transform
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+    // use it to convert ndc to world-space coordinates
+    let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+    // reduce it to a 2D value
+    Some(world_pos.truncate())
+}
+
+fn camera_window<'a>(wnds: &'a Windows, camera: &Camera) -> Option<&'a Window> {
+    if let RenderTarget::Window(id) = camera.target {
+        wnds.get(id)
+    } else {
+        wnds.get_primary()
+    }
+}
+
+/// Start dragging a dice when the player presses one of the dice of the bag.
+fn drag_dice_from_bag(
+    dice_bag: Res<DiceBag>,
+    mut dragged: ResMut<DraggedDice>,
+    slots: Query<(&Interaction, &DiceBagSlot)>,
+    buttons: Res<Input<MouseButton>>,
+) {
+    if buttons.just_pressed(MouseButton::Left) {
+        for (interaction, DiceBagSlot(index)) in &slots {
+            if *interaction == Interaction::Clicked {
+                if let Some(number) = dice_bag.get(*index) {
+                    dragged.0 = Some((*index, number));
+                }
+            }
+        }
+    }
+}
+
+/// Invest the dragged dice into the ship it is released on.
+fn drop_dragged_dice_on_ships(
+    wnds: Res<Windows>,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    mut dragged: ResMut<DraggedDice>,
+    ships: Query<(&GlobalTransform, &SimId), With<Ship>>,
+    buttons: Res<Input<MouseButton>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    if !buttons.just_released(MouseButton::Left) {
+        return;
+    }
+
+    let (_, number) = match dragged.0.take() {
+        Some(dragged) => dragged,
+        None => return,
+    };
+
+    let (camera, camera_transform) = camera.single();
+    let world_pos = match cursor_world_position(&wnds, camera, camera_transform) {
+        Some(world_pos) => world_pos,
+        None => return,
+    };
+
+    let ship = ships.iter().find(|(transform, _)| {
+        transform.translation().xy().distance(world_pos) <= SHIP_DICE_DROP_RADIUS
+    });
+
+    if let Some((_, ship)) = ship {
+        let ship = *ship;
+        player_inputs.send(PlayerInputEvent(PlayerInput::Invest { ship, number }));
+    }
+}
+
+/// Every pip of the invested dice makes the bumps of the ship stronger or the
+/// blast of its destruction wider.
+fn invest_dice_into_ships(
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut ships: Query<(&SimId, &mut DiceInvestment), With<Ship>>,
+) {
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let (ship, number) = match *input {
+            PlayerInput::Invest { ship, number } => (ship, number),
+            _otherwise => continue,
+        };
+
+        let ship = ships.iter_mut().find(|(id, _)| **id == ship);
+        if let Some((_, mut investment)) = ship {
+            // The bag can have changed since the dice was dropped, the dice may have moved.
+            let index = dice_bag.iter().position(|n| *n == number);
+            if let Some(number) = index.and_then(|i| dice_bag.remove(i)) {
+                investment.pips += number.pips();
+                investment.dice.push(number);
+            }
+        }
+    }
+}
+
+fn manage_dice_events(
+    time: Res<Time>,
+    wave: Res<Wave>,
+    mut log: ResMut<EventLog>,
+    mut insurance: ResMut<DiceInsurance>,
+    mut dice_lost: EventReader<DiceLostEvent>,
+    mut dice_owned: EventReader<DiceOwnedEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+) {
+    for DiceLostEvent in dice_lost.iter() {
+        // The insurance intercepts the first loss of every wave.
+        if insurance.is_armed(wave.number) {
+            insurance.used_in_wave = Some(wave.number);
+            log.push(&time, "Asteroid leaked - the insurance saved a dice");
+        } else if let Some([number]) = dice_bag.try_consume::<1>() {
+            log.push(&time, format!("Asteroid leaked - lost a {}", number.pips()));
+        } else {
+            log.push(&time, "Asteroid leaked - the bag is empty");
+        }
+    }
+
+    for DiceOwnedEvent(number, _) in dice_owned.iter() {
+        dice_bag.push(*number);
+    }
+}
+
+// We need to rewrite this part and not clear and recreate the UI from scratch
+// on every change, it makes it impossible to animate stuff and things...
+fn draw_dice_bag(
+    mut commands: Commands,
+    dice_bag: Res<DiceBag>,
+    dragged: Res<DraggedDice>,
+    wave: Res<Wave>,
+    insurance: Res<DiceInsurance>,
+    mut armed: Local<bool>,
+    mut dice_bag_numbers: Query<Entity, With<DiceBagNumbers>>,
+    image_assets: Res<ImageAssets>,
+    wnds: Res<Windows>,
+) {
+    let _span = info_span!("draw_dice_bag").entered();
+    // The wave changes every frame, only the insurance breaking matters.
+    let was_armed = mem::replace(&mut *armed, insurance.is_armed(wave.number));
+    let changed = dice_bag.is_changed() || dragged.is_changed() || insurance.is_changed();
+    if !changed && was_armed == *armed && !dice_bag_numbers.is_empty() {
+        return;
+    }
+
+    // We clear the screen of the bag dice numbers list.
+    dice_bag_numbers.for_each_mut(|entity| commands.entity(entity).despawn_recursive());
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(DiceBagNumbers)
+        .with_children(|parent| {
+            for (i, dice_number) in dice_bag.iter().enumerate() {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                            position_type: PositionType::Absolute,
+                            position: UiRect {
+                                left: Val::Px(20.0),
+                                bottom: Val::Px(30.0 * i as f32 + 20.0),
+                                ..default()
+                            },
+                            justify_content: JustifyContent::FlexStart,
+                            align_items: AlignItems::FlexStart,
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        let is_dragged = matches!(dragged.0, Some((index, _)) if index == i);
+                        let color =
+                            if is_dragged { Color::rgba(1.0, 1.0, 1.0, 0.3) } else { Color::WHITE };
+                        parent
+                            .spawn_bundle(ImageBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(25.0), Val::Auto),
+                                    ..default()
+                                },
+                                image: image_assets
+                                    .handle_for_dice_number(*dice_number)
+                                    .clone()
+                                    .into(),
+                                color: color.into(),
+                                ..default()
+                            })
+                            .insert(Interaction::default())
+                            .insert(DiceBagSlot(i));
+                    });
+            }
+
+            // The insurance is displayed next to the bag and breaks once used.
+            if insurance.owned {
+                let image = if insurance.is_armed(wave.number) {
+                    &image_assets.insurance
+                } else {
+                    &image_assets.insurance_broken
+                };
+
+                parent.spawn_bundle(ImageBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(25.0), Val::Px(25.0)),
+                        position_type: PositionType::Absolute,
+                        position: UiRect {
+                            left: Val::Px(55.0),
+                            bottom: Val::Px(20.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    image: image.clone().into(),
+                    focus_policy: FocusPolicy::Pass,
+                    ..default()
+                });
+            }
+
+            // The dragged dice follows the cursor.
+            let cursor = wnds.get_primary().and_then(|wnd| wnd.cursor_position());
+            if let (Some((_, number)), Some(cursor)) = (dragged.0, cursor) {
+                let follows = FollowsCursor { half_size: Vec2::splat(12.5) };
+                parent
+                    .spawn_bundle(ImageBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(25.0), Val::Auto),
+                            position_type: PositionType::Absolute,
+                            position: follows.position(cursor),
+                            ..default()
+                        },
+                        image: image_assets.handle_for_dice_number(number).clone().into(),
+                        focus_policy: FocusPolicy::Pass,
+                        ..default()
+                    })
+                    .insert(follows);
+            }
+        });
+}
+
+fn move_cursor_followers(
+    mut cursor_moved: EventReader<CursorMoved>,
+    mut followers: Query<(&mut Style, &FollowsCursor)>,
+) {
+    if let Some(CursorMoved { position, .. }) = cursor_moved.iter().last() {
+        for (mut style, follows) in &mut followers {
+            style.position = follows.position(*position);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum GameState {
+    MainMenu,
+    LevelSelect,
+    Playing,
+    GameOver,
+    Victory,
+    Credits,
+    Settings,
+    Controls,
+    Mods,
+    CustomGame,
+    Lobby,
+    /// Pushed on top of `Playing`, the run is paused until it is popped.
+    PhotoMode,
+    /// Pushed on top of `Playing` at the start of the run for the intro.
+    Cinematic,
+    /// Pushed on top of `Playing` when the mobile app goes to the background.
+    Suspended,
+    /// Pushed on top of `Playing` while the quit dialog is shown.
+    Quitting,
+}
+
+impl GameState {
+    /// The states replacing the previous one, the others are pushed on top of the run.
+    const ALL: [GameState; 11] = [
+        GameState::MainMenu,
+        GameState::LevelSelect,
+        GameState::Playing,
+        GameState::GameOver,
+        GameState::Victory,
+        GameState::Credits,
+        GameState::Settings,
+        GameState::Controls,
+        GameState::Mods,
+        GameState::CustomGame,
+        GameState::Lobby,
+    ];
+}
+
+/// The mode of the current run, chosen in the menus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GameMode {
+    /// The game is won once enough waves are survived.
+    Standard,
+    /// The waves never stop getting harder.
+    Endless(Difficulty),
+    /// The level of the campaign at this index.
+    Campaign(usize),
+}
+
+/// How a run starts, chosen in the menus before playing.
+#[derive(Debug, Clone)]
+struct RunSetup {
+    fleet: Vec<ShipPower>,
+    dice: Vec<DiceNumber>,
+    /// The seed of the run when played from a code, a random one otherwise.
+    seed: Option<u64>,
+}
+
+impl Default for RunSetup {
+    fn default() -> RunSetup {
+        RunSetup { fleet: vec![ShipPower::Bump, ShipPower::Destroy], dice: Vec::new(), seed: None }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum ShipPower {
+    Bump,
+    Destroy,
+    /// Latches onto the asteroids to extract their scrap and dice.
+    Mine,
+}
+
+impl ShipPower {
+    fn label(self) -> &'static str {
+        match self {
+            ShipPower::Bump => "Bump ship",
+            ShipPower::Destroy => "Destroy ship",
+            ShipPower::Mine => "Miner ship",
+        }
+    }
+}
+
+/// The entities that survive the screen changes, e.g. the camera.
+#[derive(Component, Debug)]
+struct Persistent;
+
+#[derive(Component, Debug)]
+struct SpaceCamera;
+
+/// The random number generator of the run, every gameplay roll must go through it
+/// so that a run can be replayed from its seed.
+#[derive(Debug)]
+struct GameRng {
+    rng: StdRng,
+}
+
+impl GameRng {
+    fn from_seed(seed: u64) -> GameRng {
+        GameRng { rng: StdRng::seed_from_u64(seed) }
+    }
+
+    fn from_entropy() -> GameRng {
+        GameRng::from_seed(thread_rng().gen())
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// The random number generator of the asteroids spawned by the waves, apart from the
+/// [`GameRng`] so that a seed spawns the same asteroids whatever the players do.
+#[derive(Debug)]
+struct AsteroidRng(GameRng);
+
+impl AsteroidRng {
+    fn from_seed(seed: u64) -> AsteroidRng {
+        // Another stream than the one of the other rolls of the run.
+        AsteroidRng(GameRng::from_seed(!seed))
+    }
+}
+
+/// The distance from the planet after which entities are considered lost in space.
+#[derive(Debug)]
+struct WorldBounds {
+    radius: f32,
+}
+
+/// What to do with an entity once it goes beyond the [`WorldBounds`].
+#[derive(Component, Debug, Clone, Copy)]
+enum OutOfBounds {
+    /// Bring it back near the planet, used for the ships.
+    Recall,
+    /// Remove it from the world, used for asteroids, loot and anything ephemeral.
+    Despawn,
+}
+
+#[derive(Component, Debug)]
+struct Asteroid;
+
+/// The number of bumps an asteroid can take before breaking.
+#[derive(Component, Debug)]
+struct AsteroidHealth(u32);
+
+/// The name of the kind of an asteroid in the tuning, e.g. `Gold`.
+#[derive(Component, Debug)]
+struct AsteroidKindName(String);
+
+struct AsteroidSpawnConfig {
+    /// How often to spawn a new asteroid (repeating timer)
+    timer: Timer,
+    /// The disc shared by all the asteroids, tinted with the color of their kind.
+    texture: Handle<Image>,
+}
+
+#[derive(Component, Debug)]
+struct Planet;
+
+/// The number of asteroid impacts the planet can absorb without losing dice.
+#[derive(Debug)]
+struct PlanetShield {
+    charges: u32,
+}
+
+#[derive(Component, Debug)]
+struct PlanetShieldBubble;
+
+/// The number of cities still standing on the planet, one is ruined by every
+/// impact the shield doesn't absorb and the run is lost once none stands.
+#[derive(Debug)]
+struct PlanetHealth {
+    current: u32,
+}
+
+#[derive(Component, Debug)]
+struct PlanetHealthIndicator;
+
+#[derive(Component, Debug)]
+struct Ship;
+
+#[derive(Component, Debug)]
+struct ContactBumpPower;
+
+#[derive(Component, Debug)]
+struct ContactDestroyPower;
+
+#[derive(Component, Debug)]
+struct MinerPower;
+
+#[derive(Component, Debug)]
+struct ShipTarget(Option<Entity>);
+
+/// The sum of the pips of the dice the player invested into a ship.
+#[derive(Component, Debug, Default)]
+struct DiceInvestment {
+    pips: u32,
+    dice: Vec<DiceNumber>,
+}
+
+/// The dice paid in the shop for a ship, the starting fleet is free.
+#[derive(Component, Debug, Default)]
+struct ShipCost(Vec<DiceNumber>);
+
+#[derive(Component, Debug)]
+struct DiceLoot {
+    number: DiceNumber,
+}
+
+/// The list of dice numbers displayed on the left of the screen.
+#[derive(Component, Debug)]
+struct DiceBagNumbers;
+
+/// A dragged UI node, moved under the cursor when it moves.
+#[derive(Component, Debug, Clone, Copy)]
+struct FollowsCursor {
+    half_size: Vec2,
+}
+
+impl FollowsCursor {
+    fn position(self, cursor: Vec2) -> UiRect<Val> {
+        UiRect {
+            left: Val::Px(cursor.x - self.half_size.x),
+            bottom: Val::Px(cursor.y - self.half_size.y),
+            ..default()
+        }
+    }
+}
+
+/// The position of a dice in the bag, attached to its image in the UI.
+#[derive(Component, Debug)]
+struct DiceBagSlot(usize);
+
+/// The index in the bag and the number of the dice being dragged by the player.
+#[derive(Debug, Default)]
+struct DraggedDice(Option<(usize, DiceNumber)>);
+
+/// A dice added to the bag, with the player who collected it when it was collected.
+struct DiceOwnedEvent(DiceNumber, Option<PlayerId>);
+
+impl DiceOwnedEvent {
+    /// Whether a player picked the dice up, the refunds and the gifts of the run are not.
+    fn is_pickup(&self) -> bool {
+        self.1.is_some()
+    }
+}
+
+struct DiceLostEvent;
+
+/// An asteroid hit the planet, the shield absorbed the impact when it was charged.
+struct PlanetImpactEvent {
+    shielded: bool,
+    /// Where the asteroid hit the planet.
+    position: Vec2,
+}
+
+/// Sent when an asteroid gets destroyed by the fleet, the asteroid is despawned
+/// and its loot dropped by the system reading these events.
+struct AsteroidDestroyedEvent {
+    entity: Entity,
+    translation: Vec3,
+    cause: DestroyCause,
+    /// The player whose ship or mine destroyed the asteroid.
+    player: Option<PlayerId>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DestroyCause {
+    /// Touched by a ship with the destroy power.
+    Destroy,
+    /// Bumped by ships until its health reached zero.
+    Bump,
+    /// Emptied by the miner ships latched onto it.
+    Mined,
+}
+
+#[derive(AssetCollection)]
+struct ImageAssets {
+    #[asset(path = "images/dice_1.png")]
+    pub dice_1: Handle<Image>,
+    #[asset(path = "images/dice_2.png")]
+    pub dice_2: Handle<Image>,
+    #[asset(path = "images/dice_3.png")]
+    pub dice_3: Handle<Image>,
+    #[asset(path = "images/dice_4.png")]
+    pub dice_4: Handle<Image>,
+    #[asset(path = "images/dice_5.png")]
+    pub dice_5: Handle<Image>,
+    #[asset(path = "images/dice_6.png")]
+    pub dice_6: Handle<Image>,
+    #[asset(path = "images/insurance.png")]
+    pub insurance: Handle<Image>,
+    #[asset(path = "images/insurance_broken.png")]
+    pub insurance_broken: Handle<Image>,
+    #[asset(path = "images/scrap.png")]
+    pub scrap: Handle<Image>,
+    #[asset(path = "images/mine.png")]
+    pub mine: Handle<Image>,
+    #[asset(path = "images/gravity_well.png")]
+    pub gravity_well: Handle<Image>,
+    #[asset(path = "images/emp.png")]
+    pub emp: Handle<Image>,
+    #[asset(path = "images/speaker.png")]
+    pub speaker: Handle<Image>,
+    #[asset(path = "images/speaker_muted.png")]
+    pub speaker_muted: Handle<Image>,
+}
+
+#[derive(AssetCollection)]
+struct FontAssets {
+    #[asset(path = "fonts/FiraSans-Bold.ttf")]
+    pub fira_sans: Handle<Font>,
+}
+
+impl ImageAssets {
+    /// The images of the faces of a dice, from one to six.
+    fn dice_faces(&self) -> Vec<Handle<Image>> {
+        DiceNumber::ALL.map(|number| self.handle_for_dice_number(number).clone()).to_vec()
+    }
+
+    fn handle_for_dice_number(&self, dice: DiceNumber) -> &Handle<Image> {
+        match dice {
+            DiceNumber::One => &self.dice_1,
+            DiceNumber::Two => &self.dice_2,
+            DiceNumber::Three => &self.dice_3,
+            DiceNumber::Four => &self.dice_4,
+            DiceNumber::Five => &self.dice_5,
+            DiceNumber::Six => &self.dice_6,
+        }
+    }
+}
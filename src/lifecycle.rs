@@ -0,0 +1,102 @@
+//! The run suspended when the mobile app goes to the background, Android pauses
+//! the activity and stops the frames without a warning but the loss of focus.
+//!
+//! The run stays suspended once back, until the player taps or presses anything,
+//! the first frame back carries the whole time spent in the background.
+
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+use bevy_rapier2d::prelude::*;
+
+use crate::{FontAssets, GameState};
+
+/// The desktop windows keep playing without the focus.
+const SUSPEND_ON_FOCUS_LOSS: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+pub struct LifecyclePlugin;
+
+impl Plugin for LifecyclePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(suspend_on_focus_loss),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Suspended)
+                .with_system(pause_physics)
+                .with_system(setup_suspended_overlay),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Suspended).with_system(resume_on_any_press))
+        .add_system_set(
+            SystemSet::on_exit(GameState::Suspended)
+                .with_system(resume_physics)
+                .with_system(despawn_suspended_overlay),
+        );
+    }
+}
+
+#[derive(Component, Debug)]
+struct SuspendedOverlay;
+
+fn suspend_on_focus_loss(
+    mut focused: EventReader<WindowFocused>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if SUSPEND_ON_FOCUS_LOSS && focused.iter().any(|event| !event.focused) {
+        let _ = state.push(GameState::Suspended);
+    }
+}
+
+fn resume_on_any_press(
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    touches: Res<Touches>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let pressed = keys.get_just_pressed().next().is_some()
+        || buttons.get_just_pressed().next().is_some()
+        || touches.any_just_released();
+    if pressed {
+        let _ = state.pop();
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn setup_suspended_overlay(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(SuspendedOverlay)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Paused, tap to resume",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 40.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn despawn_suspended_overlay(
+    mut commands: Commands,
+    overlay: Query<Entity, With<SuspendedOverlay>>,
+) {
+    overlay.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
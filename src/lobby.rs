@@ -0,0 +1,451 @@
+//! The lobby of the co-op runs, the host opens a room on the relay server and
+//! gives its code to the partner who joins it with this code.
+//!
+//! Once both players are in the room, the host chooses the difficulty, the
+//! starting fleet and the seed of the run, the guest sees the choices of the
+//! host, and the run starts on both games when the host starts it.
+
+use std::mem;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::endless::Difficulty;
+use crate::lockstep::LockstepSession;
+use crate::menu::{spawn_menu_screen, MenuButton};
+use crate::net::{NetMessage, Peer};
+use crate::players::Players;
+use crate::seeds::RunSeed;
+use crate::settings::GameSettings;
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::toasts::ToastEvent;
+use crate::waves::WaveSchedule;
+use crate::{FontAssets, GameMode, GameState, RunSetup, ShipPower};
+
+const ROOM_CODE_LENGTH: usize = 4;
+/// The starting fleets the host can choose from.
+const FLEETS: [[ShipPower; 2]; 3] = [
+    [ShipPower::Bump, ShipPower::Destroy],
+    [ShipPower::Bump, ShipPower::Bump],
+    [ShipPower::Destroy, ShipPower::Destroy],
+];
+
+pub struct LobbyPlugin;
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Lobby::default())
+            .add_system_set(SystemSet::on_enter(GameState::Lobby).with_system(setup_lobby))
+            .add_system_set(
+                SystemSet::on_update(GameState::Lobby)
+                    .with_system(type_room_code)
+                    .with_system(press_lobby_buttons)
+                    .with_system(receive_lobby_messages)
+                    .with_system(draw_lobby.after(press_lobby_buttons).after(type_room_code))
+                    .with_system(highlight_lobby_buttons),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Lobby).with_system(leave_lobby));
+    }
+}
+
+/// The run chosen by the host, the same on both games.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoopSetup {
+    difficulty: Difficulty,
+    fleet: Vec<ShipPower>,
+    seed: u64,
+}
+
+impl Default for CoopSetup {
+    fn default() -> CoopSetup {
+        CoopSetup {
+            difficulty: Difficulty::default(),
+            fleet: FLEETS[0].to_vec(),
+            seed: RunSeed::random().0,
+        }
+    }
+}
+
+impl CoopSetup {
+    /// How the run starts, the same on both games and on the server.
+    pub fn run_setup(&self) -> RunSetup {
+        RunSetup { fleet: self.fleet.clone(), dice: Vec::new(), seed: Some(self.seed) }
+    }
+
+    pub fn wave_schedule(&self) -> WaveSchedule {
+        WaveSchedule { difficulty: self.difficulty, ..default() }
+    }
+}
+
+#[derive(Default)]
+struct Lobby {
+    /// The code of the room to join, typed by the guest.
+    code: String,
+    connection: Connection,
+}
+
+#[derive(Default)]
+enum Connection {
+    #[default]
+    Idle,
+    /// Connected to the server, waiting for the partner.
+    Waiting { peer: Peer, host: bool, room: Option<String> },
+    /// Both players are in the room.
+    Ready { peer: Peer, host: bool, setup: CoopSetup },
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum LobbyButton {
+    Host,
+    Join,
+    Difficulty,
+    Fleet,
+    Seed,
+    Start,
+}
+
+impl LobbyButton {
+    const ALL: [LobbyButton; 6] = [
+        LobbyButton::Host,
+        LobbyButton::Join,
+        LobbyButton::Difficulty,
+        LobbyButton::Fleet,
+        LobbyButton::Seed,
+        LobbyButton::Start,
+    ];
+
+    fn label(self, lobby: &Lobby) -> String {
+        let setup = match &lobby.connection {
+            Connection::Ready { setup, .. } => Some(setup),
+            Connection::Idle | Connection::Waiting { .. } => None,
+        };
+
+        match (self, setup) {
+            (LobbyButton::Host, _) => "Host a room".to_string(),
+            (LobbyButton::Join, _) => {
+                let typed = lobby.code.chars().chain(std::iter::repeat('_'));
+                format!("Join {}", typed.take(ROOM_CODE_LENGTH).collect::<String>())
+            }
+            (LobbyButton::Difficulty, Some(setup)) => {
+                format!("Difficulty: {}", setup.difficulty.label())
+            }
+            (LobbyButton::Fleet, Some(setup)) => {
+                let ships: Vec<_> = setup.fleet.iter().map(|power| power.label()).collect();
+                format!("Fleet: {}", ships.join(", "))
+            }
+            (LobbyButton::Seed, Some(setup)) => format!("Seed {}", RunSeed(setup.seed).code()),
+            (LobbyButton::Difficulty | LobbyButton::Fleet | LobbyButton::Seed, None) => {
+                String::new()
+            }
+            (LobbyButton::Start, _) => "Start the run".to_string(),
+        }
+    }
+
+    fn spoken_name(self) -> &'static str {
+        match self {
+            LobbyButton::Host => "Host a room",
+            LobbyButton::Join => "Join the room of the typed code",
+            LobbyButton::Difficulty => "Switch to the next difficulty",
+            LobbyButton::Fleet => "Switch to the next starting fleet",
+            LobbyButton::Seed => "Roll another seed",
+            LobbyButton::Start => "Start the run",
+        }
+    }
+
+    fn is_enabled(self, lobby: &Lobby) -> bool {
+        match (self, &lobby.connection) {
+            (LobbyButton::Host, Connection::Idle) => true,
+            (LobbyButton::Join, Connection::Idle) => lobby.code.len() == ROOM_CODE_LENGTH,
+            (
+                LobbyButton::Difficulty
+                | LobbyButton::Fleet
+                | LobbyButton::Seed
+                | LobbyButton::Start,
+                Connection::Ready { host, .. },
+            ) => *host,
+            _otherwise => false,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct LobbyStatus;
+
+fn setup_lobby(mut commands: Commands, font_assets: Res<FontAssets>) {
+    spawn_menu_screen(
+        &mut commands,
+        &font_assets,
+        "Co-op",
+        &["Defend the planet with a partner".to_string()],
+        &[MenuButton::Back],
+    );
+
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                .insert(LobbyStatus);
+
+            let button = ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(320.0), Val::Px(36.0)),
+                    margin: UiRect::all(Val::Px(4.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                color: Color::NONE.into(),
+                ..default()
+            };
+
+            for lobby_button in LobbyButton::ALL {
+                parent
+                    .spawn_bundle(button.clone())
+                    .insert(lobby_button)
+                    .insert(AccessibleLabel::new(lobby_button.spoken_name()))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section("", text_style.clone()));
+                    });
+            }
+        });
+}
+
+/// Type the code of the room to join, the letters only.
+fn type_room_code(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut lobby: ResMut<Lobby>,
+) {
+    if keys.just_pressed(KeyCode::Back) {
+        lobby.code.pop();
+    }
+
+    for ReceivedCharacter { char, .. } in characters.iter() {
+        if lobby.code.len() < ROOM_CODE_LENGTH && char.is_ascii_alphabetic() {
+            lobby.code.push(char.to_ascii_uppercase());
+        }
+    }
+}
+
+fn press_lobby_buttons(
+    buttons: Query<(&Interaction, &LobbyButton), Changed<Interaction>>,
+    settings: Res<GameSettings>,
+    mut lobby: ResMut<Lobby>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut commands: Commands,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+    mut players: ResMut<Players>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked || !button.is_enabled(&lobby) {
+            continue;
+        }
+
+        match button {
+            LobbyButton::Host | LobbyButton::Join => {
+                let host = *button == LobbyButton::Host;
+                let request =
+                    if host { "HOST".to_string() } else { format!("JOIN {}", lobby.code) };
+                match Peer::connect(&settings.relay_address, &request) {
+                    Ok(peer) => lobby.connection = Connection::Waiting { peer, host, room: None },
+                    Err(e) => toasts.send(ToastEvent::warning(format!(
+                        "Could not reach the server at {}: {}",
+                        settings.relay_address, e
+                    ))),
+                }
+            }
+            LobbyButton::Difficulty | LobbyButton::Fleet | LobbyButton::Seed => {
+                if let Connection::Ready { peer, setup, .. } = &mut lobby.connection {
+                    match button {
+                        LobbyButton::Difficulty => setup.difficulty = setup.difficulty.next(),
+                        LobbyButton::Fleet => {
+                            let index = FLEETS.iter().position(|f| *f == *setup.fleet);
+                            let next = index.map_or(0, |i| (i + 1) % FLEETS.len());
+                            setup.fleet = FLEETS[next].to_vec();
+                        }
+                        _otherwise => setup.seed = RunSeed::random().0,
+                    }
+                    peer.send(&NetMessage::Lobby(setup.clone()));
+                }
+            }
+            LobbyButton::Start => {
+                if let Connection::Ready { mut peer, host, setup } =
+                    mem::take(&mut lobby.connection)
+                {
+                    peer.send(&NetMessage::Start(setup.clone()));
+                    commands.insert_resource(LockstepSession::new(peer, host));
+                    start_coop_run(
+                        setup,
+                        host,
+                        &mut game_mode,
+                        &mut run_setup,
+                        &mut schedule,
+                        &mut players,
+                        &mut speed,
+                        &mut rapier_config,
+                        &mut state,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn receive_lobby_messages(
+    mut commands: Commands,
+    mut lobby: ResMut<Lobby>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+    mut players: ResMut<Players>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let (messages, closed) = match &mut lobby.connection {
+        Connection::Idle => return,
+        Connection::Waiting { peer, .. } | Connection::Ready { peer, .. } => {
+            (peer.receive(), peer.is_closed())
+        }
+    };
+
+    for message in messages {
+        lobby.connection = match (mem::take(&mut lobby.connection), message) {
+            (Connection::Waiting { peer, host, .. }, NetMessage::Room(code)) => {
+                Connection::Waiting { peer, host, room: Some(code) }
+            }
+            (Connection::Waiting { mut peer, host, .. }, NetMessage::Joined) => {
+                let setup = CoopSetup::default();
+                if host {
+                    peer.send(&NetMessage::Lobby(setup.clone()));
+                }
+                Connection::Ready { peer, host, setup }
+            }
+            (Connection::Ready { peer, host, .. }, NetMessage::Lobby(setup)) => {
+                Connection::Ready { peer, host, setup }
+            }
+            (Connection::Ready { peer, host, .. }, NetMessage::Start(setup)) => {
+                commands.insert_resource(LockstepSession::new(peer, host));
+                start_coop_run(
+                    setup,
+                    host,
+                    &mut game_mode,
+                    &mut run_setup,
+                    &mut schedule,
+                    &mut players,
+                    &mut speed,
+                    &mut rapier_config,
+                    &mut state,
+                );
+                return;
+            }
+            (_, NetMessage::Error(error)) => {
+                toasts.send(ToastEvent::warning(error));
+                Connection::Idle
+            }
+            (connection, _) => connection,
+        };
+    }
+
+    if closed && !matches!(lobby.connection, Connection::Idle) {
+        lobby.connection = Connection::Idle;
+        toasts.send(ToastEvent::warning("The connection with the room is lost"));
+    }
+}
+
+/// Start a standard game with the setup of the host, in lockstep with the partner.
+/// Only the steps of the lockstep are simulated, not the frame entering the run.
+fn start_coop_run(
+    setup: CoopSetup,
+    host: bool,
+    game_mode: &mut GameMode,
+    run_setup: &mut RunSetup,
+    schedule: &mut WaveSchedule,
+    players: &mut Players,
+    speed: &mut SimulationSpeed,
+    rapier_config: &mut RapierConfiguration,
+    state: &mut State<GameState>,
+) {
+    *players = Players::coop(host);
+    *game_mode = GameMode::Standard;
+    *run_setup = setup.run_setup();
+    *schedule = setup.wave_schedule();
+    speed.set_step(Some(Duration::ZERO));
+    rapier_config.physics_pipeline_active = false;
+    let _ = state.set(GameState::Playing);
+}
+
+fn draw_lobby(
+    lobby: Res<Lobby>,
+    spawned: Query<(), Added<LobbyStatus>>,
+    mut status: Query<&mut Text, With<LobbyStatus>>,
+    buttons: Query<(&LobbyButton, &Children)>,
+    mut texts: Query<&mut Text, Without<LobbyStatus>>,
+) {
+    if !lobby.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    let value = match &lobby.connection {
+        Connection::Idle => "Host a room or type the code of your partner's room".to_string(),
+        Connection::Waiting { host: true, room: None, .. } => "Opening a room...".to_string(),
+        Connection::Waiting { host: true, room: Some(code), .. } => {
+            format!("Room {}, give this code to your partner", code)
+        }
+        Connection::Waiting { host: false, .. } => format!("Joining the room {}...", lobby.code),
+        Connection::Ready { host: true, .. } => "Your partner is here, start the run".to_string(),
+        Connection::Ready { host: false, .. } => {
+            "Waiting for the host to start the run".to_string()
+        }
+    };
+    for mut text in &mut status {
+        text.sections[0].value = value.clone();
+    }
+
+    for (button, children) in &buttons {
+        let label = button.label(&lobby);
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+fn highlight_lobby_buttons(
+    palette: Res<Palette>,
+    lobby: Res<Lobby>,
+    mut buttons: Query<(&Interaction, &LobbyButton, &mut UiColor)>,
+) {
+    for (interaction, button, mut color) in &mut buttons {
+        *color = palette.button_if(button.is_enabled(&lobby), *interaction).into();
+    }
+}
+
+/// Leaving the lobby before the run starts closes the room.
+fn leave_lobby(mut lobby: ResMut<Lobby>) {
+    lobby.connection = Connection::Idle;
+}
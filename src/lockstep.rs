@@ -0,0 +1,467 @@
+//! The lockstep synchronization of the co-op runs, both games simulate the same
+//! deterministic run and only exchange the inputs of their player.
+//!
+//! The gameplay inputs go through the `PlayerInputEvent`s and come back as
+//! `SimInputEvent`s once they must be applied. Alone, an input is applied on the
+//! next frame. In a co-op run, it is applied `INPUT_DELAY` steps later, the time
+//! it needs to reach the partner, and a step is only simulated once the inputs
+//! of both players for this step are known, the run stalls otherwise. The steps
+//! tick at the rate of the physics whatever the frame rate, the frames between
+//! two steps are interpolated.
+//!
+//! The entities differ between the games of a co-op run, the inputs name the
+//! ships, the asteroids and the dice by their `SimId`, given in the same order
+//! by every game to the entities spawned by a step.
+//!
+//! The server simulates the run from the same inputs and sends checkpoints of
+//! its state, the players are warned when their run no longer matches it.
+
+use std::collections::BTreeMap;
+use std::mem;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::Ability;
+use crate::chat::PartnerEvent;
+use crate::dice::DiceNumber;
+use crate::interpolation::TickProgress;
+use crate::net::{NetMessage, Peer};
+use crate::players::{PlayerId, Players};
+use crate::selection::ShipAction;
+use crate::shop::ShopItem;
+use crate::speed::SimulationSpeed;
+use crate::toasts::ToastEvent;
+use crate::waves::Wave;
+use crate::{
+    physics_timestep_mode, Asteroid, DiceLoot, GameState, PlanetHealth, Ship, PHYSICS_TIMESTEP,
+};
+
+/// The number of steps between an input and the step it is applied at.
+pub const INPUT_DELAY: u64 = 6;
+/// The number of steps between two checkpoints of the server.
+pub const CHECKPOINT_INTERVAL: u64 = 60;
+
+pub struct LockstepPlugin;
+
+impl Plugin for LockstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<PlayerInputEvent>()
+            .add_event::<SimInputEvent>()
+            .insert_resource(SimIds::default())
+            .add_system_to_stage(CoreStage::PreUpdate, exchange_inputs)
+            .add_system_to_stage(CoreStage::PreUpdate, end_lost_session.after(exchange_inputs))
+            .add_system_to_stage(CoreStage::Last, assign_sim_ids)
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_sim_ids))
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(end_session));
+    }
+}
+
+/// A gameplay action of a player, the same on every game of the run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PlayerInput {
+    Ability(Ability),
+    /// A consumable of this slot of the hotbar deployed at this position.
+    Consumable {
+        slot: usize,
+        position: [f32; 2],
+    },
+    /// The ship ordered to attack the asteroid.
+    Attack {
+        ship: SimId,
+        target: SimId,
+    },
+    /// The dice loot picked up.
+    CollectDice {
+        dice: SimId,
+    },
+    /// A dice of this number invested into the ship.
+    Invest {
+        ship: SimId,
+        number: DiceNumber,
+    },
+    /// An item of the shop bought during an intermission.
+    Buy(ShopItem),
+    /// The ship repaired, merged or scuttled.
+    ShipAction {
+        ship: SimId,
+        action: ShipAction,
+    },
+    /// The gamble wheel spun with this many dice wagered.
+    Gamble {
+        wager: usize,
+    },
+    /// The fusion recipe at this index of the recipes file executed.
+    Fuse {
+        recipe: usize,
+    },
+    /// The crafting recipe at this index of the recipes file crafted.
+    Craft {
+        recipe: usize,
+    },
+}
+
+/// The identifier of a ship, an asteroid or a dice loot, the same on every game of the run.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SimId(pub u64);
+
+/// The next `SimId` to give, the entities of a run are numbered from zero.
+#[derive(Debug, Default)]
+pub struct SimIds {
+    next: u64,
+}
+
+/// The state of the run at a step, compared with the one simulated by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub wave: u32,
+    pub planet_health: u32,
+    /// The number of entities given a `SimId` since the start of the run.
+    pub spawned: u64,
+    pub ships: u32,
+    pub asteroids: u32,
+    /// The sum of the hashes of the positions of the ships and the asteroids,
+    /// whatever the order of the entities.
+    pub positions: u64,
+}
+
+impl Checkpoint {
+    pub fn new<'a>(
+        wave: &Wave,
+        planet_health: &PlanetHealth,
+        sim_ids: &SimIds,
+        ships: impl Iterator<Item = &'a Transform>,
+        asteroids: impl Iterator<Item = &'a Transform>,
+    ) -> Checkpoint {
+        let mut checkpoint = Checkpoint {
+            wave: wave.number,
+            planet_health: planet_health.current,
+            spawned: sim_ids.next,
+            ships: 0,
+            asteroids: 0,
+            positions: 0,
+        };
+        for transform in ships {
+            checkpoint.ships += 1;
+            checkpoint.positions = checkpoint.positions.wrapping_add(position_hash(transform));
+        }
+        for transform in asteroids {
+            checkpoint.asteroids += 1;
+            checkpoint.positions = checkpoint.positions.wrapping_add(position_hash(transform));
+        }
+        checkpoint
+    }
+}
+
+fn position_hash(transform: &Transform) -> u64 {
+    let [x, y] = transform.translation.truncate().to_array();
+    let bits = (x.to_bits() as u64) << 32 | y.to_bits() as u64;
+    // Mixed, so that two moves don't cancel each other out in the sum.
+    bits.wrapping_mul(0x9E37_79B9_7F4A_7C15).rotate_left(31)
+}
+
+/// Sent by the systems reading the keyboard and the mouse.
+pub struct PlayerInputEvent(pub PlayerInput);
+
+/// Sent when an input of this player must be applied to the simulation.
+pub struct SimInputEvent {
+    pub player: PlayerId,
+    pub input: PlayerInput,
+}
+
+/// The state of the lockstep of a co-op run, only present during a co-op run.
+pub struct LockstepSession {
+    peer: Peer,
+    /// The host inputs are applied first, the order must be the same on both games.
+    host: bool,
+    /// The next step to simulate.
+    step: u64,
+    local: BTreeMap<u64, Vec<PlayerInput>>,
+    remote: BTreeMap<u64, Vec<PlayerInput>>,
+    /// The local inputs made while the run stalls, sent with the next step.
+    pending: Vec<PlayerInput>,
+    /// The time elapsed since the last step, not simulated yet.
+    lag: Duration,
+    /// The checkpoints of this game and of the server, until the other one arrives.
+    local_checkpoints: BTreeMap<u64, Checkpoint>,
+    server_checkpoints: BTreeMap<u64, Checkpoint>,
+    /// The players are only warned once the run no longer matches the server.
+    desynced: bool,
+}
+
+impl LockstepSession {
+    /// Start the lockstep of a run with the partner connected to this peer.
+    pub fn new(peer: Peer, host: bool) -> LockstepSession {
+        // Nobody can make an input for the first steps.
+        let empty: BTreeMap<_, _> = (0..INPUT_DELAY).map(|step| (step, Vec::new())).collect();
+        LockstepSession {
+            peer,
+            host,
+            step: 0,
+            local: empty.clone(),
+            remote: empty,
+            pending: Vec::new(),
+            lag: Duration::ZERO,
+            local_checkpoints: BTreeMap::new(),
+            server_checkpoints: BTreeMap::new(),
+            desynced: false,
+        }
+    }
+
+    /// Send a message to the partner outside of the steps of the simulation.
+    pub fn send(&mut self, message: &NetMessage) {
+        self.peer.send(message);
+    }
+
+    /// The inputs of both players for the next step, once the partner sent them.
+    fn take_step_inputs(&mut self) -> Option<Vec<SimInputEvent>> {
+        let remote = self.remote.remove(&self.step)?;
+        let local = self.local.remove(&self.step).unwrap_or_default();
+        self.step += 1;
+
+        let (host, guest) = if self.host { (local, remote) } else { (remote, local) };
+        let host = host.into_iter().map(|input| SimInputEvent { player: PlayerId::FIRST, input });
+        let guest =
+            guest.into_iter().map(|input| SimInputEvent { player: PlayerId::SECOND, input });
+        Some(host.chain(guest).collect())
+    }
+
+    /// Compare the checkpoints of the steps both this game and the server simulated,
+    /// returns whether the run went out of sync for the first time.
+    fn check_desync(&mut self) -> bool {
+        let steps: Vec<_> = self
+            .local_checkpoints
+            .keys()
+            .filter(|step| self.server_checkpoints.contains_key(step))
+            .copied()
+            .collect();
+        let mut desynced = false;
+        for step in steps {
+            let local = self.local_checkpoints.remove(&step);
+            let server = self.server_checkpoints.remove(&step);
+            if local != server {
+                warn!("The run is out of sync at step {}: {:?} on the server", step, server);
+                desynced = true;
+            }
+        }
+
+        let first = desynced && !self.desynced;
+        self.desynced |= desynced;
+        first
+    }
+}
+
+/// Number the ships, the asteroids and the dice spawned by the last step, in the
+/// order of their positions. The entities spawned at the same position are alike,
+/// the games can number them differently. It runs once the spawned entities exist,
+/// before the next frame is interpolated.
+pub fn assign_sim_ids(
+    mut commands: Commands,
+    mut sim_ids: ResMut<SimIds>,
+    spawned: Query<
+        (Entity, &Transform, Option<&Ship>, Option<&Asteroid>),
+        (Or<(With<Ship>, With<Asteroid>, With<DiceLoot>)>, Without<SimId>),
+    >,
+) {
+    let mut spawned: Vec<_> = spawned
+        .iter()
+        .map(|(entity, transform, ship, asteroid)| {
+            let kind = if ship.is_some() {
+                0
+            } else if asteroid.is_some() {
+                1
+            } else {
+                2
+            };
+            let [x, y] = transform.translation.truncate().to_array();
+            (kind, OrderedFloat(x), OrderedFloat(y), entity)
+        })
+        .collect();
+    spawned.sort_unstable_by_key(|(kind, x, y, _)| (*kind, *x, *y));
+
+    for (.., entity) in spawned {
+        commands.entity(entity).insert(SimId(sim_ids.next));
+        sim_ids.next += 1;
+    }
+}
+
+fn reset_sim_ids(mut sim_ids: ResMut<SimIds>) {
+    *sim_ids = SimIds::default();
+}
+
+fn exchange_inputs(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    planet_health: Res<PlanetHealth>,
+    wave: Res<Wave>,
+    sim_ids: Res<SimIds>,
+    ships: Query<&Transform, With<Ship>>,
+    asteroids: Query<&Transform, With<Asteroid>>,
+    session: Option<ResMut<LockstepSession>>,
+    players: Res<Players>,
+    mut player_inputs: EventReader<PlayerInputEvent>,
+    mut sim_inputs: EventWriter<SimInputEvent>,
+    mut partner_events: EventWriter<PartnerEvent>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut progress: ResMut<TickProgress>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    let mut session = match session {
+        Some(session) => session,
+        None => {
+            sim_inputs.send_batch(player_inputs.iter().map(|PlayerInputEvent(input)| {
+                SimInputEvent { player: players.local, input: *input }
+            }));
+            return;
+        }
+    };
+
+    for message in session.peer.receive() {
+        match message {
+            NetMessage::Inputs { step, inputs } => {
+                session.remote.insert(step, inputs);
+            }
+            NetMessage::Checkpoint { step, checkpoint } => {
+                session.server_checkpoints.insert(step, checkpoint);
+            }
+            NetMessage::Chat(text) => partner_events.send(PartnerEvent::Chat(text)),
+            NetMessage::Ping(position) => {
+                partner_events.send(PartnerEvent::Ping(Vec2::from(position)));
+            }
+            // The lobby messages are over once the run started.
+            NetMessage::Room(_)
+            | NetMessage::Joined
+            | NetMessage::Error(_)
+            | NetMessage::Lobby(_)
+            | NetMessage::Start(_) => (),
+        }
+    }
+
+    // The last frame simulated a step, the world is still in its state.
+    let last_step =
+        session.step.checked_sub(1).filter(|step| step.is_multiple_of(CHECKPOINT_INTERVAL));
+    if let Some(step) = last_step.filter(|_| progress.ticked) {
+        let checkpoint =
+            Checkpoint::new(&wave, &planet_health, &sim_ids, ships.iter(), asteroids.iter());
+        session.local_checkpoints.insert(step, checkpoint);
+    }
+    if session.check_desync() {
+        toasts.send(ToastEvent::warning("The run is out of sync with the server"));
+    }
+
+    session.pending.extend(player_inputs.iter().map(|PlayerInputEvent(input)| *input));
+    let send_step = session.step + INPUT_DELAY;
+    if !session.local.contains_key(&send_step) {
+        let inputs = mem::take(&mut session.pending);
+        session.peer.send(&NetMessage::Inputs { step: send_step, inputs: inputs.clone() });
+        session.local.insert(send_step, inputs);
+    }
+
+    // The run stalls while a state is pushed on top of it, e.g. the photo mode,
+    // the physics stays paused by this state and the partner waits for the steps.
+    if *state.current() != GameState::Playing {
+        speed.set_step(Some(Duration::ZERO));
+        progress.ticked = false;
+        return;
+    }
+
+    // The lag is bounded, a stalled run doesn't rush to catch up once resumed.
+    let dt = Duration::from_secs_f32(PHYSICS_TIMESTEP);
+    session.lag = (session.lag + time.delta()).min(dt * 2);
+    let inputs = if session.lag >= dt { session.take_step_inputs() } else { None };
+    progress.ticked = inputs.is_some();
+    match inputs {
+        Some(inputs) => {
+            session.lag -= dt;
+            sim_inputs.send_batch(inputs.into_iter());
+            speed.set_step(Some(dt));
+            rapier_config.timestep_mode =
+                TimestepMode::Fixed { dt: PHYSICS_TIMESTEP * speed.factor(), substeps: 1 };
+            rapier_config.physics_pipeline_active = true;
+        }
+        None => {
+            speed.set_step(Some(Duration::ZERO));
+            rapier_config.physics_pipeline_active = false;
+        }
+    }
+    progress.alpha = (session.lag.as_secs_f32() / PHYSICS_TIMESTEP).min(1.0);
+}
+
+/// The run goes on alone when the partner leaves.
+fn end_lost_session(
+    commands: Commands,
+    session: Option<Res<LockstepSession>>,
+    speed: ResMut<SimulationSpeed>,
+    rapier_config: ResMut<RapierConfiguration>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    if session.is_some_and(|session| session.peer.is_closed()) {
+        end_session(commands, speed, rapier_config);
+        toasts.send(ToastEvent::warning("The connection with your partner is lost"));
+    }
+}
+
+/// Disconnect from the partner and simulate with the frame time again.
+fn end_session(
+    mut commands: Commands,
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+) {
+    commands.remove_resource::<LockstepSession>();
+    speed.set_step(None);
+    rapier_config.timestep_mode = physics_timestep_mode();
+    rapier_config.physics_pipeline_active = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::schedule::SystemStage;
+
+    use super::*;
+
+    fn sim_ids(world: &mut World) -> Vec<([f32; 2], u64)> {
+        let mut query = world.query::<(&Transform, &SimId)>();
+        let mut ids: Vec<_> = query
+            .iter(world)
+            .map(|(transform, id)| (transform.translation.truncate().to_array(), id.0))
+            .collect();
+        ids.sort_unstable_by_key(|(_, id)| *id);
+        ids
+    }
+
+    #[test]
+    fn the_entities_are_numbered_by_kind_and_position() {
+        let mut world = World::new();
+        world.insert_resource(SimIds::default());
+        let mut stage = SystemStage::single(assign_sim_ids);
+
+        world.spawn().insert(Asteroid).insert(Transform::from_xyz(5.0, 0.0, 0.0));
+        world.spawn().insert(Ship).insert(Transform::from_xyz(9.0, 0.0, 0.0));
+        world.spawn().insert(Asteroid).insert(Transform::from_xyz(-5.0, 2.0, 0.0));
+        world.spawn().insert(Ship).insert(Transform::from_xyz(1.0, 3.0, 0.0));
+        world.spawn().insert(Transform::from_xyz(0.0, 0.0, 0.0));
+        stage.run(&mut world);
+
+        let expected = [([1.0, 3.0], 0), ([9.0, 0.0], 1), ([-5.0, 2.0], 2), ([5.0, 0.0], 3)];
+        assert_eq!(sim_ids(&mut world), expected);
+
+        // The numbered entities keep their number, the next ones follow.
+        world.spawn().insert(Asteroid).insert(Transform::from_xyz(-8.0, 0.0, 0.0));
+        stage.run(&mut world);
+        assert_eq!(sim_ids(&mut world)[4], ([-8.0, 0.0], 4));
+        assert_eq!(world.resource::<SimIds>().next, 5);
+    }
+
+    #[test]
+    fn the_positions_of_a_checkpoint_are_changed_by_opposite_moves() {
+        let hash = |x, y| position_hash(&Transform::from_xyz(x, y, 0.0));
+        let before = hash(0.0, 0.0).wrapping_add(hash(10.0, 0.0));
+        let after = hash(1.0, 0.0).wrapping_add(hash(9.0, 0.0));
+        assert_ne!(before, after);
+        assert_ne!(hash(3.0, 4.0), hash(4.0, 3.0));
+    }
+}
@@ -0,0 +1,64 @@
+//! The level of detail of the asteroids, the ones far from the camera or drawn
+//! tiny by a zoomed out camera use a texture of a few pixels.
+//!
+//! The asteroids only switch back a little past the thresholds, not to flicker
+//! when they hover around them.
+
+use bevy::prelude::*;
+
+use crate::{shapes, Asteroid, AsteroidSpawnConfig, GameState, SpaceCamera};
+
+/// The size of the texture of the far asteroids, in pixels.
+const FAR_TEXTURE_SIZE: u32 = 8;
+/// The distance from the center of the camera from which the asteroids are far.
+const FAR_DISTANCE: f32 = 900.0;
+/// The size on the screen under which the asteroids are far, in pixels.
+const FAR_SCREEN_SIZE: f32 = 12.0;
+/// How far back past the thresholds an asteroid must come to be detailed again.
+const HYSTERESIS: f32 = 1.1;
+
+pub struct LodPlugin;
+
+impl Plugin for LodPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_far_texture))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(swap_asteroid_textures),
+            );
+    }
+}
+
+/// The texture of the far asteroids, next to the detailed one of the spawn config.
+struct FarAsteroidTexture(Handle<Image>);
+
+fn setup_far_texture(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let texture = images.add(shapes::disc(FAR_TEXTURE_SIZE));
+    commands.insert_resource(FarAsteroidTexture(texture));
+}
+
+fn swap_asteroid_textures(
+    config: Res<AsteroidSpawnConfig>,
+    far_texture: Res<FarAsteroidTexture>,
+    camera: Query<(&Transform, &OrthographicProjection), With<SpaceCamera>>,
+    mut asteroids: Query<(&Transform, &Sprite, &mut Handle<Image>), With<Asteroid>>,
+) {
+    let (camera_transform, projection) = match camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let center = camera_transform.translation.truncate();
+
+    for (transform, sprite, mut texture) in &mut asteroids {
+        let is_far = *texture == far_texture.0;
+        // A far asteroid must come closer than the thresholds to be detailed again.
+        let margin = if is_far { HYSTERESIS } else { 1.0 };
+        let distance = transform.translation.truncate().distance(center);
+        let screen_size = sprite.custom_size.map_or(0.0, |size| size.x) / projection.scale;
+        let should_be_far =
+            distance * margin > FAR_DISTANCE || screen_size < FAR_SCREEN_SIZE * margin;
+
+        if should_be_far != is_far {
+            *texture = if should_be_far { far_texture.0.clone() } else { config.texture.clone() };
+        }
+    }
+}
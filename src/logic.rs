@@ -0,0 +1,347 @@
+//! The math of the game kept apart from the ECS, so that it can be tested alone.
+//!
+//! Nothing here knows about Bevy: the positions are `[x, y]` arrays and the
+//! systems convert their vectors before calling these functions.
+
+use std::f32::consts::PI;
+
+use ordered_float::OrderedFloat;
+use rand::Rng;
+
+use crate::dice::DiceNumber;
+use crate::poker::PokerHand;
+
+/// An asteroid a ship could lock onto.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetCandidate<T> {
+    pub id: T,
+    pub position: [f32; 2],
+    /// Whether the kind of the asteroid is preferred by the behavior of the ship.
+    pub preferred: bool,
+}
+
+/// Whether this position is close enough to the planet for a ship to chase it.
+pub fn is_leashed(planet: [f32; 2], position: [f32; 2], leash_distance: f32) -> bool {
+    distance(planet, position) <= leash_distance
+}
+
+/// The closest candidate in range of the ship and leashed to the planet,
+/// the preferred candidates always come before the others.
+pub fn choose_target<T>(
+    ship: [f32; 2],
+    planet: [f32; 2],
+    trigger_range: f32,
+    leash_distance: f32,
+    candidates: impl IntoIterator<Item = TargetCandidate<T>>,
+) -> Option<T> {
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            distance(ship, candidate.position) <= trigger_range
+                && is_leashed(planet, candidate.position, leash_distance)
+        })
+        .min_by_key(|candidate| target_score(ship, candidate))
+        .map(|candidate| candidate.id)
+}
+
+/// The lowest score is targeted first.
+fn target_score<T>(ship: [f32; 2], candidate: &TargetCandidate<T>) -> (bool, OrderedFloat<f32>) {
+    (!candidate.preferred, OrderedFloat(distance_squared(ship, candidate.position)))
+}
+
+/// Where a chaser moving at this speed meets a target moving at this velocity,
+/// none when the target is too fast to ever be caught.
+pub fn intercept_point(
+    chaser: [f32; 2],
+    speed: f32,
+    target: [f32; 2],
+    velocity: [f32; 2],
+) -> Option<[f32; 2]> {
+    // The time t at which |target + velocity * t - chaser| = speed * t.
+    let offset = sub(target, chaser);
+    let a = dot(velocity, velocity) - speed * speed;
+    let b = 2.0 * dot(offset, velocity);
+    let c = dot(offset, offset);
+
+    let time = if a.abs() < f32::EPSILON {
+        // The chaser is exactly as fast as the target, it catches it only when it comes closer.
+        (b < 0.0).then(|| -c / b)
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            None
+        } else {
+            let root = discriminant.sqrt();
+            [(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)]
+                .into_iter()
+                .filter(|t| *t >= 0.0)
+                .min_by_key(|t| OrderedFloat(*t))
+        }
+    }?;
+
+    Some([target[0] + velocity[0] * time, target[1] + velocity[1] * time])
+}
+
+/// How long until a body moving in a straight line at this velocity hits the
+/// planet, none when it misses it.
+pub fn time_to_impact(
+    planet: [f32; 2],
+    planet_radius: f32,
+    position: [f32; 2],
+    velocity: [f32; 2],
+) -> Option<f32> {
+    // The time t at which |position + velocity * t - planet| = planet_radius.
+    let offset = sub(position, planet);
+    let a = dot(velocity, velocity);
+    let b = 2.0 * dot(offset, velocity);
+    let c = dot(offset, offset) - planet_radius * planet_radius;
+
+    if c <= 0.0 {
+        return Some(0.0);
+    }
+    let discriminant = b * b - 4.0 * a * c;
+    if a < f32::EPSILON || discriminant < 0.0 {
+        return None;
+    }
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+    (time >= 0.0).then_some(time)
+}
+
+/// A random position on the circle of this radius around the center.
+pub fn random_point_around<R: Rng + ?Sized>(
+    rng: &mut R,
+    center: [f32; 2],
+    radius: f32,
+) -> [f32; 2] {
+    let angle = rng.gen::<f32>() * PI * 2.0;
+    [center[0] + angle.cos() * radius, center[1] + angle.sin() * radius]
+}
+
+/// The indices of the two closest positions farther than this distance from the
+/// planet and at most this gap apart, the asteroids merged first when too many.
+pub fn closest_distant_pair(
+    planet: [f32; 2],
+    positions: &[[f32; 2]],
+    min_distance: f32,
+    max_gap: f32,
+) -> Option<(usize, usize)> {
+    let distant: Vec<_> =
+        (0..positions.len()).filter(|i| distance(planet, positions[*i]) >= min_distance).collect();
+
+    distant
+        .iter()
+        .enumerate()
+        .flat_map(|(n, a)| distant[n + 1..].iter().map(move |b| (*a, *b)))
+        .map(|(a, b)| (a, b, distance(positions[a], positions[b])))
+        .filter(|(_, _, gap)| *gap <= max_gap)
+        .min_by_key(|(_, _, gap)| OrderedFloat(*gap))
+        .map(|(a, b, _)| (a, b))
+}
+
+/// The number of dice of every number, from One to Six.
+pub fn dice_counts<'a>(dice: impl IntoIterator<Item = &'a DiceNumber>) -> [usize; 6] {
+    let mut counts = [0; 6];
+    for number in dice {
+        counts[number.pips() as usize - 1] += 1;
+    }
+    counts
+}
+
+/// Whether these dice counts hold at least all the dice of the combination.
+pub fn contains_combo(counts: &[usize; 6], combo: &[DiceNumber]) -> bool {
+    dice_counts(combo).iter().zip(counts).all(|(needed, owned)| needed <= owned)
+}
+
+/// The strongest poker hand that can be formed with these dice counts.
+pub fn best_poker_hand(counts: &[usize; 6]) -> Option<PokerHand> {
+    let three = counts.iter().position(|c| *c >= 3);
+    let pair_besides = |skip: usize| counts.iter().enumerate().any(|(i, c)| i != skip && *c >= 2);
+    let straight = counts[..5].iter().all(|c| *c > 0) || counts[1..].iter().all(|c| *c > 0);
+
+    match three {
+        Some(i) if pair_besides(i) => Some(PokerHand::FullHouse),
+        _ if straight => Some(PokerHand::Straight),
+        Some(_) => Some(PokerHand::ThreeOfAKind),
+        None if counts.iter().any(|c| *c >= 2) => Some(PokerHand::Pair),
+        None => None,
+    }
+}
+
+fn sub(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn distance_squared(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let diff = sub(a, b);
+    dot(diff, diff)
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    distance_squared(a, b).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::dice::DiceNumber::*;
+
+    const CASES: usize = 1000;
+
+    fn candidate(id: u32, position: [f32; 2], preferred: bool) -> TargetCandidate<u32> {
+        TargetCandidate { id, position, preferred }
+    }
+
+    fn random_position(rng: &mut StdRng, extent: f32) -> [f32; 2] {
+        [rng.gen_range(-extent..extent), rng.gen_range(-extent..extent)]
+    }
+
+    #[test]
+    fn the_closest_candidate_is_targeted() {
+        let candidates = [candidate(1, [100.0, 0.0], false), candidate(2, [50.0, 0.0], false)];
+        assert_eq!(choose_target([0.0, 0.0], [0.0, 0.0], 400.0, 500.0, candidates), Some(2));
+    }
+
+    #[test]
+    fn the_preferred_candidates_come_first() {
+        let candidates = [candidate(1, [300.0, 0.0], true), candidate(2, [50.0, 0.0], false)];
+        assert_eq!(choose_target([0.0, 0.0], [0.0, 0.0], 400.0, 500.0, candidates), Some(1));
+    }
+
+    #[test]
+    fn the_candidates_out_of_range_or_leash_are_ignored() {
+        let out_of_range = [candidate(1, [450.0, 0.0], false)];
+        assert_eq!(choose_target([0.0, 0.0], [0.0, 0.0], 400.0, 500.0, out_of_range), None);
+
+        // In range of the ship but too far from the planet.
+        let unleashed = [candidate(1, [600.0, 0.0], false)];
+        assert_eq!(choose_target([500.0, 0.0], [0.0, 0.0], 400.0, 500.0, unleashed), None);
+    }
+
+    #[test]
+    fn the_chosen_target_is_never_beaten_by_another_candidate() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..CASES {
+            let ship = random_position(&mut rng, 300.0);
+            let candidates: Vec<_> = (0..rng.gen_range(0..8))
+                .map(|id| candidate(id, random_position(&mut rng, 600.0), rng.gen_bool(0.3)))
+                .collect();
+
+            let chosen = choose_target(ship, [0.0, 0.0], 400.0, 500.0, candidates.clone());
+            let eligible: Vec<_> = candidates
+                .iter()
+                .filter(|c| {
+                    distance(ship, c.position) <= 400.0 && is_leashed([0.0, 0.0], c.position, 500.0)
+                })
+                .collect();
+            match chosen {
+                Some(id) => {
+                    let chosen = candidates.iter().find(|c| c.id == id).unwrap();
+                    assert!(eligible
+                        .iter()
+                        .all(|c| target_score(ship, chosen) <= target_score(ship, c)));
+                }
+                None => assert!(eligible.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn a_still_target_is_intercepted_where_it_is() {
+        let point = intercept_point([0.0, 0.0], 10.0, [30.0, 40.0], [0.0, 0.0]);
+        assert_eq!(point, Some([30.0, 40.0]));
+    }
+
+    #[test]
+    fn a_faster_target_running_away_is_never_intercepted() {
+        assert_eq!(intercept_point([0.0, 0.0], 10.0, [100.0, 0.0], [20.0, 0.0]), None);
+        assert_eq!(intercept_point([0.0, 0.0], 10.0, [100.0, 0.0], [10.0, 0.0]), None);
+    }
+
+    #[test]
+    fn the_chaser_and_the_target_reach_the_intercept_point_together() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..CASES {
+            let chaser = random_position(&mut rng, 500.0);
+            let target = random_position(&mut rng, 500.0);
+            let velocity = random_position(&mut rng, 50.0);
+            let speed = rng.gen_range(1.0..100.0);
+
+            if let Some(point) = intercept_point(chaser, speed, target, velocity) {
+                let chaser_time = distance(chaser, point) / speed;
+                let target_at =
+                    [target[0] + velocity[0] * chaser_time, target[1] + velocity[1] * chaser_time];
+                assert!(distance(target_at, point) < 0.5, "{:?} is not {:?}", target_at, point);
+            }
+        }
+    }
+
+    #[test]
+    fn only_the_bodies_heading_to_the_planet_hit_it() {
+        assert_eq!(time_to_impact([0.0, 0.0], 50.0, [100.0, 0.0], [-10.0, 0.0]), Some(5.0));
+        assert_eq!(time_to_impact([0.0, 0.0], 50.0, [100.0, 0.0], [10.0, 0.0]), None);
+        assert_eq!(time_to_impact([0.0, 0.0], 50.0, [100.0, 60.0], [-10.0, 0.0]), None);
+        assert_eq!(time_to_impact([0.0, 0.0], 50.0, [10.0, 0.0], [0.0, 0.0]), Some(0.0));
+    }
+
+    #[test]
+    fn the_random_points_are_on_the_circle() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..CASES {
+            let center = random_position(&mut rng, 1000.0);
+            let radius = rng.gen_range(0.0..1000.0);
+            let point = random_point_around(&mut rng, center, radius);
+            assert!((distance(center, point) - radius).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn only_the_distant_and_close_asteroids_are_merged() {
+        let positions = [[100.0, 0.0], [110.0, 0.0], [700.0, 0.0], [700.0, 30.0], [700.0, 90.0]];
+        assert_eq!(closest_distant_pair([0.0, 0.0], &positions, 600.0, 50.0), Some((2, 3)));
+        assert_eq!(closest_distant_pair([0.0, 0.0], &positions[..3], 600.0, 50.0), None);
+        assert_eq!(closest_distant_pair([0.0, 0.0], &positions[2..], 600.0, 20.0), None);
+    }
+
+    #[test]
+    fn a_combo_needs_every_one_of_its_dice() {
+        let counts = dice_counts(&[One, One, Three]);
+        assert!(contains_combo(&counts, &[]));
+        assert!(contains_combo(&counts, &[One, Three]));
+        assert!(contains_combo(&counts, &[One, One, Three]));
+        assert!(!contains_combo(&counts, &[One, One, One]));
+        assert!(!contains_combo(&counts, &[Six]));
+    }
+
+    #[test]
+    fn the_dice_always_contain_their_own_subsets() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..CASES {
+            let dice: Vec<_> =
+                (0..rng.gen_range(0..10)).map(|_| DiceNumber::from_rng(&mut rng)).collect();
+            let combo: Vec<_> = dice.iter().copied().filter(|_| rng.gen_bool(0.5)).collect();
+            assert!(contains_combo(&dice_counts(&dice), &combo));
+
+            let mut larger = dice.clone();
+            larger.push(DiceNumber::from_rng(&mut rng));
+            assert!(!contains_combo(&dice_counts(&dice), &larger));
+        }
+    }
+
+    #[test]
+    fn the_strongest_poker_hand_is_found() {
+        let hand = |dice: &[DiceNumber]| best_poker_hand(&dice_counts(dice));
+        assert_eq!(hand(&[One, Two, Four]), None);
+        assert_eq!(hand(&[One, One, Four]), Some(PokerHand::Pair));
+        assert_eq!(hand(&[Two, Two, Two, Five]), Some(PokerHand::ThreeOfAKind));
+        assert_eq!(hand(&[Two, Three, Four, Five, Six]), Some(PokerHand::Straight));
+        assert_eq!(hand(&[One, Two, Three, Four, Five, Five, Five]), Some(PokerHand::Straight));
+        assert_eq!(hand(&[Six, Six, Six, One, One]), Some(PokerHand::FullHouse));
+    }
+}
@@ -0,0 +1,195 @@
+//! The loot dropped by the asteroids, rolled from the loot table of their kind
+//! when they are destroyed.
+
+use std::collections::{BTreeMap, HashSet};
+
+use bevy::ecs::entity::Entities;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::controls::Actions;
+use crate::dice::DiceNumber;
+use crate::inventory::{Consumable, Inventory};
+use crate::layers::RenderLayer;
+use crate::logic;
+use crate::mutators::RunRules;
+use crate::scrap::{spawn_scrap_loot, SCRAP_BY_ASTEROID};
+use crate::sound::{PlaySoundEvent, Sound};
+use crate::{
+    bump_asteroids_on_ship_collision_with_bump_power,
+    destroy_asteroids_on_ship_collision_with_destroy_power, is_over_sprite, spawn_dice_loot,
+    AsteroidDestroyedEvent, DestroyCause, GameRng, GameState, ImageAssets, OutOfBounds,
+    SpaceCamera,
+};
+
+/// The distance between the loot dropped by the same asteroid.
+const LOOT_SPREAD_DISTANCE: f32 = 20.0;
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(
+                    roll_loot_on_asteroid_destroyed
+                        .after(bump_asteroids_on_ship_collision_with_bump_power)
+                        .after(destroy_asteroids_on_ship_collision_with_destroy_power),
+                )
+                .with_system(collect_consumables_by_clicking),
+        );
+    }
+}
+
+/// What an asteroid drops once destroyed by the destroy power,
+/// the asteroids bumped to death always drop scrap instead.
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct LootTable {
+    /// The number of dice dropped.
+    #[serde(default = "default_dice_count")]
+    dice_count: u32,
+    /// The weights of the dice numbers dropped.
+    dice: DropTable,
+    /// The chance, between 0 and 1, to drop some scrap too.
+    #[serde(default)]
+    pub scrap_chance: f64,
+    /// The chance, between 0 and 1, to drop a random consumable too.
+    #[serde(default)]
+    pub consumable_chance: f64,
+}
+
+fn default_dice_count() -> u32 {
+    1
+}
+
+impl LootTable {
+    /// The number of dice dropped with the rules of the run.
+    pub fn dice_dropped(&self, rules: &RunRules) -> u32 {
+        self.dice_count * rules.dice_drop_factor
+    }
+}
+
+/// The weight of every dice number dropped by an asteroid,
+/// the numbers missing from the table are never dropped.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct DropTable {
+    weights: BTreeMap<DiceNumber, u32>,
+}
+
+impl DropTable {
+    pub fn weight(&self, number: DiceNumber) -> u32 {
+        self.weights.get(&number).copied().unwrap_or(0)
+    }
+
+    /// Rolls a dice number, uniformly when the table is empty.
+    pub fn roll<R: Rng>(&self, rng: &mut R) -> DiceNumber {
+        match DiceNumber::ALL.choose_weighted(rng, |n| self.weight(*n)) {
+            Ok(number) => *number,
+            Err(_) => DiceNumber::from_rng(rng),
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct ConsumableLoot(Consumable);
+
+/// Roll the loot table of the destroyed asteroids and spread the loot around them.
+pub fn roll_loot_on_asteroid_destroyed(
+    mut commands: Commands,
+    entities: &Entities,
+    mut asteroid_destroyed: EventReader<AsteroidDestroyedEvent>,
+    loot_tables: Query<&LootTable>,
+    rules: Res<RunRules>,
+    mut rng: ResMut<GameRng>,
+    image_assets: Res<ImageAssets>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    let mut destroyed = HashSet::new();
+    for AsteroidDestroyedEvent { entity, translation, cause, .. } in asteroid_destroyed.iter() {
+        // An asteroid can be hit by the blast of many ships in the same frame.
+        if !destroyed.insert(*entity) {
+            continue;
+        }
+
+        // It can be despawned already, hitting the planet or merged by the watchdog.
+        if !entities.contains(*entity) {
+            continue;
+        }
+
+        let loot_table = loot_tables.get(*entity).ok().cloned();
+        commands.entity(*entity).despawn();
+        play_sound.send(PlaySoundEvent::at(Sound::Explosion, translation.truncate()));
+
+        if *cause == DestroyCause::Bump {
+            spawn_scrap_loot(&mut commands, &image_assets, *translation, SCRAP_BY_ASTEROID);
+            continue;
+        }
+
+        let loot_table = match loot_table {
+            Some(loot_table) => loot_table,
+            None => continue,
+        };
+
+        let mut dropped = 0;
+        for _ in 0..loot_table.dice_dropped(&rules) {
+            let number = loot_table.dice.roll(&mut *rng);
+            let position = loot_position(*translation, &mut dropped, &mut *rng);
+            spawn_dice_loot(&mut commands, &image_assets, position, number);
+        }
+
+        if rng.gen_bool(loot_table.scrap_chance.clamp(0.0, 1.0)) {
+            let position = loot_position(*translation, &mut dropped, &mut *rng);
+            spawn_scrap_loot(&mut commands, &image_assets, position, SCRAP_BY_ASTEROID);
+        }
+
+        if rng.gen_bool(loot_table.consumable_chance.clamp(0.0, 1.0)) {
+            let consumable = *Consumable::ALL.choose(&mut *rng).unwrap();
+            let position = loot_position(*translation, &mut dropped, &mut *rng);
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite { custom_size: Some(Vec2::splat(25.0)), ..default() },
+                    transform: Transform::from_translation(
+                        RenderLayer::Loot.at(position.truncate()),
+                    ),
+                    texture: consumable.image(&image_assets).clone(),
+                    ..default()
+                })
+                .insert(ConsumableLoot(consumable))
+                .insert(OutOfBounds::Despawn);
+        }
+    }
+}
+
+/// The first loot drops where the asteroid was and the next ones around it.
+fn loot_position<R: Rng>(translation: Vec3, dropped: &mut usize, rng: &mut R) -> Vec3 {
+    *dropped += 1;
+    if *dropped == 1 {
+        translation
+    } else {
+        let position = logic::random_point_around(
+            rng,
+            translation.truncate().to_array(),
+            LOOT_SPREAD_DISTANCE,
+        );
+        Vec2::from(position).extend(translation.z)
+    }
+}
+
+fn collect_consumables_by_clicking(
+    mut commands: Commands,
+    mut inventory: ResMut<Inventory>,
+    actions: Actions,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    loots: Query<(Entity, &Sprite, &GlobalTransform, &ConsumableLoot)>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let presses = actions.pointer_presses(camera, camera_transform);
+    for (entity, sprite, transform, ConsumableLoot(consumable)) in &loots {
+        if presses.iter().any(|world_pos| is_over_sprite(*world_pos, sprite, transform)) {
+            inventory.add(*consumable, 1);
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! The hidden lucky number of the run, collecting a dice of this number triggers a bonus.
+
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::abilities::{Ability, AbilityActivatedEvent};
+use crate::dice::DiceNumber;
+use crate::seeds::seed_run;
+use crate::speed::SimulationSpeed;
+use crate::toasts::ToastEvent;
+use crate::{
+    spawn_dice_loot, DiceOwnedEvent, FontAssets, GameRng, GameState, ImageAssets, Planet,
+    PLANET_RADIUS,
+};
+
+const LUCKY_HINT_INTERVAL: u64 = 60; // in second
+const LUCKY_LOOT_BURST_COUNT: usize = 3;
+const LUCKY_LOOT_BURST_DISTANCE: f32 = 40.0;
+
+pub struct LuckyPlugin;
+
+impl Plugin for LuckyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LuckyNumber::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(roll_lucky_number.after(seed_run))
+                    .with_system(setup_lucky_hint),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(trigger_lucky_bonus)
+                    .with_system(reveal_lucky_number)
+                    .with_system(draw_lucky_hint.after(reveal_lucky_number)),
+            );
+    }
+}
+
+/// The lucky number of the run and how much of it has been revealed to the player.
+#[derive(Debug)]
+struct LuckyNumber {
+    number: DiceNumber,
+    hint: LuckyHint,
+    timer: Timer,
+}
+
+impl Default for LuckyNumber {
+    fn default() -> LuckyNumber {
+        LuckyNumber {
+            number: DiceNumber::One,
+            hint: LuckyHint::Hidden,
+            timer: Timer::new(Duration::from_secs(LUCKY_HINT_INTERVAL), true),
+        }
+    }
+}
+
+/// The hints are revealed one after the other, narrowing down the lucky number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LuckyHint {
+    Hidden,
+    Parity,
+    Half,
+    Revealed,
+}
+
+impl LuckyHint {
+    fn next(self) -> LuckyHint {
+        match self {
+            LuckyHint::Hidden => LuckyHint::Parity,
+            LuckyHint::Parity => LuckyHint::Half,
+            LuckyHint::Half | LuckyHint::Revealed => LuckyHint::Revealed,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct LuckyHintText;
+
+fn roll_lucky_number(mut lucky: ResMut<LuckyNumber>, mut rng: ResMut<GameRng>) {
+    *lucky = LuckyNumber { number: DiceNumber::from_rng(&mut *rng), ..default() };
+}
+
+fn setup_lucky_hint(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 14.0,
+                    color: Color::rgba(1.0, 1.0, 1.0, 0.5),
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(45.0), right: Val::Px(20.0), ..default() },
+                ..default()
+            }),
+        )
+        .insert(LuckyHintText);
+}
+
+/// Collecting a dice of the lucky number either bursts more loot
+/// around the planet or triggers a free shockwave.
+fn trigger_lucky_bonus(
+    mut commands: Commands,
+    lucky: Res<LuckyNumber>,
+    mut dice_owned: EventReader<DiceOwnedEvent>,
+    mut activated: EventWriter<AbilityActivatedEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut rng: ResMut<GameRng>,
+    planet: Query<&Transform, With<Planet>>,
+    image_assets: Res<ImageAssets>,
+) {
+    for DiceOwnedEvent(number, _) in dice_owned.iter().filter(|event| event.is_pickup()) {
+        if *number != lucky.number {
+            continue;
+        }
+
+        toasts.send(ToastEvent::success(format!("Lucky number {}!", number.pips())));
+        if rng.gen_bool(0.5) {
+            let planet_translation = planet.single().translation;
+            for _ in 0..LUCKY_LOOT_BURST_COUNT {
+                let angle = rng.gen::<f32>() * PI * 2.0;
+                let distance = PLANET_RADIUS + LUCKY_LOOT_BURST_DISTANCE;
+                let offset = Vec3::new(angle.cos(), angle.sin(), 0.0) * distance;
+                let number = DiceNumber::from_rng(&mut *rng);
+                spawn_dice_loot(&mut commands, &image_assets, planet_translation + offset, number);
+            }
+        } else {
+            activated.send(AbilityActivatedEvent(Ability::Shockwave));
+        }
+    }
+}
+
+fn reveal_lucky_number(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut lucky: ResMut<LuckyNumber>,
+) {
+    if lucky.hint != LuckyHint::Revealed && lucky.timer.tick(speed.delta(&time)).just_finished() {
+        lucky.hint = lucky.hint.next();
+    }
+}
+
+fn draw_lucky_hint(lucky: Res<LuckyNumber>, mut text: Query<&mut Text, With<LuckyHintText>>) {
+    if lucky.is_changed() {
+        let pips = lucky.number.pips();
+        let parity = if pips.is_multiple_of(2) { "even" } else { "odd" };
+        let half = if pips <= 3 { "low" } else { "high" };
+        let value = match lucky.hint {
+            LuckyHint::Hidden => "Lucky number: ?".to_string(),
+            LuckyHint::Parity => format!("Lucky number: {}", parity),
+            LuckyHint::Half => format!("Lucky number: {}, {}", parity, half),
+            LuckyHint::Revealed => format!("Lucky number: {}", pips),
+        };
+
+        for mut text in &mut text {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! The main menu and the game over screen.
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::accessibility::AccessibleLabel;
+use crate::endless::{endless_score, record_endless_score, Difficulty, EndlessRun};
+use crate::metrics::ExportRunEvent;
+use crate::players::{PlayerStats, Players};
+use crate::profile::Profile;
+use crate::quit::QuitRequestedEvent;
+use crate::seeds::RunSeed;
+use crate::sound::{PlaySoundEvent, Sound};
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::victory::STANDARD_GAME_WAVES;
+use crate::waves::{Wave, WaveSchedule};
+use crate::{FontAssets, GameMode, GameState, RunSetup};
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_main_menu))
+            .add_system_set(
+                SystemSet::on_enter(GameState::GameOver)
+                    .with_system(setup_game_over.after(record_endless_score)),
+            )
+            .add_system(press_menu_buttons)
+            .add_system(highlight_menu_buttons);
+    }
+}
+
+/// A button of the menus, attached to its node.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuButton {
+    Standard,
+    Campaign,
+    Endless(Difficulty),
+    /// Opens the screen choosing the mutators of a custom game.
+    CustomGame,
+    /// Opens the lobby of the co-op runs.
+    CoOp,
+    /// Cycles through the simulation speeds of the next runs.
+    Speed,
+    Settings,
+    /// Opens the screen rebinding the actions, from the settings.
+    Controls,
+    Mods,
+    Credits,
+    Quit,
+    /// Writes the metrics of the run that just ended to CSV and JSON files.
+    ExportRun,
+    BackToMenu,
+    Back,
+    Skip,
+}
+
+impl MenuButton {
+    fn label(self) -> &'static str {
+        match self {
+            MenuButton::Standard => "Standard",
+            MenuButton::Campaign => "Campaign",
+            MenuButton::Endless(Difficulty::Easy) => "Endless - Easy",
+            MenuButton::Endless(Difficulty::Normal) => "Endless - Normal",
+            MenuButton::Endless(Difficulty::Hard) => "Endless - Hard",
+            MenuButton::CustomGame => "Custom game",
+            MenuButton::CoOp => "Co-op",
+            MenuButton::Speed => "Simulation speed",
+            MenuButton::Settings => "Settings",
+            MenuButton::Controls => "Controls",
+            MenuButton::Mods => "Mods",
+            MenuButton::Credits => "Credits",
+            MenuButton::Quit => "Quit",
+            MenuButton::ExportRun => "Export the run data",
+            MenuButton::BackToMenu => "Back to menu",
+            MenuButton::Back => "Back",
+            MenuButton::Skip => "Skip",
+        }
+    }
+}
+
+/// Spawn a full screen column with a title, some lines of text and the buttons.
+pub fn spawn_menu_screen(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    title: &str,
+    lines: &[String],
+    buttons: &[MenuButton],
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(
+                TextBundle::from_section(
+                    title,
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style { margin: UiRect::all(Val::Px(20.0)), ..default() }),
+            );
+
+            for line in lines {
+                parent.spawn_bundle(TextBundle::from_section(
+                    line.as_str(),
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 20.0,
+                        color: Color::GRAY,
+                    },
+                ));
+            }
+
+            for button in buttons {
+                spawn_menu_button(parent, font_assets, *button);
+            }
+        });
+}
+
+pub fn spawn_menu_button(parent: &mut ChildBuilder, font_assets: &FontAssets, button: MenuButton) {
+    parent
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(240.0), Val::Px(50.0)),
+                margin: UiRect::all(Val::Px(8.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            // Colored with the theme by `highlight_menu_buttons` once spawned.
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .insert(button)
+        .insert(AccessibleLabel::new(button.label()))
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                button.label(),
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 24.0,
+                    color: Color::WHITE,
+                },
+            ));
+        });
+}
+
+fn setup_main_menu(mut commands: Commands, font_assets: Res<FontAssets>) {
+    spawn_menu_screen(
+        &mut commands,
+        &font_assets,
+        "Combine and Defend",
+        &[],
+        &[
+            MenuButton::Standard,
+            MenuButton::Campaign,
+            MenuButton::Endless(Difficulty::Easy),
+            MenuButton::Endless(Difficulty::Normal),
+            MenuButton::Endless(Difficulty::Hard),
+            MenuButton::CustomGame,
+            MenuButton::CoOp,
+            MenuButton::Speed,
+            MenuButton::Settings,
+            MenuButton::Mods,
+            MenuButton::Credits,
+            MenuButton::Quit,
+        ],
+    );
+}
+
+fn setup_game_over(
+    mut commands: Commands,
+    wave: Res<Wave>,
+    game_mode: Res<GameMode>,
+    endless_run: Res<EndlessRun>,
+    speed: Res<SimulationSpeed>,
+    profile: Res<Profile>,
+    seed: Res<RunSeed>,
+    players: Res<Players>,
+    stats: Res<PlayerStats>,
+    font_assets: Res<FontAssets>,
+) {
+    let survived = format!("The planet fell during the wave {}", wave.number);
+    let mut lines = match *game_mode {
+        GameMode::Endless(difficulty) => {
+            let score = endless_score(&wave, &endless_run, difficulty, *speed);
+            let mut lines = vec![survived, format!("Score: {}", score), "Best runs".to_string()];
+            lines.extend(profile.endless_leaderboard().iter().enumerate().map(|(i, entry)| {
+                format!(
+                    "{}. {} - wave {} ({})",
+                    i + 1,
+                    entry.score,
+                    entry.wave,
+                    entry.difficulty.label()
+                )
+            }));
+            lines
+        }
+        GameMode::Standard => {
+            vec![survived, format!("Survive {} waves to win", STANDARD_GAME_WAVES)]
+        }
+        GameMode::Campaign(_) => vec![survived, "The level is lost, try again!".to_string()],
+    };
+    if endless_run.evacuated > 0 {
+        lines.push(format!("Transports evacuated: {}", endless_run.evacuated));
+    }
+    lines.extend(stats.summary(&players));
+    lines.push(format!("Seed {}, play it again from the main menu", seed.code()));
+    spawn_menu_screen(
+        &mut commands,
+        &font_assets,
+        "Planet Destroyed",
+        &lines,
+        &[MenuButton::ExportRun, MenuButton::BackToMenu],
+    );
+}
+
+fn press_menu_buttons(
+    buttons: Query<(&Interaction, &MenuButton), Changed<Interaction>>,
+    mut state: ResMut<State<GameState>>,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut quit_requested: EventWriter<QuitRequestedEvent>,
+    mut export_run: EventWriter<ExportRunEvent>,
+    mut play_sound: EventWriter<PlaySoundEvent>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        play_sound.send(PlaySoundEvent::new(Sound::Click));
+        let _ = match button {
+            MenuButton::Standard => {
+                *game_mode = GameMode::Standard;
+                *run_setup = RunSetup::default();
+                *schedule = WaveSchedule::default();
+                state.set(GameState::Playing)
+            }
+            MenuButton::Campaign => state.set(GameState::LevelSelect),
+            MenuButton::Endless(difficulty) => {
+                // The run setup is changed in place, the run starts in this same frame.
+                *game_mode = GameMode::Endless(*difficulty);
+                *run_setup = RunSetup::default();
+                *schedule = WaveSchedule { difficulty: *difficulty, ..default() };
+                state.set(GameState::Playing)
+            }
+            MenuButton::CustomGame => state.set(GameState::CustomGame),
+            MenuButton::CoOp => state.set(GameState::Lobby),
+            MenuButton::Speed => {
+                *speed = speed.next();
+                Ok(())
+            }
+            MenuButton::Credits => state.set(GameState::Credits),
+            MenuButton::Settings => state.set(GameState::Settings),
+            MenuButton::Controls => state.set(GameState::Controls),
+            MenuButton::Mods => state.set(GameState::Mods),
+            MenuButton::Back | MenuButton::Skip => state.set(GameState::MainMenu),
+            MenuButton::Quit => {
+                quit_requested.send(QuitRequestedEvent);
+                Ok(())
+            }
+            MenuButton::ExportRun => {
+                export_run.send(ExportRunEvent);
+                Ok(())
+            }
+            MenuButton::BackToMenu => match *game_mode {
+                GameMode::Standard | GameMode::Endless(_) => state.set(GameState::MainMenu),
+                GameMode::Campaign(_) => state.set(GameState::LevelSelect),
+            },
+        };
+    }
+}
+
+fn highlight_menu_buttons(
+    palette: Res<Palette>,
+    mut buttons: Query<(&Interaction, ChangeTrackers<Interaction>, &mut UiColor), With<MenuButton>>,
+) {
+    for (interaction, tracker, mut color) in &mut buttons {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! The merge of two ships of the same power and tier into a single ship of the
+//! next tier, bigger, sturdier and hitting harder than both of them.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_tweening::lens::TransformScaleLens;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use crate::event_log::EventLog;
+use crate::hull::{ShipHull, SHIP_MAX_HULL};
+use crate::layers::RenderLayer;
+use crate::players::PlayerId;
+use crate::{spawn_ship, DiceInvestment, GameState, Ship, ShipCost, ShipPower};
+
+const SHIP_BASE_SCALE: f32 = 10.0;
+/// The size gained by a ship at every tier, in part of its base size.
+const SHIP_SCALE_BY_TIER: f32 = 0.5;
+const MERGE_TWEEN_DURATION: u64 = 600; // in millisecond
+
+pub struct MergePlugin;
+
+impl Plugin for MergePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MergeShipsEvent>()
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(merge_ships));
+    }
+}
+
+/// How many times a ship was merged, a ship from the shop or the starting fleet is of tier one.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShipTier(pub u32);
+
+impl Default for ShipTier {
+    fn default() -> ShipTier {
+        ShipTier(1)
+    }
+}
+
+impl ShipTier {
+    /// The scale of the mesh and the collider of a ship of this tier.
+    pub fn scale(self) -> f32 {
+        SHIP_BASE_SCALE * (1.0 + (self.0 - 1) as f32 * SHIP_SCALE_BY_TIER)
+    }
+
+    /// The factor applied to the bump force and the blast radius of the ship.
+    pub fn power_factor(self) -> f32 {
+        self.0 as f32
+    }
+
+    pub fn max_hull(self) -> u32 {
+        SHIP_MAX_HULL * self.0
+    }
+}
+
+/// Replace both ships with a single ship of the next tier.
+#[derive(Debug)]
+pub struct MergeShipsEvent(pub [Entity; 2]);
+
+/// The closest other ship of the fleet the given ship can be merged with.
+pub fn merge_partner(
+    entity: Entity,
+    ships: &Query<(Entity, &ShipPower, &ShipTier, &Transform), With<Ship>>,
+) -> Option<Entity> {
+    let (_, power, tier, transform) = ships.get(entity).ok()?;
+    ships
+        .iter()
+        .filter(|(other, p, t, _)| *other != entity && p == &power && t == &tier)
+        .min_by(|(_, _, _, a), (_, _, _, b)| {
+            let a = a.translation.distance_squared(transform.translation);
+            let b = b.translation.distance_squared(transform.translation);
+            a.total_cmp(&b)
+        })
+        .map(|(other, ..)| other)
+}
+
+/// The merged ship keeps everything spent on both ships, spawns between them
+/// and grows from the size of a single ship to its new size.
+fn merge_ships(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut merge_events: EventReader<MergeShipsEvent>,
+    mut ships: Query<(
+        &ShipPower,
+        &ShipTier,
+        &PlayerId,
+        &Transform,
+        &mut ShipCost,
+        &mut DiceInvestment,
+    )>,
+    mut log: ResMut<EventLog>,
+) {
+    for MergeShipsEvent([first, second]) in merge_events.iter() {
+        // The merged ship belongs to the owner of the ship merged into its partner.
+        let (power, tier, owner, position, cost, investment) = match ships
+            .get_many_mut([*first, *second])
+        {
+            Ok(
+                [(power, tier, owner, a, mut a_cost, mut a_inv), (_, _, _, b, mut b_cost, mut b_inv)],
+            ) => {
+                let mut cost = std::mem::take(&mut a_cost.0);
+                cost.append(&mut b_cost.0);
+                let mut dice = std::mem::take(&mut a_inv.dice);
+                dice.append(&mut b_inv.dice);
+                let investment = DiceInvestment { pips: a_inv.pips + b_inv.pips, dice };
+                let position = a.translation.lerp(b.translation, 0.5).truncate();
+                (*power, *tier, *owner, position, cost, investment)
+            }
+            Err(_) => continue,
+        };
+
+        commands.entity(*first).despawn_recursive();
+        commands.entity(*second).despawn_recursive();
+
+        let merged_tier = ShipTier(tier.0 + 1);
+        let start = Vec3::splat(tier.scale());
+        let end = Vec3::splat(merged_tier.scale());
+        let merged = spawn_ship(&mut commands, &mut meshes, &mut materials, power, owner, position);
+        commands
+            .entity(merged)
+            .insert(merged_tier)
+            .insert(ShipHull::new(merged_tier.max_hull()))
+            .insert(ShipCost(cost))
+            .insert(investment)
+            .insert(Transform::from_translation(RenderLayer::Ships.at(position)).with_scale(start))
+            .insert(Animator::new(Tween::new(
+                EaseFunction::BackOut,
+                TweeningType::Once,
+                Duration::from_millis(MERGE_TWEEN_DURATION),
+                TransformScaleLens { start, end },
+            )));
+
+        log.push(&time, format!("{} merged into tier {}", power.label(), merged_tier.0));
+    }
+}
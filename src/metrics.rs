@@ -0,0 +1,161 @@
+//! The metrics of every wave of the run, exported to CSV and JSON files from the
+//! end of run screens for the balance testers.
+//!
+//! The dice delta of a wave includes the dice spent during its intermission.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::Serialize;
+
+use crate::dice::DiceBag;
+use crate::seeds::RunSeed;
+use crate::toasts::ToastEvent;
+use crate::waves::{Wave, WaveEvent};
+use crate::{Asteroid, AsteroidDestroyedEvent, GameState, PlanetHealth, PlanetImpactEvent};
+
+pub const EXPORT_DIRECTORY: &str = "exports";
+
+pub struct MetricsPlugin;
+
+impl Plugin for MetricsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunMetrics::default())
+            .add_event::<ExportRunEvent>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_run_metrics))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(record_wave_metrics),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Victory).with_system(export_by_pressing_e),
+            )
+            .add_system(export_run_metrics);
+    }
+}
+
+/// Sent by the end of run screens to write the metrics of the run in the exports directory.
+pub struct ExportRunEvent;
+
+#[derive(Debug, Clone, Serialize)]
+struct WaveMetrics {
+    wave: u32,
+    spawned: u32,
+    destroyed: u32,
+    /// The asteroids that reached the planet, absorbed by the shield or not.
+    leaked: u32,
+    dice_delta: i64,
+    /// The health of the planet at the end of the wave.
+    planet_health: u32,
+}
+
+#[derive(Debug, Default)]
+struct RunMetrics {
+    waves: Vec<WaveMetrics>,
+    /// The dice in the bag when the last wave started.
+    dice_at_start: usize,
+}
+
+impl RunMetrics {
+    fn start_wave(&mut self, wave: u32, dice_bag: &DiceBag, health: &PlanetHealth) {
+        self.dice_at_start = dice_bag.len();
+        self.waves.push(WaveMetrics {
+            wave,
+            spawned: 0,
+            destroyed: 0,
+            leaked: 0,
+            dice_delta: 0,
+            planet_health: health.current,
+        });
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = "wave,spawned,destroyed,leaked,dice_delta,planet_health\n".to_string();
+        for m in &self.waves {
+            let _ = writeln!(
+                csv,
+                "{},{},{},{},{},{}",
+                m.wave, m.spawned, m.destroyed, m.leaked, m.dice_delta, m.planet_health
+            );
+        }
+        csv
+    }
+}
+
+#[derive(Serialize)]
+struct RunExport<'a> {
+    seed: String,
+    waves: &'a [WaveMetrics],
+}
+
+fn reset_run_metrics(mut metrics: ResMut<RunMetrics>) {
+    *metrics = RunMetrics::default();
+}
+
+fn record_wave_metrics(
+    mut metrics: ResMut<RunMetrics>,
+    wave: Res<Wave>,
+    dice_bag: Res<DiceBag>,
+    health: Res<PlanetHealth>,
+    spawned: Query<(), Added<Asteroid>>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut asteroid_destroyed: EventReader<AsteroidDestroyedEvent>,
+    mut planet_impacts: EventReader<PlanetImpactEvent>,
+) {
+    // The first wave starts with the run, without any event.
+    if metrics.waves.is_empty() {
+        metrics.start_wave(wave.number, &dice_bag, &health);
+    }
+    for event in wave_events.iter() {
+        if let WaveEvent::Started(number) = event {
+            metrics.start_wave(*number, &dice_bag, &health);
+        }
+    }
+
+    // An asteroid can be destroyed by many ships in the same frame.
+    let destroyed: HashSet<_> = asteroid_destroyed.iter().map(|event| event.entity).collect();
+    let dice_delta = dice_bag.len() as i64 - metrics.dice_at_start as i64;
+    if let Some(current) = metrics.waves.last_mut() {
+        current.spawned += spawned.iter().count() as u32;
+        current.destroyed += destroyed.len() as u32;
+        current.leaked += planet_impacts.iter().count() as u32;
+        current.dice_delta = dice_delta;
+        current.planet_health = health.current;
+    }
+}
+
+fn export_by_pressing_e(keys: Res<Input<KeyCode>>, mut export_run: EventWriter<ExportRunEvent>) {
+    if keys.just_pressed(KeyCode::E) {
+        export_run.send(ExportRunEvent);
+    }
+}
+
+fn export_run_metrics(
+    mut export_run: EventReader<ExportRunEvent>,
+    metrics: Res<RunMetrics>,
+    seed: Res<RunSeed>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    if export_run.iter().count() == 0 {
+        return;
+    }
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let path = format!("{}/run-{}-{}", EXPORT_DIRECTORY, seed.code(), now);
+    let export = RunExport { seed: seed.code(), waves: &metrics.waves };
+    let result = fs::create_dir_all(EXPORT_DIRECTORY)
+        .and_then(|()| fs::write(format!("{}.csv", path), metrics.to_csv()))
+        .map_err(|e| e.to_string())
+        .and_then(|()| serde_json::to_string_pretty(&export).map_err(|e| e.to_string()))
+        .and_then(|json| fs::write(format!("{}.json", path), json).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(()) => toasts.send(ToastEvent::success(format!("Run exported to {}.csv", path))),
+        Err(e) => {
+            warn!("Could not export the run to {}: {}", path, e);
+            toasts.send(ToastEvent::warning("Could not export the run"));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! The mining of the asteroids, the miner ships latch onto their target and
+//! extract scrap from it over time instead of destroying it on contact.
+//!
+//! The asteroid is only destroyed once emptied, dropping the dice of its loot
+//! table. A miner lets go of its asteroid when it gets too close to the planet,
+//! when the ship is hit or when the ship changes its target.
+
+use std::time::Duration;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::sprite::Anchor;
+use bevy_rapier2d::prelude::*;
+
+use crate::hull::ShipHull;
+use crate::layers::RenderLayer;
+use crate::loot::roll_loot_on_asteroid_destroyed;
+use crate::players::PlayerId;
+use crate::scrap::ScrapOwnedEvent;
+use crate::speed::SimulationSpeed;
+use crate::{
+    move_ships, Asteroid, AsteroidDestroyedEvent, AsteroidHealth, DestroyCause, GameState,
+    MinerPower, Planet, Ship, ShipTarget, PLANET_RADIUS,
+};
+
+/// How close to the surface of its asteroid a miner must be to latch onto it.
+const LATCH_MARGIN: f32 = 20.0;
+/// The distance from the planet under which the miners let go of their asteroid.
+const DANGER_DISTANCE: f32 = PLANET_RADIUS + 150.0;
+const EXTRACTION_DURATION: u64 = 2; // in second
+const SCRAP_BY_EXTRACTION: u32 = 1;
+const PROGRESS_BAR_SIZE: Vec2 = Vec2::new(24.0, 4.0);
+/// How far above the ship the progress bar is drawn.
+const PROGRESS_BAR_OFFSET: f32 = 20.0;
+
+pub struct MiningPlugin;
+
+impl Plugin for MiningPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(latch_miners_onto_targets)
+                // The latched miners ride their asteroid whatever the ships were ordered,
+                // the emptied asteroids are destroyed with the others of the frame.
+                .with_system(
+                    extract_latched_asteroids
+                        .after(move_ships)
+                        .before(roll_loot_on_asteroid_destroyed),
+                )
+                .with_system(draw_mining_progress_bars.after(extract_latched_asteroids)),
+        );
+    }
+}
+
+/// The asteroid a miner ship is latched onto.
+#[derive(Component, Debug)]
+struct Mining {
+    asteroid: Entity,
+    extraction: Timer,
+}
+
+/// The progress of the extraction of a miner ship, drawn above it.
+#[derive(Component, Debug)]
+struct MiningProgressBar {
+    ship: Entity,
+}
+
+#[derive(Component, Debug)]
+struct MiningProgressFill;
+
+fn is_in_danger(planet_position: Vec2, asteroid_position: Vec2) -> bool {
+    asteroid_position.distance(planet_position) < DANGER_DISTANCE
+}
+
+fn latch_miners_onto_targets(
+    mut commands: Commands,
+    planet: Query<&Transform, With<Planet>>,
+    asteroids: Query<(&Transform, &Sprite), With<Asteroid>>,
+    miners: Query<
+        (Entity, &Transform, &ShipTarget, &ShipHull),
+        (With<MinerPower>, Without<Mining>),
+    >,
+) {
+    let planet_position = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    for (ship, ship_transform, target, hull) in &miners {
+        let (asteroid, (transform, sprite)) = match target.0.map(|e| (e, asteroids.get(e))) {
+            Some((asteroid, Ok(components))) => (asteroid, components),
+            _otherwise => continue,
+        };
+
+        let position = transform.translation.xy();
+        let radius = sprite.custom_size.map_or(0.0, |size| size.x / 2.0);
+        let touching = ship_transform.translation.xy().distance(position) <= radius + LATCH_MARGIN;
+        if touching && hull.is_out_of_combat() && !is_in_danger(planet_position, position) {
+            let extraction = Timer::new(Duration::from_secs(EXTRACTION_DURATION), true);
+            commands.entity(ship).insert(Mining { asteroid, extraction });
+            commands
+                .spawn_bundle(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgba(0.0, 0.0, 0.0, 0.6),
+                        custom_size: Some(PROGRESS_BAR_SIZE),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(RenderLayer::Overlays.at(position)),
+                    ..default()
+                })
+                .insert(MiningProgressBar { ship })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            sprite: Sprite {
+                                color: Color::GOLD,
+                                custom_size: Some(Vec2::new(0.0, PROGRESS_BAR_SIZE.y)),
+                                anchor: Anchor::CenterLeft,
+                                ..default()
+                            },
+                            transform: Transform::from_xyz(-PROGRESS_BAR_SIZE.x / 2.0, 0.0, 1.0),
+                            ..default()
+                        })
+                        .insert(MiningProgressFill);
+                });
+        }
+    }
+}
+
+/// The miners follow their asteroid and take a bit of its health with every
+/// extraction, the last one destroys it.
+fn extract_latched_asteroids(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    planet: Query<&Transform, With<Planet>>,
+    mut asteroids: Query<
+        (&Transform, &Velocity, &mut AsteroidHealth),
+        (With<Asteroid>, Without<Ship>),
+    >,
+    mut miners: Query<
+        (Entity, &ShipTarget, &ShipHull, &PlayerId, &mut Mining, &mut Velocity),
+        With<Ship>,
+    >,
+    mut scrap_owned: EventWriter<ScrapOwnedEvent>,
+    mut asteroid_destroyed: EventWriter<AsteroidDestroyedEvent>,
+) {
+    let planet_position = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    for (ship, target, hull, owner, mut mining, mut ship_velocity) in &mut miners {
+        let (transform, velocity, mut health) = match asteroids.get_mut(mining.asteroid) {
+            Ok(components) if target.0 == Some(mining.asteroid) => components,
+            _otherwise => {
+                commands.entity(ship).remove::<Mining>();
+                continue;
+            }
+        };
+
+        if !hull.is_out_of_combat() || is_in_danger(planet_position, transform.translation.xy()) {
+            commands.entity(ship).remove::<Mining>();
+            continue;
+        }
+
+        ship_velocity.linvel = velocity.linvel;
+        if mining.extraction.tick(speed.delta(&time)).just_finished() && health.0 > 0 {
+            health.0 -= 1;
+            scrap_owned.send(ScrapOwnedEvent(SCRAP_BY_EXTRACTION));
+            if health.0 == 0 {
+                commands.entity(ship).remove::<Mining>();
+                asteroid_destroyed.send(AsteroidDestroyedEvent {
+                    entity: mining.asteroid,
+                    translation: transform.translation,
+                    cause: DestroyCause::Mined,
+                    player: Some(*owner),
+                });
+            }
+        }
+    }
+}
+
+/// The bars follow their ship and are removed once it lets go of its asteroid.
+fn draw_mining_progress_bars(
+    mut commands: Commands,
+    miners: Query<(&Transform, &Mining), Without<MiningProgressBar>>,
+    mut bars: Query<(Entity, &MiningProgressBar, &mut Transform, &Children)>,
+    mut fills: Query<&mut Sprite, With<MiningProgressFill>>,
+) {
+    for (entity, bar, mut transform, children) in &mut bars {
+        let (ship_transform, mining) = match miners.get(bar.ship) {
+            Ok(components) => components,
+            Err(_) => {
+                commands.entity(entity).despawn_recursive();
+                continue;
+            }
+        };
+
+        let position = ship_transform.translation.xy() + Vec2::Y * PROGRESS_BAR_OFFSET;
+        transform.translation = RenderLayer::Overlays.at(position);
+        let width = PROGRESS_BAR_SIZE.x * mining.extraction.percent();
+        for child in children {
+            if let Ok(mut sprite) = fills.get_mut(*child) {
+                sprite.custom_size = Some(Vec2::new(width, PROGRESS_BAR_SIZE.y));
+            }
+        }
+    }
+}
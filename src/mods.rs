@@ -0,0 +1,259 @@
+//! The mods, the directories of the `mods` folder overlaid on top of the built-in
+//! assets, e.g. `mods/gold-dice/images/dice_6.png` replaces the art of the six.
+//!
+//! The mods are enabled by default and can be disabled from the mods screen, the
+//! changes apply to the assets loaded afterward and to every asset after a restart.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use bevy::asset::{
+    create_platform_default_asset_io, AssetIo, AssetIoError, BoxedFuture, FileType, Metadata,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::menu::{spawn_menu_screen, MenuButton};
+use crate::save::{load_ron_file, save_ron_file};
+use crate::theme::Palette;
+use crate::{FontAssets, GameState};
+
+const MODS_DIRECTORY: &str = "mods";
+const MOD_LIST_PATH: &str = "mods.ron";
+
+/// Replaces the asset server with one reading the enabled mods first,
+/// it must be added before the `AssetPlugin` of the default plugins.
+pub struct ModAssetIoPlugin;
+
+impl Plugin for ModAssetIoPlugin {
+    fn build(&self, app: &mut App) {
+        let mod_list: ModList = load_ron_file(MOD_LIST_PATH);
+        let enabled = EnabledMods(Arc::new(RwLock::new(mod_list.enabled_directories())));
+
+        let builtin = create_platform_default_asset_io(app);
+        let asset_io = ModAssetIo { builtin, enabled: enabled.0.clone() };
+        app.insert_resource(AssetServer::new(asset_io))
+            .insert_resource(mod_list)
+            .insert_resource(enabled);
+    }
+}
+
+pub struct ModsPlugin;
+
+impl Plugin for ModsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_enter(GameState::Mods).with_system(setup_mods_screen))
+            .add_system_set(
+                SystemSet::on_update(GameState::Mods)
+                    .with_system(press_mod_toggles)
+                    .with_system(draw_mod_toggles.after(press_mod_toggles))
+                    .with_system(highlight_mod_toggles),
+            );
+    }
+}
+
+/// The mods disabled by the player, saved on disk, the others are enabled.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ModList {
+    disabled: Vec<String>,
+}
+
+impl ModList {
+    fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.iter().any(|disabled| disabled == name)
+    }
+
+    /// The directories of the enabled mods, the last ones override the first ones.
+    fn enabled_directories(&self) -> Vec<PathBuf> {
+        installed_mods()
+            .into_iter()
+            .filter(|name| self.is_enabled(name))
+            .map(|name| Path::new(MODS_DIRECTORY).join(name))
+            .collect()
+    }
+}
+
+/// The directories of the enabled mods shared with the asset server.
+struct EnabledMods(Arc<RwLock<Vec<PathBuf>>>);
+
+/// The button switching a mod on and off.
+#[derive(Component, Debug)]
+struct ModToggle(String);
+
+/// The names of the mods in the mods directory, sorted alphabetically.
+fn installed_mods() -> Vec<String> {
+    let entries = match fs::read_dir(MODS_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut names: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+/// Reads the files of the enabled mods instead of the built-in assets when they exist.
+struct ModAssetIo {
+    builtin: Box<dyn AssetIo>,
+    enabled: Arc<RwLock<Vec<PathBuf>>>,
+}
+
+impl ModAssetIo {
+    /// The file of the last enabled mod overriding this asset path.
+    fn overriding_path(&self, path: &Path) -> Option<PathBuf> {
+        let enabled = self.enabled.read().unwrap();
+        enabled.iter().rev().map(|directory| directory.join(path)).find(|path| path.is_file())
+    }
+}
+
+impl AssetIo for ModAssetIo {
+    fn load_path<'a>(&'a self, path: &'a Path) -> BoxedFuture<'a, Result<Vec<u8>, AssetIoError>> {
+        match self.overriding_path(path) {
+            Some(mod_path) => Box::pin(async move { Ok(fs::read(mod_path)?) }),
+            None => self.builtin.load_path(path),
+        }
+    }
+
+    /// The mods can add files to the directories, e.g. extra data files.
+    fn read_directory(
+        &self,
+        path: &Path,
+    ) -> Result<Box<dyn Iterator<Item = PathBuf>>, AssetIoError> {
+        let mut entries: BTreeSet<_> = match self.builtin.read_directory(path) {
+            Ok(entries) => entries.collect(),
+            Err(_) => BTreeSet::new(),
+        };
+
+        for directory in self.enabled.read().unwrap().iter() {
+            if let Ok(mod_entries) = fs::read_dir(directory.join(path)) {
+                let names = mod_entries.filter_map(Result::ok).map(|entry| entry.file_name());
+                entries.extend(names.map(|name| path.join(name)));
+            }
+        }
+
+        Ok(Box::new(entries.into_iter()))
+    }
+
+    fn get_metadata(&self, path: &Path) -> Result<Metadata, AssetIoError> {
+        match self.overriding_path(path) {
+            Some(_) => Ok(Metadata::new(FileType::File)),
+            None => self.builtin.get_metadata(path),
+        }
+    }
+
+    fn watch_path_for_changes(&self, path: &Path) -> Result<(), AssetIoError> {
+        self.builtin.watch_path_for_changes(path)
+    }
+
+    fn watch_for_changes(&self) -> Result<(), AssetIoError> {
+        self.builtin.watch_for_changes()
+    }
+}
+
+fn setup_mods_screen(mut commands: Commands, font_assets: Res<FontAssets>) {
+    let mods = installed_mods();
+    let line = if mods.is_empty() {
+        format!("No mod found, put them in the {} directory", MODS_DIRECTORY)
+    } else {
+        "Restart the game to apply the changes to every asset".to_string()
+    };
+    spawn_menu_screen(&mut commands, &font_assets, "Mods", &[line], &[MenuButton::Back]);
+
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            for name in mods {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(280.0), Val::Px(36.0)),
+                            margin: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: Color::NONE.into(),
+                        ..default()
+                    })
+                    .insert(AccessibleLabel::new(format!("Toggle the {} mod", name)))
+                    .insert(ModToggle(name))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section("", text_style.clone()));
+                    });
+            }
+        });
+}
+
+fn press_mod_toggles(
+    toggles: Query<(&Interaction, &ModToggle), Changed<Interaction>>,
+    mut mod_list: ResMut<ModList>,
+    enabled: Res<EnabledMods>,
+) {
+    for (interaction, ModToggle(name)) in &toggles {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        if mod_list.is_enabled(name) {
+            mod_list.disabled.push(name.clone());
+        } else {
+            mod_list.disabled.retain(|disabled| disabled != name);
+        }
+
+        *enabled.0.write().unwrap() = mod_list.enabled_directories();
+        save_ron_file(MOD_LIST_PATH, &*mod_list);
+    }
+}
+
+fn draw_mod_toggles(
+    mod_list: Res<ModList>,
+    spawned: Query<(), Added<ModToggle>>,
+    toggles: Query<(&ModToggle, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !mod_list.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for (ModToggle(name), children) in &toggles {
+        let state = if mod_list.is_enabled(name) { "on" } else { "off" };
+        let value = format!("{}: {}", name, state);
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+        }
+    }
+}
+
+fn highlight_mod_toggles(
+    palette: Res<Palette>,
+    mut toggles: Query<(&Interaction, ChangeTrackers<Interaction>, &mut UiColor), With<ModToggle>>,
+) {
+    for (interaction, tracker, mut color) in &mut toggles {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+}
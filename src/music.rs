@@ -0,0 +1,159 @@
+//! The music of the game, a track for every moment of a run
+//! crossfading into the next one when the moment changes.
+//!
+//! The tracks are read from the `music/` directory of the assets.
+
+use std::time::Duration;
+
+use bevy::audio::AudioSink;
+use bevy::prelude::*;
+
+use crate::settings::GameSettings;
+use crate::sound::{AudioChannel, Muted, PlaySoundEvent, Sound};
+use crate::waves::Wave;
+use crate::GameState;
+
+const CROSSFADE_DURATION: f32 = 2.0; // in second
+const STING_DUCKING_DURATION: u64 = 3; // in second
+const DUCKED_VOLUME: f32 = 0.2;
+/// There are no boss asteroids, the hardest waves get the boss track.
+const BOSS_WAVE_INTERVAL: u32 = 5;
+
+pub struct MusicPlugin;
+
+impl Plugin for MusicPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MusicController::default())
+            .add_system(switch_music_track)
+            .add_system(duck_music_under_stings)
+            .add_system(crossfade_music.after(switch_music_track).after(duck_music_under_stings));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MusicTrack {
+    Menu,
+    Calm,
+    Combat,
+    Boss,
+}
+
+impl MusicTrack {
+    fn path(self) -> &'static str {
+        match self {
+            MusicTrack::Menu => "music/menu.ogg",
+            MusicTrack::Calm => "music/calm.ogg",
+            MusicTrack::Combat => "music/combat.ogg",
+            MusicTrack::Boss => "music/boss.ogg",
+        }
+    }
+
+    fn for_moment(state: &GameState, wave: &Wave) -> MusicTrack {
+        match state {
+            GameState::Playing
+            | GameState::PhotoMode
+            | GameState::Cinematic
+            | GameState::Suspended
+            | GameState::Quitting => {
+                if wave.is_intermission() {
+                    MusicTrack::Calm
+                } else if wave.number.is_multiple_of(BOSS_WAVE_INTERVAL) {
+                    MusicTrack::Boss
+                } else {
+                    MusicTrack::Combat
+                }
+            }
+            _ => MusicTrack::Menu,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PlayingTrack {
+    track: MusicTrack,
+    sink: Handle<AudioSink>,
+    /// The crossfade progress, from silent to full volume.
+    fade: f32,
+}
+
+#[derive(Debug)]
+struct MusicController {
+    current: Option<PlayingTrack>,
+    fading_out: Vec<PlayingTrack>,
+    ducking: Timer,
+}
+
+impl Default for MusicController {
+    fn default() -> MusicController {
+        let mut ducking = Timer::new(Duration::from_secs(STING_DUCKING_DURATION), false);
+        ducking.tick(ducking.duration());
+        MusicController { current: None, fading_out: Vec::new(), ducking }
+    }
+}
+
+/// Start the track of the current moment silently, the crossfade brings it up.
+fn switch_music_track(
+    state: Res<State<GameState>>,
+    wave: Res<Wave>,
+    mut controller: ResMut<MusicController>,
+    asset_server: Res<AssetServer>,
+    audio: Res<Audio>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    let track = MusicTrack::for_moment(state.current(), &wave);
+    if controller.current.as_ref().is_some_and(|playing| playing.track == track) {
+        return;
+    }
+
+    let source = asset_server.load(track.path());
+    let sink = audio.play_with_settings(source, PlaybackSettings::LOOP.with_volume(0.0));
+    let sink = audio_sinks.get_handle(sink);
+    let previous = controller.current.replace(PlayingTrack { track, sink, fade: 0.0 });
+    controller.fading_out.extend(previous);
+}
+
+fn duck_music_under_stings(
+    time: Res<Time>,
+    mut play_sound: EventReader<PlaySoundEvent>,
+    mut controller: ResMut<MusicController>,
+) {
+    if play_sound.iter().any(|event| event.sound == Sound::GameOver) {
+        controller.ducking.reset();
+    }
+    controller.ducking.tick(time.delta());
+}
+
+fn crossfade_music(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    muted: Res<Muted>,
+    mut controller: ResMut<MusicController>,
+    audio_sinks: Res<Assets<AudioSink>>,
+) {
+    let step = time.delta_seconds() / CROSSFADE_DURATION;
+    let ducking = if controller.ducking.finished() { 1.0 } else { DUCKED_VOLUME };
+    let volume = if muted.0 { 0.0 } else { settings.volumes.output(AudioChannel::Music) * ducking };
+    let controller = &mut *controller;
+
+    if let Some(playing) = &mut controller.current {
+        playing.fade = (playing.fade + step).min(1.0);
+        if let Some(sink) = audio_sinks.get(&playing.sink) {
+            sink.set_volume(playing.fade * volume);
+        }
+    }
+
+    controller.fading_out.retain_mut(|playing| {
+        playing.fade -= step;
+        match audio_sinks.get(&playing.sink) {
+            Some(sink) if playing.fade <= 0.0 => {
+                sink.stop();
+                false
+            }
+            Some(sink) => {
+                sink.set_volume(playing.fade * volume);
+                true
+            }
+            None => playing.fade > 0.0,
+        }
+    });
+}
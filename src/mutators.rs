@@ -0,0 +1,434 @@
+//! The mutators, the custom rules of a run chosen on the custom game screen.
+//!
+//! Every mutator modifies the `RunRules` computed at the start of a run,
+//! the rules are the default ones when no mutator is enabled. The roguelike
+//! mutator also rolls a random wave modifier at the start of every wave.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::accessibility::AccessibleLabel;
+use crate::dice::{DiceBag, DiceNumber};
+use crate::event_log::EventLog;
+use crate::menu::{spawn_menu_screen, MenuButton};
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::toasts::ToastEvent;
+use crate::waves::{WaveEvent, WaveSchedule};
+use crate::{FontAssets, GameMode, GameRng, GameState, RunSetup, ShipPower};
+
+const DICE_DECAY_INTERVAL: u64 = 15; // in second
+const GIANT_ASTEROID_SCALE: f32 = 2.0;
+const FAST_ASTEROID_FACTOR: f32 = 1.2;
+/// The distance from the planet the ships can go after the asteroids when kept in orbit.
+const ORBIT_LEASH_DISTANCE: f32 = 250.0;
+
+pub struct MutatorsPlugin;
+
+impl Plugin for MutatorsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(CustomRules::default())
+            .insert_resource(RunMutators::default())
+            .insert_resource(RunRules::default())
+            .insert_resource(WaveModifier::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::CustomGame).with_system(setup_custom_game),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::CustomGame)
+                    .with_system(press_mutator_toggles)
+                    .with_system(draw_mutator_toggles.after(press_mutator_toggles))
+                    .with_system(highlight_mutator_toggles),
+            )
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(apply_mutators))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(decay_dice)
+                    .with_system(roll_wave_modifier),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(clear_mutators));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mutator {
+    NoBumpShips,
+    DoubleSpawnRate,
+    DiceDecay,
+    GiantAsteroids,
+    /// Rolls one of the wave modifiers at the start of every wave.
+    Roguelike,
+    FastAsteroids,
+    DoubleDiceDrops,
+    OrbitOnly,
+}
+
+impl Mutator {
+    /// The mutators that can be toggled on the custom game screen.
+    const ALL: [Mutator; 5] = [
+        Mutator::NoBumpShips,
+        Mutator::DoubleSpawnRate,
+        Mutator::DiceDecay,
+        Mutator::GiantAsteroids,
+        Mutator::Roguelike,
+    ];
+
+    /// The mutators rolled for a single wave by the roguelike mutator.
+    const WAVE_MODIFIERS: [Mutator; 3] =
+        [Mutator::FastAsteroids, Mutator::DoubleDiceDrops, Mutator::OrbitOnly];
+
+    fn label(self) -> &'static str {
+        match self {
+            Mutator::NoBumpShips => "No bump ships",
+            Mutator::DoubleSpawnRate => "Double spawn rate",
+            Mutator::DiceDecay => "Dice decay",
+            Mutator::GiantAsteroids => "Giant asteroids",
+            Mutator::Roguelike => "Random modifier each wave",
+            Mutator::FastAsteroids => "Asteroids are 20% faster",
+            Mutator::DoubleDiceDrops => "Dice drops doubled",
+            Mutator::OrbitOnly => "Ships can't leave the planet orbit",
+        }
+    }
+
+    /// The mutators compose, each one only changes its own part of the rules.
+    fn apply(self, rules: &mut RunRules) {
+        match self {
+            Mutator::NoBumpShips => rules.banned_power = Some(ShipPower::Bump),
+            Mutator::DoubleSpawnRate => rules.spawn_rate_factor *= 2.0,
+            Mutator::DiceDecay => {
+                rules.dice_decay = Some(Duration::from_secs(DICE_DECAY_INTERVAL));
+            }
+            Mutator::GiantAsteroids => {
+                rules.asteroid_scale *= GIANT_ASTEROID_SCALE;
+                rules.asteroid_health_factor *= 2;
+            }
+            Mutator::Roguelike => (),
+            Mutator::FastAsteroids => rules.asteroid_speed_factor *= FAST_ASTEROID_FACTOR,
+            Mutator::DoubleDiceDrops => rules.dice_drop_factor *= 2,
+            Mutator::OrbitOnly => rules.leash_distance = Some(ORBIT_LEASH_DISTANCE),
+        }
+    }
+}
+
+/// The mutators enabled on the custom game screen for the next custom game.
+#[derive(Debug, Default)]
+struct CustomRules(BTreeSet<Mutator>);
+
+/// The mutators of the current run, empty for the standard, endless and campaign runs.
+#[derive(Debug, Default)]
+pub struct RunMutators(pub Vec<Mutator>);
+
+/// The modifier rolled for the current wave by the roguelike mutator.
+#[derive(Debug, Default)]
+struct WaveModifier(Option<Mutator>);
+
+/// The rules of the current run, built from its mutators.
+#[derive(Debug)]
+pub struct RunRules {
+    /// The ships of this power can't be bought or start in the fleet.
+    pub banned_power: Option<ShipPower>,
+    pub spawn_rate_factor: f32,
+    pub asteroid_scale: f32,
+    pub asteroid_health_factor: u32,
+    pub asteroid_speed_factor: f32,
+    pub dice_drop_factor: u32,
+    /// The distance from the planet beyond which the ships give up on their target.
+    pub leash_distance: Option<f32>,
+    /// How often a dice of the bag loses a pip, the ones are lost.
+    dice_decay: Option<Duration>,
+    decay_timer: Timer,
+}
+
+impl RunRules {
+    fn new<'a>(mutators: impl IntoIterator<Item = &'a Mutator>) -> RunRules {
+        let mut rules = RunRules::default();
+        mutators.into_iter().for_each(|mutator| mutator.apply(&mut rules));
+        if let Some(interval) = rules.dice_decay {
+            rules.decay_timer = Timer::new(interval, true);
+        }
+        rules
+    }
+}
+
+impl Default for RunRules {
+    fn default() -> RunRules {
+        RunRules {
+            banned_power: None,
+            spawn_rate_factor: 1.0,
+            asteroid_scale: 1.0,
+            asteroid_health_factor: 1,
+            asteroid_speed_factor: 1.0,
+            dice_drop_factor: 1,
+            leash_distance: None,
+            dice_decay: None,
+            decay_timer: Timer::default(),
+        }
+    }
+}
+
+/// A mutator switched on and off by clicking its button.
+#[derive(Component, Debug)]
+struct MutatorToggle(Mutator);
+
+/// Starts a standard game with the enabled mutators.
+#[derive(Component, Debug)]
+struct StartCustomGame;
+
+fn apply_mutators(
+    mutators: Res<RunMutators>,
+    mut rules: ResMut<RunRules>,
+    mut modifier: ResMut<WaveModifier>,
+) {
+    *rules = RunRules::new(&mutators.0);
+    *modifier = WaveModifier::default();
+}
+
+/// Replace the modifier of the previous wave by a new random one and announce it.
+fn roll_wave_modifier(
+    mut wave_events: EventReader<WaveEvent>,
+    mutators: Res<RunMutators>,
+    mut rules: ResMut<RunRules>,
+    mut modifier: ResMut<WaveModifier>,
+    mut rng: ResMut<GameRng>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    for event in wave_events.iter() {
+        let number = match event {
+            WaveEvent::Started(number) if mutators.0.contains(&Mutator::Roguelike) => number,
+            _otherwise => continue,
+        };
+
+        let rolled = *Mutator::WAVE_MODIFIERS.choose(&mut *rng).unwrap();
+        modifier.0 = Some(rolled);
+
+        // The dice keep decaying at the same pace from one wave to the next.
+        let decay_timer = std::mem::take(&mut rules.decay_timer);
+        *rules = RunRules::new(mutators.0.iter().chain(&modifier.0));
+        rules.decay_timer = decay_timer;
+
+        toasts.send(ToastEvent::warning(format!("Wave {}: {}", number, rolled.label())));
+    }
+}
+
+fn clear_mutators(mut mutators: ResMut<RunMutators>) {
+    *mutators = RunMutators::default();
+}
+
+/// A random dice of the bag loses a pip, a one is removed from the bag.
+fn decay_dice(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut rules: ResMut<RunRules>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut rng: ResMut<GameRng>,
+    mut log: ResMut<EventLog>,
+) {
+    if rules.dice_decay.is_none() || dice_bag.is_empty() {
+        return;
+    }
+
+    rules.decay_timer.tick(speed.delta(&time));
+    if !rules.decay_timer.just_finished() {
+        return;
+    }
+
+    let index = rng.gen_range(0..dice_bag.len());
+    if let Some(number) = dice_bag.remove(index) {
+        match DiceNumber::ALL.iter().position(|n| *n == number).filter(|i| *i > 0) {
+            Some(i) => dice_bag.push(DiceNumber::ALL[i - 1]),
+            None => log.push(&time, "A one decayed away"),
+        }
+    }
+}
+
+fn setup_custom_game(mut commands: Commands, font_assets: Res<FontAssets>) {
+    spawn_menu_screen(
+        &mut commands,
+        &font_assets,
+        "Custom Game",
+        &["A standard game with the rules of your choice".to_string()],
+        &[MenuButton::Back],
+    );
+
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            let button = ButtonBundle {
+                style: Style {
+                    size: Size::new(Val::Px(280.0), Val::Px(36.0)),
+                    margin: UiRect::all(Val::Px(4.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                color: Color::NONE.into(),
+                ..default()
+            };
+
+            for mutator in Mutator::ALL {
+                parent
+                    .spawn_bundle(button.clone())
+                    .insert(MutatorToggle(mutator))
+                    .insert(AccessibleLabel::new(format!("Toggle {}", mutator.label())))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section("", text_style.clone()));
+                    });
+            }
+
+            parent
+                .spawn_bundle(button)
+                .insert(StartCustomGame)
+                .insert(AccessibleLabel::new("Start the custom game"))
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section("Start", text_style.clone()));
+                });
+        });
+}
+
+fn press_mutator_toggles(
+    toggles: Query<(&Interaction, &MutatorToggle), Changed<Interaction>>,
+    start: Query<&Interaction, (Changed<Interaction>, With<StartCustomGame>)>,
+    mut custom_rules: ResMut<CustomRules>,
+    mut run_mutators: ResMut<RunMutators>,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for (interaction, MutatorToggle(mutator)) in &toggles {
+        if *interaction == Interaction::Clicked && !custom_rules.0.remove(mutator) {
+            custom_rules.0.insert(*mutator);
+        }
+    }
+
+    if start.iter().any(|interaction| *interaction == Interaction::Clicked) {
+        run_mutators.0 = custom_rules.0.iter().copied().collect();
+        *game_mode = GameMode::Standard;
+        *run_setup = RunSetup::default();
+        *schedule = WaveSchedule::default();
+
+        // The banned ships of the starting fleet are replaced by the other power.
+        let rules = RunRules::new(&run_mutators.0);
+        for power in &mut run_setup.fleet {
+            if rules.banned_power == Some(*power) {
+                *power = match power {
+                    ShipPower::Bump => ShipPower::Destroy,
+                    ShipPower::Destroy | ShipPower::Mine => ShipPower::Bump,
+                };
+            }
+        }
+
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+fn draw_mutator_toggles(
+    custom_rules: Res<CustomRules>,
+    spawned: Query<(), Added<MutatorToggle>>,
+    toggles: Query<(&MutatorToggle, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !custom_rules.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for (MutatorToggle(mutator), children) in &toggles {
+        let state = if custom_rules.0.contains(mutator) { "on" } else { "off" };
+        let value = format!("{}: {}", mutator.label(), state);
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+        }
+    }
+}
+
+fn highlight_mutator_toggles(
+    palette: Res<Palette>,
+    mut toggles: Query<
+        (&Interaction, ChangeTrackers<Interaction>, &mut UiColor),
+        Or<(With<MutatorToggle>, With<StartCustomGame>)>,
+    >,
+) {
+    for (interaction, tracker, mut color) in &mut toggles {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_run_without_mutators_has_the_default_rules() {
+        let rules = RunRules::new(&[]);
+        assert_eq!(rules.banned_power, None);
+        assert_eq!(rules.spawn_rate_factor, 1.0);
+        assert_eq!(rules.asteroid_scale, 1.0);
+        assert_eq!(rules.asteroid_health_factor, 1);
+        assert_eq!(rules.asteroid_speed_factor, 1.0);
+        assert_eq!(rules.dice_drop_factor, 1);
+        assert_eq!(rules.leash_distance, None);
+        assert_eq!(rules.dice_decay, None);
+    }
+
+    #[test]
+    fn the_mutators_compose_in_any_order() {
+        let mutators = [
+            Mutator::NoBumpShips,
+            Mutator::DoubleSpawnRate,
+            Mutator::GiantAsteroids,
+            Mutator::FastAsteroids,
+            Mutator::DoubleDiceDrops,
+            Mutator::OrbitOnly,
+        ];
+        let forward = RunRules::new(&mutators);
+        let backward = RunRules::new(mutators.iter().rev());
+        for rules in [forward, backward] {
+            assert_eq!(rules.banned_power, Some(ShipPower::Bump));
+            assert_eq!(rules.spawn_rate_factor, 2.0);
+            assert_eq!(rules.asteroid_scale, GIANT_ASTEROID_SCALE);
+            assert_eq!(rules.asteroid_health_factor, 2);
+            assert_eq!(rules.asteroid_speed_factor, FAST_ASTEROID_FACTOR);
+            assert_eq!(rules.dice_drop_factor, 2);
+            assert_eq!(rules.leash_distance, Some(ORBIT_LEASH_DISTANCE));
+            assert_eq!(rules.dice_decay, None);
+        }
+    }
+
+    #[test]
+    fn the_dice_decay_repeats_at_its_interval() {
+        let rules = RunRules::new(&[Mutator::DiceDecay]);
+        let interval = Duration::from_secs(DICE_DECAY_INTERVAL);
+        assert_eq!(rules.dice_decay, Some(interval));
+        assert_eq!(rules.decay_timer.duration(), interval);
+        assert!(rules.decay_timer.repeating());
+    }
+
+    #[test]
+    fn the_wave_modifiers_are_not_toggled_on_the_custom_game_screen() {
+        for modifier in Mutator::WAVE_MODIFIERS {
+            assert!(!Mutator::ALL.contains(&modifier));
+        }
+    }
+}
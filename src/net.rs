@@ -0,0 +1,217 @@
+//! The connection between the two games of a co-op run, a TCP stream to the
+//! relay server carrying one RON message by line.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::lobby::CoopSetup;
+use crate::lockstep::{Checkpoint, PlayerInput};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// The longest line of a message, a longer line comes from a broken or a hostile peer.
+pub const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+/// A message sent to the partner of a co-op run, or by the relay server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum NetMessage {
+    /// The code of the room opened by the host, sent by the server.
+    Room(String),
+    /// Both players are in the room, sent by the server.
+    Joined,
+    /// The room couldn't be opened or joined, sent by the server.
+    Error(String),
+    /// The run chosen by the host in the lobby.
+    Lobby(CoopSetup),
+    /// The host started the run.
+    Start(CoopSetup),
+    /// A chat message of the partner.
+    Chat(String),
+    /// The partner dropped a ping at this position of the world.
+    Ping([f32; 2]),
+    /// The inputs of the player to apply at this step of the simulation.
+    Inputs { step: u64, inputs: Vec<PlayerInput> },
+    /// The state of the run once this step is simulated, sent by the server.
+    Checkpoint { step: u64, checkpoint: Checkpoint },
+}
+
+/// The connection with the partner, it never blocks the frame.
+pub struct Peer {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// The bytes of the line being received, a line can arrive in many packets.
+    line: Vec<u8>,
+    closed: bool,
+}
+
+impl Peer {
+    /// Connect to the relay server and send it the request, `HOST` or `JOIN <code>`.
+    pub fn connect(address: &str, request: &str) -> io::Result<Peer> {
+        let address = address.to_socket_addrs()?.next().ok_or(ErrorKind::AddrNotAvailable)?;
+        let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        writeln!(stream, "{}", request)?;
+        Peer::new(stream)
+    }
+
+    fn new(stream: TcpStream) -> io::Result<Peer> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        let writer = stream.try_clone()?;
+        Ok(Peer { reader: BufReader::new(stream), writer, line: Vec::new(), closed: false })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    pub fn send(&mut self, message: &NetMessage) {
+        if self.closed {
+            return;
+        }
+
+        let result = match ron::to_string(message) {
+            Ok(line) => writeln!(self.writer, "{}", line),
+            Err(e) => Err(io::Error::new(ErrorKind::InvalidData, e)),
+        };
+        if let Err(e) = result {
+            warn!("Could not send a message to the partner: {}", e);
+            self.closed = true;
+        }
+    }
+
+    /// The messages fully received since the last call.
+    pub fn receive(&mut self) -> Vec<NetMessage> {
+        let mut messages = Vec::new();
+        while !self.closed {
+            let limit = (MAX_LINE_LENGTH - self.line.len()) as u64;
+            match (&mut self.reader).take(limit).read_until(b'\n', &mut self.line) {
+                Ok(_) if self.line.ends_with(b"\n") => {
+                    match ron::de::from_bytes(&self.line) {
+                        Ok(message) => messages.push(message),
+                        Err(e) => warn!("Could not read a message of the partner: {}", e),
+                    }
+                    self.line.clear();
+                }
+                Ok(_) if self.line.len() >= MAX_LINE_LENGTH => {
+                    warn!("The partner sent a message longer than {} bytes", MAX_LINE_LENGTH);
+                    self.closed = true;
+                }
+                Ok(0) => self.closed = true,
+                // The rest of the line is still on its way.
+                Ok(_) => (),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("The connection with the partner is lost: {}", e);
+                    self.closed = true;
+                }
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::lockstep::SimId;
+    use crate::selection::ShipAction;
+
+    /// A peer connected to the returned stream, the other end of the connection.
+    fn connected_peer() -> (Peer, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (partner, _) = listener.accept().unwrap();
+        (Peer::new(stream).unwrap(), partner)
+    }
+
+    /// The messages received until this many arrived or the peer is closed.
+    fn receive(peer: &mut Peer, count: usize) -> Vec<NetMessage> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut messages = Vec::new();
+        while messages.len() < count && !peer.is_closed() && Instant::now() < deadline {
+            messages.extend(peer.receive());
+            thread::sleep(Duration::from_millis(1));
+        }
+        messages
+    }
+
+    #[test]
+    fn the_messages_are_read_as_they_are_written() {
+        let messages = [
+            NetMessage::Room("XVFH".to_string()),
+            NetMessage::Ping([12.5, -3.0]),
+            NetMessage::Inputs {
+                step: 42,
+                inputs: vec![
+                    PlayerInput::Attack { ship: SimId(1), target: SimId(7) },
+                    PlayerInput::CollectDice { dice: SimId(3) },
+                    PlayerInput::ShipAction { ship: SimId(1), action: ShipAction::Merge },
+                    PlayerInput::Consumable { slot: 2, position: [1.0, 2.0] },
+                ],
+            },
+            NetMessage::Checkpoint {
+                step: 60,
+                checkpoint: Checkpoint {
+                    wave: 3,
+                    planet_health: 2,
+                    spawned: 120,
+                    ships: 4,
+                    asteroids: 9,
+                    positions: u64::MAX,
+                },
+            },
+        ];
+        for message in messages {
+            let line = ron::to_string(&message).unwrap();
+            assert!(!line.contains('\n'));
+            let read: NetMessage = ron::from_str(&line).unwrap();
+            assert_eq!(ron::to_string(&read).unwrap(), line);
+        }
+    }
+
+    #[test]
+    fn a_message_split_in_many_packets_is_received_once_complete() {
+        let (mut peer, mut partner) = connected_peer();
+        let line = format!("{}\n", ron::to_string(&NetMessage::Chat("hello".into())).unwrap());
+        let (start, end) = line.split_at(line.len() / 2);
+
+        partner.write_all(start.as_bytes()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(peer.receive().is_empty());
+
+        partner.write_all(end.as_bytes()).unwrap();
+        let messages = receive(&mut peer, 1);
+        assert!(matches!(&messages[..], [NetMessage::Chat(text)] if text == "hello"));
+        assert!(!peer.is_closed());
+    }
+
+    #[test]
+    fn an_invalid_message_is_skipped() {
+        let (mut peer, mut partner) = connected_peer();
+        partner.write_all(b"Teleport(1, 2)\nJoined\n").unwrap();
+        let messages = receive(&mut peer, 1);
+        assert!(matches!(messages[..], [NetMessage::Joined]));
+        assert!(!peer.is_closed());
+    }
+
+    #[test]
+    fn a_line_too_long_closes_the_connection() {
+        let (mut peer, mut partner) = connected_peer();
+        let writer = thread::spawn(move || {
+            // The peer stops reading, the end of the line may never be sent.
+            let _ = partner.write_all(&vec![b'A'; MAX_LINE_LENGTH + 1]);
+        });
+        let messages = receive(&mut peer, 1);
+        assert!(messages.is_empty());
+        assert!(peer.is_closed());
+        drop(peer);
+        writer.join().unwrap();
+    }
+}
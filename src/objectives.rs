@@ -0,0 +1,302 @@
+//! The optional objectives of every wave, rewarded when completed before the wave ends.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use crate::abilities::PowerCharges;
+use crate::inventory::{Consumable, Inventory};
+use crate::scrap::{Scrap, ScrapOwnedEvent};
+use crate::seeds::seed_run;
+use crate::toasts::ToastEvent;
+use crate::waves::{reset_waves, Wave, WaveEvent};
+use crate::{
+    AsteroidDestroyedEvent, DestroyCause, DiceLostEvent, DiceOwnedEvent, FontAssets, GameRng,
+    GameState,
+};
+
+const OBJECTIVES_BY_WAVE: usize = 2;
+
+pub struct ObjectivesPlugin;
+
+impl Plugin for ObjectivesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Objectives::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(roll_first_wave_objectives.after(reset_waves).after(seed_run))
+                    .with_system(setup_objectives_checklist),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(roll_objectives_on_wave_start)
+                    .with_system(track_objectives.after(roll_objectives_on_wave_start))
+                    .with_system(draw_objectives_checklist.after(track_objectives)),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectiveKind {
+    /// Break this number of asteroids by bumping them.
+    BumpKills(u32),
+    /// Destroy this number of asteroids with the destroy power.
+    DestroyKills(u32),
+    /// Collect this number of dice.
+    CollectDice(u32),
+    /// Collect this amount of scrap.
+    CollectScrap(u32),
+    /// Let no asteroid reach the planet unshielded, checked when the wave ends.
+    NoLeaks,
+}
+
+impl ObjectiveKind {
+    /// The objectives get harder with the waves.
+    fn roll<R: Rng>(wave: u32, rng: &mut R) -> ObjectiveKind {
+        let extra = wave / 2;
+        match rng.gen_range(0..5) {
+            0 => ObjectiveKind::BumpKills(3 + extra),
+            1 => ObjectiveKind::DestroyKills(5 + extra),
+            2 => ObjectiveKind::CollectDice(6 + extra),
+            3 => ObjectiveKind::CollectScrap(2 + extra),
+            _ => ObjectiveKind::NoLeaks,
+        }
+    }
+
+    fn target(self) -> u32 {
+        match self {
+            ObjectiveKind::BumpKills(n)
+            | ObjectiveKind::DestroyKills(n)
+            | ObjectiveKind::CollectDice(n)
+            | ObjectiveKind::CollectScrap(n) => n,
+            ObjectiveKind::NoLeaks => 1,
+        }
+    }
+
+    fn description(self) -> String {
+        match self {
+            ObjectiveKind::BumpKills(n) => format!("Break {} asteroids with bumps", n),
+            ObjectiveKind::DestroyKills(n) => format!("Destroy {} asteroids", n),
+            ObjectiveKind::CollectDice(n) => format!("Collect {} dice", n),
+            ObjectiveKind::CollectScrap(n) => format!("Collect {} scrap", n),
+            ObjectiveKind::NoLeaks => "Let no asteroid hit the bare planet".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Reward {
+    Scrap(u32),
+    PowerCharge,
+    Consumable(Consumable),
+}
+
+impl Reward {
+    fn roll<R: Rng>(rng: &mut R) -> Reward {
+        match rng.gen_range(0..3) {
+            0 => Reward::Scrap(3),
+            1 => Reward::PowerCharge,
+            _ => Reward::Consumable(*Consumable::ALL.choose(rng).unwrap()),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Reward::Scrap(amount) => format!("{} scrap", amount),
+            Reward::PowerCharge => "a power charge".to_string(),
+            Reward::Consumable(consumable) => format!("a {}", consumable.label()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Objective {
+    kind: ObjectiveKind,
+    progress: u32,
+    reward: Reward,
+    state: ObjectiveState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ObjectiveState {
+    InProgress,
+    Completed,
+    Failed,
+}
+
+/// The objectives of the current wave.
+#[derive(Debug, Default)]
+pub struct Objectives {
+    objectives: Vec<Objective>,
+}
+
+impl Objectives {
+    fn roll<R: Rng>(wave: u32, rng: &mut R) -> Objectives {
+        let mut objectives: Vec<Objective> = Vec::with_capacity(OBJECTIVES_BY_WAVE);
+        while objectives.len() < OBJECTIVES_BY_WAVE {
+            let kind = ObjectiveKind::roll(wave, rng);
+            // The same kind of objective is never asked twice in a wave.
+            if objectives
+                .iter()
+                .all(|o| std::mem::discriminant(&o.kind) != std::mem::discriminant(&kind))
+            {
+                let reward = Reward::roll(rng);
+                objectives.push(Objective {
+                    kind,
+                    progress: 0,
+                    reward,
+                    state: ObjectiveState::InProgress,
+                });
+            }
+        }
+        Objectives { objectives }
+    }
+}
+
+#[derive(Component, Debug)]
+struct ObjectivesChecklist;
+
+fn roll_first_wave_objectives(
+    wave: Res<Wave>,
+    mut objectives: ResMut<Objectives>,
+    mut rng: ResMut<GameRng>,
+) {
+    *objectives = Objectives::roll(wave.number, &mut *rng);
+}
+
+fn roll_objectives_on_wave_start(
+    mut wave_events: EventReader<WaveEvent>,
+    mut objectives: ResMut<Objectives>,
+    mut rng: ResMut<GameRng>,
+) {
+    for event in wave_events.iter() {
+        if let WaveEvent::Started(number) = event {
+            *objectives = Objectives::roll(*number, &mut *rng);
+        }
+    }
+}
+
+/// Observe the events of the game to make the objectives progress,
+/// the rewards are given as soon as an objective is completed.
+fn track_objectives(
+    mut objectives: ResMut<Objectives>,
+    mut asteroid_destroyed: EventReader<AsteroidDestroyedEvent>,
+    mut dice_owned: EventReader<DiceOwnedEvent>,
+    mut scrap_owned: EventReader<ScrapOwnedEvent>,
+    mut dice_lost: EventReader<DiceLostEvent>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut scrap: ResMut<Scrap>,
+    mut power_charges: ResMut<PowerCharges>,
+    mut inventory: ResMut<Inventory>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    // An asteroid can be hit by the blast of many ships in the same frame.
+    let mut destroyed = HashSet::new();
+    let (mut bump_kills, mut destroy_kills) = (0, 0);
+    for event in asteroid_destroyed.iter() {
+        if destroyed.insert(event.entity) {
+            match event.cause {
+                DestroyCause::Bump => bump_kills += 1,
+                DestroyCause::Destroy => destroy_kills += 1,
+                DestroyCause::Mined => (),
+            }
+        }
+    }
+
+    let dice_collected = dice_owned.iter().filter(|event| event.is_pickup()).count() as u32;
+    let scrap_collected: u32 = scrap_owned.iter().map(|ScrapOwnedEvent(amount)| amount).sum();
+    let leaked = dice_lost.iter().count() > 0;
+    let wave_cleared = wave_events.iter().any(|event| matches!(event, WaveEvent::Cleared(_)));
+
+    for objective in &mut objectives.objectives {
+        if objective.state != ObjectiveState::InProgress {
+            continue;
+        }
+
+        match objective.kind {
+            ObjectiveKind::BumpKills(_) => objective.progress += bump_kills,
+            ObjectiveKind::DestroyKills(_) => objective.progress += destroy_kills,
+            ObjectiveKind::CollectDice(_) => objective.progress += dice_collected,
+            ObjectiveKind::CollectScrap(_) => objective.progress += scrap_collected,
+            ObjectiveKind::NoLeaks if leaked => objective.state = ObjectiveState::Failed,
+            ObjectiveKind::NoLeaks if wave_cleared => objective.progress = 1,
+            ObjectiveKind::NoLeaks => (),
+        }
+
+        if objective.state == ObjectiveState::Failed {
+            continue;
+        } else if objective.progress >= objective.kind.target() {
+            objective.progress = objective.kind.target();
+            objective.state = ObjectiveState::Completed;
+            match objective.reward {
+                Reward::Scrap(amount) => scrap.0 += amount,
+                Reward::PowerCharge => power_charges.0 += 1,
+                Reward::Consumable(consumable) => inventory.add(consumable, 1),
+            }
+            toasts.send(ToastEvent::success(format!(
+                "Objective completed, you got {}",
+                objective.reward.label()
+            )));
+        } else if wave_cleared {
+            objective.state = ObjectiveState::Failed;
+        }
+    }
+}
+
+fn setup_objectives_checklist(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(70.0), right: Val::Px(20.0), ..default() },
+                ..default()
+            }),
+        )
+        .insert(ObjectivesChecklist);
+}
+
+/// The checklist is only displayed during the combat, the gamble station
+/// and the shop take its place during the intermission.
+fn draw_objectives_checklist(
+    wave: Res<Wave>,
+    objectives: Res<Objectives>,
+    mut checklist: Query<(&mut Text, &mut Visibility), With<ObjectivesChecklist>>,
+) {
+    for (mut text, mut visibility) in &mut checklist {
+        if visibility.is_visible == wave.is_intermission() {
+            visibility.is_visible = !wave.is_intermission();
+        }
+
+        if objectives.is_changed() {
+            text.sections[0].value = objectives
+                .objectives
+                .iter()
+                .map(|objective| {
+                    let check = match objective.state {
+                        ObjectiveState::InProgress => "[ ]",
+                        ObjectiveState::Completed => "[x]",
+                        ObjectiveState::Failed => "[-]",
+                    };
+                    format!(
+                        "{} {} ({}/{}) - {}",
+                        check,
+                        objective.kind.description(),
+                        objective.progress,
+                        objective.kind.target(),
+                        objective.reward.label(),
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! The photo mode, toggled with the P key, pausing the run and hiding the UI
+//! while the camera is freely moved around to frame the battle.
+
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::{FontAssets, GameState, SpaceCamera};
+
+const PAN_SPEED: f32 = 600.0; // by second, at a 100% zoom
+const ROTATION_SPEED: f32 = 1.0; // radians by second
+const ZOOM_STEP: f32 = 0.1; // by mouse wheel line
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+
+pub struct PhotoPlugin;
+
+impl Plugin for PhotoPlugin {
+    fn build(&self, app: &mut App) {
+        // The photo mode is pushed on top of the run to pause every system of it,
+        // the run resumes as it was left once the photo mode is popped.
+        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(enter_photo_mode))
+            .add_system_set(
+                SystemSet::on_enter(GameState::PhotoMode)
+                    .with_system(pause_physics)
+                    .with_system(hide_ui)
+                    .with_system(save_camera)
+                    .with_system(setup_photo_mode_hint),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::PhotoMode)
+                    .with_system(leave_photo_mode)
+                    .with_system(move_photo_camera)
+                    .with_system(toggle_photo_mode_hint),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::PhotoMode)
+                    .with_system(resume_physics)
+                    .with_system(show_ui)
+                    .with_system(restore_camera)
+                    .with_system(despawn_photo_mode_hint),
+            );
+    }
+}
+
+/// A node that was visible before the photo mode, shown again once it is left.
+#[derive(Component, Debug)]
+struct HiddenForPhoto;
+
+#[derive(Component, Debug)]
+struct PhotoModeHint;
+
+/// The camera as it was before the photo mode, the run is played from there.
+#[derive(Debug)]
+struct SavedCamera {
+    transform: Transform,
+    scale: f32,
+}
+
+fn enter_photo_mode(mut keys: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keys.just_pressed(KeyCode::P) {
+        // The key is consumed to not leave the photo mode in this same frame.
+        keys.clear_just_pressed(KeyCode::P);
+        let _ = state.push(GameState::PhotoMode);
+    }
+}
+
+fn leave_photo_mode(mut keys: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keys.just_pressed(KeyCode::P) {
+        // The key is consumed to not enter the photo mode again in the resumed run.
+        keys.clear_just_pressed(KeyCode::P);
+        let _ = state.pop();
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn hide_ui(
+    mut commands: Commands,
+    mut nodes: Query<(Entity, &mut Visibility), (With<Node>, Without<Parent>)>,
+) {
+    for (entity, mut visibility) in &mut nodes {
+        if visibility.is_visible {
+            visibility.is_visible = false;
+            commands.entity(entity).insert(HiddenForPhoto);
+        }
+    }
+}
+
+fn show_ui(
+    mut commands: Commands,
+    mut nodes: Query<(Entity, &mut Visibility), With<HiddenForPhoto>>,
+) {
+    for (entity, mut visibility) in &mut nodes {
+        visibility.is_visible = true;
+        commands.entity(entity).remove::<HiddenForPhoto>();
+    }
+}
+
+fn save_camera(
+    mut commands: Commands,
+    camera: Query<(&Transform, &OrthographicProjection), With<SpaceCamera>>,
+) {
+    if let Ok((transform, projection)) = camera.get_single() {
+        commands.insert_resource(SavedCamera { transform: *transform, scale: projection.scale });
+    }
+}
+
+fn restore_camera(
+    mut commands: Commands,
+    saved: Option<Res<SavedCamera>>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<SpaceCamera>>,
+) {
+    if let (Some(saved), Ok((mut transform, mut projection))) = (saved, camera.get_single_mut()) {
+        *transform = saved.transform;
+        projection.scale = saved.scale;
+        commands.remove_resource::<SavedCamera>();
+    }
+}
+
+/// Pan with the arrows, WASD or by dragging the mouse,
+/// zoom with the mouse wheel and rotate with the Q and E keys.
+fn move_photo_camera(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    buttons: Res<Input<MouseButton>>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<SpaceCamera>>,
+) {
+    let (mut transform, mut projection) = match camera.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    let lines: f32 = wheel.iter().map(|event| event.y).sum();
+    projection.scale = (projection.scale * (1.0 - lines * ZOOM_STEP)).clamp(MIN_ZOOM, MAX_ZOOM);
+
+    let mut rotation = 0.0;
+    if keys.pressed(KeyCode::Q) {
+        rotation += ROTATION_SPEED;
+    }
+    if keys.pressed(KeyCode::E) {
+        rotation -= ROTATION_SPEED;
+    }
+    transform.rotate_z(rotation * time.delta_seconds());
+
+    let mut direction = Vec2::ZERO;
+    if keys.any_pressed([KeyCode::Left, KeyCode::A]) {
+        direction.x -= 1.0;
+    }
+    if keys.any_pressed([KeyCode::Right, KeyCode::D]) {
+        direction.x += 1.0;
+    }
+    if keys.any_pressed([KeyCode::Down, KeyCode::S]) {
+        direction.y -= 1.0;
+    }
+    if keys.any_pressed([KeyCode::Up, KeyCode::W]) {
+        direction.y += 1.0;
+    }
+    let mut pan = direction.normalize_or_zero() * PAN_SPEED * time.delta_seconds();
+
+    // The scene follows the cursor, the screen Y axis goes downward.
+    let dragged = motion.iter().fold(Vec2::ZERO, |sum, event| sum + event.delta);
+    if buttons.pressed(MouseButton::Left) {
+        pan += Vec2::new(-dragged.x, dragged.y);
+    }
+
+    // The pan is in screen space, it follows the zoom and the rotation of the camera.
+    let pan = transform.rotation * (pan * projection.scale).extend(0.0);
+    transform.translation += pan;
+}
+
+fn setup_photo_mode_hint(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "Photo mode: P to leave, H to hide this help\n\
+                 Arrows or drag to pan, wheel to zoom, Q and E to rotate",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), bottom: Val::Px(20.0), ..default() },
+                ..default()
+            }),
+        )
+        .insert(PhotoModeHint);
+}
+
+/// The help is hidden to take the screenshots with nothing but the battle on screen.
+fn toggle_photo_mode_hint(
+    keys: Res<Input<KeyCode>>,
+    mut hint: Query<&mut Visibility, With<PhotoModeHint>>,
+) {
+    if keys.just_pressed(KeyCode::H) {
+        for mut visibility in &mut hint {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+fn despawn_photo_mode_hint(mut commands: Commands, hint: Query<Entity, With<PhotoModeHint>>) {
+    hint.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -0,0 +1,129 @@
+//! The players of a run, alone or two in a co-op run, and what each of them did.
+//!
+//! The ships, the deployed consumables and the collected dice remember the
+//! player they belong to, the kills, the dice and the purchases are counted by
+//! player and shown at the end of the co-op runs.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{AsteroidDestroyedEvent, DiceOwnedEvent, GameState};
+
+pub struct PlayersPlugin;
+
+impl Plugin for PlayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Players::default())
+            .insert_resource(PlayerStats::default())
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(play_alone))
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(reset_player_stats))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(count_player_kills)
+                    .with_system(count_player_dice),
+            );
+    }
+}
+
+#[derive(
+    Component, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    /// The player alone or the host of a co-op run.
+    pub const FIRST: PlayerId = PlayerId(0);
+    pub const SECOND: PlayerId = PlayerId(1);
+
+    pub fn label(self) -> String {
+        format!("Player {}", self.0 + 1)
+    }
+}
+
+/// The players of the run and the one playing on this game.
+#[derive(Debug, Clone, Copy)]
+pub struct Players {
+    pub local: PlayerId,
+    pub count: u8,
+}
+
+impl Default for Players {
+    fn default() -> Players {
+        Players { local: PlayerId::FIRST, count: 1 }
+    }
+}
+
+impl Players {
+    pub fn coop(host: bool) -> Players {
+        let local = if host { PlayerId::FIRST } else { PlayerId::SECOND };
+        Players { local, count: 2 }
+    }
+
+    /// The starting ships are handed to the players in turn.
+    pub fn owner_of_ship(&self, index: usize) -> PlayerId {
+        PlayerId((index % self.count as usize) as u8)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlayerTally {
+    pub kills: u32,
+    pub dice: u32,
+    pub purchases: u32,
+}
+
+/// What each player did during the run.
+#[derive(Debug, Default)]
+pub struct PlayerStats(BTreeMap<PlayerId, PlayerTally>);
+
+impl PlayerStats {
+    pub fn record_purchase(&mut self, player: PlayerId) {
+        self.0.entry(player).or_default().purchases += 1;
+    }
+
+    /// A line by player for the end of the run, none when playing alone.
+    pub fn summary(&self, players: &Players) -> Vec<String> {
+        if players.count < 2 {
+            return Vec::new();
+        }
+
+        (0..players.count)
+            .map(|id| {
+                let tally = self.0.get(&PlayerId(id)).copied().unwrap_or_default();
+                format!(
+                    "{}: {} kills, {} dice, {} purchases",
+                    PlayerId(id).label(),
+                    tally.kills,
+                    tally.dice,
+                    tally.purchases
+                )
+            })
+            .collect()
+    }
+}
+
+/// The co-op runs are started from the lobby, every other run is played alone.
+fn play_alone(mut players: ResMut<Players>) {
+    *players = Players::default();
+}
+
+fn reset_player_stats(mut stats: ResMut<PlayerStats>) {
+    *stats = PlayerStats::default();
+}
+
+fn count_player_kills(
+    mut asteroid_destroyed: EventReader<AsteroidDestroyedEvent>,
+    mut stats: ResMut<PlayerStats>,
+) {
+    for player in asteroid_destroyed.iter().filter_map(|event| event.player) {
+        stats.0.entry(player).or_default().kills += 1;
+    }
+}
+
+fn count_player_dice(mut dice_owned: EventReader<DiceOwnedEvent>, mut stats: ResMut<PlayerStats>) {
+    for player in dice_owned.iter().filter_map(|DiceOwnedEvent(_, player)| *player) {
+        stats.0.entry(player).or_default().dice += 1;
+    }
+}
@@ -0,0 +1,140 @@
+//! Poker-style hands evaluated on the dice bag, granting global buffs while they are held.
+
+use bevy::prelude::*;
+
+use crate::dice::DiceBag;
+use crate::logic;
+use crate::{FontAssets, GameState};
+
+pub struct PokerPlugin;
+
+impl Plugin for PokerPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HeldHand::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(setup_held_hand_indicator),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(evaluate_held_hand)
+                    .with_system(draw_held_hand_indicator.after(evaluate_held_hand)),
+            );
+    }
+}
+
+/// The hands that can be formed with the dice of the bag, from the weakest to the strongest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PokerHand {
+    /// Two dice of the same number.
+    Pair,
+    /// Three dice of the same number.
+    ThreeOfAKind,
+    /// Five dice following each other, One to Five or Two to Six.
+    Straight,
+    /// Three dice of the same number and a pair of another one.
+    FullHouse,
+}
+
+impl PokerHand {
+    /// Finds the strongest hand that can be formed with the dice of the bag.
+    pub fn evaluate(dice_bag: &DiceBag) -> Option<PokerHand> {
+        logic::best_poker_hand(&logic::dice_counts(dice_bag))
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            PokerHand::Pair => "Pair",
+            PokerHand::ThreeOfAKind => "Three of a Kind",
+            PokerHand::Straight => "Straight",
+            PokerHand::FullHouse => "Full House",
+        }
+    }
+
+    fn ship_speed_factor(self) -> f32 {
+        match self {
+            PokerHand::Pair => 1.1,
+            PokerHand::ThreeOfAKind => 1.2,
+            PokerHand::Straight => 1.3,
+            PokerHand::FullHouse => 1.4,
+        }
+    }
+
+    fn spawn_interval_factor(self) -> f32 {
+        match self {
+            PokerHand::Pair => 1.0,
+            PokerHand::ThreeOfAKind => 1.1,
+            PokerHand::Straight => 1.25,
+            PokerHand::FullHouse => 1.4,
+        }
+    }
+}
+
+/// The strongest hand currently held in the dice bag.
+#[derive(Debug, Default)]
+pub struct HeldHand(Option<PokerHand>);
+
+impl HeldHand {
+    /// How much faster the ships are moving.
+    pub fn ship_speed_factor(&self) -> f32 {
+        self.0.map_or(1.0, PokerHand::ship_speed_factor)
+    }
+
+    /// How much longer it takes for an asteroid to spawn.
+    pub fn spawn_interval_factor(&self) -> f32 {
+        self.0.map_or(1.0, PokerHand::spawn_interval_factor)
+    }
+}
+
+/// The text displaying the held hand on the top right of the screen.
+#[derive(Component, Debug)]
+struct HeldHandIndicator;
+
+fn setup_held_hand_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.0,
+                    color: Color::GOLD,
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(20.0), right: Val::Px(20.0), ..default() },
+                ..default()
+            }),
+        )
+        .insert(HeldHandIndicator);
+}
+
+fn evaluate_held_hand(dice_bag: Res<DiceBag>, mut held_hand: ResMut<HeldHand>) {
+    if dice_bag.is_changed() {
+        let hand = PokerHand::evaluate(&dice_bag);
+        if held_hand.0 != hand {
+            held_hand.0 = hand;
+        }
+    }
+}
+
+fn draw_held_hand_indicator(
+    held_hand: Res<HeldHand>,
+    mut indicator: Query<&mut Text, With<HeldHandIndicator>>,
+) {
+    if held_hand.is_changed() {
+        let value = match held_hand.0 {
+            Some(hand) => format!(
+                "{}: ships +{:.0}%, spawns -{:.0}%",
+                hand.label(),
+                (hand.ship_speed_factor() - 1.0) * 100.0,
+                (1.0 - 1.0 / hand.spawn_interval_factor()) * 100.0,
+            ),
+            None => String::new(),
+        };
+
+        for mut text in &mut indicator {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
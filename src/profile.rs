@@ -0,0 +1,80 @@
+//! The profile of the player, saved on disk between the game sessions.
+
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::endless::LeaderboardEntry;
+use crate::headless::Headless;
+use crate::save::{load_ron_file, save_ron_file};
+
+const PROFILE_PATH: &str = "profile.ron";
+const LEADERBOARD_SIZE: usize = 10;
+
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_ron_file::<Profile>(PROFILE_PATH))
+            .add_system(save_profile_on_change);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// The best number of stars earned in the campaign levels, by level name.
+    #[serde(default)]
+    level_stars: BTreeMap<String, u8>,
+    /// The best endless runs, from the best score to the lowest.
+    #[serde(default)]
+    endless_leaderboard: Vec<LeaderboardEntry>,
+    /// The seconds since the Unix epoch when the profile was last changed.
+    #[serde(default)]
+    updated_at: u64,
+}
+
+impl Profile {
+    /// The best number of stars earned in this level, zero when it was never cleared.
+    pub fn level_stars(&self, level: &str) -> u8 {
+        self.level_stars.get(level).copied().unwrap_or(0)
+    }
+
+    /// Keeps the number of stars earned in this level when it beats the best one.
+    pub fn record_level_stars(&mut self, level: &str, stars: u8) {
+        if stars > self.level_stars(level) {
+            self.level_stars.insert(level.to_string(), stars);
+            self.touch();
+        }
+    }
+
+    pub fn endless_leaderboard(&self) -> &[LeaderboardEntry] {
+        &self.endless_leaderboard
+    }
+
+    /// Inserts the run in the leaderboard, it is forgotten when it doesn't beat the best runs.
+    pub fn record_endless_run(&mut self, entry: LeaderboardEntry) {
+        let position = self.endless_leaderboard.partition_point(|e| e.score >= entry.score);
+        self.endless_leaderboard.insert(position, entry);
+        self.endless_leaderboard.truncate(LEADERBOARD_SIZE);
+        self.touch();
+    }
+
+    #[cfg(feature = "cloud-sync")]
+    pub fn updated_at(&self) -> u64 {
+        self.updated_at
+    }
+
+    fn touch(&mut self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.updated_at = now.as_secs();
+    }
+}
+
+/// The headless runs of the server and of the sweeps don't count for the player.
+fn save_profile_on_change(profile: Res<Profile>, headless: Option<Res<Headless>>) {
+    if profile.is_changed() && !profile.is_added() && headless.is_none() {
+        save_ron_file(PROFILE_PATH, &*profile);
+    }
+}
@@ -0,0 +1,48 @@
+//! The graphics quality presets, chosen in the settings for the low-end laptops
+//! to draw coarser shapes and fewer effects.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Quality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl Quality {
+    pub const ALL: [Quality; 3] = [Quality::Low, Quality::Medium, Quality::High];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Quality::Low => "Low",
+            Quality::Medium => "Medium",
+            Quality::High => "High",
+        }
+    }
+
+    /// The preset coming after this one in the settings.
+    pub fn next(self) -> Quality {
+        let index = Quality::ALL.iter().position(|quality| *quality == self).unwrap_or(0);
+        Quality::ALL[(index + 1) % Quality::ALL.len()]
+    }
+
+    /// The size of the texture of the asteroids, in pixels.
+    pub fn asteroid_texture_size(self) -> u32 {
+        match self {
+            Quality::Low => 32,
+            Quality::Medium => 64,
+            Quality::High => 128,
+        }
+    }
+
+    /// The number of particles of every burst of the effects.
+    pub fn particle_budget(self) -> usize {
+        match self {
+            Quality::Low => 8,
+            Quality::Medium => 16,
+            Quality::High => 32,
+        }
+    }
+}
@@ -0,0 +1,212 @@
+//! The confirmation asked before quitting, either from the window close button
+//! or from the quit button of the main menu, so a run isn't lost by accident.
+//!
+//! The run is paused while the dialog is shown.
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use bevy::window::WindowCloseRequested;
+use bevy_rapier2d::prelude::*;
+
+use crate::accessibility::AccessibleLabel;
+use crate::theme::{Palette, ThemedPanel};
+use crate::{FontAssets, GameState, Persistent};
+
+pub struct QuitPlugin;
+
+impl Plugin for QuitPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<QuitRequestedEvent>()
+            .add_system(request_quit_on_window_close)
+            .add_system(open_quit_dialog.after(request_quit_on_window_close))
+            .add_system(press_quit_dialog_buttons)
+            .add_system(cancel_quit_by_pressing_escape)
+            .add_system(highlight_quit_dialog_buttons)
+            .add_system_set(SystemSet::on_enter(GameState::Quitting).with_system(pause_physics))
+            .add_system_set(SystemSet::on_exit(GameState::Quitting).with_system(resume_physics));
+    }
+}
+
+/// The player asked to quit the game, it is confirmed in a dialog.
+pub struct QuitRequestedEvent;
+
+/// The modal dialog confirming that the player wants to quit.
+#[derive(Component, Debug)]
+struct QuitDialog;
+
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum QuitDialogButton {
+    Quit,
+    Cancel,
+}
+
+impl QuitDialogButton {
+    fn label(self) -> &'static str {
+        match self {
+            QuitDialogButton::Quit => "Quit",
+            QuitDialogButton::Cancel => "Cancel",
+        }
+    }
+}
+
+/// The window isn't closed directly, see the `WindowSettings` of the app.
+fn request_quit_on_window_close(
+    mut close_requested: EventReader<WindowCloseRequested>,
+    mut quit_requested: EventWriter<QuitRequestedEvent>,
+) {
+    if close_requested.iter().count() > 0 {
+        quit_requested.send(QuitRequestedEvent);
+    }
+}
+
+fn open_quit_dialog(
+    mut commands: Commands,
+    mut quit_requested: EventReader<QuitRequestedEvent>,
+    dialog: Query<(), With<QuitDialog>>,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if quit_requested.iter().count() == 0 || !dialog.is_empty() {
+        return;
+    }
+
+    if *state.current() == GameState::Playing {
+        let _ = state.push(GameState::Quitting);
+    }
+
+    // The dialog covers the whole screen to block the clicks on the game behind it.
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(QuitDialog)
+        .insert(Persistent)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    color: palette.panel.into(),
+                    ..default()
+                })
+                .insert(ThemedPanel)
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section(
+                        "Quit the game? The current run will be lost.",
+                        TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 24.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+
+                    parent
+                        .spawn_bundle(NodeBundle { color: Color::NONE.into(), ..default() })
+                        .with_children(|parent| {
+                            for button in [QuitDialogButton::Quit, QuitDialogButton::Cancel] {
+                                parent
+                                    .spawn_bundle(ButtonBundle {
+                                        style: Style {
+                                            size: Size::new(Val::Px(140.0), Val::Px(40.0)),
+                                            margin: UiRect::all(Val::Px(8.0)),
+                                            justify_content: JustifyContent::Center,
+                                            align_items: AlignItems::Center,
+                                            ..default()
+                                        },
+                                        color: palette.button.into(),
+                                        ..default()
+                                    })
+                                    .insert(button)
+                                    .insert(AccessibleLabel::new(button.label()))
+                                    .with_children(|parent| {
+                                        parent.spawn_bundle(TextBundle::from_section(
+                                            button.label(),
+                                            TextStyle {
+                                                font: font_assets.fira_sans.clone(),
+                                                font_size: 20.0,
+                                                color: Color::WHITE,
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+fn press_quit_dialog_buttons(
+    mut commands: Commands,
+    buttons: Query<(&Interaction, &QuitDialogButton), Changed<Interaction>>,
+    dialog: Query<Entity, With<QuitDialog>>,
+    mut state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        match button {
+            QuitDialogButton::Quit => exit.send(AppExit),
+            QuitDialogButton::Cancel => close_quit_dialog(&mut commands, &dialog, &mut state),
+        }
+    }
+}
+
+fn cancel_quit_by_pressing_escape(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    dialog: Query<Entity, With<QuitDialog>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keys.just_pressed(KeyCode::Escape) && !dialog.is_empty() {
+        close_quit_dialog(&mut commands, &dialog, &mut state);
+    }
+}
+
+/// Despawn the dialog and resume the run it paused.
+fn close_quit_dialog(
+    commands: &mut Commands,
+    dialog: &Query<Entity, With<QuitDialog>>,
+    state: &mut State<GameState>,
+) {
+    dialog.for_each(|entity| commands.entity(entity).despawn_recursive());
+    if *state.current() == GameState::Quitting {
+        let _ = state.pop();
+    }
+}
+
+fn pause_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+fn highlight_quit_dialog_buttons(
+    palette: Res<Palette>,
+    mut buttons: Query<
+        (&Interaction, ChangeTrackers<Interaction>, &mut UiColor),
+        With<QuitDialogButton>,
+    >,
+) {
+    for (interaction, tracker, mut color) in &mut buttons {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+}
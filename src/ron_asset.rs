@@ -0,0 +1,37 @@
+//! A generic loader for the game data files written in RON.
+
+use std::marker::PhantomData;
+
+use bevy::asset::{Asset, AssetLoader, BoxedFuture, Error, LoadContext, LoadedAsset};
+use serde::de::DeserializeOwned;
+
+/// Loads any deserializable asset from the RON files with the given extensions,
+/// e.g. `fusion.ron` for the `dice.fusion.ron` file.
+pub struct RonAssetLoader<T> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RonAssetLoader<T> {
+    pub fn new(extensions: &'static [&'static str]) -> RonAssetLoader<T> {
+        RonAssetLoader { extensions, _marker: PhantomData }
+    }
+}
+
+impl<T: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let asset: T = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
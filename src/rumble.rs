@@ -0,0 +1,92 @@
+//! The rumble of the gamepads, short pulses felt on the impacts on the planet,
+//! the dice pickups and the power activations.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use gilrs::ff::{BaseEffect, BaseEffectType, EffectBuilder, Repeat, Replay, Ticks};
+use gilrs::{GamepadId, Gilrs};
+
+use crate::abilities::AbilityActivatedEvent;
+use crate::settings::GameSettings;
+use crate::{DiceOwnedEvent, PlanetImpactEvent};
+
+/// The duration of the strongest pulses, the weaker ones are shorter.
+const RUMBLE_DURATION: u32 = 250; // in millisecond
+
+pub struct RumblePlugin;
+
+impl Plugin for RumblePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_non_send_resource(Rumbles::default())
+            .add_system(rumble_on_game_events)
+            .add_system(stop_finished_rumbles);
+    }
+}
+
+/// The effects being played, they stop when they are dropped.
+#[derive(Default)]
+struct Rumbles {
+    playing: Vec<(gilrs::ff::Effect, Timer)>,
+}
+
+/// Plays a pulse with an intensity between 0 and 1 on every gamepad supporting force feedback.
+fn play_rumble(gilrs: &mut Gilrs, rumbles: &mut Rumbles, intensity: f32) {
+    let gamepads: Vec<GamepadId> = gilrs
+        .gamepads()
+        .filter(|(_, gamepad)| gamepad.is_ff_supported())
+        .map(|(id, _)| id)
+        .collect();
+    if gamepads.is_empty() {
+        return;
+    }
+
+    let intensity = intensity.clamp(0.0, 1.0);
+    let duration = (RUMBLE_DURATION as f32 * (0.5 + intensity / 2.0)) as u32;
+    let effect = EffectBuilder::new()
+        .add_effect(BaseEffect {
+            kind: BaseEffectType::Strong { magnitude: (u16::MAX as f32 * intensity) as u16 },
+            scheduling: Replay { play_for: Ticks::from_ms(duration), ..default() },
+            ..default()
+        })
+        .repeat(Repeat::For(Ticks::from_ms(duration)))
+        .gamepads(&gamepads)
+        .finish(gilrs);
+
+    match effect.and_then(|effect| effect.play().map(|()| effect)) {
+        Ok(effect) => {
+            let timer = Timer::new(Duration::from_millis(duration as u64), false);
+            rumbles.playing.push((effect, timer));
+        }
+        Err(e) => warn!("Could not play the rumble: {}", e),
+    }
+}
+
+fn rumble_on_game_events(
+    settings: Res<GameSettings>,
+    gilrs: Option<NonSendMut<Gilrs>>,
+    mut rumbles: NonSendMut<Rumbles>,
+    mut planet_impacts: EventReader<PlanetImpactEvent>,
+    mut dice_owned: EventReader<DiceOwnedEvent>,
+    mut abilities: EventReader<AbilityActivatedEvent>,
+) {
+    // The strongest event of the frame gives the intensity of the pulse.
+    let mut intensity: f32 = 0.0;
+    for PlanetImpactEvent { shielded, .. } in planet_impacts.iter() {
+        intensity = intensity.max(if *shielded { 0.5 } else { 1.0 });
+    }
+    if dice_owned.iter().count() > 0 {
+        intensity = intensity.max(0.2);
+    }
+    if abilities.iter().count() > 0 {
+        intensity = intensity.max(0.6);
+    }
+
+    if let (Some(mut gilrs), true) = (gilrs, settings.rumble && intensity > 0.0) {
+        play_rumble(&mut gilrs, &mut rumbles, intensity);
+    }
+}
+
+fn stop_finished_rumbles(time: Res<Time>, mut rumbles: NonSendMut<Rumbles>) {
+    rumbles.playing.retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+}
@@ -0,0 +1,43 @@
+//! The files saved on disk between the game sessions, written in RON.
+//!
+//! The files are in the working directory, or in the internal storage of the app
+//! on Android where the working directory can't be written.
+
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Loads the value saved in this file, the default value is returned
+/// when there is no such file or when it is invalid.
+pub fn load_ron_file<T: DeserializeOwned + Default>(path: &str) -> T {
+    match fs::read_to_string(save_path(path)) {
+        Ok(content) => ron::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring the invalid save file {}: {}", path, e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Saves the value in this file, the errors are logged and ignored.
+pub fn save_ron_file<T: Serialize>(path: &str, value: &T) {
+    let result = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|content| fs::write(save_path(path), content).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Could not save to {}: {}", path, e);
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+fn save_path(path: &str) -> PathBuf {
+    PathBuf::from(path)
+}
+
+#[cfg(target_os = "android")]
+fn save_path(path: &str) -> PathBuf {
+    bevy::ndk_glue::native_activity().internal_data_path().join(path)
+}
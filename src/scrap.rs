@@ -0,0 +1,154 @@
+//! The scrap, a second currency dropped by the asteroids bumped to death
+//! and spent on structural purchases while dice remain the combine currency.
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::controls::Actions;
+use crate::layers::RenderLayer;
+use crate::{is_over_sprite, FontAssets, GameState, ImageAssets, OutOfBounds, SpaceCamera};
+
+pub const SCRAP_BY_ASTEROID: u32 = 1;
+
+pub struct ScrapPlugin;
+
+impl Plugin for ScrapPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Scrap::default())
+            .add_event::<ScrapOwnedEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_scrap)
+                    .with_system(setup_scrap_counter),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(collect_scrap_by_clicking)
+                    .with_system(manage_scrap_events.after(collect_scrap_by_clicking))
+                    .with_system(draw_scrap_counter.after(manage_scrap_events)),
+            );
+    }
+}
+
+/// The amount of scrap owned by the player.
+#[derive(Debug, Default)]
+pub struct Scrap(pub u32);
+
+impl Scrap {
+    /// Removes the amount of scrap, only if there is enough of it.
+    pub fn try_spend(&mut self, amount: u32) -> bool {
+        match self.0.checked_sub(amount) {
+            Some(remaining) => {
+                self.0 = remaining;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct ScrapOwnedEvent(pub u32);
+
+#[derive(Component, Debug)]
+pub struct ScrapLoot {
+    amount: u32,
+}
+
+/// The text displaying the amount of scrap next to the dice bag.
+#[derive(Component, Debug)]
+struct ScrapCounter;
+
+/// Spawn a scrap the player can collect by clicking on it.
+pub fn spawn_scrap_loot(
+    commands: &mut Commands,
+    image_assets: &ImageAssets,
+    translation: Vec3,
+    amount: u32,
+) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite { custom_size: Some(Vec2::splat(20.0)), ..default() },
+            transform: Transform::from_translation(RenderLayer::Loot.at(translation.truncate())),
+            texture: image_assets.scrap.clone(),
+            ..default()
+        })
+        .insert(ScrapLoot { amount })
+        .insert(OutOfBounds::Despawn);
+}
+
+fn reset_scrap(mut scrap: ResMut<Scrap>) {
+    *scrap = Scrap::default();
+}
+
+fn setup_scrap_counter(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(90.0), bottom: Val::Px(20.0), ..default() },
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(ImageBundle {
+                style: Style {
+                    size: Size::new(Val::Px(20.0), Val::Px(20.0)),
+                    margin: UiRect::all(Val::Px(4.0)),
+                    ..default()
+                },
+                image: image_assets.scrap.clone().into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            });
+
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "0",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(ScrapCounter);
+        });
+}
+
+fn collect_scrap_by_clicking(
+    mut commands: Commands,
+    mut scrap_owned: EventWriter<ScrapOwnedEvent>,
+    actions: Actions,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    scraps: Query<(Entity, &Sprite, &GlobalTransform, &ScrapLoot)>,
+) {
+    let (camera, camera_transform) = camera.single();
+    let presses = actions.pointer_presses(camera, camera_transform);
+    for (entity, sprite, transform, scrap_loot) in &scraps {
+        if presses.iter().any(|world_pos| is_over_sprite(*world_pos, sprite, transform)) {
+            scrap_owned.send(ScrapOwnedEvent(scrap_loot.amount));
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn manage_scrap_events(mut scrap_owned: EventReader<ScrapOwnedEvent>, mut scrap: ResMut<Scrap>) {
+    for ScrapOwnedEvent(amount) in scrap_owned.iter() {
+        scrap.0 += amount;
+    }
+}
+
+fn draw_scrap_counter(scrap: Res<Scrap>, mut counter: Query<&mut Text, With<ScrapCounter>>) {
+    if scrap.is_changed() {
+        for mut text in &mut counter {
+            text.sections[0].value = scrap.0.to_string();
+        }
+    }
+}
@@ -0,0 +1,370 @@
+//! The script hooks, the wave and event logic written in the rhai scripts of
+//! the `scripts` folder, e.g. a meteor shower announced by a banner at the
+//! start of a wave. The mods can add their own scripts.
+//!
+//! A script defines the hooks it needs among `on_wave_started(wave)`,
+//! `on_wave_cleared(wave)` and `on_planet_hit()`. The scripts are sandboxed,
+//! they can only call the functions of the API registered here and can't load
+//! any file, and a hook running for too long or asking for too many actions is stopped.
+
+use std::sync::{Arc, Mutex};
+
+use bevy::asset::{AssetLoader, BoxedFuture, Error, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST, INT};
+
+use crate::dice::DiceNumber;
+use crate::mutators::RunRules;
+use crate::toasts::ToastEvent;
+use crate::tuning::{Tuning, TuningHandle};
+use crate::waves::WaveEvent;
+use crate::{
+    spawn_asteroid, AsteroidSpawnConfig, DiceOwnedEvent, GameRng, GameState, Planet, PlanetHealth,
+    PlanetImpactEvent,
+};
+
+const SCRIPTS_FOLDER: &str = "scripts";
+/// The most asteroids a single hook run can spawn, to keep a script from flooding the run.
+const MAX_SCRIPTED_ASTEROIDS: u32 = 20;
+/// The most actions a single hook run can ask for, the hook is stopped at the next one.
+const MAX_HOOK_ACTIONS: usize = 32;
+/// The operations a hook can run before being stopped, e.g. in an endless loop.
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 1024;
+const MAX_ARRAY_SIZE: usize = 1024;
+
+pub struct ScriptingPlugin;
+
+impl Plugin for ScriptingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Script>()
+            .add_asset_loader(ScriptLoader)
+            .insert_resource(ScriptEngine::new())
+            .add_startup_system(load_scripts)
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(run_script_hooks));
+    }
+}
+
+#[derive(Debug, TypeUuid)]
+#[uuid = "7d4e2b19-5f8a-4c63-a0e7-3b9d1c6f8e24"]
+pub struct Script {
+    ast: AST,
+}
+
+/// Compiles the `.rhai` files, the syntax errors are reported when loading.
+struct ScriptLoader;
+
+impl AssetLoader for ScriptLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes)?;
+            let ast = sandboxed_engine().compile(source)?;
+            load_context.set_default_asset(LoadedAsset::new(Script { ast }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["rhai"]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Trigger {
+    /// The asteroids of the wave with this number start spawning.
+    WaveStarted(u32),
+    /// The wave with this number is over.
+    WaveCleared(u32),
+    /// An asteroid hit the planet, shielded or not.
+    PlanetHit,
+}
+
+impl Trigger {
+    /// The name of the script function called and its arguments.
+    fn hook(self) -> (&'static str, Vec<Dynamic>) {
+        match self {
+            Trigger::WaveStarted(wave) => ("on_wave_started", vec![Dynamic::from(wave as INT)]),
+            Trigger::WaveCleared(wave) => ("on_wave_cleared", vec![Dynamic::from(wave as INT)]),
+            Trigger::PlanetHit => ("on_planet_hit", Vec::new()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    /// Spawns this number of asteroids of the tuning around the planet.
+    SpawnAsteroids(u32),
+    /// Puts a dice of this number in the bag.
+    GrantDice(DiceNumber),
+    /// Shows a message at the top of the screen.
+    Banner(String),
+}
+
+/// What the API shows to the scripts and the actions they ask for,
+/// applied to the run once the hooks are done.
+#[derive(Debug, Default)]
+struct ScriptState {
+    planet_health: u32,
+    actions: Vec<Action>,
+    /// The actions asked by the running hook.
+    hook_actions: usize,
+    /// The asteroids spawned by the running hook.
+    hook_asteroids: u32,
+}
+
+impl ScriptState {
+    fn start_hook(&mut self) {
+        self.hook_actions = 0;
+        self.hook_asteroids = 0;
+    }
+
+    fn push(&mut self, action: Action) -> Result<(), Box<EvalAltResult>> {
+        if self.hook_actions >= MAX_HOOK_ACTIONS {
+            let message = format!("a hook can't ask for more than {} actions", MAX_HOOK_ACTIONS);
+            return Err(message.into());
+        }
+        self.hook_actions += 1;
+        self.actions.push(action);
+        Ok(())
+    }
+}
+
+struct ScriptEngine {
+    engine: Engine,
+    state: Arc<Mutex<ScriptState>>,
+}
+
+impl ScriptEngine {
+    fn new() -> ScriptEngine {
+        let state = Arc::new(Mutex::new(ScriptState::default()));
+        let mut engine = sandboxed_engine();
+
+        let api = state.clone();
+        engine.register_fn(
+            "spawn_asteroids",
+            move |count: INT| -> Result<(), Box<EvalAltResult>> {
+                let mut state = api.lock().unwrap();
+                let left = MAX_SCRIPTED_ASTEROIDS - state.hook_asteroids;
+                let count = count.clamp(0, left as INT) as u32;
+                state.hook_asteroids += count;
+                state.push(Action::SpawnAsteroids(count))
+            },
+        );
+        let api = state.clone();
+        engine.register_fn("grant_dice", move |number: INT| -> Result<(), Box<EvalAltResult>> {
+            let index = usize::try_from(number - 1).ok();
+            match index.and_then(|index| DiceNumber::ALL.get(index)) {
+                Some(number) => api.lock().unwrap().push(Action::GrantDice(*number)),
+                None => Err(format!("{} is not a dice number", number).into()),
+            }
+        });
+        let api = state.clone();
+        engine.register_fn("banner", move |message: &str| -> Result<(), Box<EvalAltResult>> {
+            api.lock().unwrap().push(Action::Banner(message.to_string()))
+        });
+        let api = state.clone();
+        engine.register_fn("planet_health", move || api.lock().unwrap().planet_health as INT);
+
+        ScriptEngine { engine, state }
+    }
+}
+
+/// An engine with the standard functions of rhai, bounded not to freeze the game.
+fn sandboxed_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(MAX_CALL_LEVELS)
+        .set_max_string_size(MAX_STRING_SIZE)
+        .set_max_array_size(MAX_ARRAY_SIZE)
+        .on_print(|message| info!("script: {}", message))
+        .on_debug(|message, _, position| debug!("script at {}: {}", position, message));
+    engine
+}
+
+pub struct ScriptHandles(pub Vec<HandleUntyped>);
+
+fn load_scripts(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = asset_server.load_folder(SCRIPTS_FOLDER).unwrap_or_else(|e| {
+        warn!("Could not load the scripts: {}", e);
+        Vec::new()
+    });
+    commands.insert_resource(ScriptHandles(handles));
+}
+
+fn run_script_hooks(
+    mut commands: Commands,
+    engine: Res<ScriptEngine>,
+    scripts: Res<Assets<Script>>,
+    handles: Res<ScriptHandles>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut planet_impacts: EventReader<PlanetImpactEvent>,
+    planet: Query<&Transform, With<Planet>>,
+    planet_health: Res<PlanetHealth>,
+    tunings: Res<Assets<Tuning>>,
+    tuning: Res<TuningHandle>,
+    rules: Res<RunRules>,
+    config: Res<AsteroidSpawnConfig>,
+    mut rng: ResMut<GameRng>,
+    mut dice_owned: EventWriter<DiceOwnedEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    let mut triggers: Vec<_> = wave_events
+        .iter()
+        .map(|event| match *event {
+            WaveEvent::Started(number) => Trigger::WaveStarted(number),
+            WaveEvent::Cleared(number) => Trigger::WaveCleared(number),
+        })
+        .collect();
+    triggers.extend(planet_impacts.iter().map(|_| Trigger::PlanetHit));
+
+    if triggers.is_empty() {
+        return;
+    }
+
+    engine.state.lock().unwrap().planet_health = planet_health.current;
+    let asts = handles
+        .0
+        .iter()
+        .filter_map(|handle| scripts.get(&handle.typed_weak()))
+        .map(|script| &script.ast);
+
+    for ast in asts {
+        for trigger in &triggers {
+            let (name, args) = trigger.hook();
+            let defined =
+                ast.iter_functions().any(|f| f.name == name && f.params.len() == args.len());
+            if !defined {
+                continue;
+            }
+
+            // The actions asked before an error are still applied.
+            engine.state.lock().unwrap().start_hook();
+            let result = engine.engine.call_fn::<Dynamic>(&mut Scope::new(), ast, name, args);
+            if let Err(e) = result {
+                warn!("The {} hook of a script failed: {}", name, e);
+            }
+        }
+    }
+
+    let actions = std::mem::take(&mut engine.state.lock().unwrap().actions);
+    for action in actions {
+        match action {
+            Action::SpawnAsteroids(count) => {
+                let planet_translation = match planet.get_single() {
+                    Ok(planet_transform) => planet_transform.translation,
+                    Err(_) => continue,
+                };
+                let tuning = match tunings.get(&tuning.0) {
+                    Some(tuning) => tuning,
+                    None => continue,
+                };
+
+                for _ in 0..count {
+                    if let Some(kind) = tuning.choose_asteroid_kind(&mut *rng) {
+                        spawn_asteroid(
+                            &mut commands,
+                            &config.texture,
+                            &mut rng,
+                            &rules,
+                            kind,
+                            planet_translation,
+                        );
+                    }
+                }
+            }
+            Action::GrantDice(number) => dice_owned.send(DiceOwnedEvent(number, None)),
+            Action::Banner(message) => toasts.send(ToastEvent::info(message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the hook of this script and return the actions it asked for.
+    fn run_hook(engine: &ScriptEngine, script: &str) -> (Result<(), String>, Vec<Action>) {
+        let ast = engine.engine.compile(script).unwrap();
+        engine.state.lock().unwrap().start_hook();
+        let result = engine
+            .engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &ast, "on_planet_hit", ())
+            .map(drop)
+            .map_err(|e| e.to_string());
+        let actions = std::mem::take(&mut engine.state.lock().unwrap().actions);
+        (result, actions)
+    }
+
+    fn spawned(actions: &[Action]) -> Vec<u32> {
+        actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::SpawnAsteroids(count) => Some(*count),
+                _otherwise => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_hook_spawns_a_bounded_number_of_asteroids() {
+        let engine = ScriptEngine::new();
+        let script = "fn on_planet_hit() { spawn_asteroids(15); spawn_asteroids(15); }";
+        let (result, actions) = run_hook(&engine, script);
+        assert!(result.is_ok());
+        assert_eq!(spawned(&actions), [15, MAX_SCRIPTED_ASTEROIDS - 15]);
+
+        // The bound is per hook run.
+        let (_, actions) = run_hook(&engine, script);
+        assert_eq!(spawned(&actions), [15, MAX_SCRIPTED_ASTEROIDS - 15]);
+
+        let (_, actions) = run_hook(&engine, "fn on_planet_hit() { spawn_asteroids(-3); }");
+        assert_eq!(spawned(&actions), [0]);
+    }
+
+    #[test]
+    fn a_hook_asking_for_too_many_actions_is_stopped() {
+        let engine = ScriptEngine::new();
+        let script = r#"fn on_planet_hit() { for i in 0..100 { banner("hello"); } }"#;
+        let (result, actions) = run_hook(&engine, script);
+        assert!(result.is_err());
+        // The actions asked before the error are kept.
+        assert_eq!(actions.len(), MAX_HOOK_ACTIONS);
+    }
+
+    #[test]
+    fn a_hook_running_forever_is_stopped() {
+        let engine = ScriptEngine::new();
+        let (result, actions) = run_hook(&engine, "fn on_planet_hit() { loop {} }");
+        assert!(result.is_err());
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn only_the_dice_numbers_can_be_granted() {
+        let engine = ScriptEngine::new();
+        let (result, actions) = run_hook(&engine, "fn on_planet_hit() { grant_dice(6); }");
+        assert!(result.is_ok());
+        assert!(matches!(actions[..], [Action::GrantDice(DiceNumber::Six)]));
+
+        for number in [0, 7] {
+            let script = format!("fn on_planet_hit() {{ grant_dice({}); }}", number);
+            let (result, actions) = run_hook(&engine, &script);
+            assert!(result.is_err());
+            assert!(actions.is_empty());
+        }
+    }
+
+    #[test]
+    fn the_scripts_cannot_load_files() {
+        let engine = ScriptEngine::new();
+        let script = r#"import "secrets" as secrets; fn on_planet_hit() {}"#;
+        assert!(engine.engine.compile(script).is_err());
+    }
+}
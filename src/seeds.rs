@@ -0,0 +1,259 @@
+//! The seed of every run, shown as a short code on the HUD and the game over
+//! screen, e.g. `K7QM-2XWD`, and typed on the main menu to play it again.
+//!
+//! The same code always spawns the same asteroid sequence, it starts a standard
+//! game without mutators so that everybody plays it by the same rules.
+
+use bevy::prelude::*;
+use rand::{thread_rng, Rng};
+
+use crate::accessibility::AccessibleLabel;
+use crate::theme::Palette;
+use crate::waves::WaveSchedule;
+use crate::{AsteroidRng, FontAssets, GameMode, GameRng, GameState, RunSetup};
+
+/// The digits of the codes, the ones easily mistaken for another (`0`, `O`, `1`, `I`) are left out.
+const CODE_ALPHABET: &[u8; 32] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+const CODE_LENGTH: usize = 8;
+/// A code digit holds five bits, the seeds are kept small enough to fit in a code.
+const SEED_MASK: u64 = (1 << (5 * CODE_LENGTH)) - 1;
+
+pub struct SeedsPlugin;
+
+impl Plugin for SeedsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunSeed::default())
+            .insert_resource(CodeField::default())
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(setup_code_field))
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu)
+                    .with_system(type_seed_code)
+                    .with_system(draw_code_field.after(type_seed_code))
+                    .with_system(press_play_from_code)
+                    .with_system(highlight_play_from_code),
+            )
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(seed_run)
+                    .with_system(setup_seed_indicator.after(seed_run)),
+            );
+    }
+}
+
+/// The seed the random number generator of the current run started from.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    pub fn random() -> RunSeed {
+        RunSeed(thread_rng().gen::<u64>() & SEED_MASK)
+    }
+
+    /// The code of this seed, two groups of four digits.
+    pub fn code(self) -> String {
+        let mut code = String::with_capacity(CODE_LENGTH + 1);
+        for i in (0..CODE_LENGTH).rev() {
+            let digit = (self.0 >> (5 * i)) & 0b11111;
+            code.push(CODE_ALPHABET[digit as usize] as char);
+            if i == CODE_LENGTH / 2 {
+                code.push('-');
+            }
+        }
+        code
+    }
+
+    /// Read a code typed by the player, ignoring the case, the spaces and the dash.
+    fn from_code(code: &str) -> Option<RunSeed> {
+        let digits: Vec<_> = code.chars().filter(|c| !c.is_whitespace() && *c != '-').collect();
+        if digits.len() != CODE_LENGTH {
+            return None;
+        }
+
+        digits.into_iter().try_fold(RunSeed(0), |RunSeed(seed), c| {
+            let digit = CODE_ALPHABET.iter().position(|d| *d as char == c.to_ascii_uppercase())?;
+            Some(RunSeed(seed << 5 | digit as u64))
+        })
+    }
+}
+
+/// The code typed on the main menu, without its dash.
+#[derive(Debug, Default)]
+struct CodeField(String);
+
+#[derive(Component, Debug)]
+struct CodeFieldText;
+
+/// Starts a standard game from the code of the field.
+#[derive(Component, Debug)]
+struct PlayFromCode;
+
+/// Restart the random number generators from the seed of the run setup or a new one.
+pub fn seed_run(
+    run_setup: Res<RunSetup>,
+    mut seed: ResMut<RunSeed>,
+    mut rng: ResMut<GameRng>,
+    mut asteroid_rng: ResMut<AsteroidRng>,
+) {
+    *seed = match run_setup.seed {
+        Some(seed) => RunSeed(seed & SEED_MASK),
+        None => RunSeed::random(),
+    };
+    *rng = GameRng::from_seed(seed.0);
+    *asteroid_rng = AsteroidRng::from_seed(seed.0);
+}
+
+fn setup_seed_indicator(mut commands: Commands, font_assets: Res<FontAssets>, seed: Res<RunSeed>) {
+    commands.spawn_bundle(
+        TextBundle::from_section(
+            format!("Seed {}", seed.code()),
+            TextStyle { font: font_assets.fira_sans.clone(), font_size: 16.0, color: Color::GRAY },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect { right: Val::Px(20.0), bottom: Val::Px(4.0), ..default() },
+            ..default()
+        }),
+    );
+}
+
+fn setup_code_field(mut commands: Commands, font_assets: Res<FontAssets>) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect { left: Val::Px(20.0), top: Val::Px(20.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                .insert(CodeFieldText);
+
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(200.0), Val::Px(36.0)),
+                        margin: UiRect::all(Val::Px(4.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    color: Color::NONE.into(),
+                    ..default()
+                })
+                .insert(PlayFromCode)
+                .insert(AccessibleLabel::new("Play from the typed code"))
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle::from_section("Play from code", text_style));
+                });
+        });
+}
+
+/// Type the code with the keyboard, the characters that can't be in a code are ignored.
+fn type_seed_code(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut field: ResMut<CodeField>,
+) {
+    if keys.just_pressed(KeyCode::Back) {
+        field.0.pop();
+    }
+
+    for ReceivedCharacter { char, .. } in characters.iter() {
+        let c = char.to_ascii_uppercase();
+        if field.0.len() < CODE_LENGTH && c.is_ascii() && CODE_ALPHABET.contains(&(c as u8)) {
+            field.0.push(c);
+        }
+    }
+}
+
+fn draw_code_field(
+    field: Res<CodeField>,
+    spawned: Query<(), Added<CodeFieldText>>,
+    mut texts: Query<&mut Text, With<CodeFieldText>>,
+) {
+    if !field.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    let mut digits = field.0.chars().chain(std::iter::repeat('_'));
+    let first: String = digits.by_ref().take(CODE_LENGTH / 2).collect();
+    let second: String = digits.take(CODE_LENGTH / 2).collect();
+    for mut text in &mut texts {
+        text.sections[0].value = format!("Code: {}-{}", first, second);
+    }
+}
+
+fn press_play_from_code(
+    buttons: Query<&Interaction, (Changed<Interaction>, With<PlayFromCode>)>,
+    field: Res<CodeField>,
+    mut game_mode: ResMut<GameMode>,
+    mut run_setup: ResMut<RunSetup>,
+    mut schedule: ResMut<WaveSchedule>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !buttons.iter().any(|interaction| *interaction == Interaction::Clicked) {
+        return;
+    }
+
+    if let Some(RunSeed(seed)) = RunSeed::from_code(&field.0) {
+        *game_mode = GameMode::Standard;
+        *run_setup = RunSetup { seed: Some(seed), ..default() };
+        *schedule = WaveSchedule::default();
+        let _ = state.set(GameState::Playing);
+    }
+}
+
+fn highlight_play_from_code(
+    palette: Res<Palette>,
+    field: Res<CodeField>,
+    mut buttons: Query<(&Interaction, &mut UiColor), With<PlayFromCode>>,
+) {
+    let valid = RunSeed::from_code(&field.0).is_some();
+    for (interaction, mut color) in &mut buttons {
+        *color = palette.button_if(valid, *interaction).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn the_code_of_a_seed_gives_back_the_seed() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..1000 {
+            let seed = rng.gen::<u64>() & SEED_MASK;
+            let code = RunSeed(seed).code();
+            assert_eq!(code.len(), CODE_LENGTH + 1);
+            assert_eq!(RunSeed::from_code(&code).map(|RunSeed(seed)| seed), Some(seed));
+        }
+    }
+
+    #[test]
+    fn the_typed_codes_ignore_the_case_the_spaces_and_the_dash() {
+        let RunSeed(seed) = RunSeed::from_code("ABCD-2345").unwrap();
+        for typed in ["abcd-2345", "ABCD2345", " abCD 2345 ", "AB-CD-23-45"] {
+            assert_eq!(RunSeed::from_code(typed).map(|RunSeed(seed)| seed), Some(seed));
+        }
+    }
+
+    #[test]
+    fn the_invalid_codes_are_rejected() {
+        // Too short, too long, and the digits left out because they look like letters.
+        for code in ["", "ABCD-234", "ABCD-23456", "ABCD-0000", "ABCD-1111", "OOOO-IIII"] {
+            assert!(RunSeed::from_code(code).is_none(), "{:?} is accepted", code);
+        }
+    }
+}
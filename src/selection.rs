@@ -0,0 +1,459 @@
+//! The ship selected by clicking on it and the panel of the actions on it.
+//!
+//! Clicking an asteroid while a ship is selected orders the ship to attack it,
+//! the order is dropped like any target once the asteroid leaves the leash.
+
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy::sprite::{MaterialMesh2dBundle, Mesh2dHandle};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::behavior::{BehaviorProfile, BehaviorProfileHandles};
+use crate::controls::Actions;
+use crate::dice::{DiceBag, DiceNumber};
+use crate::event_log::EventLog;
+use crate::hull::{best_repair_dice, ShipHull};
+use crate::layers::RenderLayer;
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimId, SimInputEvent};
+use crate::merge::{merge_partner, MergeShipsEvent, ShipTier};
+use crate::shapes;
+use crate::theme::{Palette, ThemedPanel};
+use crate::{
+    Asteroid, DiceInvestment, DraggedDice, FontAssets, GameState, Ship, ShipCost, ShipPower,
+    ShipTarget, SpaceCamera,
+};
+
+const SHIP_SELECT_RADIUS: f32 = 20.0;
+const ASTEROID_SELECT_RADIUS: f32 = 30.0;
+const SHIP_COLOR: Color = Color::PURPLE;
+const SELECTED_SHIP_COLOR: Color = Color::PINK;
+const RANGE_RING_COLOR: Color = Color::rgba(1.0, 0.08, 0.58, 0.3);
+const RANGE_RING_WIDTH: f32 = 2.0;
+/// The part of the dice spent on a ship given back when it is scuttled.
+const SCUTTLE_REFUND: f32 = 0.5;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SelectedShip::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_selected_ship)
+                    .with_system(setup_ship_panel)
+                    .with_system(setup_range_ring),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(select_ships_on_click)
+                    .with_system(press_ship_actions)
+                    .with_system(apply_attack_orders)
+                    .with_system(apply_ship_actions)
+                    .with_system(
+                        forget_despawned_ship
+                            .after(select_ships_on_click)
+                            .after(apply_ship_actions),
+                    )
+                    .with_system(color_selected_ship.after(forget_despawned_ship))
+                    .with_system(draw_ship_panel.after(forget_despawned_ship))
+                    .with_system(draw_range_ring.after(forget_despawned_ship))
+                    .with_system(highlight_ship_actions.after(press_ship_actions)),
+            );
+    }
+}
+
+/// The ship the actions of the panel are done on.
+#[derive(Debug, Default)]
+pub struct SelectedShip(pub Option<Entity>);
+
+/// An action on the selected ship, attached to its button.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShipAction {
+    /// Spend a dice of the bag to restore as much hull as its pips.
+    Repair,
+    /// Merge the ship with the closest ship of the same power and tier.
+    Merge,
+    Scuttle,
+}
+
+impl ShipAction {
+    const ALL: [ShipAction; 3] = [ShipAction::Repair, ShipAction::Merge, ShipAction::Scuttle];
+
+    fn label(self) -> &'static str {
+        match self {
+            ShipAction::Repair => "Repair",
+            ShipAction::Merge => "Merge",
+            ShipAction::Scuttle => "Scuttle",
+        }
+    }
+
+    fn is_available(self, hull: &ShipHull, dice_bag: &DiceBag, partner: Option<Entity>) -> bool {
+        match self {
+            ShipAction::Repair => {
+                hull.is_damaged() && hull.is_out_of_combat() && !dice_bag.is_empty()
+            }
+            ShipAction::Merge => partner.is_some(),
+            ShipAction::Scuttle => true,
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct ShipPanel;
+
+#[derive(Component, Debug)]
+struct ShipPanelText;
+
+/// The circle around the selected ship showing the range of its targeting, of the given radius.
+#[derive(Component, Debug)]
+struct RangeRing(f32);
+
+/// The dice given back when scuttling a ship, the smallest ones of what was spent on it.
+fn scuttle_refund(cost: &ShipCost, investment: &DiceInvestment) -> Vec<DiceNumber> {
+    let mut dice: Vec<_> = cost.0.iter().chain(&investment.dice).copied().collect();
+    dice.sort_unstable();
+    dice.truncate((dice.len() as f32 * SCUTTLE_REFUND).floor() as usize);
+    dice
+}
+
+fn reset_selected_ship(mut selected: ResMut<SelectedShip>) {
+    *selected = SelectedShip::default();
+}
+
+fn setup_ship_panel(mut commands: Commands, palette: Res<Palette>, font_assets: Res<FontAssets>) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 16.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(200.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), bottom: Val::Px(110.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(6.0)),
+                ..default()
+            },
+            color: palette.panel.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(ShipPanel)
+        .insert(ThemedPanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style.clone()))
+                .insert(ShipPanelText);
+
+            for action in ShipAction::ALL {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(180.0), Val::Px(28.0)),
+                            margin: UiRect::all(Val::Px(2.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button.into(),
+                        ..default()
+                    })
+                    .insert(action)
+                    .insert(AccessibleLabel::new(format!("{} the selected ship", action.label())))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            action.label(),
+                            text_style.clone(),
+                        ));
+                    });
+            }
+        });
+}
+
+fn setup_range_ring(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands
+        .spawn_bundle(MaterialMesh2dBundle {
+            mesh: meshes.add(Mesh::from(shape::Quad::default())).into(),
+            material: materials.add(ColorMaterial::from(RANGE_RING_COLOR)),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(RangeRing(0.0));
+}
+
+/// Clicking or tapping a ship selects it, clicking it again or pressing Escape
+/// deselects it, clicking an asteroid orders the selected ship to attack it.
+fn select_ships_on_click(
+    actions: Actions,
+    camera: Query<(&Camera, &GlobalTransform), With<SpaceCamera>>,
+    keys: Res<Input<KeyCode>>,
+    dragged: Res<DraggedDice>,
+    ships: Query<(Entity, &GlobalTransform, &SimId), With<Ship>>,
+    asteroids: Query<(&GlobalTransform, &SimId), With<Asteroid>>,
+    mut selected: ResMut<SelectedShip>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    if keys.just_pressed(KeyCode::Escape) {
+        selected.0 = None;
+    }
+
+    if dragged.0.is_some() {
+        return;
+    }
+
+    let (camera, camera_transform) = camera.single();
+    for world_pos in actions.pointer_presses(camera, camera_transform) {
+        let clicked = ships.iter().find(|(_, transform, _)| {
+            transform.translation().truncate().distance(world_pos) <= SHIP_SELECT_RADIUS
+        });
+
+        if let Some((entity, ..)) = clicked {
+            selected.0 = if selected.0 == Some(entity) { None } else { Some(entity) };
+            continue;
+        }
+
+        let ship = match selected.0.and_then(|entity| ships.get(entity).ok()) {
+            Some((.., ship)) => *ship,
+            None => continue,
+        };
+        let target = asteroids
+            .iter()
+            .map(|(transform, id)| (transform.translation().truncate().distance(world_pos), id))
+            .filter(|(distance, _)| *distance <= ASTEROID_SELECT_RADIUS)
+            .min_by_key(|(distance, _)| OrderedFloat(*distance));
+        if let Some((_, target)) = target {
+            let target = *target;
+            player_inputs.send(PlayerInputEvent(PlayerInput::Attack { ship, target }));
+        }
+    }
+}
+
+fn apply_attack_orders(
+    mut sim_inputs: EventReader<SimInputEvent>,
+    asteroids: Query<(Entity, &SimId), With<Asteroid>>,
+    mut ships: Query<(&SimId, &mut ShipTarget), With<Ship>>,
+) {
+    let _span = info_span!("apply_attack_orders").entered();
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let (ship, target) = match *input {
+            PlayerInput::Attack { ship, target } => (ship, target),
+            _otherwise => continue,
+        };
+
+        let asteroid = asteroids.iter().find(|(_, id)| **id == target);
+        let ship = ships.iter_mut().find(|(id, _)| **id == ship);
+        if let (Some((asteroid, _)), Some((_, mut ship_target))) = (asteroid, ship) {
+            ship_target.0 = Some(asteroid);
+        }
+    }
+}
+
+/// The actions clicked on the selected ship, when they are available.
+fn press_ship_actions(
+    buttons: Query<(&Interaction, &ShipAction), Changed<Interaction>>,
+    ships: Query<(&SimId, &ShipHull), With<Ship>>,
+    partners: Query<(Entity, &ShipPower, &ShipTier, &Transform), With<Ship>>,
+    selected: Res<SelectedShip>,
+    dice_bag: Res<DiceBag>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    let entity = match selected.0 {
+        Some(entity) => entity,
+        None => return,
+    };
+
+    for (interaction, action) in &buttons {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        let partner = merge_partner(entity, &partners);
+        if let Ok((ship, hull)) = ships.get(entity) {
+            if action.is_available(hull, &dice_bag, partner) {
+                let ship = *ship;
+                player_inputs
+                    .send(PlayerInputEvent(PlayerInput::ShipAction { ship, action: *action }));
+            }
+        }
+    }
+}
+
+fn apply_ship_actions(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut ships: Query<(Entity, &SimId, &ShipCost, &DiceInvestment, &mut ShipHull), With<Ship>>,
+    partners: Query<(Entity, &ShipPower, &ShipTier, &Transform), With<Ship>>,
+    mut selected: ResMut<SelectedShip>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut merge_ships: EventWriter<MergeShipsEvent>,
+    mut log: ResMut<EventLog>,
+) {
+    // Both players can scuttle the same ship in the same step.
+    let mut scuttled = HashSet::new();
+    for SimInputEvent { input, .. } in sim_inputs.iter() {
+        let (ship, action) = match *input {
+            PlayerInput::ShipAction { ship, action } => (ship, action),
+            _otherwise => continue,
+        };
+
+        let found = ships
+            .iter()
+            .find(|(entity, id, ..)| **id == ship && !scuttled.contains(entity))
+            .map(|(entity, ..)| entity);
+        let entity = match found {
+            Some(entity) => entity,
+            None => continue,
+        };
+
+        let partner = merge_partner(entity, &partners);
+        let (_, _, cost, investment, mut hull) = match ships.get_mut(entity) {
+            Ok(ship) if action.is_available(&ship.4, &dice_bag, partner) => ship,
+            _ => continue,
+        };
+
+        match action {
+            ShipAction::Repair => {
+                let dice = best_repair_dice(&hull, &dice_bag).and_then(|i| dice_bag.remove(i));
+                if let Some(number) = dice {
+                    hull.repair(number.pips());
+                    log.push(&time, format!("Ship repaired with a {}", number.pips()));
+                }
+            }
+            ShipAction::Merge => {
+                if let Some(partner) = partner {
+                    merge_ships.send(MergeShipsEvent([entity, partner]));
+                }
+            }
+            ShipAction::Scuttle => {
+                let refund = scuttle_refund(cost, investment);
+                log.push(&time, format!("Ship scuttled - {} dice refunded", refund.len()));
+                // Refunded dice go straight back into the bag, they are not collected.
+                dice_bag.extend(refund);
+                commands.entity(entity).despawn_recursive();
+                scuttled.insert(entity);
+                if selected.0 == Some(entity) {
+                    selected.0 = None;
+                }
+            }
+        }
+    }
+}
+
+/// The selected ship can be destroyed or recalled by other systems.
+fn forget_despawned_ship(mut selected: ResMut<SelectedShip>, ships: Query<(), With<Ship>>) {
+    if selected.0.is_some_and(|entity| ships.get(entity).is_err()) {
+        selected.0 = None;
+    }
+}
+
+fn color_selected_ship(
+    selected: Res<SelectedShip>,
+    ships: Query<(Entity, &Handle<ColorMaterial>), With<Ship>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    if !selected.is_changed() {
+        return;
+    }
+
+    for (entity, handle) in &ships {
+        if let Some(material) = materials.get_mut(handle) {
+            let selected = selected.0 == Some(entity);
+            material.color = if selected { SELECTED_SHIP_COLOR } else { SHIP_COLOR };
+        }
+    }
+}
+
+fn draw_ship_panel(
+    selected: Res<SelectedShip>,
+    ships: Query<(&ShipPower, &ShipTier, &ShipCost, &DiceInvestment, &ShipHull), With<Ship>>,
+    mut panel: Query<&mut Visibility, With<ShipPanel>>,
+    mut text: Query<&mut Text, With<ShipPanelText>>,
+) {
+    let ship = selected.0.and_then(|entity| ships.get(entity).ok());
+
+    for mut visibility in &mut panel {
+        if visibility.is_visible != ship.is_some() {
+            visibility.is_visible = ship.is_some();
+        }
+    }
+
+    if let Some((power, tier, cost, investment, hull)) = ship {
+        let value = format!(
+            "{} - tier {}\nHull {}/{}{}\n{} pips invested\nScuttling refunds {} dice",
+            power.label(),
+            tier.0,
+            hull.current,
+            hull.max,
+            if hull.is_out_of_combat() { "" } else { " (in combat)" },
+            investment.pips,
+            scuttle_refund(cost, investment).len()
+        );
+        for mut text in &mut text {
+            if text.sections[0].value != value {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+}
+
+fn highlight_ship_actions(
+    palette: Res<Palette>,
+    selected: Res<SelectedShip>,
+    dice_bag: Res<DiceBag>,
+    ships: Query<&ShipHull, With<Ship>>,
+    partners: Query<(Entity, &ShipPower, &ShipTier, &Transform), With<Ship>>,
+    mut buttons: Query<(&Interaction, &ShipAction, &mut UiColor)>,
+) {
+    let (entity, hull) = match selected.0.and_then(|e| ships.get(e).ok().map(|hull| (e, hull))) {
+        Some(ship) => ship,
+        None => return,
+    };
+
+    let partner = merge_partner(entity, &partners);
+    for (interaction, action, mut color) in &mut buttons {
+        let available = action.is_available(hull, &dice_bag, partner);
+        *color = palette.button_if(available, *interaction).into();
+    }
+}
+
+/// The ring follows the selected ship and is rebuilt when the range changes, e.g. a
+/// ship of another power is selected or the behavior profiles are reloaded.
+fn draw_range_ring(
+    selected: Res<SelectedShip>,
+    profiles: Res<Assets<BehaviorProfile>>,
+    handles: Res<BehaviorProfileHandles>,
+    ships: Query<(&ShipPower, &Transform), With<Ship>>,
+    mut rings: Query<
+        (&mut RangeRing, &Mesh2dHandle, &mut Transform, &mut Visibility),
+        Without<Ship>,
+    >,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let ship = selected.0.and_then(|entity| ships.get(entity).ok());
+
+    for (mut ring, mesh, mut transform, mut visibility) in &mut rings {
+        if visibility.is_visible != ship.is_some() {
+            visibility.is_visible = ship.is_some();
+        }
+
+        if let Some((power, ship_transform)) = ship {
+            let range = handles.trigger_range(&profiles, *power);
+            if let Some(mesh) = meshes.get_mut(&mesh.0).filter(|_| ring.0 != range) {
+                ring.0 = range;
+                *mesh = shapes::ring(range, RANGE_RING_WIDTH);
+            }
+            // The ring is drawn behind the ships.
+            transform.translation =
+                RenderLayer::Background.at(ship_transform.translation.truncate());
+        }
+    }
+}
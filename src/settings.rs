@@ -0,0 +1,466 @@
+//! The settings of the game, changed from the settings screen of the main menu
+//! and saved on disk between the game sessions.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::controls::InputMap;
+use crate::menu::{spawn_menu_button, MenuButton};
+use crate::quality::Quality;
+use crate::save::{load_ron_file, save_ron_file};
+use crate::sound::{AudioChannel, Volumes};
+use crate::theme::{Palette, Theme};
+use crate::{FontAssets, GameState};
+
+const SETTINGS_PATH: &str = "settings.ron";
+const SLIDER_WIDTH: f32 = 200.0;
+const MIN_UI_SCALE: f32 = 0.75;
+const MAX_UI_SCALE: f32 = 2.0;
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load_ron_file::<GameSettings>(SETTINGS_PATH))
+            .add_system(save_settings_on_change)
+            .add_system_set(SystemSet::on_enter(GameState::Settings).with_system(setup_settings))
+            .add_system_set(
+                SystemSet::on_update(GameState::Settings)
+                    .with_system(drag_setting_sliders)
+                    .with_system(draw_setting_sliders.after(drag_setting_sliders))
+                    .with_system(press_setting_toggles)
+                    .with_system(draw_setting_toggles.after(press_setting_toggles))
+                    .with_system(color_setting_widgets),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameSettings {
+    #[serde(default)]
+    pub volumes: Volumes,
+    /// Whether the gamepads rumble on the impacts, the pickups and the powers.
+    #[serde(default = "default_rumble")]
+    pub rumble: bool,
+    /// Whether the UI elements and the key events of the run are read out.
+    #[serde(default)]
+    pub announcements: bool,
+    /// The scale of the text, the icons and the menus, between 75% and 200%.
+    #[serde(default = "default_ui_scale")]
+    pub ui_scale: f32,
+    /// The colors of the panels and the buttons.
+    #[serde(default)]
+    pub theme: Theme,
+    /// The address of the server relaying the co-op runs.
+    #[serde(default = "default_relay_address")]
+    pub relay_address: String,
+    /// Whether the endless runs are compared with the best one at every wave.
+    #[serde(default = "default_ghost")]
+    pub ghost: bool,
+    /// The keys and the buttons of the actions of a run.
+    #[serde(default)]
+    pub controls: InputMap,
+    /// How detailed the shapes and how many the particles.
+    #[serde(default)]
+    pub quality: Quality,
+    /// Whether a corner of the screen frames the asteroid that will hit the planet first.
+    #[serde(default)]
+    pub threat_view: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> GameSettings {
+        GameSettings {
+            volumes: Volumes::default(),
+            rumble: default_rumble(),
+            announcements: false,
+            ui_scale: default_ui_scale(),
+            theme: Theme::default(),
+            relay_address: default_relay_address(),
+            ghost: default_ghost(),
+            controls: InputMap::default(),
+            quality: Quality::default(),
+            threat_view: false,
+        }
+    }
+}
+
+fn default_rumble() -> bool {
+    true
+}
+
+fn default_relay_address() -> String {
+    "127.0.0.1:7878".to_string()
+}
+
+fn default_ghost() -> bool {
+    true
+}
+
+/// The touchscreens need larger buttons for the fingers.
+fn default_ui_scale() -> f32 {
+    if cfg!(any(target_os = "android", target_os = "ios")) {
+        1.5
+    } else {
+        1.0
+    }
+}
+
+/// A setting switched on and off, or to its next value, by clicking its button.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingToggle {
+    Rumble,
+    Announcements,
+    Theme,
+    Ghost,
+    Quality,
+    ThreatView,
+}
+
+impl SettingToggle {
+    const ALL: [SettingToggle; 6] = [
+        SettingToggle::Rumble,
+        SettingToggle::Announcements,
+        SettingToggle::Theme,
+        SettingToggle::Ghost,
+        SettingToggle::Quality,
+        SettingToggle::ThreatView,
+    ];
+
+    fn label(self, settings: &GameSettings) -> String {
+        match self {
+            SettingToggle::Rumble => format!("Rumble: {}", on_off(settings.rumble)),
+            SettingToggle::Announcements => {
+                format!("Announcements: {}", on_off(settings.announcements))
+            }
+            SettingToggle::Theme => format!("Theme: {}", settings.theme.label()),
+            SettingToggle::Ghost => format!("Best run ghost: {}", on_off(settings.ghost)),
+            SettingToggle::Quality => format!("Graphics: {}", settings.quality.label()),
+            SettingToggle::ThreatView => format!("Threat view: {}", on_off(settings.threat_view)),
+        }
+    }
+
+    fn spoken_name(self) -> &'static str {
+        match self {
+            SettingToggle::Rumble => "Toggle the gamepad rumble",
+            SettingToggle::Announcements => "Toggle the announcements",
+            SettingToggle::Theme => "Switch to the next color theme",
+            SettingToggle::Ghost => "Toggle the ghost of the best endless run",
+            SettingToggle::Quality => "Switch to the next graphics quality",
+            SettingToggle::ThreatView => "Toggle the view of the closest threat",
+        }
+    }
+
+    fn toggle(self, settings: &mut GameSettings) {
+        match self {
+            SettingToggle::Rumble => settings.rumble = !settings.rumble,
+            SettingToggle::Announcements => settings.announcements = !settings.announcements,
+            SettingToggle::Theme => settings.theme = settings.theme.next(),
+            SettingToggle::Ghost => settings.ghost = !settings.ghost,
+            SettingToggle::Quality => settings.quality = settings.quality.next(),
+            SettingToggle::ThreatView => settings.threat_view = !settings.threat_view,
+        }
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// A setting changed by dragging a slider, the fill child shows the value.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum SettingSlider {
+    Volume(AudioChannel),
+    UiScale,
+}
+
+impl SettingSlider {
+    fn label(self) -> &'static str {
+        match self {
+            SettingSlider::Volume(channel) => channel.label(),
+            SettingSlider::UiScale => "UI scale",
+        }
+    }
+
+    fn spoken_name(self) -> String {
+        match self {
+            SettingSlider::Volume(channel) => format!("{} volume", channel.label()),
+            SettingSlider::UiScale => "Scale of the interface".to_string(),
+        }
+    }
+
+    /// The position of the slider, between 0 and 1.
+    fn fraction(self, settings: &GameSettings) -> f32 {
+        match self {
+            SettingSlider::Volume(channel) => settings.volumes.get(channel),
+            SettingSlider::UiScale => {
+                (settings.ui_scale - MIN_UI_SCALE) / (MAX_UI_SCALE - MIN_UI_SCALE)
+            }
+        }
+    }
+
+    fn set_fraction(self, settings: &mut GameSettings, fraction: f32) {
+        match self {
+            SettingSlider::Volume(channel) => settings.volumes.set(channel, fraction),
+            SettingSlider::UiScale => {
+                // The scale snaps to 5% steps, the nodes are resized on every change.
+                let scale = MIN_UI_SCALE + fraction * (MAX_UI_SCALE - MIN_UI_SCALE);
+                settings.ui_scale = (scale * 20.0).round() / 20.0;
+            }
+        }
+    }
+
+    fn value(self, settings: &GameSettings) -> String {
+        match self {
+            SettingSlider::Volume(channel) => {
+                format!("{:.0}%", settings.volumes.get(channel) * 100.0)
+            }
+            SettingSlider::UiScale => format!("{:.0}%", settings.ui_scale * 100.0),
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct SettingSliderFill(SettingSlider);
+
+#[derive(Component, Debug)]
+struct SettingSliderValue(SettingSlider);
+
+fn save_settings_on_change(settings: Res<GameSettings>) {
+    if settings.is_changed() && !settings.is_added() {
+        save_ron_file(SETTINGS_PATH, &*settings);
+    }
+}
+
+fn press_setting_toggles(
+    toggles: Query<(&Interaction, &SettingToggle), Changed<Interaction>>,
+    mut settings: ResMut<GameSettings>,
+) {
+    for (interaction, toggle) in &toggles {
+        if *interaction == Interaction::Clicked {
+            toggle.toggle(&mut settings);
+        }
+    }
+}
+
+fn draw_setting_toggles(
+    settings: Res<GameSettings>,
+    spawned: Query<(), Added<SettingToggle>>,
+    toggles: Query<(&SettingToggle, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !settings.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for (toggle, children) in &toggles {
+        let value = toggle.label(&settings);
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+        }
+    }
+}
+
+fn setup_settings(mut commands: Commands, palette: Res<Palette>, font_assets: Res<FontAssets>) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 20.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(
+                TextBundle::from_section(
+                    "Settings",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 48.0,
+                        color: Color::WHITE,
+                    },
+                )
+                .with_style(Style { margin: UiRect::all(Val::Px(20.0)), ..default() }),
+            );
+
+            for channel in AudioChannel::ALL {
+                spawn_setting_slider(parent, &palette, &text_style, SettingSlider::Volume(channel));
+            }
+
+            parent.spawn_bundle(
+                TextBundle::from_section("Accessibility", text_style.clone())
+                    .with_style(Style { margin: UiRect::all(Val::Px(10.0)), ..default() }),
+            );
+
+            spawn_setting_slider(parent, &palette, &text_style, SettingSlider::UiScale);
+
+            for toggle in SettingToggle::ALL {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(240.0), Val::Px(36.0)),
+                            margin: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button.into(),
+                        ..default()
+                    })
+                    .insert(toggle)
+                    .insert(AccessibleLabel::new(toggle.spoken_name()))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section("", text_style.clone()));
+                    });
+            }
+
+            spawn_menu_button(parent, &font_assets, MenuButton::Controls);
+            spawn_menu_button(parent, &font_assets, MenuButton::Back);
+        });
+}
+
+fn spawn_setting_slider(
+    parent: &mut ChildBuilder,
+    palette: &Palette,
+    text_style: &TextStyle,
+    slider: SettingSlider,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                margin: UiRect::all(Val::Px(6.0)),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(
+                TextBundle::from_section(slider.label(), text_style.clone())
+                    .with_style(Style { size: Size::new(Val::Px(100.0), Val::Auto), ..default() }),
+            );
+
+            parent
+                .spawn_bundle(ButtonBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(SLIDER_WIDTH), Val::Px(20.0)),
+                        ..default()
+                    },
+                    color: palette.button.into(),
+                    ..default()
+                })
+                .insert(slider)
+                .insert(AccessibleLabel::new(slider.spoken_name()))
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+                                ..default()
+                            },
+                            color: palette.slider_fill.into(),
+                            focus_policy: bevy::ui::FocusPolicy::Pass,
+                            ..default()
+                        })
+                        .insert(SettingSliderFill(slider));
+                });
+
+            parent
+                .spawn_bundle(TextBundle::from_section("", text_style.clone()).with_style(Style {
+                    margin: UiRect { left: Val::Px(10.0), ..default() },
+                    ..default()
+                }))
+                .insert(SettingSliderValue(slider));
+        });
+}
+
+/// The setting follows the cursor while the slider is pressed.
+fn drag_setting_sliders(
+    windows: Res<Windows>,
+    sliders: Query<(&Interaction, &SettingSlider, &Node, &GlobalTransform)>,
+    mut settings: ResMut<GameSettings>,
+) {
+    let cursor = match windows.get_primary().and_then(|window| window.cursor_position()) {
+        Some(cursor) => cursor,
+        None => return,
+    };
+
+    for (interaction, slider, node, transform) in &sliders {
+        if *interaction == Interaction::Clicked {
+            let left = transform.translation().x - node.size.x / 2.0;
+            let fraction = ((cursor.x - left) / node.size.x).clamp(0.0, 1.0);
+            let mut changed = settings.clone();
+            slider.set_fraction(&mut changed, fraction);
+            if slider.fraction(&changed) != slider.fraction(&settings) {
+                *settings = changed;
+            }
+        }
+    }
+}
+
+fn draw_setting_sliders(
+    settings: Res<GameSettings>,
+    spawned: Query<(), Added<SettingSliderFill>>,
+    mut fills: Query<(&SettingSliderFill, &mut Style)>,
+    mut values: Query<(&SettingSliderValue, &mut Text)>,
+) {
+    if !settings.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for (SettingSliderFill(slider), mut style) in &mut fills {
+        let width = Val::Percent(slider.fraction(&settings) * 100.0);
+        if style.size.width != width {
+            style.size.width = width;
+        }
+    }
+
+    for (SettingSliderValue(slider), mut text) in &mut values {
+        let value = slider.value(&settings);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+/// The toggles and the sliders follow the theme, it can be switched from this screen.
+fn color_setting_widgets(
+    palette: Res<Palette>,
+    mut toggles: Query<
+        (&Interaction, ChangeTrackers<Interaction>, &mut UiColor),
+        With<SettingToggle>,
+    >,
+    mut sliders: Query<&mut UiColor, (With<SettingSlider>, Without<SettingToggle>)>,
+    mut fills: Query<
+        &mut UiColor,
+        (With<SettingSliderFill>, Without<SettingSlider>, Without<SettingToggle>),
+    >,
+) {
+    for (interaction, tracker, mut color) in &mut toggles {
+        if palette.is_changed() || tracker.is_changed() {
+            color.0 = palette.button(*interaction);
+        }
+    }
+
+    if palette.is_changed() {
+        sliders.for_each_mut(|mut color| color.0 = palette.button);
+        fills.for_each_mut(|mut color| color.0 = palette.slider_fill);
+    }
+}
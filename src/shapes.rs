@@ -0,0 +1,86 @@
+//! The 2D shapes of the game built as flat meshes, the circles get as many
+//! segments as needed for their edges to stay smooth when zooming in.
+//!
+//! The shapes drawn by hundreds are textures instead, the sprites sharing a
+//! texture are drawn in a single batch whatever their number.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy::render::mesh::Indices;
+use bevy::render::render_resource::{Extent3d, PrimitiveTopology, TextureDimension, TextureFormat};
+
+/// The maximum distance between a curve and the segments drawing it, in world units.
+const TOLERANCE: f32 = 0.05;
+const MIN_SEGMENTS: usize = 8;
+
+/// A filled convex polygon, the points are given counter-clockwise.
+pub fn polygon(points: &[Vec2]) -> Mesh {
+    let indices = (1..points.len().saturating_sub(1) as u32).flat_map(|i| [0, i, i + 1]).collect();
+    flat_mesh(points, indices)
+}
+
+pub fn circle(radius: f32) -> Mesh {
+    polygon(&circle_points(radius))
+}
+
+/// The outline of a circle, the width is drawn inside of the radius.
+pub fn ring(radius: f32, width: f32) -> Mesh {
+    let outer = circle_points(radius);
+    let inner_radius = (radius - width).max(0.0);
+    let points: Vec<_> =
+        outer.iter().flat_map(|point| [*point, *point * inner_radius / radius]).collect();
+
+    let len = points.len() as u32;
+    let indices = (0..len)
+        .step_by(2)
+        .flat_map(|i| {
+            let (outer, inner) = (i, i + 1);
+            let (next_outer, next_inner) = ((i + 2) % len, (i + 3) % len);
+            [outer, next_outer, inner, inner, next_outer, next_inner]
+        })
+        .collect();
+    flat_mesh(&points, indices)
+}
+
+/// The points of a circle, counter-clockwise from the right.
+fn circle_points(radius: f32) -> Vec<Vec2> {
+    // The number of segments for the sagitta of every segment to be under the tolerance.
+    let angle = 2.0 * (1.0 - TOLERANCE / radius).clamp(-1.0, 1.0).acos();
+    let segments = ((2.0 * PI / angle).ceil() as usize).max(MIN_SEGMENTS);
+    (0..segments)
+        .map(|i| {
+            let angle = i as f32 / segments as f32 * 2.0 * PI;
+            Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// A white disc with a smoothed edge, filling a square texture of this size,
+/// the sprites drawing it give it their color.
+pub fn disc(size: u32) -> Image {
+    let radius = size as f32 / 2.0;
+    let data = (0..size * size)
+        .flat_map(|i| {
+            let pixel = Vec2::new((i % size) as f32, (i / size) as f32) + 0.5;
+            let alpha = (radius - pixel.distance(Vec2::splat(radius))).clamp(0.0, 1.0);
+            [255, 255, 255, (alpha * 255.0) as u8]
+        })
+        .collect();
+
+    let extent = Extent3d { width: size, height: size, depth_or_array_layers: 1 };
+    Image::new(extent, TextureDimension::D2, data, TextureFormat::Rgba8UnormSrgb)
+}
+
+fn flat_mesh(points: &[Vec2], indices: Vec<u32>) -> Mesh {
+    let positions: Vec<_> = points.iter().map(|p| p.extend(0.0).to_array()).collect();
+    let normals = vec![[0.0, 0.0, 1.0]; points.len()];
+    let uvs = vec![[0.0, 0.0]; points.len()];
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
@@ -0,0 +1,319 @@
+//! The shipyard, a station built from the shop that orbits the planet.
+//!
+//! Once built, the ships bought in the shop are queued in the shipyard and
+//! built one after the other, during the waves too. The station is hit by the
+//! asteroids like the planet, the queued ships are lost with it.
+
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use bevy_rapier2d::prelude::*;
+use bevy_tweening::lens::TransformScaleLens;
+use bevy_tweening::{Animator, EaseFunction, Tween, TweeningType};
+
+use crate::dice::DiceNumber;
+use crate::event_log::EventLog;
+use crate::layers::RenderLayer;
+use crate::merge::ShipTier;
+use crate::players::PlayerId;
+use crate::speed::SimulationSpeed;
+use crate::theme::Palette;
+use crate::toasts::ToastEvent;
+use crate::{
+    spawn_ship, Asteroid, FontAssets, GameState, Planet, ShipCost, ShipPower, PLANET_RADIUS,
+};
+
+pub const SHIPYARD_MAX_HULL: u32 = 5;
+const SHIP_BUILD_DURATION: u64 = 10; // in second
+const SHIP_LAUNCH_DURATION: u64 = 800; // in millisecond
+/// The part of its size a ship starts from when launched.
+const SHIP_LAUNCH_START_SCALE: f32 = 0.1;
+const ORBIT_RADIUS: f32 = PLANET_RADIUS + 120.0;
+/// How fast the station goes around the planet, in radian per second.
+const ORBIT_SPEED: f32 = 0.15;
+const STATION_SIZE: f32 = 24.0;
+const STATION_COLOR: Color = Color::SILVER;
+
+pub struct ShipyardPlugin;
+
+impl Plugin for ShipyardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Shipyard::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_shipyard)
+                    .with_system(setup_build_queue_panel),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(build_shipyard_station)
+                    .with_system(orbit_shipyard_station)
+                    .with_system(build_queued_ships)
+                    .with_system(damage_shipyard_on_asteroid_collision)
+                    .with_system(draw_build_queue.after(build_queued_ships)),
+            );
+    }
+}
+
+/// A ship bought in the shop, waiting to be built.
+#[derive(Debug)]
+pub struct ShipOrder {
+    pub power: ShipPower,
+    pub owner: PlayerId,
+    pub cost: Vec<DiceNumber>,
+}
+
+/// The shipyard of the run, the station itself only exists while it is built.
+#[derive(Debug, Default)]
+pub struct Shipyard {
+    pub built: bool,
+    pub hull: u32,
+    /// The ships to build, the first one is being built.
+    pub orders: VecDeque<ShipOrder>,
+}
+
+impl Shipyard {
+    pub fn construct(&mut self) {
+        self.built = true;
+        self.hull = SHIPYARD_MAX_HULL;
+    }
+}
+
+/// The station in orbit around the planet, building the first ship of the queue.
+#[derive(Component, Debug)]
+struct ShipyardStation {
+    angle: f32,
+    build: Timer,
+}
+
+#[derive(Component, Debug)]
+struct BuildQueuePanel;
+
+#[derive(Component, Debug)]
+struct BuildQueueText;
+
+fn reset_shipyard(mut shipyard: ResMut<Shipyard>) {
+    *shipyard = Shipyard::default();
+}
+
+fn setup_build_queue_panel(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                // Next to the shop, built from there.
+                position: UiRect { right: Val::Px(250.0), top: Val::Px(180.0), ..default() },
+                padding: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            color: palette.panel.into(),
+            focus_policy: FocusPolicy::Pass,
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(BuildQueuePanel)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(BuildQueueText);
+        });
+}
+
+fn build_shipyard_station(
+    mut commands: Commands,
+    shipyard: Res<Shipyard>,
+    planet: Query<&Transform, With<Planet>>,
+    stations: Query<(), With<ShipyardStation>>,
+) {
+    if !shipyard.built || !stations.is_empty() {
+        return;
+    }
+
+    let center = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: STATION_COLOR,
+                custom_size: Some(Vec2::splat(STATION_SIZE)),
+                ..default()
+            },
+            transform: Transform::from_translation(
+                RenderLayer::Ships.at(center + Vec2::X * ORBIT_RADIUS),
+            ),
+            ..default()
+        })
+        .insert(ShipyardStation {
+            angle: 0.0,
+            build: Timer::new(Duration::from_secs(SHIP_BUILD_DURATION), false),
+        })
+        .insert(Collider::cuboid(STATION_SIZE / 2.0, STATION_SIZE / 2.0))
+        .insert(Sensor)
+        .insert(ActiveEvents::COLLISION_EVENTS);
+}
+
+fn orbit_shipyard_station(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    planet: Query<&Transform, (With<Planet>, Without<ShipyardStation>)>,
+    mut stations: Query<(&mut ShipyardStation, &mut Transform)>,
+) {
+    let center = match planet.get_single() {
+        Ok(transform) => transform.translation.xy(),
+        Err(_) => return,
+    };
+
+    for (mut station, mut transform) in &mut stations {
+        station.angle += ORBIT_SPEED * speed.delta_seconds(&time);
+        let offset = Vec2::new(station.angle.cos(), station.angle.sin()) * ORBIT_RADIUS;
+        transform.translation = RenderLayer::Ships.at(center + offset);
+        transform.rotation = Quat::from_rotation_z(station.angle);
+    }
+}
+
+/// The first ship of the queue is launched from the station once built,
+/// growing out of it.
+fn build_queued_ships(
+    mut commands: Commands,
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    mut log: ResMut<EventLog>,
+    mut shipyard: ResMut<Shipyard>,
+    mut stations: Query<(&mut ShipyardStation, &Transform)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    for (mut station, transform) in &mut stations {
+        if shipyard.orders.is_empty() || !station.build.tick(speed.delta(&time)).finished() {
+            continue;
+        }
+
+        let order = match shipyard.orders.pop_front() {
+            Some(order) => order,
+            None => continue,
+        };
+        station.build.reset();
+
+        let position = transform.translation.xy();
+        let ship = spawn_ship(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            order.power,
+            order.owner,
+            position,
+        );
+        // The physics doesn't handle the colliders scaled down to nothing.
+        let end = Vec3::splat(ShipTier::default().scale());
+        let start = end * SHIP_LAUNCH_START_SCALE;
+        commands
+            .entity(ship)
+            .insert(ShipCost(order.cost))
+            .insert(Transform::from_translation(RenderLayer::Ships.at(position)).with_scale(start))
+            .insert(Animator::new(Tween::new(
+                EaseFunction::BackOut,
+                TweeningType::Once,
+                Duration::from_millis(SHIP_LAUNCH_DURATION),
+                TransformScaleLens { start, end },
+            )));
+
+        log.push(&time, format!("{} launched from the shipyard", order.power.label()));
+    }
+}
+
+/// Every asteroid hitting the station breaks on it and damages its hull,
+/// the station and its queue are lost once the hull is gone.
+fn damage_shipyard_on_asteroid_collision(
+    mut commands: Commands,
+    mut shipyard: ResMut<Shipyard>,
+    stations: Query<Entity, With<ShipyardStation>>,
+    asteroids: Query<(), With<Asteroid>>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    let mut hits = HashSet::new();
+    for event in collision_events.iter() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let hit = if stations.contains(*e1) && asteroids.contains(*e2) {
+                Some((*e1, *e2))
+            } else if stations.contains(*e2) && asteroids.contains(*e1) {
+                Some((*e2, *e1))
+            } else {
+                None
+            };
+
+            if let Some((station, asteroid)) = hit.filter(|(_, asteroid)| hits.insert(*asteroid)) {
+                commands.entity(asteroid).despawn();
+                if shipyard.hull == 0 {
+                    continue;
+                }
+
+                shipyard.hull -= 1;
+                if shipyard.hull == 0 {
+                    commands.entity(station).despawn();
+                    let lost = shipyard.orders.len();
+                    *shipyard = Shipyard::default();
+                    toasts.send(ToastEvent::warning(format!(
+                        "The shipyard was destroyed, {} queued ships lost",
+                        lost
+                    )));
+                } else {
+                    toasts.send(ToastEvent::warning(format!(
+                        "Shipyard hit - hull {}/{}",
+                        shipyard.hull, SHIPYARD_MAX_HULL
+                    )));
+                }
+            }
+        }
+    }
+}
+
+fn draw_build_queue(
+    shipyard: Res<Shipyard>,
+    stations: Query<&ShipyardStation>,
+    mut panel: Query<&mut Visibility, With<BuildQueuePanel>>,
+    mut text: Query<&mut Text, With<BuildQueueText>>,
+) {
+    for mut visibility in &mut panel {
+        if visibility.is_visible != shipyard.built {
+            visibility.is_visible = shipyard.built;
+        }
+    }
+
+    let mut lines = vec![format!("Shipyard - hull {}/{}", shipyard.hull, SHIPYARD_MAX_HULL)];
+    let progress = stations.iter().next().map_or(0.0, |station| station.build.percent());
+    for (i, order) in shipyard.orders.iter().enumerate() {
+        if i == 0 {
+            lines.push(format!("{} - {:.0}%", order.power.label(), progress * 100.0));
+        } else {
+            lines.push(format!("{} - queued", order.power.label()));
+        }
+    }
+    if shipyard.orders.is_empty() {
+        lines.push("No ship in the queue".to_string());
+    }
+
+    let value = lines.join("\n");
+    for mut text in &mut text {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
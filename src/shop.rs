@@ -0,0 +1,469 @@
+//! The shop where the player buys upgrades and ships with their dice
+//! and structures with their scrap during the intermissions.
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::dice::{DiceBag, DiceNumber};
+use crate::fleet::{FleetCapacity, MAX_FLEET_CAPACITY};
+use crate::inventory::{Consumable, Inventory};
+use crate::lockstep::{PlayerInput, PlayerInputEvent, SimInputEvent};
+use crate::mutators::RunRules;
+use crate::players::PlayerStats;
+use crate::scrap::Scrap;
+use crate::shipyard::{ShipOrder, Shipyard};
+use crate::theme::{Palette, ThemedPanel};
+use crate::waves::Wave;
+use crate::{
+    fleet_position, spawn_ship, FontAssets, GameState, ImageAssets, PlanetShield, Ship, ShipCost,
+    ShipPower, PLANET_SHIELD_MAX_CHARGES,
+};
+
+pub struct ShopPlugin;
+
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(DiceInsurance::default())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_dice_insurance)
+                    .with_system(setup_shop),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(show_shop_during_intermission)
+                    .with_system(press_shop_items)
+                    .with_system(buy_shop_items)
+                    .with_system(grey_out_unavailable_items.after(buy_shop_items))
+                    .with_system(draw_shop_tooltip.after(buy_shop_items)),
+            );
+    }
+}
+
+/// An item sold in the shop, attached to its buy button.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShopItem {
+    DiceInsurance,
+    ShieldCharge,
+    Ship(ShipPower),
+    /// Grows the capacity of the fleet by one ship.
+    HangarBay,
+    /// The station building the ships bought afterwards.
+    Shipyard,
+    Consumable(Consumable),
+}
+
+impl ShopItem {
+    const ALL: [ShopItem; 10] = [
+        ShopItem::DiceInsurance,
+        ShopItem::ShieldCharge,
+        ShopItem::Ship(ShipPower::Bump),
+        ShopItem::Ship(ShipPower::Destroy),
+        ShopItem::Ship(ShipPower::Mine),
+        ShopItem::HangarBay,
+        ShopItem::Shipyard,
+        ShopItem::Consumable(Consumable::Mine),
+        ShopItem::Consumable(Consumable::GravityWell),
+        ShopItem::Consumable(Consumable::Emp),
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ShopItem::DiceInsurance => "Dice Insurance",
+            ShopItem::ShieldCharge => "Shield Charge",
+            ShopItem::Ship(ShipPower::Bump) => "Bump Ship",
+            ShopItem::Ship(ShipPower::Destroy) => "Destroy Ship",
+            ShopItem::Ship(ShipPower::Mine) => "Miner Ship",
+            ShopItem::HangarBay => "Hangar Bay",
+            ShopItem::Shipyard => "Shipyard",
+            ShopItem::Consumable(consumable) => consumable.label(),
+        }
+    }
+
+    fn cost(self) -> ShopCost {
+        match self {
+            ShopItem::DiceInsurance => ShopCost::Dice(&[DiceNumber::Four, DiceNumber::Four]),
+            ShopItem::ShieldCharge => ShopCost::Scrap(3),
+            ShopItem::Ship(ShipPower::Bump) => {
+                ShopCost::Dice(&[DiceNumber::Three, DiceNumber::Three, DiceNumber::Three])
+            }
+            ShopItem::Ship(ShipPower::Destroy) => {
+                ShopCost::Dice(&[DiceNumber::Five, DiceNumber::Five, DiceNumber::Five])
+            }
+            ShopItem::Ship(ShipPower::Mine) => {
+                ShopCost::Dice(&[DiceNumber::Four, DiceNumber::Four, DiceNumber::Four])
+            }
+            ShopItem::HangarBay => ShopCost::Scrap(8),
+            ShopItem::Shipyard => ShopCost::Scrap(10),
+            ShopItem::Consumable(Consumable::Mine) => ShopCost::Scrap(2),
+            ShopItem::Consumable(Consumable::GravityWell) => ShopCost::Scrap(4),
+            ShopItem::Consumable(Consumable::Emp) => ShopCost::Scrap(5),
+        }
+    }
+
+    /// Why the item can't be bought anymore, passives can only be bought once.
+    fn unavailable_reason(
+        self,
+        insurance: &DiceInsurance,
+        shield: &PlanetShield,
+        fleet: FleetStatus,
+    ) -> Option<&'static str> {
+        match self {
+            ShopItem::DiceInsurance if insurance.owned => Some("The insurance is already owned"),
+            ShopItem::ShieldCharge if shield.charges >= PLANET_SHIELD_MAX_CHARGES => {
+                Some("The shield is fully charged")
+            }
+            ShopItem::Ship(power) if fleet.banned_power == Some(power) => {
+                Some("This ship is banned by the rules of the run")
+            }
+            ShopItem::Ship(_) if fleet.ships >= fleet.capacity => {
+                Some("The fleet is full, buy a hangar bay to grow it")
+            }
+            ShopItem::HangarBay if fleet.capacity >= MAX_FLEET_CAPACITY => {
+                Some("The fleet can't grow any bigger")
+            }
+            ShopItem::Shipyard if fleet.shipyard => Some("The shipyard is already built"),
+            _ => None,
+        }
+    }
+
+    fn is_available(
+        self,
+        insurance: &DiceInsurance,
+        shield: &PlanetShield,
+        fleet: FleetStatus,
+    ) -> bool {
+        self.unavailable_reason(insurance, shield, fleet).is_none()
+    }
+}
+
+/// The size of the fleet, the ships can only be bought while it isn't full
+/// and their power isn't banned by the run.
+#[derive(Debug, Clone, Copy)]
+struct FleetStatus {
+    /// The ships in space and the ones queued in the shipyard.
+    ships: usize,
+    capacity: usize,
+    banned_power: Option<ShipPower>,
+    shipyard: bool,
+}
+
+impl FleetStatus {
+    fn new(
+        ships: &Query<(), With<Ship>>,
+        capacity: &FleetCapacity,
+        rules: &RunRules,
+        shipyard: &Shipyard,
+    ) -> FleetStatus {
+        FleetStatus {
+            ships: ships.iter().count() + shipyard.orders.len(),
+            capacity: capacity.0,
+            banned_power: rules.banned_power,
+            shipyard: shipyard.built,
+        }
+    }
+}
+
+/// What must be spent to buy an item.
+#[derive(Debug, Clone, Copy)]
+enum ShopCost {
+    /// The dice consumed from the bag.
+    Dice(&'static [DiceNumber]),
+    /// The amount of scrap spent.
+    Scrap(u32),
+}
+
+impl ShopCost {
+    fn is_affordable(self, dice_bag: &DiceBag, scrap: &Scrap) -> bool {
+        match self {
+            ShopCost::Dice(combo) => dice_bag.contains_combo(combo),
+            ShopCost::Scrap(amount) => scrap.0 >= amount,
+        }
+    }
+
+    fn try_pay(self, dice_bag: &mut DiceBag, scrap: &mut Scrap) -> bool {
+        match self {
+            ShopCost::Dice(combo) => dice_bag.try_consume_combo(combo),
+            ShopCost::Scrap(amount) => scrap.try_spend(amount),
+        }
+    }
+}
+
+/// A passive protecting the bag from the first dice loss of every wave.
+#[derive(Debug, Default)]
+pub struct DiceInsurance {
+    pub owned: bool,
+    /// The wave in which the insurance already blocked a loss.
+    pub used_in_wave: Option<u32>,
+}
+
+impl DiceInsurance {
+    pub fn is_armed(&self, wave: u32) -> bool {
+        self.owned && self.used_in_wave != Some(wave)
+    }
+}
+
+/// The root of the shop, only visible during intermissions.
+#[derive(Component, Debug)]
+struct ShopPanel;
+
+/// Explains why the hovered item can't be bought.
+#[derive(Component, Debug)]
+struct ShopTooltip;
+
+fn reset_dice_insurance(mut insurance: ResMut<DiceInsurance>) {
+    *insurance = DiceInsurance::default();
+}
+
+fn setup_shop(
+    mut commands: Commands,
+    palette: Res<Palette>,
+    font_assets: Res<FontAssets>,
+    image_assets: Res<ImageAssets>,
+) {
+    let text_style =
+        TextStyle { font: font_assets.fira_sans.clone(), font_size: 16.0, color: Color::WHITE };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(220.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { right: Val::Px(20.0), top: Val::Px(180.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            color: palette.panel.into(),
+            visibility: Visibility { is_visible: false },
+            ..default()
+        })
+        .insert(ShopPanel)
+        .insert(ThemedPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section("Shop", text_style.clone()));
+
+            for item in ShopItem::ALL {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(210.0), Val::Px(30.0)),
+                            margin: UiRect::all(Val::Px(2.0)),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            justify_content: JustifyContent::SpaceBetween,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        color: palette.button_disabled.into(),
+                        ..default()
+                    })
+                    .insert(item)
+                    .insert(AccessibleLabel::new(format!("Buy {}", item.label())))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle::from_section(
+                            item.label(),
+                            text_style.clone(),
+                        ));
+
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                color: Color::NONE.into(),
+                                focus_policy: FocusPolicy::Pass,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                let cost_icon = |image: Handle<Image>| ImageBundle {
+                                    style: Style {
+                                        size: Size::new(Val::Px(18.0), Val::Px(18.0)),
+                                        margin: UiRect::all(Val::Px(1.0)),
+                                        ..default()
+                                    },
+                                    image: image.into(),
+                                    focus_policy: FocusPolicy::Pass,
+                                    ..default()
+                                };
+
+                                match item.cost() {
+                                    ShopCost::Dice(combo) => {
+                                        for number in combo {
+                                            let image =
+                                                image_assets.handle_for_dice_number(*number);
+                                            parent.spawn_bundle(cost_icon(image.clone()));
+                                        }
+                                    }
+                                    ShopCost::Scrap(amount) => {
+                                        parent.spawn_bundle(TextBundle::from_section(
+                                            amount.to_string(),
+                                            text_style.clone(),
+                                        ));
+                                        parent.spawn_bundle(cost_icon(image_assets.scrap.clone()));
+                                    }
+                                }
+                            });
+                    });
+            }
+
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle { font_size: 14.0, color: Color::GRAY, ..text_style.clone() },
+                ))
+                .insert(ShopTooltip);
+        });
+}
+
+fn show_shop_during_intermission(
+    wave: Res<Wave>,
+    mut panel: Query<&mut Visibility, With<ShopPanel>>,
+) {
+    for mut visibility in &mut panel {
+        if visibility.is_visible != wave.is_intermission() {
+            visibility.is_visible = wave.is_intermission();
+        }
+    }
+}
+
+fn press_shop_items(
+    wave: Res<Wave>,
+    buttons: Query<(&Interaction, &ShopItem), Changed<Interaction>>,
+    mut player_inputs: EventWriter<PlayerInputEvent>,
+) {
+    for (interaction, item) in &buttons {
+        if *interaction == Interaction::Clicked && wave.is_intermission() {
+            player_inputs.send(PlayerInputEvent(PlayerInput::Buy(*item)));
+        }
+    }
+}
+
+fn buy_shop_items(
+    mut commands: Commands,
+    wave: Res<Wave>,
+    mut sim_inputs: EventReader<SimInputEvent>,
+    mut dice_bag: ResMut<DiceBag>,
+    mut scrap: ResMut<Scrap>,
+    mut insurance: ResMut<DiceInsurance>,
+    mut shield: ResMut<PlanetShield>,
+    mut inventory: ResMut<Inventory>,
+    mut capacity: ResMut<FleetCapacity>,
+    mut shipyard: ResMut<Shipyard>,
+    rules: Res<RunRules>,
+    mut stats: ResMut<PlayerStats>,
+    ships: Query<(), With<Ship>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    // The bought ships are only spawned at the end of the stage, they are counted here.
+    let mut fleet = FleetStatus::new(&ships, &capacity, &rules, &shipyard);
+
+    for SimInputEvent { player, input } in sim_inputs.iter() {
+        let item = match input {
+            PlayerInput::Buy(item) if wave.is_intermission() => item,
+            _otherwise => continue,
+        };
+
+        if item.is_available(&insurance, &shield, fleet)
+            && item.cost().try_pay(&mut dice_bag, &mut scrap)
+        {
+            match item {
+                ShopItem::DiceInsurance => insurance.owned = true,
+                ShopItem::ShieldCharge => shield.charges += 1,
+                // Once built, the shipyard builds the bought ships over time.
+                ShopItem::Ship(power) if shipyard.built => {
+                    let cost = match item.cost() {
+                        ShopCost::Dice(combo) => combo.to_vec(),
+                        ShopCost::Scrap(_) => Vec::new(),
+                    };
+                    let owner = *player;
+                    shipyard.orders.push_back(ShipOrder { power: *power, owner, cost });
+                    fleet.ships += 1;
+                }
+                ShopItem::Ship(power) => {
+                    let position = fleet_position(fleet.ships);
+                    let ship = spawn_ship(
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                        *power,
+                        *player,
+                        position,
+                    );
+                    if let ShopCost::Dice(combo) = item.cost() {
+                        commands.entity(ship).insert(ShipCost(combo.to_vec()));
+                    }
+                    fleet.ships += 1;
+                }
+                ShopItem::HangarBay => {
+                    capacity.0 += 1;
+                    fleet.capacity = capacity.0;
+                }
+                ShopItem::Shipyard => {
+                    shipyard.construct();
+                    fleet.shipyard = true;
+                }
+                ShopItem::Consumable(consumable) => inventory.add(*consumable, 1),
+            }
+            stats.record_purchase(*player);
+        }
+    }
+}
+
+fn grey_out_unavailable_items(
+    palette: Res<Palette>,
+    dice_bag: Res<DiceBag>,
+    scrap: Res<Scrap>,
+    insurance: Res<DiceInsurance>,
+    shield: Res<PlanetShield>,
+    capacity: Res<FleetCapacity>,
+    shipyard: Res<Shipyard>,
+    rules: Res<RunRules>,
+    ships: Query<(), With<Ship>>,
+    mut buttons: Query<(&Interaction, &ShopItem, &mut UiColor)>,
+) {
+    let fleet = FleetStatus::new(&ships, &capacity, &rules, &shipyard);
+    for (interaction, item, mut color) in &mut buttons {
+        let available = item.is_available(&insurance, &shield, fleet)
+            && item.cost().is_affordable(&dice_bag, &scrap);
+        *color = palette.button_if(available, *interaction).into();
+    }
+}
+
+fn draw_shop_tooltip(
+    insurance: Res<DiceInsurance>,
+    shield: Res<PlanetShield>,
+    capacity: Res<FleetCapacity>,
+    shipyard: Res<Shipyard>,
+    rules: Res<RunRules>,
+    ships: Query<(), With<Ship>>,
+    added_ships: Query<(), Added<Ship>>,
+    removed_ships: RemovedComponents<Ship>,
+    buttons: Query<(&Interaction, &ShopItem)>,
+    hovered: Query<(), (Changed<Interaction>, With<ShopItem>)>,
+    mut tooltip: Query<&mut Text, With<ShopTooltip>>,
+) {
+    let _span = info_span!("draw_shop_tooltip").entered();
+    let resources_changed = insurance.is_changed()
+        || shield.is_changed()
+        || capacity.is_changed()
+        || shipyard.is_changed()
+        || rules.is_changed();
+    let fleet_changed = !added_ships.is_empty() || removed_ships.iter().next().is_some();
+    if !resources_changed && !fleet_changed && hovered.is_empty() {
+        return;
+    }
+
+    let fleet = FleetStatus::new(&ships, &capacity, &rules, &shipyard);
+    let reason = buttons
+        .iter()
+        .filter(|(interaction, _)| **interaction != Interaction::None)
+        .find_map(|(_, item)| item.unavailable_reason(&insurance, &shield, fleet))
+        .unwrap_or("");
+
+    for mut text in &mut tooltip {
+        if text.sections[0].value != reason {
+            text.sections[0].value = reason.to_string();
+        }
+    }
+}
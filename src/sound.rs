@@ -0,0 +1,284 @@
+//! The sound hooks of the game, systems send events describing
+//! the sound to play and the audio backend decides how to play them.
+//!
+//! The sounds are read from the `sounds/` directory of the assets.
+
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use bevy::audio::{play_queued_audio_system, AudioOutput};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rodio::source::ChannelVolume;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::AccessibleLabel;
+use crate::controls::{Action, Actions};
+use crate::settings::GameSettings;
+use crate::{ImageAssets, Persistent, SpaceCamera};
+
+/// The distance from the camera at which the sounds are the quietest.
+const HEARING_DISTANCE: f32 = 900.0;
+const FARTHEST_SOUND_VOLUME: f32 = 0.2;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Muted(false))
+            .init_non_send_resource::<AudioOutput<PannedSound>>()
+            .add_asset::<PannedSound>()
+            .init_resource::<Audio<PannedSound>>()
+            .add_system_to_stage(CoreStage::PostUpdate, play_queued_audio_system::<PannedSound>)
+            .add_event::<PlaySoundEvent>()
+            .add_startup_system(load_sounds)
+            .add_startup_system(setup_mute_button)
+            .add_system(toggle_mute)
+            .add_system(draw_mute_button.after(toggle_mute))
+            .add_system(play_sounds.after(toggle_mute));
+    }
+}
+
+/// The channels the sounds are mixed in, every channel has its own volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioChannel {
+    /// Scales the volume of all the other channels.
+    Master,
+    Music,
+    Sfx,
+    Ui,
+}
+
+impl AudioChannel {
+    pub const ALL: [AudioChannel; 4] =
+        [AudioChannel::Master, AudioChannel::Music, AudioChannel::Sfx, AudioChannel::Ui];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AudioChannel::Master => "Master",
+            AudioChannel::Music => "Music",
+            AudioChannel::Sfx => "Effects",
+            AudioChannel::Ui => "Interface",
+        }
+    }
+}
+
+/// The volume of every channel, between 0 and 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Volumes {
+    master: f32,
+    music: f32,
+    sfx: f32,
+    ui: f32,
+}
+
+impl Default for Volumes {
+    fn default() -> Volumes {
+        Volumes { master: 1.0, music: 0.7, sfx: 1.0, ui: 0.8 }
+    }
+}
+
+impl Volumes {
+    pub fn get(&self, channel: AudioChannel) -> f32 {
+        match channel {
+            AudioChannel::Master => self.master,
+            AudioChannel::Music => self.music,
+            AudioChannel::Sfx => self.sfx,
+            AudioChannel::Ui => self.ui,
+        }
+    }
+
+    pub fn set(&mut self, channel: AudioChannel, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match channel {
+            AudioChannel::Master => self.master = volume,
+            AudioChannel::Music => self.music = volume,
+            AudioChannel::Sfx => self.sfx = volume,
+            AudioChannel::Ui => self.ui = volume,
+        }
+    }
+
+    /// The volume the sounds of this channel are played at, scaled by the master volume.
+    pub fn output(&self, channel: AudioChannel) -> f32 {
+        match channel {
+            AudioChannel::Master => self.master,
+            channel => self.master * self.get(channel),
+        }
+    }
+}
+
+/// Whether all the audio is muted, only for the current session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Muted(pub bool);
+
+/// The speaker icon of the HUD, clicking it mutes or unmutes the audio.
+#[derive(Component, Debug)]
+struct MuteButton;
+
+/// The sounds effects of the game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Sound {
+    /// The ultimate froze the asteroids.
+    TimeStop,
+    /// The planet was destroyed, the music ducks under it.
+    GameOver,
+    /// An asteroid was destroyed.
+    Explosion,
+    /// An asteroid hit the planet or its shield.
+    PlanetImpact,
+    /// A button of the menus was clicked.
+    Click,
+}
+
+impl Sound {
+    const ALL: [Sound; 5] =
+        [Sound::TimeStop, Sound::GameOver, Sound::Explosion, Sound::PlanetImpact, Sound::Click];
+
+    fn path(self) -> &'static str {
+        match self {
+            Sound::TimeStop => "sounds/time_stop.ogg",
+            Sound::GameOver => "sounds/game_over.ogg",
+            Sound::Explosion => "sounds/explosion.ogg",
+            Sound::PlanetImpact => "sounds/planet_impact.ogg",
+            Sound::Click => "sounds/click.ogg",
+        }
+    }
+
+    fn channel(self) -> AudioChannel {
+        match self {
+            Sound::Click => AudioChannel::Ui,
+            _ => AudioChannel::Sfx,
+        }
+    }
+}
+
+pub struct PlaySoundEvent {
+    pub sound: Sound,
+    /// Where the sound is emitted in the world, the sounds without position
+    /// are played at full volume.
+    pub position: Option<Vec2>,
+}
+
+impl PlaySoundEvent {
+    pub fn new(sound: Sound) -> PlaySoundEvent {
+        PlaySoundEvent { sound, position: None }
+    }
+
+    pub fn at(sound: Sound, position: Vec2) -> PlaySoundEvent {
+        PlaySoundEvent { sound, position: Some(position) }
+    }
+}
+
+/// The sounds, kept loaded to be panned as soon as they are played.
+struct SoundHandles(HashMap<Sound, Handle<AudioSource>>);
+
+/// A sound played louder on the speaker on the side it is emitted,
+/// a new one is added for every play and dropped once played.
+#[derive(TypeUuid)]
+#[uuid = "4f2a8c1e-6b3d-4e7a-9c51-2d8f0b6a7e93"]
+struct PannedSound {
+    source: AudioSource,
+    /// The volumes of the left and the right speakers.
+    speakers: [f32; 2],
+}
+
+impl Decodable for PannedSound {
+    type Decoder = ChannelVolume<rodio::Decoder<Cursor<AudioSource>>>;
+    type DecoderItem = i16;
+
+    fn decoder(&self) -> Self::Decoder {
+        ChannelVolume::new(self.source.decoder(), self.speakers.to_vec())
+    }
+}
+
+fn load_sounds(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let handles = Sound::ALL.iter().map(|sound| (*sound, asset_server.load(sound.path())));
+    commands.insert_resource(SoundHandles(handles.collect()));
+}
+
+/// The sounds emitted away from the camera are attenuated with the distance
+/// and panned to the side of the screen they are emitted on.
+fn play_sounds(
+    mut play_sound: EventReader<PlaySoundEvent>,
+    camera: Query<&GlobalTransform, With<SpaceCamera>>,
+    settings: Res<GameSettings>,
+    muted: Res<Muted>,
+    handles: Res<SoundHandles>,
+    sources: Res<Assets<AudioSource>>,
+    mut panned_sounds: ResMut<Assets<PannedSound>>,
+    audio: Res<Audio>,
+    panned_audio: Res<Audio<PannedSound>>,
+) {
+    if muted.0 {
+        play_sound.clear();
+        return;
+    }
+
+    let listener = camera.get_single().map_or(Vec2::ZERO, |t| t.translation().truncate());
+    for PlaySoundEvent { sound, position } in play_sound.iter() {
+        let attenuation = match position {
+            Some(position) => {
+                let distance = position.distance(listener);
+                (1.0 - distance / HEARING_DISTANCE).max(FARTHEST_SOUND_VOLUME)
+            }
+            None => 1.0,
+        };
+        let volume = settings.volumes.output(sound.channel()) * attenuation;
+        let playback = PlaybackSettings::ONCE.with_volume(volume);
+        let handle = &handles.0[sound];
+
+        // The sounds not loaded yet are played in the middle.
+        match (position, sources.get(handle)) {
+            (Some(position), Some(source)) => {
+                let pan = ((position.x - listener.x) / HEARING_DISTANCE).clamp(-1.0, 1.0);
+                let speakers = [(1.0 - pan).min(1.0), (1.0 + pan).min(1.0)];
+                let panned = panned_sounds.add(PannedSound { source: source.clone(), speakers });
+                panned_audio.play_with_settings(panned, playback);
+            }
+            _otherwise => {
+                audio.play_with_settings(handle.clone(), playback);
+            }
+        }
+    }
+}
+
+fn setup_mute_button(mut commands: Commands, image_assets: Res<ImageAssets>) {
+    commands
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(32.0), Val::Px(32.0)),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(20.0), right: Val::Px(20.0), ..default() },
+                ..default()
+            },
+            image: image_assets.speaker.clone().into(),
+            ..default()
+        })
+        .insert(MuteButton)
+        .insert(AccessibleLabel::new("Mute the audio"))
+        .insert(Persistent);
+}
+
+fn toggle_mute(
+    actions: Actions,
+    button: Query<&Interaction, (Changed<Interaction>, With<MuteButton>)>,
+    mut muted: ResMut<Muted>,
+) {
+    let clicked = button.iter().any(|interaction| *interaction == Interaction::Clicked);
+    if actions.just_pressed(Action::ToggleMute) || clicked {
+        muted.0 = !muted.0;
+    }
+}
+
+fn draw_mute_button(
+    muted: Res<Muted>,
+    image_assets: Res<ImageAssets>,
+    mut button: Query<&mut UiImage, With<MuteButton>>,
+) {
+    if muted.is_changed() {
+        for mut image in &mut button {
+            let speaker = if muted.0 { &image_assets.speaker_muted } else { &image_assets.speaker };
+            *image = speaker.clone().into();
+        }
+    }
+}
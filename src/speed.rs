@@ -0,0 +1,151 @@
+//! The simulation speed chosen in the main menu before a run, scaling the physics
+//! and the timers of the run and multiplying its final score.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+
+use crate::headless::Headless;
+use crate::interpolation::TickProgress;
+use crate::lockstep::LockstepSession;
+use crate::menu::MenuButton;
+use crate::{GameState, PHYSICS_TIMESTEP};
+
+pub struct SpeedPlugin;
+
+impl Plugin for SpeedPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SimulationSpeed::default())
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(scale_physics))
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu).with_system(draw_speed_button),
+            );
+
+        // The lockstep ticks the co-op runs itself, the headless apps step every update.
+        if cfg!(feature = "deterministic") {
+            app.add_system_to_stage(CoreStage::PreUpdate, tick_at_fixed_rate)
+                .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_ticking));
+        }
+    }
+}
+
+/// The factor applied to the elapsed time of the gameplay systems of a run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimulationSpeed {
+    factor: f32,
+    /// The time simulated this frame by the lockstep runs, the frame time otherwise.
+    step: Option<Duration>,
+}
+
+impl Default for SimulationSpeed {
+    fn default() -> SimulationSpeed {
+        SimulationSpeed { factor: 1.0, step: None }
+    }
+}
+
+impl SimulationSpeed {
+    const ALL: [f32; 4] = [0.8, 1.0, 1.25, 1.5];
+
+    /// The speed coming after this one in the main menu.
+    pub fn next(self) -> SimulationSpeed {
+        let index = SimulationSpeed::ALL.iter().position(|f| *f == self.factor).unwrap_or(0);
+        let factor = SimulationSpeed::ALL[(index + 1) % SimulationSpeed::ALL.len()];
+        SimulationSpeed { factor, step: None }
+    }
+
+    pub fn factor(self) -> f32 {
+        self.factor
+    }
+
+    /// Simulate this amount of time every frame instead of the frame time,
+    /// a zero step pauses the gameplay timers.
+    pub fn set_step(&mut self, step: Option<Duration>) {
+        self.step = step;
+    }
+
+    /// The time elapsed in the run since the last frame.
+    pub fn delta(self, time: &Time) -> Duration {
+        self.step.unwrap_or_else(|| time.delta()).mul_f32(self.factor)
+    }
+
+    pub fn delta_seconds(self, time: &Time) -> f32 {
+        self.delta(time).as_secs_f32()
+    }
+
+    /// The time simulated this frame before the speed factor, for the
+    /// velocities the physics already scales by the speed.
+    pub fn step_seconds(self, time: &Time) -> f32 {
+        self.step.unwrap_or_else(|| time.delta()).as_secs_f32()
+    }
+
+    /// The final score of a run, a faster run gives a better score.
+    pub fn scale_score(self, score: u64) -> u64 {
+        (score as f64 * self.factor as f64).round() as u64
+    }
+}
+
+fn scale_physics(speed: Res<SimulationSpeed>, mut rapier_config: ResMut<RapierConfiguration>) {
+    match &mut rapier_config.timestep_mode {
+        TimestepMode::Variable { time_scale, .. }
+        | TimestepMode::Interpolated { time_scale, .. } => *time_scale = speed.factor,
+        TimestepMode::Fixed { dt, .. } => *dt = PHYSICS_TIMESTEP * speed.factor,
+    }
+}
+
+/// The deterministic runs are simulated by fixed steps at the rate of the physics,
+/// whatever the frame rate, the frames between two steps are interpolated.
+fn tick_at_fixed_rate(
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    session: Option<Res<LockstepSession>>,
+    headless: Option<Res<Headless>>,
+    mut lag: Local<Duration>,
+    mut speed: ResMut<SimulationSpeed>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut progress: ResMut<TickProgress>,
+) {
+    // The physics stays paused by the states pushed on top of the run.
+    if session.is_some() || headless.is_some() || *state.current() != GameState::Playing {
+        return;
+    }
+
+    // The lag is bounded, a slow frame doesn't make the run rush to catch up.
+    let dt = Duration::from_secs_f32(PHYSICS_TIMESTEP);
+    *lag = (*lag + time.delta()).min(dt * 2);
+    progress.ticked = *lag >= dt;
+    if progress.ticked {
+        *lag -= dt;
+        speed.set_step(Some(dt));
+    } else {
+        speed.set_step(Some(Duration::ZERO));
+    }
+    rapier_config.physics_pipeline_active = progress.ticked;
+    progress.alpha = (lag.as_secs_f32() / PHYSICS_TIMESTEP).min(1.0);
+}
+
+fn stop_ticking(mut speed: ResMut<SimulationSpeed>) {
+    speed.set_step(None);
+}
+
+fn draw_speed_button(
+    speed: Res<SimulationSpeed>,
+    spawned: Query<(), Added<MenuButton>>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !speed.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    let value = format!("Speed: {}x", speed.factor);
+    for (_, children) in buttons.iter().filter(|(button, _)| **button == MenuButton::Speed) {
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != value {
+                    text.sections[0].value = value.clone();
+                }
+            }
+        }
+    }
+}
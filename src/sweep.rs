@@ -0,0 +1,201 @@
+//! The balance sweeps, started with the `--sweep` argument instead of the game.
+//!
+//! Every point of a grid of balance numbers is played on many seeds by headless
+//! games running in parallel, without a window, at the fixed physics timestep.
+//! The fleet plays alone from the default setup: nobody collects the
+//! dropped dice nor spends them, so the dice economy is the number of dice
+//! dropped. The report is printed and written in the exports directory.
+//!
+//! The mods are ignored, the numbers come from the files of the assets directory.
+
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+
+use crate::behavior::BehaviorProfileFiles;
+use crate::headless::{headless_app, read_tuning, update_step, TUNING_FILE_PATH};
+use crate::loot::LootTable;
+use crate::metrics::EXPORT_DIRECTORY;
+use crate::mutators::RunRules;
+use crate::tuning::{Balance, Tuning};
+use crate::waves::{Wave, WaveSchedule};
+use crate::{
+    bump_asteroids_on_ship_collision_with_bump_power,
+    destroy_asteroids_on_ship_collision_with_destroy_power, AsteroidDestroyedEvent, DestroyCause,
+    PlanetHealth, RunSetup, PHYSICS_TIMESTEP,
+};
+
+const SWEEP_SEEDS: u64 = 8;
+/// The runs still alive after this many waves are stopped.
+const SWEEP_MAX_WAVES: u32 = 10;
+/// The factors applied to the balance numbers of the tuning file.
+const SWEEP_FACTORS: [f32; 3] = [0.8, 1.0, 1.25];
+
+/// The balance numbers of a point of the grid.
+#[derive(Debug, Clone, Copy)]
+struct SweepPoint {
+    spawn_interval: f32,
+    ship_speed: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RunOutcome {
+    /// The simulated seconds until the planet was destroyed or the run stopped.
+    survived: f32,
+    wave: u32,
+    destroyed: u32,
+    dice_dropped: u32,
+    planet_destroyed: bool,
+}
+
+/// The dice dropped and the asteroids destroyed during a simulated run.
+#[derive(Debug, Default)]
+struct SweepTally {
+    destroyed: u32,
+    dice_dropped: u32,
+}
+
+/// Run the sweep over the grid and report the outcomes, the game doesn't start.
+pub fn run_sweep() {
+    let tuning = match read_tuning() {
+        Ok(tuning) => tuning,
+        Err(e) => return eprintln!("Could not read the tuning from {}: {}", TUNING_FILE_PATH, e),
+    };
+    let behaviors = match BehaviorProfileFiles::read() {
+        Ok(behaviors) => behaviors,
+        Err(e) => return eprintln!("Could not read the behavior profiles: {}", e),
+    };
+
+    let points: Vec<_> = SWEEP_FACTORS
+        .iter()
+        .flat_map(|interval| SWEEP_FACTORS.iter().map(move |speed| (interval, speed)))
+        .map(|(interval, speed)| SweepPoint {
+            spawn_interval: tuning.balance.spawn_interval * interval,
+            ship_speed: tuning.balance.ship_speed * speed,
+        })
+        .collect();
+    let jobs: Vec<_> = points
+        .iter()
+        .enumerate()
+        .flat_map(|(i, _)| (0..SWEEP_SEEDS).map(move |s| (i, s)))
+        .collect();
+
+    let workers = thread::available_parallelism().map_or(1, |n| n.get());
+    println!("Sweeping {} runs on {} threads...", jobs.len(), workers);
+
+    let next_job = AtomicUsize::new(0);
+    let outcomes = Mutex::new(vec![Vec::new(); points.len()]);
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(&(point, seed)) = jobs.get(next_job.fetch_add(1, Ordering::Relaxed))
+                {
+                    let balance = Balance {
+                        spawn_interval: points[point].spawn_interval,
+                        ship_speed: points[point].ship_speed,
+                        ..tuning.balance
+                    };
+                    let tuning = Tuning { balance, asteroids: tuning.asteroids.clone() };
+                    let outcome = simulate_run(tuning, behaviors.clone(), seed);
+                    outcomes.lock().unwrap()[point].push(outcome);
+                }
+            });
+        }
+    });
+
+    let report = sweep_report(&points, &outcomes.into_inner().unwrap());
+    print!("{}", report);
+
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let path = format!("{}/sweep-{}.csv", EXPORT_DIRECTORY, now);
+    match fs::create_dir_all(EXPORT_DIRECTORY).and_then(|()| fs::write(&path, report)) {
+        Ok(()) => println!("Report written to {}", path),
+        Err(e) => eprintln!("Could not write the report to {}: {}", path, e),
+    }
+}
+
+/// One line by point of the grid, the outcomes averaged over the seeds.
+fn sweep_report(points: &[SweepPoint], outcomes: &[Vec<RunOutcome>]) -> String {
+    let mut report = "spawn_interval,ship_speed,runs,planet_destroyed,mean_survival,mean_wave,\
+                      mean_destroyed,mean_dice_dropped,dice_per_minute\n"
+        .to_string();
+    for (point, outcomes) in points.iter().zip(outcomes) {
+        let runs = outcomes.len().max(1) as f32;
+        let mean = |f: fn(&RunOutcome) -> f32| outcomes.iter().map(f).sum::<f32>() / runs;
+        let survival = mean(|o| o.survived);
+        let dice = mean(|o| o.dice_dropped as f32);
+        let _ = writeln!(
+            report,
+            "{:.2},{:.0},{},{},{:.1},{:.1},{:.1},{:.1},{:.2}",
+            point.spawn_interval,
+            point.ship_speed,
+            outcomes.len(),
+            outcomes.iter().filter(|o| o.planet_destroyed).count(),
+            survival,
+            mean(|o| o.wave as f32),
+            mean(|o| o.destroyed as f32),
+            dice,
+            if survival > 0.0 { dice * 60.0 / survival } else { 0.0 },
+        );
+    }
+    report
+}
+
+/// Play a run without any input, until the planet is destroyed or the last wave is over.
+fn simulate_run(tuning: Tuning, behaviors: BehaviorProfileFiles, seed: u64) -> RunOutcome {
+    let run_setup = RunSetup { seed: Some(seed), ..default() };
+    let mut app = headless_app(tuning, behaviors, run_setup, WaveSchedule::default());
+    app.insert_resource(SweepTally::default()).add_system(
+        count_dropped_dice
+            .after(bump_asteroids_on_ship_collision_with_bump_power)
+            .after(destroy_asteroids_on_ship_collision_with_destroy_power),
+    );
+
+    let mut frames = 0;
+    loop {
+        update_step(&mut app);
+        frames += 1;
+
+        let planet_destroyed = app.world.resource::<PlanetHealth>().current == 0;
+        let wave = app.world.resource::<Wave>().number;
+        if planet_destroyed || wave > SWEEP_MAX_WAVES {
+            let tally = app.world.resource::<SweepTally>();
+            return RunOutcome {
+                survived: frames as f32 * PHYSICS_TIMESTEP,
+                wave: wave.min(SWEEP_MAX_WAVES),
+                destroyed: tally.destroyed,
+                dice_dropped: tally.dice_dropped,
+                planet_destroyed,
+            };
+        }
+    }
+}
+
+/// Count the dice the destroyed asteroids would drop, instead of spawning the loot.
+fn count_dropped_dice(
+    mut asteroid_destroyed: EventReader<AsteroidDestroyedEvent>,
+    loot_tables: Query<&LootTable>,
+    rules: Res<RunRules>,
+    mut tally: ResMut<SweepTally>,
+) {
+    let mut destroyed = HashSet::new();
+    for AsteroidDestroyedEvent { entity, cause, .. } in asteroid_destroyed.iter() {
+        // An asteroid can be hit by the blast of many ships in the same frame.
+        if !destroyed.insert(*entity) {
+            continue;
+        }
+
+        tally.destroyed += 1;
+        if *cause == DestroyCause::Destroy {
+            if let Ok(loot_table) = loot_tables.get(*entity) {
+                tally.dice_dropped += loot_table.dice_dropped(&rules);
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! The color themes of the UI, chosen in the settings and switched at runtime.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::GameSettings;
+
+pub struct ThemePlugin;
+
+impl Plugin for ThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Theme::default().palette())
+            .add_system_to_stage(CoreStage::PreUpdate, sync_palette_with_settings)
+            .add_system(recolor_themed_panels);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Theme {
+    #[default]
+    Normal,
+    HighContrast,
+    Dark,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 3] = [Theme::Normal, Theme::HighContrast, Theme::Dark];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Theme::Normal => "Normal",
+            Theme::HighContrast => "High contrast",
+            Theme::Dark => "Dark",
+        }
+    }
+
+    /// The theme coming after this one in the settings.
+    pub fn next(self) -> Theme {
+        let index = Theme::ALL.iter().position(|theme| *theme == self).unwrap_or(0);
+        Theme::ALL[(index + 1) % Theme::ALL.len()]
+    }
+
+    pub fn palette(self) -> Palette {
+        match self {
+            Theme::Normal => Palette {
+                panel: Color::rgba(0.1, 0.1, 0.2, 0.8),
+                button: Color::rgb(0.15, 0.15, 0.35),
+                button_hovered: Color::rgb(0.25, 0.25, 0.55),
+                button_disabled: Color::rgba(0.15, 0.15, 0.15, 0.5),
+                slider_fill: Color::rgb(0.35, 0.35, 0.75),
+            },
+            // Opaque panels and saturated buttons that stand out from the space background.
+            Theme::HighContrast => Palette {
+                panel: Color::BLACK,
+                button: Color::rgb(0.0, 0.0, 0.6),
+                button_hovered: Color::rgb(0.0, 0.45, 1.0),
+                button_disabled: Color::rgb(0.3, 0.3, 0.3),
+                slider_fill: Color::YELLOW,
+            },
+            Theme::Dark => Palette {
+                panel: Color::rgba(0.02, 0.02, 0.02, 0.9),
+                button: Color::rgb(0.12, 0.12, 0.12),
+                button_hovered: Color::rgb(0.22, 0.22, 0.22),
+                button_disabled: Color::rgba(0.08, 0.08, 0.08, 0.6),
+                slider_fill: Color::rgb(0.45, 0.45, 0.45),
+            },
+        }
+    }
+}
+
+/// The colors of the UI elements for the current theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Palette {
+    pub panel: Color,
+    pub button: Color,
+    pub button_hovered: Color,
+    pub button_disabled: Color,
+    pub slider_fill: Color,
+}
+
+impl Palette {
+    /// The color of an enabled button for the given interaction.
+    pub fn button(&self, interaction: Interaction) -> Color {
+        match interaction {
+            Interaction::None => self.button,
+            Interaction::Hovered | Interaction::Clicked => self.button_hovered,
+        }
+    }
+
+    /// The color of a button that can be greyed out, like the unaffordable ones.
+    pub fn button_if(&self, enabled: bool, interaction: Interaction) -> Color {
+        if enabled {
+            self.button(interaction)
+        } else {
+            self.button_disabled
+        }
+    }
+}
+
+/// A background node colored with the panel color of the theme.
+#[derive(Component, Debug)]
+pub struct ThemedPanel;
+
+fn sync_palette_with_settings(settings: Res<GameSettings>, mut palette: ResMut<Palette>) {
+    let wanted = settings.theme.palette();
+    if *palette != wanted {
+        *palette = wanted;
+    }
+}
+
+fn recolor_themed_panels(
+    palette: Res<Palette>,
+    mut panels: Query<(&mut UiColor, ChangeTrackers<ThemedPanel>), With<ThemedPanel>>,
+) {
+    for (mut color, tracker) in &mut panels {
+        if palette.is_changed() || tracker.is_added() {
+            color.0 = palette.panel;
+        }
+    }
+}
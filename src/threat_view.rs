@@ -0,0 +1,128 @@
+//! The threat view, a second camera drawn in a corner of the screen framing the
+//! asteroid that will hit the planet first, enabled in the settings.
+//!
+//! The view is hidden while no asteroid is heading to the planet.
+
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::math::Vec3Swizzles;
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy_rapier2d::prelude::*;
+use ordered_float::OrderedFloat;
+
+use crate::settings::GameSettings;
+use crate::{logic, Asteroid, GameState, Planet, SpaceCamera, PLANET_RADIUS};
+
+/// The part of the width and the height of the window taken by the view.
+const VIEW_FRACTION: f32 = 0.25;
+/// The space between the view and the corner of the window, in logical pixels.
+const VIEW_MARGIN: f32 = 16.0;
+const VIEW_ZOOM: f32 = 0.5;
+const VIEW_BACKGROUND: Color = Color::rgb(0.05, 0.05, 0.12);
+
+pub struct ThreatViewPlugin;
+
+impl Plugin for ThreatViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_enter(GameState::Playing).with_system(setup_threat_camera),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(place_threat_viewport)
+                .with_system(frame_closest_threat),
+        );
+    }
+}
+
+#[derive(Component, Debug)]
+struct ThreatCamera;
+
+fn setup_threat_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(Camera2dBundle {
+            camera: Camera { priority: 1, is_active: false, ..default() },
+            camera_2d: Camera2d { clear_color: ClearColorConfig::Custom(VIEW_BACKGROUND) },
+            projection: OrthographicProjection { scale: VIEW_ZOOM, ..default() },
+            ..default()
+        })
+        // The UI is only drawn once, by the main camera.
+        .insert(UiCameraConfig { show_ui: false })
+        .insert(ThreatCamera);
+}
+
+/// The view sits in the bottom right corner, it follows the size of the window.
+fn place_threat_viewport(
+    windows: Res<Windows>,
+    mut cameras: Query<&mut Camera, With<ThreatCamera>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    let margin = (VIEW_MARGIN * window.scale_factor() as f32) as u32;
+    let size = (window_size.as_vec2() * VIEW_FRACTION).as_uvec2();
+    // A minimized window has no size, the view keeps its last place.
+    if size.x <= margin || size.y <= margin {
+        return;
+    }
+    let viewport = Viewport {
+        physical_position: window_size - size - UVec2::splat(margin),
+        physical_size: size,
+        ..default()
+    };
+
+    for mut camera in &mut cameras {
+        let unchanged = camera.viewport.as_ref().is_some_and(|current| {
+            current.physical_position == viewport.physical_position
+                && current.physical_size == viewport.physical_size
+        });
+        if !unchanged {
+            camera.viewport = Some(viewport.clone());
+        }
+    }
+}
+
+fn frame_closest_threat(
+    settings: Res<GameSettings>,
+    planet: Query<&Transform, (With<Planet>, Without<ThreatCamera>)>,
+    asteroids: Query<(&Transform, &Velocity), With<Asteroid>>,
+    main_camera: Query<&Transform, (With<SpaceCamera>, Without<ThreatCamera>)>,
+    mut cameras: Query<(&mut Camera, &mut Transform), (With<ThreatCamera>, Without<Asteroid>)>,
+) {
+    let planet_position = match planet.get_single() {
+        Ok(transform) => transform.translation.xy().to_array(),
+        Err(_) => return,
+    };
+
+    let threats = asteroids.iter().filter(|_| settings.threat_view);
+    let threat = threats
+        .filter_map(|(transform, velocity)| {
+            let position = transform.translation.xy();
+            let time = logic::time_to_impact(
+                planet_position,
+                PLANET_RADIUS,
+                position.to_array(),
+                velocity.linvel.to_array(),
+            )?;
+            Some((position, time))
+        })
+        .min_by_key(|(_, time)| OrderedFloat(*time))
+        .map(|(position, _)| position);
+
+    // The view sees the same layers as the main camera.
+    let depth = match main_camera.get_single() {
+        Ok(transform) => transform.translation.z,
+        Err(_) => return,
+    };
+    for (mut camera, mut transform) in &mut cameras {
+        if camera.is_active != threat.is_some() {
+            camera.is_active = threat.is_some();
+        }
+        if let Some(position) = threat {
+            transform.translation = position.extend(depth);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! The toasts, short banners stacked at the top of the screen that dismiss themselves.
+//!
+//! Any system can notify the player by sending a `ToastEvent`.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::ui::FocusPolicy;
+
+use crate::{FontAssets, Persistent};
+
+const TOAST_DURATION: u64 = 3; // in second
+const TOAST_FADE_DURATION: f32 = 0.5; // in second
+const TOAST_MAX_VISIBLE: usize = 3;
+
+pub struct ToastsPlugin;
+
+impl Plugin for ToastsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ToastQueue::default())
+            .add_event::<ToastEvent>()
+            .add_startup_system(setup_toast_stack)
+            .add_system(queue_toasts)
+            .add_system(dismiss_toasts)
+            .add_system(show_queued_toasts.after(queue_toasts).after(dismiss_toasts));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastKind {
+    Info,
+    Success,
+    Warning,
+}
+
+impl ToastKind {
+    fn color(self) -> Color {
+        match self {
+            ToastKind::Info => Color::rgba(0.15, 0.15, 0.35, 0.9),
+            ToastKind::Success => Color::rgba(0.15, 0.4, 0.15, 0.9),
+            ToastKind::Warning => Color::rgba(0.5, 0.15, 0.1, 0.9),
+        }
+    }
+}
+
+/// Sent by any system to display a message to the player.
+#[derive(Debug, Clone)]
+pub struct ToastEvent {
+    pub message: String,
+    pub kind: ToastKind,
+}
+
+impl ToastEvent {
+    pub fn info(message: impl Into<String>) -> ToastEvent {
+        ToastEvent { message: message.into(), kind: ToastKind::Info }
+    }
+
+    pub fn success(message: impl Into<String>) -> ToastEvent {
+        ToastEvent { message: message.into(), kind: ToastKind::Success }
+    }
+
+    pub fn warning(message: impl Into<String>) -> ToastEvent {
+        ToastEvent { message: message.into(), kind: ToastKind::Warning }
+    }
+}
+
+/// The toasts waiting for a free place in the stack.
+#[derive(Debug, Default)]
+struct ToastQueue(VecDeque<ToastEvent>);
+
+#[derive(Component, Debug)]
+struct ToastStack;
+
+/// A displayed toast, despawned when the timer finishes.
+#[derive(Component, Debug)]
+struct Toast {
+    timer: Timer,
+    kind: ToastKind,
+}
+
+fn setup_toast_stack(mut commands: Commands) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(80.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: FocusPolicy::Pass,
+            ..default()
+        })
+        .insert(ToastStack)
+        .insert(Persistent);
+}
+
+fn queue_toasts(mut toast_events: EventReader<ToastEvent>, mut queue: ResMut<ToastQueue>) {
+    queue.0.extend(toast_events.iter().cloned());
+}
+
+fn show_queued_toasts(
+    mut commands: Commands,
+    mut queue: ResMut<ToastQueue>,
+    stack: Query<Entity, With<ToastStack>>,
+    toasts: Query<&Toast>,
+    font_assets: Res<FontAssets>,
+) {
+    let mut visible = toasts.iter().filter(|toast| !toast.timer.finished()).count();
+    while visible < TOAST_MAX_VISIBLE {
+        let ToastEvent { message, kind } = match queue.0.pop_front() {
+            Some(toast) => toast,
+            None => break,
+        };
+
+        let toast = commands
+            .spawn_bundle(NodeBundle {
+                style: Style {
+                    margin: UiRect::all(Val::Px(3.0)),
+                    padding: UiRect::new(Val::Px(12.0), Val::Px(12.0), Val::Px(6.0), Val::Px(6.0)),
+                    ..default()
+                },
+                color: kind.color().into(),
+                focus_policy: FocusPolicy::Pass,
+                ..default()
+            })
+            .insert(Toast { timer: Timer::new(Duration::from_secs(TOAST_DURATION), false), kind })
+            .with_children(|parent| {
+                parent.spawn_bundle(TextBundle::from_section(
+                    message,
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 18.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            })
+            .id();
+
+        commands.entity(stack.single()).add_child(toast);
+        visible += 1;
+    }
+}
+
+/// Fade the toasts out at the end of their time and despawn them.
+fn dismiss_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast, &mut UiColor)>,
+) {
+    for (entity, mut toast, mut color) in &mut toasts {
+        if toast.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            let remaining = toast.timer.duration().as_secs_f32() - toast.timer.elapsed_secs();
+            let alpha = (remaining / TOAST_FADE_DURATION).min(1.0);
+            let kind_color = toast.kind.color();
+            color.0 = kind_color;
+            color.0.set_a(kind_color.a() * alpha);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! The touchscreen controls of a run: a tap presses like the left mouse button,
+//! two fingers pinch to zoom the camera and drag together to pan it.
+//!
+//! The fingers are followed here instead of reading the `Touch` deltas, a finger
+//! that doesn't move keeps its last delta and a released finger forgets where it
+//! started. The taps end a gesture made with a single finger that barely moved.
+
+use std::collections::HashMap;
+
+use bevy::input::touch::Touch;
+use bevy::input::InputSystem;
+use bevy::prelude::*;
+
+use crate::{GameState, SpaceCamera};
+
+/// The distance a finger can move and still tap, in logical pixels.
+const TAP_MAX_DISTANCE: f32 = 20.0;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 2.0;
+/// Bevy only flips the touch positions of the mobile windows, the desktop
+/// touchscreens give them from the top while the cursor is from the bottom.
+const FLIP_TOUCH_Y: bool = !cfg!(any(target_os = "android", target_os = "ios"));
+
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TouchGestures::default())
+            .add_system_to_stage(CoreStage::PreUpdate, detect_gestures.after(InputSystem))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(pinch_and_pan_camera),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(reset_camera));
+    }
+}
+
+/// The gestures of this frame, the positions are in screen space from the bottom.
+#[derive(Debug)]
+pub struct TouchGestures {
+    pub taps: Vec<Vec2>,
+    /// How far the two fingers dragged together.
+    pan: Vec2,
+    /// How much the camera zooms out, under one when the fingers spread.
+    zoom: f32,
+    fingers: HashMap<u64, Finger>,
+    /// Whether many fingers touched the screen since it was last free.
+    multi_touch: bool,
+}
+
+impl Default for TouchGestures {
+    fn default() -> TouchGestures {
+        TouchGestures {
+            taps: Vec::new(),
+            pan: Vec2::ZERO,
+            zoom: 1.0,
+            fingers: HashMap::new(),
+            multi_touch: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    start: Vec2,
+    last: Vec2,
+}
+
+fn detect_gestures(
+    windows: Res<Windows>,
+    touches: Res<Touches>,
+    mut gestures: ResMut<TouchGestures>,
+) {
+    let height = windows.get_primary().map_or(0.0, |window| window.height());
+    let screen = |touch: &Touch| {
+        let position = touch.position();
+        if FLIP_TOUCH_Y {
+            Vec2::new(position.x, height - position.y)
+        } else {
+            position
+        }
+    };
+
+    let gestures = &mut *gestures;
+    gestures.taps.clear();
+    gestures.pan = Vec2::ZERO;
+    gestures.zoom = 1.0;
+
+    for touch in touches.iter_just_cancelled() {
+        gestures.fingers.remove(&touch.id());
+    }
+    for touch in touches.iter_just_released() {
+        // A finger can touch and leave the screen in the same frame.
+        let position = screen(touch);
+        let start = gestures.fingers.remove(&touch.id()).map_or(position, |f| f.start);
+        if !gestures.multi_touch && start.distance(position) <= TAP_MAX_DISTANCE {
+            gestures.taps.push(position);
+        }
+    }
+
+    let fingers: Vec<_> = touches.iter().map(|touch| (touch.id(), screen(touch))).collect();
+    if let [(first, first_position), (second, second_position)] = fingers[..] {
+        if let (Some(first), Some(second)) =
+            (gestures.fingers.get(&first), gestures.fingers.get(&second))
+        {
+            let previous = first.last.distance(second.last);
+            let current = first_position.distance(second_position);
+            if previous > 0.0 && current > 0.0 {
+                gestures.zoom = previous / current;
+            }
+            gestures.pan = ((first_position - first.last) + (second_position - second.last)) / 2.0;
+        }
+    }
+
+    for (id, position) in &fingers {
+        let finger =
+            gestures.fingers.entry(*id).or_insert(Finger { start: *position, last: *position });
+        finger.last = *position;
+    }
+    if fingers.len() > 1 {
+        gestures.multi_touch = true;
+    } else if fingers.is_empty() {
+        gestures.multi_touch = false;
+    }
+}
+
+fn pinch_and_pan_camera(
+    gestures: Res<TouchGestures>,
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<SpaceCamera>>,
+) {
+    let (mut transform, mut projection) = match camera.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+
+    projection.scale = (projection.scale * gestures.zoom).clamp(MIN_ZOOM, MAX_ZOOM);
+    // The scene follows the fingers.
+    let pan = transform.rotation * (-gestures.pan * projection.scale).extend(0.0);
+    transform.translation += pan;
+}
+
+/// The next run starts centered on the planet.
+fn reset_camera(
+    mut camera: Query<(&mut Transform, &mut OrthographicProjection), With<SpaceCamera>>,
+) {
+    for (mut transform, mut projection) in &mut camera {
+        transform.translation.x = 0.0;
+        transform.translation.y = 0.0;
+        projection.scale = 1.0;
+    }
+}
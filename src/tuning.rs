@@ -0,0 +1,126 @@
+//! The tuning of the game, the numbers a designer wants to tweak without recompiling.
+//!
+//! The tuning is defined in the `game.tuning.ron` data file, in the dev builds
+//! the file is watched and every change sends a `TuningChanged` event.
+
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::loot::LootTable;
+use crate::ron_asset::RonAssetLoader;
+use crate::toasts::ToastEvent;
+
+pub struct TuningPlugin;
+
+impl Plugin for TuningPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<Tuning>()
+            .add_asset_loader(RonAssetLoader::<Tuning>::new(&["tuning.ron"]))
+            .add_event::<TuningChanged>()
+            .add_startup_system(load_tuning)
+            .add_system(notify_tuning_changes);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "9e2d7a41-6c3b-4f80-b5a9-1d8e4f7c2a63"]
+pub struct Tuning {
+    #[serde(default)]
+    pub balance: Balance,
+    pub asteroids: Vec<AsteroidKind>,
+}
+
+/// The numbers of the fleet and of the spawning shared by every kind of asteroid.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Balance {
+    /// The seconds between two asteroids in the first wave.
+    pub spawn_interval: f32,
+    /// The speed of the ships, by second.
+    pub ship_speed: f32,
+    /// The impulse given to the bumped asteroids.
+    pub bump_force: f32,
+    /// The hull the ships lose to every asteroid they hit, none by default.
+    pub asteroid_hull_damage: u32,
+}
+
+impl Default for Balance {
+    fn default() -> Balance {
+        Balance {
+            spawn_interval: 1.0,
+            ship_speed: 2400.0,
+            bump_force: 4.0,
+            asteroid_hull_damage: 0,
+        }
+    }
+}
+
+impl Tuning {
+    /// Picks the kind of the next asteroid to spawn according to the spawn weights.
+    pub fn choose_asteroid_kind<R: Rng>(&self, rng: &mut R) -> Option<&AsteroidKind> {
+        self.asteroids.choose_weighted(rng, |kind| kind.spawn_weight).ok()
+    }
+
+    pub fn asteroid_kind(&self, name: &str) -> Option<&AsteroidKind> {
+        self.asteroids.iter().find(|kind| kind.name == name)
+    }
+}
+
+impl TuningHandle {
+    /// The balance of the tuning, the default one until the tuning is loaded.
+    pub fn balance(&self, tunings: &Assets<Tuning>) -> Balance {
+        tunings.get(&self.0).map_or_else(Balance::default, |tuning| tuning.balance)
+    }
+}
+
+/// Sent when the tuning file is modified while the game is running.
+#[derive(Debug)]
+pub struct TuningChanged;
+
+/// A kind of asteroid, e.g. the common rocks or the rare gold asteroids.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsteroidKind {
+    pub name: String,
+    pub spawn_weight: u32,
+    /// The impulse given to the asteroid toward the planet.
+    #[serde(default = "default_asteroid_speed")]
+    pub speed: f32,
+    colors: Vec<(f32, f32, f32)>,
+    pub loot: LootTable,
+}
+
+fn default_asteroid_speed() -> f32 {
+    1.0
+}
+
+impl AsteroidKind {
+    pub fn choose_color<R: Rng>(&self, rng: &mut R) -> Color {
+        let (r, g, b) = self.colors.choose(rng).copied().unwrap_or((0.5, 0.5, 0.5));
+        Color::rgb(r, g, b)
+    }
+}
+
+pub struct TuningHandle(pub Handle<Tuning>);
+
+fn load_tuning(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(TuningHandle(asset_server.load("game.tuning.ron")));
+}
+
+fn notify_tuning_changes(
+    mut tuning_events: EventReader<AssetEvent<Tuning>>,
+    handle: Res<TuningHandle>,
+    mut tuning_changed: EventWriter<TuningChanged>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    let modified = tuning_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle: h } => *h == handle.0,
+        AssetEvent::Created { .. } | AssetEvent::Removed { .. } => false,
+    });
+
+    if modified {
+        tuning_changed.send(TuningChanged);
+        toasts.send(ToastEvent::info("Tuning reloaded"));
+    }
+}
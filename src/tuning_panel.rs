@@ -0,0 +1,122 @@
+//! The live tuning panel of the designers, only built with the `dev-tools` feature.
+//!
+//! The F2 key toggles an egui window with sliders bound to the numbers of the
+//! tuning, the changes apply during the run and can be saved back to the
+//! `game.tuning.ron` file. The comments of the file are lost when saving.
+
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+use bevy_egui::egui::{self, emath::Numeric};
+use bevy_egui::{EguiContext, EguiPlugin};
+
+use crate::hull::SHIP_MAX_HULL;
+use crate::toasts::ToastEvent;
+use crate::tuning::{Tuning, TuningHandle};
+use crate::GameState;
+
+const TUNING_FILE_PATH: &str = "assets/game.tuning.ron";
+
+pub struct TuningPanelPlugin;
+
+impl Plugin for TuningPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(EguiPlugin).insert_resource(TuningPanel::default()).add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(toggle_tuning_panel)
+                .with_system(draw_tuning_panel.after(toggle_tuning_panel)),
+        );
+    }
+}
+
+/// The sliders edit a copy of the tuning, it replaces the asset when a slider is
+/// let go, not to reload the tuning on every frame of a drag.
+#[derive(Debug, Default)]
+struct TuningPanel {
+    open: bool,
+    draft: Option<Tuning>,
+}
+
+fn toggle_tuning_panel(keys: Res<Input<KeyCode>>, mut panel: ResMut<TuningPanel>) {
+    if keys.just_pressed(KeyCode::F2) {
+        panel.open = !panel.open;
+        panel.draft = None;
+    }
+}
+
+/// Whether the slider changed the number for good, i.e. not in the middle of a drag.
+fn slider<N: Numeric>(
+    ui: &mut egui::Ui,
+    value: &mut N,
+    range: RangeInclusive<N>,
+    text: &str,
+) -> bool {
+    let response = ui.add(egui::Slider::new(value, range).text(text));
+    response.drag_released() || (response.changed() && !response.dragged())
+}
+
+fn draw_tuning_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut panel: ResMut<TuningPanel>,
+    mut tuning_events: EventReader<AssetEvent<Tuning>>,
+    mut tunings: ResMut<Assets<Tuning>>,
+    handle: Res<TuningHandle>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    // The file can be reloaded while the panel is open.
+    let modified = tuning_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle: h } => *h == handle.0,
+        AssetEvent::Created { .. } | AssetEvent::Removed { .. } => false,
+    });
+    if !panel.open {
+        return;
+    }
+    if modified || panel.draft.is_none() {
+        panel.draft = tunings.get(&handle.0).cloned();
+    }
+    let draft = match &mut panel.draft {
+        Some(draft) => draft,
+        None => return,
+    };
+
+    let mut apply = false;
+    let mut save = false;
+    egui::Window::new("Tuning (F2)").show(egui_context.ctx_mut(), |ui| {
+        let balance = &mut draft.balance;
+        apply |= slider(ui, &mut balance.spawn_interval, 0.05..=5.0, "Spawn interval");
+        apply |= slider(ui, &mut balance.ship_speed, 0.0..=6000.0, "Ship speed");
+        apply |= slider(ui, &mut balance.bump_force, 0.0..=20.0, "Bump force");
+        apply |= slider(ui, &mut balance.asteroid_hull_damage, 0..=SHIP_MAX_HULL, "Hull damage");
+
+        for kind in &mut draft.asteroids {
+            ui.collapsing(kind.name.clone(), |ui| {
+                apply |= slider(ui, &mut kind.spawn_weight, 0..=100, "Spawn weight");
+                apply |= slider(ui, &mut kind.speed, 0.0..=5.0, "Speed");
+                apply |= slider(ui, &mut kind.loot.scrap_chance, 0.0..=1.0, "Scrap chance");
+                apply |=
+                    slider(ui, &mut kind.loot.consumable_chance, 0.0..=1.0, "Consumable chance");
+            });
+        }
+
+        save = ui.button("Save to the file").clicked();
+    });
+
+    // Changing the asset sends a modification event, the tuning is reloaded everywhere.
+    if apply {
+        if let Some(tuning) = tunings.get_mut(&handle.0) {
+            *tuning = draft.clone();
+        }
+    }
+
+    if save {
+        let content = ron::ser::to_string_pretty(&*draft, ron::ser::PrettyConfig::default()).ok();
+        match content.map(|content| std::fs::write(TUNING_FILE_PATH, content)) {
+            Some(Ok(())) => toasts.send(ToastEvent::success("Tuning saved")),
+            Some(Err(e)) => {
+                warn!("Could not save the tuning to {}: {}", TUNING_FILE_PATH, e);
+                toasts.send(ToastEvent::warning("Could not save the tuning"));
+            }
+            None => toasts.send(ToastEvent::warning("Could not serialize the tuning")),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! The scale of the UI chosen in the accessibility settings,
+//! applied to the sizes, the spacings and the texts of every node.
+
+use bevy::prelude::*;
+use bevy::ui::UiSystem;
+
+use crate::settings::GameSettings;
+
+pub struct UiScalePlugin;
+
+impl Plugin for UiScalePlugin {
+    fn build(&self, app: &mut App) {
+        // The nodes are scaled once spawned, before the layout is computed.
+        app.add_system_to_stage(CoreStage::PostUpdate, scale_new_nodes.before(UiSystem::Flex))
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                rescale_nodes_on_change.before(UiSystem::Flex),
+            );
+    }
+}
+
+/// The sizes of the node as spawned, at a 100% scale.
+#[derive(Component, Debug)]
+struct Unscaled {
+    size: Size<Val>,
+    min_size: Size<Val>,
+    margin: UiRect<Val>,
+    padding: UiRect<Val>,
+    font_sizes: Vec<f32>,
+}
+
+impl Unscaled {
+    fn new(style: &Style, text: Option<&Text>) -> Unscaled {
+        Unscaled {
+            size: style.size,
+            min_size: style.min_size,
+            margin: style.margin,
+            padding: style.padding,
+            font_sizes: text.map_or(Vec::new(), |text| {
+                text.sections.iter().map(|section| section.style.font_size).collect()
+            }),
+        }
+    }
+
+    /// The values changed at runtime, like the percentages of the slider fills,
+    /// are left untouched unless they were spawned in pixels.
+    fn apply(&self, scale: f32, style: &mut Style, text: Option<Mut<Text>>) {
+        scale_size(self.size, scale, &mut style.size);
+        scale_size(self.min_size, scale, &mut style.min_size);
+        scale_rect(self.margin, scale, &mut style.margin);
+        scale_rect(self.padding, scale, &mut style.padding);
+        if let Some(mut text) = text {
+            for (section, font_size) in text.sections.iter_mut().zip(&self.font_sizes) {
+                section.style.font_size = font_size * scale;
+            }
+        }
+    }
+}
+
+/// Only the pixel values are scaled, the percentages follow their parent.
+fn scale_val(unscaled: Val, scale: f32, val: &mut Val) {
+    if let Val::Px(px) = unscaled {
+        *val = Val::Px(px * scale);
+    }
+}
+
+fn scale_size(unscaled: Size<Val>, scale: f32, size: &mut Size<Val>) {
+    scale_val(unscaled.width, scale, &mut size.width);
+    scale_val(unscaled.height, scale, &mut size.height);
+}
+
+fn scale_rect(unscaled: UiRect<Val>, scale: f32, rect: &mut UiRect<Val>) {
+    scale_val(unscaled.left, scale, &mut rect.left);
+    scale_val(unscaled.right, scale, &mut rect.right);
+    scale_val(unscaled.top, scale, &mut rect.top);
+    scale_val(unscaled.bottom, scale, &mut rect.bottom);
+}
+
+fn scale_new_nodes(
+    mut commands: Commands,
+    settings: Res<GameSettings>,
+    mut nodes: Query<(Entity, &mut Style, Option<&mut Text>), Without<Unscaled>>,
+) {
+    for (entity, mut style, text) in &mut nodes {
+        let unscaled = Unscaled::new(&style, text.as_deref());
+        if settings.ui_scale != 1.0 {
+            unscaled.apply(settings.ui_scale, &mut style, text);
+        }
+        commands.entity(entity).insert(unscaled);
+    }
+}
+
+fn rescale_nodes_on_change(
+    settings: Res<GameSettings>,
+    mut last_scale: Local<Option<f32>>,
+    mut nodes: Query<(&Unscaled, &mut Style, Option<&mut Text>)>,
+) {
+    if *last_scale == Some(settings.ui_scale) {
+        return;
+    }
+
+    // The new nodes are already spawned at the current scale.
+    if last_scale.is_some() {
+        for (unscaled, mut style, text) in &mut nodes {
+            unscaled.apply(settings.ui_scale, &mut style, text);
+        }
+    }
+    *last_scale = Some(settings.ui_scale);
+}
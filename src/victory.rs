@@ -0,0 +1,269 @@
+//! The victory of the standard games, once enough waves are survived
+//! the ships fly by the planet under the fireworks before the final tally.
+
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::sprite::MaterialMesh2dBundle;
+use bevy_tweening::lens::TransformPositionLens;
+use bevy_tweening::{Animator, Delay, EaseFunction, Tween, TweeningType};
+use rand::prelude::*;
+
+use crate::endless::EndlessRun;
+use crate::evacuation::EVACUEE_SCORE_BONUS;
+use crate::layers::RenderLayer;
+use crate::players::{PlayerStats, Players};
+use crate::settings::GameSettings;
+use crate::shapes;
+use crate::speed::SimulationSpeed;
+use crate::watchdog::Watchdog;
+use crate::waves::{Wave, WaveEvent};
+use crate::{
+    FontAssets, GameMode, GameState, PlanetHealth, RunSetup, PLANET_MAX_HEALTH, PLANET_RADIUS,
+};
+
+/// The number of waves to survive to win a standard game.
+pub const STANDARD_GAME_WAVES: u32 = 10;
+const VICTORY_SEQUENCE_DURATION: u64 = 12; // in second
+const FIREWORK_INTERVAL: u64 = 600; // in millisecond
+const FIREWORK_GRAVITY: f32 = 60.0;
+
+pub struct VictoryPlugin;
+
+impl Plugin for VictoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(VictorySequence::default())
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(win_standard_game))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Victory)
+                    .with_system(reset_victory_sequence)
+                    .with_system(setup_victory_flyby)
+                    .with_system(setup_victory_tally),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Victory)
+                    .with_system(launch_fireworks)
+                    .with_system(animate_firework_particles)
+                    .with_system(end_victory_sequence),
+            );
+    }
+}
+
+/// The timers of the victory sequence.
+#[derive(Debug)]
+struct VictorySequence {
+    duration: Timer,
+    fireworks: Timer,
+}
+
+impl Default for VictorySequence {
+    fn default() -> VictorySequence {
+        VictorySequence {
+            duration: Timer::new(Duration::from_secs(VICTORY_SEQUENCE_DURATION), false),
+            fireworks: Timer::new(Duration::from_millis(FIREWORK_INTERVAL), true),
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct FireworkParticle {
+    velocity: Vec2,
+    lifetime: Timer,
+}
+
+/// The final score of a standard game, the dice collected in every wave
+/// and a bonus for every city left standing and every evacuated transport.
+fn standard_score(
+    wave: &Wave,
+    run: &EndlessRun,
+    health: &PlanetHealth,
+    speed: SimulationSpeed,
+) -> u64 {
+    let bonus = health.current as u64 * 50 + run.evacuated as u64 * EVACUEE_SCORE_BONUS;
+    speed.scale_score(wave.number as u64 * run.dice_collected as u64 + bonus)
+}
+
+fn win_standard_game(
+    game_mode: Res<GameMode>,
+    mut wave_events: EventReader<WaveEvent>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for event in wave_events.iter() {
+        if let (GameMode::Standard, WaveEvent::Cleared(STANDARD_GAME_WAVES)) = (*game_mode, event) {
+            let _ = state.set(GameState::Victory);
+        }
+    }
+}
+
+fn reset_victory_sequence(mut sequence: ResMut<VictorySequence>) {
+    *sequence = VictorySequence::default();
+}
+
+/// The fleet flies in formation from the left of the screen to the right,
+/// passing over the saved planet.
+fn setup_victory_flyby(
+    mut commands: Commands,
+    run_setup: Res<RunSetup>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    commands.spawn_bundle(MaterialMesh2dBundle {
+        mesh: meshes.add(shapes::circle(PLANET_RADIUS)).into(),
+        material: materials.add(ColorMaterial::from(Color::rgb(0.302, 0.302, 1.0))),
+        transform: Transform::from_translation(RenderLayer::Planet.at(Vec2::ZERO)),
+        ..default()
+    });
+
+    let a = Vec2::new(-0.5, 0.0);
+    let b = Vec2::new(0.0, 1.0);
+    let c = Vec2::new(0.5, 0.0);
+
+    for i in 0..run_setup.fleet.len() {
+        // The ships form a V, the leader in front.
+        let rank = i.div_ceil(2) as f32;
+        let side = if i % 2 == 0 { 1.0 } else { -1.0 };
+        let offset = Vec2::new(-rank * 40.0, side * rank * 30.0);
+        let start = RenderLayer::Ships.at(Vec2::new(-900.0, 0.0) + offset);
+        let end = RenderLayer::Ships.at(Vec2::new(900.0, 0.0) + offset);
+
+        commands
+            .spawn_bundle(MaterialMesh2dBundle {
+                mesh: meshes.add(shapes::polygon(&[a, c, b])).into(),
+                transform: Transform::from_translation(start)
+                    .with_rotation(Quat::from_rotation_z(-PI / 2.0))
+                    .with_scale(Vec3::splat(10.)),
+                material: materials.add(ColorMaterial::from(Color::PURPLE)),
+                ..default()
+            })
+            .insert(Animator::new(Delay::new(Duration::from_millis(500)).then(Tween::new(
+                EaseFunction::QuadraticInOut,
+                TweeningType::Once,
+                Duration::from_secs(4),
+                TransformPositionLens { start, end },
+            ))));
+    }
+}
+
+fn setup_victory_tally(
+    mut commands: Commands,
+    wave: Res<Wave>,
+    run: Res<EndlessRun>,
+    health: Res<PlanetHealth>,
+    speed: Res<SimulationSpeed>,
+    players: Res<Players>,
+    stats: Res<PlayerStats>,
+    font_assets: Res<FontAssets>,
+) {
+    let mut lines = vec![
+        format!("Waves survived: {}", wave.number),
+        format!("Dice collected: {}", run.dice_collected),
+        format!("Cities standing: {}/{}", health.current, PLANET_MAX_HEALTH),
+        format!("Transports evacuated: {}", run.evacuated),
+        format!("Final score: {}", standard_score(&wave, &run, &health, *speed)),
+    ];
+    lines.extend(stats.summary(&players));
+    lines.push("Press E to export the run data".to_string());
+    lines.push("Press Enter to return to the menu".to_string());
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(40.0), ..default() },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle::from_section(
+                "Victory!",
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 48.0,
+                    color: Color::GOLD,
+                },
+            ));
+
+            for line in lines {
+                parent.spawn_bundle(TextBundle::from_section(
+                    line,
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
+        });
+}
+
+fn launch_fireworks(
+    time: Res<Time>,
+    settings: Res<GameSettings>,
+    watchdog: Res<Watchdog>,
+    mut commands: Commands,
+    mut sequence: ResMut<VictorySequence>,
+) {
+    if !sequence.fireworks.tick(time.delta()).just_finished() {
+        return;
+    }
+
+    // The fireworks are only visual, they don't need the game rng.
+    let mut rng = thread_rng();
+    let center = RenderLayer::Effects
+        .at(Vec2::new(rng.gen_range(-400.0..400.0), rng.gen_range(-50.0..250.0)));
+    let color = Color::hsl(rng.gen_range(0.0..360.0), 1.0, 0.6);
+
+    let particles = watchdog.particle_budget(settings.quality);
+    for i in 0..particles {
+        let angle = i as f32 / particles as f32 * PI * 2.0;
+        let speed = rng.gen_range(80.0..140.0);
+        commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite { color, custom_size: Some(Vec2::splat(4.0)), ..default() },
+                transform: Transform::from_translation(center),
+                ..default()
+            })
+            .insert(FireworkParticle {
+                velocity: Vec2::new(angle.cos(), angle.sin()) * speed,
+                lifetime: Timer::new(Duration::from_millis(rng.gen_range(1000..1600)), false),
+            });
+    }
+}
+
+fn animate_firework_particles(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut particles: Query<(Entity, &mut FireworkParticle, &mut Transform, &mut Sprite)>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut particle, mut transform, mut sprite) in &mut particles {
+        if particle.lifetime.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        particle.velocity.y -= FIREWORK_GRAVITY * delta;
+        transform.translation += (particle.velocity * delta).extend(0.0);
+        sprite.color.set_a(1.0 - particle.lifetime.percent());
+    }
+}
+
+/// The sequence returns to the menu by itself or when skipped.
+fn end_victory_sequence(
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mut sequence: ResMut<VictorySequence>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let skipped = keys.just_pressed(KeyCode::Return) || keys.just_pressed(KeyCode::Escape);
+    if sequence.duration.tick(time.delta()).just_finished() || skipped {
+        let _ = state.set(GameState::MainMenu);
+    }
+}
@@ -0,0 +1,150 @@
+//! The watchdog of the budgets of the game. When the frames get too long or the
+//! entities too many, the game degrades gracefully instead of letting the frame
+//! rate collapse: the asteroids spawn slower, the distant asteroids merge
+//! together and the effects spawn fewer particles.
+//!
+//! The game only recovers once well under the budgets, not to flicker between
+//! the two modes.
+//!
+//! The frame time differs between the games, the deterministic, the co-op and
+//! the headless runs only spawn fewer particles, their simulation must not
+//! depend on it.
+
+use bevy::ecs::entity::Entities;
+use bevy::prelude::*;
+
+use crate::headless::Headless;
+use crate::lockstep::LockstepSession;
+use crate::quality::Quality;
+use crate::{logic, Asteroid, AsteroidHealth, GameState, Planet};
+
+const FRAME_TIME_BUDGET: f32 = 1.0 / 30.0; // in second
+const FRAME_TIME_RECOVERED: f32 = 1.0 / 45.0; // in second
+/// The longest frame counted, the first frame back from the background is not a slowdown.
+const MAX_FRAME_TIME_SAMPLE: f32 = 0.25;
+/// How much the last frame weighs in the smoothed frame time.
+const FRAME_TIME_SMOOTHING: f32 = 0.05;
+const ENTITY_BUDGET: u32 = 5000;
+const ASTEROID_BUDGET: usize = 250;
+/// Only the asteroids this far from the planet are merged, where the fleet can't see them.
+const MERGE_MIN_DISTANCE: f32 = 600.0;
+const MERGE_MAX_GAP: f32 = 100.0;
+const DEGRADED_SPAWN_RATE_FACTOR: f32 = 0.5;
+
+pub struct WatchdogPlugin;
+
+impl Plugin for WatchdogPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Watchdog::default()).add_system(watch_budgets).add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(merge_distant_asteroids.after(watch_budgets)),
+        );
+    }
+}
+
+#[derive(Debug)]
+pub struct Watchdog {
+    /// The smoothed duration of the frames, in second.
+    frame_time: f32,
+    degraded: bool,
+    /// Whether the degraded mode can slow the spawns and merge the asteroids.
+    degrades_simulation: bool,
+}
+
+impl Default for Watchdog {
+    fn default() -> Watchdog {
+        Watchdog {
+            frame_time: 1.0 / 60.0,
+            degraded: false,
+            degrades_simulation: !cfg!(feature = "deterministic"),
+        }
+    }
+}
+
+impl Watchdog {
+    /// The factor of the spawn rate of the asteroids, lower while degraded.
+    pub fn spawn_rate_factor(&self) -> f32 {
+        if self.degraded && self.degrades_simulation {
+            DEGRADED_SPAWN_RATE_FACTOR
+        } else {
+            1.0
+        }
+    }
+
+    /// The number of particles of every burst of the effects, halved while degraded.
+    pub fn particle_budget(&self, quality: Quality) -> usize {
+        if self.degraded {
+            quality.particle_budget() / 2
+        } else {
+            quality.particle_budget()
+        }
+    }
+}
+
+fn watch_budgets(
+    time: Res<Time>,
+    entities: &Entities,
+    asteroids: Query<(), With<Asteroid>>,
+    session: Option<Res<LockstepSession>>,
+    headless: Option<Res<Headless>>,
+    mut watchdog: ResMut<Watchdog>,
+) {
+    watchdog.degrades_simulation =
+        !cfg!(feature = "deterministic") && session.is_none() && headless.is_none();
+
+    let sample = time.delta_seconds().min(MAX_FRAME_TIME_SAMPLE);
+    watchdog.frame_time += (sample - watchdog.frame_time) * FRAME_TIME_SMOOTHING;
+
+    let entity_count = entities.len();
+    let asteroid_count = asteroids.iter().count();
+    let over_budget = watchdog.frame_time > FRAME_TIME_BUDGET
+        || entity_count > ENTITY_BUDGET
+        || asteroid_count > ASTEROID_BUDGET;
+    let well_under_budget = watchdog.frame_time < FRAME_TIME_RECOVERED
+        && entity_count < ENTITY_BUDGET * 4 / 5
+        && asteroid_count < ASTEROID_BUDGET * 4 / 5;
+
+    if over_budget && !watchdog.degraded {
+        info!(
+            "Over budget ({:.1}ms, {} entities, {} asteroids), degrading",
+            watchdog.frame_time * 1000.0,
+            entity_count,
+            asteroid_count
+        );
+        watchdog.degraded = true;
+    } else if well_under_budget && watchdog.degraded {
+        info!("Back under budget, recovering");
+        watchdog.degraded = false;
+    }
+}
+
+/// Merge the two closest distant asteroids every frame while degraded,
+/// the remaining one takes the health of both.
+fn merge_distant_asteroids(
+    mut commands: Commands,
+    watchdog: Res<Watchdog>,
+    planet: Query<&Transform, With<Planet>>,
+    mut asteroids: Query<(Entity, &Transform, &mut AsteroidHealth), With<Asteroid>>,
+) {
+    if !watchdog.degraded || !watchdog.degrades_simulation {
+        return;
+    }
+    let planet_position = match planet.get_single() {
+        Ok(transform) => transform.translation.truncate().to_array(),
+        Err(_) => return,
+    };
+
+    let (entities, positions): (Vec<_>, Vec<_>) = asteroids
+        .iter()
+        .map(|(entity, transform, _)| (entity, transform.translation.truncate().to_array()))
+        .unzip();
+    let pair =
+        logic::closest_distant_pair(planet_position, &positions, MERGE_MIN_DISTANCE, MERGE_MAX_GAP);
+    if let Some((kept, merged)) = pair {
+        let absorbed = asteroids.get(entities[merged]).map_or(0, |(_, _, health)| health.0);
+        if let Ok((_, _, mut health)) = asteroids.get_mut(entities[kept]) {
+            health.0 += absorbed;
+        }
+        commands.entity(entities[merged]).despawn_recursive();
+    }
+}
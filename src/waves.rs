@@ -0,0 +1,220 @@
+//! The waves of asteroids, separated by calm intermissions.
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::controls::{Action, Actions};
+use crate::endless::Difficulty;
+use crate::speed::SimulationSpeed;
+use crate::toasts::ToastEvent;
+use crate::{FontAssets, GameState};
+
+const WAVE_DURATION: u64 = 30; // in second
+const INTERMISSION_DURATION: u64 = 20; // in second
+const WAVE_SPAWN_RATE_INCREASE: f32 = 0.15;
+
+pub struct WavesPlugin;
+
+impl Plugin for WavesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WaveSchedule::default())
+            .insert_resource(Wave::first(&WaveSchedule::default()))
+            .add_event::<WaveEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_waves)
+                    .with_system(setup_wave_indicator),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(advance_waves)
+                    .with_system(skip_intermission_by_pressing_enter)
+                    .with_system(draw_wave_indicator),
+            );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavePhase {
+    /// The asteroids are spawning.
+    Combat,
+    /// No asteroid spawns, the player can spend their dice.
+    Intermission,
+}
+
+pub enum WaveEvent {
+    /// The asteroids of this wave start spawning.
+    Started(u32),
+    /// The wave is over and the intermission starts.
+    Cleared(u32),
+}
+
+/// The waves authored for a campaign level, once they are all played the last one repeats.
+/// Without scripted waves every wave spawns the asteroids a bit faster than the previous one.
+#[derive(Debug, Clone, Default)]
+pub struct WaveSchedule {
+    pub waves: Vec<ScriptedWave>,
+    /// The difficulty of the endless waves.
+    pub difficulty: Difficulty,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ScriptedWave {
+    /// The duration of the combat phase, in second.
+    duration: u64,
+    /// How much faster the asteroids spawn compared to the first endless wave.
+    spawn_rate: f32,
+}
+
+impl WaveSchedule {
+    fn wave(&self, number: u32) -> ScriptedWave {
+        let index = number.saturating_sub(1) as usize;
+        match self.waves.get(index).or_else(|| self.waves.last()) {
+            Some(scripted) => *scripted,
+            None => ScriptedWave {
+                duration: WAVE_DURATION,
+                spawn_rate: endless_spawn_rate(number) * self.difficulty.spawn_rate_factor(),
+            },
+        }
+    }
+}
+
+/// The endless waves grow harder without a limit, the first wave is the reference.
+fn endless_spawn_rate(number: u32) -> f32 {
+    1.0 + (number - 1) as f32 * WAVE_SPAWN_RATE_INCREASE
+}
+
+/// The current wave and the time remaining in its phase.
+#[derive(Debug)]
+pub struct Wave {
+    pub number: u32,
+    pub phase: WavePhase,
+    spawn_rate: f32,
+    timer: Timer,
+}
+
+impl Wave {
+    fn first(schedule: &WaveSchedule) -> Wave {
+        Wave::combat(1, schedule)
+    }
+
+    fn combat(number: u32, schedule: &WaveSchedule) -> Wave {
+        let ScriptedWave { duration, spawn_rate } = schedule.wave(number);
+        Wave {
+            number,
+            phase: WavePhase::Combat,
+            spawn_rate,
+            timer: Timer::new(Duration::from_secs(duration), false),
+        }
+    }
+
+    pub fn is_intermission(&self) -> bool {
+        self.phase == WavePhase::Intermission
+    }
+
+    /// How much faster the asteroids spawn compared to the first endless wave.
+    pub fn spawn_rate_factor(&self) -> f32 {
+        self.spawn_rate
+    }
+
+    fn start_intermission(&mut self) {
+        self.phase = WavePhase::Intermission;
+        self.timer = Timer::new(Duration::from_secs(INTERMISSION_DURATION), false);
+    }
+
+    fn start_next_wave(&mut self, schedule: &WaveSchedule) {
+        *self = Wave::combat(self.number + 1, schedule);
+    }
+}
+
+/// The text displaying the wave on the top of the screen.
+#[derive(Component, Debug)]
+struct WaveIndicator;
+
+pub fn reset_waves(schedule: Res<WaveSchedule>, mut wave: ResMut<Wave>) {
+    *wave = Wave::first(&schedule);
+}
+
+fn setup_wave_indicator(mut commands: Commands, font_assets: Res<FontAssets>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Auto),
+                position_type: PositionType::Absolute,
+                position: UiRect { top: Val::Px(20.0), ..default() },
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            color: Color::NONE.into(),
+            focus_policy: bevy::ui::FocusPolicy::Pass,
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font_assets.fira_sans.clone(),
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                    },
+                ))
+                .insert(WaveIndicator);
+        });
+}
+
+fn advance_waves(
+    time: Res<Time>,
+    speed: Res<SimulationSpeed>,
+    schedule: Res<WaveSchedule>,
+    mut wave: ResMut<Wave>,
+    mut wave_events: EventWriter<WaveEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    if wave.timer.tick(speed.delta(&time)).finished() {
+        match wave.phase {
+            WavePhase::Combat => {
+                wave.start_intermission();
+                wave_events.send(WaveEvent::Cleared(wave.number));
+                toasts.send(ToastEvent::success(format!("Wave {} cleared", wave.number)));
+            }
+            WavePhase::Intermission => {
+                wave.start_next_wave(&schedule);
+                wave_events.send(WaveEvent::Started(wave.number));
+                toasts.send(ToastEvent::info(format!("Wave {} incoming", wave.number)));
+            }
+        }
+    }
+}
+
+fn skip_intermission_by_pressing_enter(
+    actions: Actions,
+    schedule: Res<WaveSchedule>,
+    mut wave: ResMut<Wave>,
+    mut wave_events: EventWriter<WaveEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    if wave.is_intermission() && actions.just_pressed(Action::SkipIntermission) {
+        wave.start_next_wave(&schedule);
+        wave_events.send(WaveEvent::Started(wave.number));
+        toasts.send(ToastEvent::info(format!("Wave {} incoming", wave.number)));
+    }
+}
+
+fn draw_wave_indicator(wave: Res<Wave>, mut indicator: Query<&mut Text, With<WaveIndicator>>) {
+    let remaining = wave.timer.duration().saturating_sub(wave.timer.elapsed()).as_secs();
+    let value = match wave.phase {
+        WavePhase::Combat => format!("Wave {} - {}s", wave.number, remaining),
+        WavePhase::Intermission => {
+            format!("Intermission - wave {} in {}s (Enter to start)", wave.number + 1, remaining)
+        }
+    };
+
+    for mut text in &mut indicator {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}